@@ -0,0 +1,110 @@
+//! Serialization throughput for representative command streams.
+//!
+//! Run with `cargo bench`. The throughput is reported in commands per second.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use gerber_types::{
+    Aperture, ApertureDefinition, ApertureMacro, Circle, Command, CoordinateFormat, Coordinates,
+    DCode, ExtendedCode, GCode, GerberCode, MCode, Operation, OutlinePrimitive, RegionMode, Unit,
+};
+
+const SIZE: i32 = 10_000;
+
+fn header(cf: CoordinateFormat) -> Vec<Command> {
+    vec![
+        ExtendedCode::CoordinateFormat(cf).into(),
+        ExtendedCode::Unit(Unit::Millimeters).into(),
+    ]
+}
+
+/// A drill-like file with many flashes of a single aperture.
+fn dense_flashes() -> Vec<Command> {
+    let cf = CoordinateFormat::new(4, 6);
+    let mut commands = header(cf);
+    commands.push(
+        ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+            10,
+            Aperture::Circle(Circle::new(0.3)),
+        ))
+        .into(),
+    );
+    commands.push(DCode::SelectAperture(10).into());
+    for i in 0..SIZE {
+        let coords = Coordinates::new(i % 100, i / 100, cf);
+        commands.push(DCode::Operation(Operation::Flash(coords)).into());
+    }
+    commands.push(MCode::EndOfFile.into());
+    commands
+}
+
+/// A copper pour like file with many small regions.
+fn regions() -> Vec<Command> {
+    let cf = CoordinateFormat::new(4, 6);
+    let mut commands = header(cf);
+    for i in 0..SIZE / 6 {
+        let (x, y) = (i % 100, i / 100);
+        commands.push(GCode::RegionMode(RegionMode::Begin).into());
+        commands.push(DCode::Operation(Operation::Move(Coordinates::new(x, y, cf))).into());
+        for (dx, dy) in &[(1, 0), (1, 1), (0, 1), (0, 0)] {
+            let coords = Coordinates::new(x + dx, y + dy, cf);
+            commands.push(DCode::Operation(Operation::Interpolate(coords, None)).into());
+        }
+        commands.push(GCode::RegionMode(RegionMode::End).into());
+    }
+    commands.push(MCode::EndOfFile.into());
+    commands
+}
+
+/// A file with many aperture macros and macro apertures.
+fn macros() -> Vec<Command> {
+    let cf = CoordinateFormat::new(4, 6);
+    let mut commands = header(cf);
+    for i in 0..SIZE / 4 {
+        let name = format!("PAD{}", i);
+        let outline = OutlinePrimitive::new()
+            .add_point((0.0.into(), 0.0.into()))
+            .add_point((1.25.into(), 0.0.into()))
+            .add_point((1.25.into(), 0.75.into()))
+            .add_point((0.0.into(), 0.0.into()));
+        commands.push(
+            ExtendedCode::ApertureMacro(ApertureMacro::new(name.clone()).add_content(outline))
+                .into(),
+        );
+        commands.push(
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10 + i,
+                Aperture::Other(name.into()),
+            ))
+            .into(),
+        );
+        commands.push(DCode::SelectAperture(10 + i).into());
+        let coords = Coordinates::new(i % 100, i / 100, cf);
+        commands.push(DCode::Operation(Operation::Flash(coords)).into());
+    }
+    commands.push(MCode::EndOfFile.into());
+    commands
+}
+
+fn bench_serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    let streams = [
+        ("dense_flashes", dense_flashes()),
+        ("regions", regions()),
+        ("macros", macros()),
+    ];
+    for (name, commands) in streams.iter() {
+        group.throughput(Throughput::Elements(commands.len() as u64));
+        group.bench_function(*name, |b| {
+            b.iter_batched_ref(
+                || Vec::with_capacity(1 << 20),
+                |buf| commands.serialize(buf).unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_serialize);
+criterion_main!(benches);
@@ -5,12 +5,12 @@ use conv::TryFrom;
 
 use gerber_types::*;
 
-const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-fn main() {
+fn main() -> GerberResult<()> {
     let cf = CoordinateFormat::new(2, 6);
     let commands: Vec<Command> = vec![
-        FunctionCode::GCode(GCode::Comment("Ucamco ex. 2: Shapes".to_string())).into(),
+        FunctionCode::GCode(GCode::Comment("Ucamco ex. 2: Shapes".into())).into(),
         ExtendedCode::CoordinateFormat(cf).into(),
         ExtendedCode::Unit(Unit::Inches).into(),
         ExtendedCode::FileAttribute(FileAttribute::GenerationSoftware(GenerationSoftware::new(
@@ -24,7 +24,7 @@ fn main() {
         )))
         .into(),
         ExtendedCode::LoadPolarity(Polarity::Dark).into(),
-        FunctionCode::GCode(GCode::Comment("Define Apertures".to_string())).into(),
+        FunctionCode::GCode(GCode::Comment("Define Apertures".into())).into(),
         ExtendedCode::ApertureMacro(ApertureMacro::new("TARGET125").add_content(MoirePrimitive {
             center: (0.0.into(), 0.0.into()),
             diameter: 0.125.into(),
@@ -48,111 +48,64 @@ fn main() {
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 10,
-            aperture: Aperture::Circle(Circle {
-                diameter: 0.01,
-                hole_diameter: None,
-            }),
+            aperture: Aperture::Circle(Circle::new(0.01)),
         })
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 11,
-            aperture: Aperture::Circle(Circle {
-                diameter: 0.06,
-                hole_diameter: None,
-            }),
+            aperture: Aperture::Circle(Circle::new(0.06)),
         })
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 12,
-            aperture: Aperture::Rectangle(Rectangular {
-                x: 0.06,
-                y: 0.06,
-                hole_diameter: None,
-            }),
+            aperture: Aperture::Rectangle(Rectangular::new(0.06, 0.06)),
         })
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 13,
-            aperture: Aperture::Rectangle(Rectangular {
-                x: 0.04,
-                y: 0.1,
-                hole_diameter: None,
-            }),
+            aperture: Aperture::Rectangle(Rectangular::new(0.04, 0.1)),
         })
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 14,
-            aperture: Aperture::Rectangle(Rectangular {
-                x: 0.1,
-                y: 0.04,
-                hole_diameter: None,
-            }),
+            aperture: Aperture::Rectangle(Rectangular::new(0.1, 0.04)),
         })
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 15,
-            aperture: Aperture::Obround(Rectangular {
-                x: 0.04,
-                y: 0.1,
-                hole_diameter: None,
-            }),
+            aperture: Aperture::Obround(Obround::new(0.04, 0.1)),
         })
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 16,
-            aperture: Aperture::Polygon(Polygon {
-                diameter: 0.1,
-                vertices: 3,
-                rotation: None,
-                hole_diameter: None,
-            }),
+            aperture: Aperture::Polygon(Polygon::new(0.1, 3)),
         })
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 18,
-            aperture: Aperture::Other("TARGET125".to_string()),
+            aperture: Aperture::Other("TARGET125".into()),
         })
         .into(),
         ExtendedCode::ApertureDefinition(ApertureDefinition {
             code: 19,
-            aperture: Aperture::Other("THERMAL80".to_string()),
+            aperture: Aperture::Other("THERMAL80".into()),
         })
         .into(),
-        FunctionCode::GCode(GCode::Comment("Start image generation".to_string())).into(),
+        FunctionCode::GCode(GCode::Comment("Start image generation".into())).into(),
         FunctionCode::DCode(DCode::SelectAperture(10)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Move(Coordinates::new(
-            0,
-            CoordinateNumber::try_from(0.25).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::move_xy(0.0, 0.25, cf)?)).into(),
         FunctionCode::GCode(GCode::InterpolationMode(InterpolationMode::Linear)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::interpolate_xy(0.0, 0.0, cf)?)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::interpolate_xy(0.25, 0.0, cf)?)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::move_xy(1.0, 1.0, cf)?)).into(),
         FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::new(0, 0, cf),
-            None,
-        )))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::new(CoordinateNumber::try_from(0.25).unwrap(), 0, cf),
-            None,
-        )))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Move(Coordinates::new(
-            1, 1, cf,
-        ))))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::at_x(CoordinateNumber::try_from(1.5).unwrap(), cf),
-            None,
-        )))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::new(2, CoordinateNumber::try_from(1.5).unwrap(), cf),
+            Coordinates::at_x(CoordinateNumber::try_from(1.5)?, cf),
             None,
         )))
         .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::interpolate_xy(2.0, 1.5, cf)?)).into(),
         FunctionCode::DCode(DCode::Operation(Operation::Move(Coordinates::at_x(
-            CoordinateNumber::try_from(2.5).unwrap(),
+            CoordinateNumber::try_from(2.5)?,
             cf,
         ))))
         .into(),
@@ -162,108 +115,44 @@ fn main() {
         )))
         .into(),
         FunctionCode::DCode(DCode::SelectAperture(11)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            1, 1, cf,
-        ))))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            2, 1, cf,
-        ))))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            CoordinateNumber::try_from(2.5).unwrap(),
-            1,
-            cf,
-        ))))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            CoordinateNumber::try_from(2.5).unwrap(),
-            CoordinateNumber::try_from(1.5).unwrap(),
-            cf,
-        ))))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            2,
-            CoordinateNumber::try_from(1.5).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(1.0, 1.0, cf)?)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(2.0, 1.0, cf)?)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(2.5, 1.0, cf)?)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(2.5, 1.5, cf)?)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(2.0, 1.5, cf)?)).into(),
         FunctionCode::DCode(DCode::SelectAperture(12)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            1,
-            CoordinateNumber::try_from(1.5).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(1.0, 1.5, cf)?)).into(),
         FunctionCode::DCode(DCode::SelectAperture(13)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            3,
-            CoordinateNumber::try_from(1.5).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(3.0, 1.5, cf)?)).into(),
         FunctionCode::DCode(DCode::SelectAperture(14)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            3,
-            CoordinateNumber::try_from(1.25).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(3.0, 1.25, cf)?)).into(),
         FunctionCode::DCode(DCode::SelectAperture(15)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            3, 1, cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(3.0, 1.0, cf)?)).into(),
         FunctionCode::DCode(DCode::SelectAperture(10)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Move(Coordinates::new(
-            CoordinateNumber::try_from(3.75).unwrap(),
-            1,
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::move_xy(3.75, 1.0, cf)?)).into(),
         FunctionCode::GCode(GCode::QuadrantMode(QuadrantMode::Multi)).into(),
         FunctionCode::GCode(GCode::InterpolationMode(
             InterpolationMode::CounterclockwiseCircular,
         ))
         .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::new(CoordinateNumber::try_from(3.75).unwrap(), 1, cf),
-            Some(CoordinateOffset::new(
-                CoordinateNumber::try_from(0.25).unwrap(),
-                0,
-                cf,
-            )),
-        )))
+        FunctionCode::DCode(DCode::Operation(Operation::interpolate_arc_xy(
+            3.75, 1.0, 0.25, 0.0, cf,
+        )?))
         .into(),
         FunctionCode::DCode(DCode::SelectAperture(16)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            CoordinateNumber::try_from(3.4).unwrap(),
-            1,
-            cf,
-        ))))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            CoordinateNumber::try_from(3.5).unwrap(),
-            CoordinateNumber::try_from(0.9).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(3.4, 1.0, cf)?)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(3.5, 0.9, cf)?)).into(),
         FunctionCode::DCode(DCode::SelectAperture(10)).into(),
-        FunctionCode::GCode(GCode::RegionMode(true)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Move(Coordinates::new(
-            CoordinateNumber::try_from(0.5).unwrap(),
-            2,
-            cf,
-        ))))
-        .into(),
+        FunctionCode::GCode(GCode::RegionMode(RegionMode::Begin)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::move_xy(0.5, 2.0, cf)?)).into(),
         FunctionCode::GCode(GCode::InterpolationMode(InterpolationMode::Linear)).into(),
         FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::at_y(CoordinateNumber::try_from(3.75).unwrap(), cf),
+            Coordinates::at_y(CoordinateNumber::try_from(3.75)?, cf),
             None,
         )))
         .into(),
         FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::at_x(CoordinateNumber::try_from(3.75).unwrap(), cf),
+            Coordinates::at_x(CoordinateNumber::try_from(3.75)?, cf),
             None,
         )))
         .into(),
@@ -273,32 +162,17 @@ fn main() {
         )))
         .into(),
         FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::at_x(CoordinateNumber::try_from(0.5).unwrap(), cf),
+            Coordinates::at_x(CoordinateNumber::try_from(0.5)?, cf),
             None,
         )))
         .into(),
-        FunctionCode::GCode(GCode::RegionMode(false)).into(),
+        FunctionCode::GCode(GCode::RegionMode(RegionMode::End)).into(),
         FunctionCode::DCode(DCode::SelectAperture(18)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            0,
-            CoordinateNumber::try_from(3.875).unwrap(),
-            cf,
-        ))))
-        .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            CoordinateNumber::try_from(3.875).unwrap(),
-            CoordinateNumber::try_from(3.875).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(0.0, 3.875, cf)?)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(3.875, 3.875, cf)?)).into(),
         ExtendedCode::LoadPolarity(Polarity::Clear).into(),
-        FunctionCode::GCode(GCode::RegionMode(true)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Move(Coordinates::new(
-            1,
-            CoordinateNumber::try_from(2.5).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::GCode(GCode::RegionMode(RegionMode::Begin)).into(),
+        FunctionCode::DCode(DCode::Operation(Operation::move_xy(1.0, 2.5, cf)?)).into(),
         FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
             Coordinates::at_y(3, cf),
             None,
@@ -309,18 +183,9 @@ fn main() {
             InterpolationMode::ClockwiseCircular,
         ))
         .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::new(
-                CoordinateNumber::try_from(1.25).unwrap(),
-                CoordinateNumber::try_from(3.25).unwrap(),
-                cf,
-            ),
-            Some(CoordinateOffset::new(
-                CoordinateNumber::try_from(0.25).unwrap(),
-                0,
-                cf,
-            )),
-        )))
+        FunctionCode::DCode(DCode::Operation(Operation::interpolate_arc_xy(
+            1.25, 3.25, 0.25, 0.0, cf,
+        )?))
         .into(),
         FunctionCode::GCode(GCode::InterpolationMode(InterpolationMode::Linear)).into(),
         FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
@@ -333,14 +198,9 @@ fn main() {
             InterpolationMode::ClockwiseCircular,
         ))
         .into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
-            Coordinates::new(3, CoordinateNumber::try_from(2.5).unwrap(), cf),
-            Some(CoordinateOffset::new(
-                0,
-                CoordinateNumber::try_from(0.375).unwrap(),
-                cf,
-            )),
-        )))
+        FunctionCode::DCode(DCode::Operation(Operation::interpolate_arc_xy(
+            3.0, 2.5, 0.0, 0.375, cf,
+        )?))
         .into(),
         FunctionCode::GCode(GCode::InterpolationMode(InterpolationMode::Linear)).into(),
         FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
@@ -348,35 +208,20 @@ fn main() {
             None,
         )))
         .into(),
-        FunctionCode::GCode(GCode::RegionMode(false)).into(),
+        FunctionCode::GCode(GCode::RegionMode(RegionMode::End)).into(),
         ExtendedCode::LoadPolarity(Polarity::Dark).into(),
         FunctionCode::DCode(DCode::SelectAperture(10)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Move(Coordinates::new(
-            CoordinateNumber::try_from(1.5).unwrap(),
-            CoordinateNumber::try_from(2.875).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::move_xy(1.5, 2.875, cf)?)).into(),
         FunctionCode::DCode(DCode::Operation(Operation::Interpolate(
             Coordinates::at_x(2, cf),
             None,
         )))
         .into(),
         FunctionCode::DCode(DCode::SelectAperture(11)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            CoordinateNumber::try_from(1.5).unwrap(),
-            CoordinateNumber::try_from(2.875).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(1.5, 2.875, cf)?)).into(),
         FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::at_x(2, cf)))).into(),
         FunctionCode::DCode(DCode::SelectAperture(19)).into(),
-        FunctionCode::DCode(DCode::Operation(Operation::Flash(Coordinates::new(
-            CoordinateNumber::try_from(2.875).unwrap(),
-            CoordinateNumber::try_from(2.875).unwrap(),
-            cf,
-        ))))
-        .into(),
+        FunctionCode::DCode(DCode::Operation(Operation::flash_xy(2.875, 2.875, cf)?)).into(),
         ExtendedCode::FileAttribute(FileAttribute::Md5(
             "6ab9e892830469cdff7e3e346331d404".to_string(),
         ))
@@ -384,5 +229,5 @@ fn main() {
         FunctionCode::MCode(MCode::EndOfFile).into(),
     ];
     let mut stdout = stdout();
-    commands.serialize(&mut stdout).unwrap();
+    commands.serialize(&mut stdout)
 }
@@ -0,0 +1,207 @@
+//! Computation of arc center offsets (I/J) for circular interpolation.
+
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use crate::coordinates::{CoordinateNumber, CoordinateOffset, Coordinates};
+use crate::errors::{GerberError, GerberResult};
+use crate::function_codes::{InterpolationMode, QuadrantMode};
+
+/// Tolerance for rounding errors of angles (in radians) and radii.
+const TOLERANCE: f64 = 1e-9;
+
+fn point(coords: &Coordinates, what: &str) -> GerberResult<(f64, f64)> {
+    match (coords.x, coords.y) {
+        (Some(x), Some(y)) => Ok((x.into(), y.into())),
+        _ => Err(GerberError::MissingDataError(format!(
+            "The {} of an arc needs both X and Y coordinates",
+            what
+        ))),
+    }
+}
+
+fn is_clockwise(direction: InterpolationMode) -> GerberResult<bool> {
+    match direction {
+        InterpolationMode::ClockwiseCircular => Ok(true),
+        InterpolationMode::CounterclockwiseCircular => Ok(false),
+        InterpolationMode::Linear => Err(GerberError::UnsupportedError(
+            "Linear interpolation has no arc center".into(),
+        )),
+    }
+}
+
+impl CoordinateOffset {
+    /// Compute the offset of an arc from its start point to its center.
+    ///
+    /// In single quadrant mode the offset is unsigned and the arc may not
+    /// span more than 90 degrees in the given direction, otherwise a
+    /// `RangeError` is returned. The offset uses the format of `start`.
+    pub fn from_arc_center(
+        start: &Coordinates,
+        end: &Coordinates,
+        center: &Coordinates,
+        direction: InterpolationMode,
+        quadrant: QuadrantMode,
+    ) -> GerberResult<Self> {
+        let clockwise = is_clockwise(direction)?;
+        let (sx, sy) = point(start, "start")?;
+        let (ex, ey) = point(end, "end")?;
+        let (cx, cy) = point(center, "center")?;
+        let (i, j) = (cx - sx, cy - sy);
+        if i == 0.0 && j == 0.0 {
+            return Err(GerberError::RangeError(
+                "The center of an arc must differ from its start".into(),
+            ));
+        }
+        if quadrant == QuadrantMode::Multi {
+            return Ok(CoordinateOffset {
+                x: Some(CoordinateNumber::from_f64_rounded(i)?),
+                y: Some(CoordinateNumber::from_f64_rounded(j)?),
+                format: start.format,
+            });
+        }
+        let start_angle = (sy - cy).atan2(sx - cx);
+        let end_angle = (ey - cy).atan2(ex - cx);
+        let counterclockwise = (end_angle - start_angle).rem_euclid(2.0 * PI);
+        let sweep = if clockwise && counterclockwise > 0.0 {
+            2.0 * PI - counterclockwise
+        } else {
+            counterclockwise
+        };
+        if sweep > FRAC_PI_2 + TOLERANCE {
+            return Err(GerberError::RangeError(format!(
+                "An arc of {:.1} degrees exceeds a single quadrant",
+                sweep.to_degrees()
+            )));
+        }
+        Ok(CoordinateOffset {
+            x: Some(CoordinateNumber::from_f64_rounded(i.abs())?),
+            y: Some(CoordinateNumber::from_f64_rounded(j.abs())?),
+            format: start.format,
+        })
+    }
+
+    /// Compute the offset of an arc from its start point to its center,
+    /// given the radius of the arc.
+    ///
+    /// A positive radius selects the shorter arc of at most 180 degrees, a
+    /// negative radius the longer one. A `RangeError` is returned if the
+    /// points are more than twice the radius apart, see
+    /// [`from_arc_center`](#method.from_arc_center) for the other checks.
+    pub fn from_arc_radius(
+        start: &Coordinates,
+        end: &Coordinates,
+        radius: f64,
+        direction: InterpolationMode,
+        quadrant: QuadrantMode,
+    ) -> GerberResult<Self> {
+        let clockwise = is_clockwise(direction)?;
+        let (sx, sy) = point(start, "start")?;
+        let (ex, ey) = point(end, "end")?;
+        let (dx, dy) = (ex - sx, ey - sy);
+        let chord = dx.hypot(dy);
+        if !radius.is_finite() || radius == 0.0 {
+            return Err(GerberError::RangeError(format!(
+                "Invalid arc radius {}",
+                radius
+            )));
+        }
+        if chord == 0.0 {
+            return Err(GerberError::RangeError(
+                "The center of an arc with identical start and end is ambiguous".into(),
+            ));
+        }
+        let half = chord / 2.0;
+        let height_squared = radius * radius - half * half;
+        if height_squared < -TOLERANCE * radius.abs() {
+            return Err(GerberError::RangeError(format!(
+                "The end points of an arc with radius {} are {} apart",
+                radius.abs(),
+                chord
+            )));
+        }
+        let height = height_squared.max(0.0).sqrt();
+        // Going counterclockwise around the center, the center of the
+        // shorter arc is left of the chord.
+        let left = if clockwise == (radius > 0.0) {
+            -1.0
+        } else {
+            1.0
+        };
+        let cx = sx + dx / 2.0 - left * height * dy / chord;
+        let cy = sy + dy / 2.0 + left * height * dx / chord;
+        let center = Coordinates {
+            x: Some(CoordinateNumber::from_f64_rounded(cx)?),
+            y: Some(CoordinateNumber::from_f64_rounded(cy)?),
+            format: start.format,
+        };
+        CoordinateOffset::from_arc_center(start, end, &center, direction, quadrant)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::CoordinateFormat;
+
+    const CW: InterpolationMode = InterpolationMode::ClockwiseCircular;
+    const CCW: InterpolationMode = InterpolationMode::CounterclockwiseCircular;
+
+    #[test]
+    fn test_from_arc_center() {
+        let cf = CoordinateFormat::new(2, 4);
+        let start = Coordinates::new(1, 0, cf);
+        let end = Coordinates::new(0, 1, cf);
+        let center = Coordinates::new(0, 0, cf);
+        let offset = |direction, quadrant| {
+            CoordinateOffset::from_arc_center(&start, &end, &center, direction, quadrant)
+        };
+        assert_eq!(
+            offset(CCW, QuadrantMode::Multi).unwrap(),
+            CoordinateOffset::new(-1, 0, cf)
+        );
+        assert_eq!(
+            offset(CCW, QuadrantMode::Single).unwrap(),
+            CoordinateOffset::new(1, 0, cf)
+        );
+        // Clockwise, the arc spans 270 degrees
+        assert!(offset(CW, QuadrantMode::Multi).is_ok());
+        assert!(matches!(
+            offset(CW, QuadrantMode::Single),
+            Err(GerberError::RangeError(_))
+        ));
+        assert!(offset(InterpolationMode::Linear, QuadrantMode::Multi).is_err());
+        assert!(CoordinateOffset::from_arc_center(
+            &Coordinates::at_x(1, cf),
+            &end,
+            &center,
+            CCW,
+            QuadrantMode::Multi
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_from_arc_radius() {
+        let cf = CoordinateFormat::new(2, 4);
+        let start = Coordinates::new(1, 0, cf);
+        let end = Coordinates::new(-1, 0, cf);
+        let offset = |radius, direction| {
+            CoordinateOffset::from_arc_radius(&start, &end, radius, direction, QuadrantMode::Multi)
+        };
+        // Half circles, the center is on the chord
+        assert_eq!(offset(1.0, CCW).unwrap(), CoordinateOffset::new(-1, 0, cf));
+        assert_eq!(offset(-1.0, CW).unwrap(), CoordinateOffset::new(-1, 0, cf));
+        assert!(offset(0.5, CCW).is_err());
+        assert!(offset(0.0, CCW).is_err());
+
+        let end = Coordinates::new(0, 1, cf);
+        let offset = |radius, direction| {
+            CoordinateOffset::from_arc_radius(&start, &end, radius, direction, QuadrantMode::Multi)
+        };
+        assert_eq!(offset(1.0, CCW).unwrap(), CoordinateOffset::new(-1, 0, cf));
+        assert_eq!(offset(-1.0, CW).unwrap(), CoordinateOffset::new(-1, 0, cf));
+        assert_eq!(offset(1.0, CW).unwrap(), CoordinateOffset::new(0, 1, cf));
+        assert_eq!(offset(-1.0, CCW).unwrap(), CoordinateOffset::new(0, 1, cf));
+    }
+}
@@ -0,0 +1,230 @@
+//! ZIP archives of Gerber files, requires the `zip` feature.
+//!
+//! The files are stored without compression, which every ZIP reader
+//! supports and keeps this free of dependencies.
+
+use std::convert::TryFrom;
+use std::io::Write;
+
+use crate::attributes::{FileAttribute, FilePolarity};
+use crate::errors::{GerberError, GerberResult};
+use crate::layers::LayerSet;
+use crate::naming::{file_name, NamingScheme};
+use crate::optimize::extended_codes;
+use crate::traits::GerberCodeExt;
+use crate::types::{Command, ExtendedCode};
+
+/// The DOS date of all entries, 1980-01-01.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Version 2.0 of the ZIP specification, for readers and writers.
+const VERSION: u16 = 20;
+
+/// The file names are UTF-8.
+const FLAGS: u16 = 1 << 11;
+
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// A writer of uncompressed ZIP archives.
+///
+/// ```
+/// use gerber_types::*;
+///
+/// let mut zip = ZipWriter::new(Vec::new());
+/// zip.add_commands("board-edge.gbr", &[MCode::EndOfFile.into()]).unwrap();
+/// let archive = zip.finish().unwrap();
+/// ```
+pub struct ZipWriter<W: Write> {
+    writer: W,
+    offset: u32,
+    entries: Vec<Entry>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(writer: W) -> Self {
+        ZipWriter {
+            writer,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add a file with the given content, e.g. a serialized job file.
+    ///
+    /// Names must be unique, otherwise a `NameError` is returned.
+    pub fn add_file(&mut self, name: &str, content: &[u8]) -> GerberResult<()> {
+        if self.entries.iter().any(|e| e.name == name) {
+            return Err(GerberError::NameError(name.into()));
+        }
+        let size = to_u32(content.len())?;
+        let crc = crc32(content);
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x0403_4b50_u32.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        write_common(&mut header, name, crc, size)?;
+        header.extend_from_slice(name.as_bytes());
+        self.writer.write_all(&header)?;
+        self.writer.write_all(content)?;
+        self.entries.push(Entry {
+            name: name.into(),
+            crc,
+            size,
+            offset: self.offset,
+        });
+        self.offset = to_u32(self.offset as usize + header.len() + content.len())?;
+        Ok(())
+    }
+
+    /// Add a Gerber file with the serialized commands.
+    pub fn add_commands(&mut self, name: &str, commands: &[Command]) -> GerberResult<()> {
+        let content = commands.to_code_string()?;
+        self.add_file(name, content.as_bytes())
+    }
+
+    /// Add the layers of a set, named after their function and polarity
+    /// with the given scheme, e.g. `board-copper_l1_top.gbr`. Returns the
+    /// names, which a job file refers to.
+    pub fn add_layer_set(
+        &mut self,
+        set: &LayerSet,
+        prefix: &str,
+        scheme: NamingScheme,
+    ) -> GerberResult<Vec<String>> {
+        let mut names = Vec::with_capacity(set.layers.len());
+        for layer in &set.layers {
+            let polarity = extended_codes(&layer.commands)
+                .find_map(|code| match code {
+                    ExtendedCode::FileAttribute(FileAttribute::FilePolarity(p)) => Some(p.clone()),
+                    _ => None,
+                })
+                .unwrap_or(FilePolarity::Positive);
+            let name = file_name(prefix, &layer.function, &polarity, scheme)?;
+            self.add_commands(&name, &layer.commands)?;
+            names.push(name);
+        }
+        Ok(names)
+    }
+
+    /// Write the central directory and return the inner writer.
+    pub fn finish(mut self) -> GerberResult<W> {
+        let count = u16::try_from(self.entries.len())
+            .map_err(|_| GerberError::RangeError("Too many files for a ZIP archive".into()))?;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x0201_4b50_u32.to_le_bytes());
+            directory.extend_from_slice(&VERSION.to_le_bytes());
+            directory.extend_from_slice(&VERSION.to_le_bytes());
+            write_common(&mut directory, &entry.name, entry.crc, entry.size)?;
+            // Comment length, disk number, internal and external attributes
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let size = to_u32(directory.len())?;
+        directory.extend_from_slice(&0x0605_4b50_u32.to_le_bytes());
+        directory.extend_from_slice(&[0; 4]);
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&size.to_le_bytes());
+        directory.extend_from_slice(&self.offset.to_le_bytes());
+        directory.extend_from_slice(&[0; 2]);
+        self.writer.write_all(&directory)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// The fields shared by local and central headers, from the flags to the
+/// extra field length.
+fn write_common(buf: &mut Vec<u8>, name: &str, crc: u32, size: u32) -> GerberResult<()> {
+    let name_len = u16::try_from(name.len())
+        .map_err(|_| GerberError::NameError(format!("{} is too long", name)))?;
+    buf.extend_from_slice(&FLAGS.to_le_bytes());
+    // Stored, at midnight
+    buf.extend_from_slice(&[0; 4]);
+    buf.extend_from_slice(&DOS_DATE.to_le_bytes());
+    buf.extend_from_slice(&crc.to_le_bytes());
+    buf.extend_from_slice(&size.to_le_bytes());
+    buf.extend_from_slice(&size.to_le_bytes());
+    buf.extend_from_slice(&name_len.to_le_bytes());
+    buf.extend_from_slice(&[0; 2]);
+    Ok(())
+}
+
+fn to_u32(value: usize) -> GerberResult<u32> {
+    u32::try_from(value).map_err(|_| GerberError::RangeError("ZIP archive exceeds 4 GiB".into()))
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::{ExtendedPosition, FileFunction, Position};
+    use crate::function_codes::MCode;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn test_zip_writer() {
+        let eof: Vec<Command> = vec![MCode::EndOfFile.into()];
+        let set = LayerSet::new()
+            .with_layer(
+                FileFunction::Copper {
+                    layer: 1,
+                    pos: ExtendedPosition::Top,
+                    copper_type: None,
+                },
+                eof.clone(),
+            )
+            .with_layer(
+                FileFunction::Legend {
+                    pos: Position::Top,
+                    index: None,
+                },
+                eof,
+            );
+        let mut zip = ZipWriter::new(Vec::new());
+        let names = zip
+            .add_layer_set(&set, "board", NamingScheme::Gerber)
+            .unwrap();
+        assert_eq!(names, ["board-copper_l1_top.gbr", "board-legend_top.gbr"]);
+        zip.add_file("board.gbrjob", b"{}").unwrap();
+        assert!(matches!(
+            zip.add_file("board.gbrjob", b"{}"),
+            Err(GerberError::NameError(_))
+        ));
+        let archive = zip.finish().unwrap();
+
+        assert_eq!(&archive[..4], b"PK\x03\x04");
+        assert_eq!(&archive[30..53], b"board-copper_l1_top.gbr");
+        assert_eq!(&archive[53..58], b"M02*\n");
+        // The end of central directory record with three entries
+        let end = &archive[archive.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(&end[8..12], &[3, 0, 3, 0]);
+    }
+}
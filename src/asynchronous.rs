@@ -0,0 +1,69 @@
+//! Asynchronous serialization into a tokio `AsyncWrite`, requires the `tokio`
+//! feature.
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::context::SerializationContext;
+use crate::errors::GerberResult;
+use crate::traits::GerberCode;
+use crate::types::CommandRef;
+
+/// The buffer size after which the serialized code is written.
+const BUFFER_SIZE: usize = 8192;
+
+/// Serialize the commands into an asynchronous writer.
+///
+/// The commands are serialized into a small buffer, which is written whenever
+/// it exceeds a few kilobytes, so the complete file is never held in memory.
+/// The writer is flushed at the end.
+pub async fn serialize_async<'a, I, C, W>(
+    commands: I,
+    writer: &mut W,
+    ctx: &SerializationContext,
+) -> GerberResult<()>
+where
+    I: IntoIterator<Item = C>,
+    C: Into<CommandRef<'a>>,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = Vec::with_capacity(BUFFER_SIZE + 256);
+    for command in commands {
+        command.into().serialize_with_context(&mut buf, ctx)?;
+        if buf.len() >= BUFFER_SIZE {
+            writer.write_all(&buf).await?;
+            buf.clear();
+        }
+    }
+    writer.write_all(&buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::{CoordinateFormat, Coordinates};
+    use crate::function_codes::{DCode, MCode, Operation};
+    use crate::traits::GerberCodeExt;
+    use crate::types::Command;
+
+    #[tokio::test]
+    async fn test_serialize_async() {
+        let cf = CoordinateFormat::new(2, 4);
+        let mut commands: Vec<Command> = (0..2000)
+            .map(|i| {
+                DCode::Operation(Operation::Flash(Coordinates::new(i % 100, i / 100, cf))).into()
+            })
+            .collect();
+        commands.push(MCode::EndOfFile.into());
+        let ctx = SerializationContext::new();
+
+        let mut output = Vec::new();
+        serialize_async(&commands, &mut output, &ctx).await.unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            commands.to_code_string_with_context(&ctx).unwrap()
+        );
+    }
+}
@@ -1,87 +1,222 @@
 //! Attributes.
 
+use std::borrow::Cow;
 use std::io::Write;
 
-use chrono::{DateTime, Utc};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, FixedOffset, SecondsFormat, Utc};
+#[cfg(feature = "uuid")]
 use uuid::Uuid;
 
-use crate::errors::GerberResult;
+use crate::charset::{escape_field, validate_name};
+use crate::context::SerializationContext;
+use crate::decimal::Decimal;
+use crate::errors::{GerberError, GerberResult};
 use crate::traits::PartialGerberCode;
 
 // FileAttribute
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum FileAttribute {
     Part(Part),
     FileFunction(FileFunction),
     FilePolarity(FilePolarity),
     GenerationSoftware(GenerationSoftware),
+    /// The creation date, requires the `chrono` feature
+    #[cfg(feature = "chrono")]
     CreationDate(DateTime<Utc>),
+    /// The creation date in local time, which keeps its UTC offset in the
+    /// output, requires the `chrono` feature
+    #[cfg(feature = "chrono")]
+    CreationDateWithOffset(DateTime<FixedOffset>),
+    /// The creation date as an ISO 8601 string, e.g.
+    /// `2015-02-23T15:59:51+01:00`
+    CreationDateText(String),
+    /// The project id, requires the `uuid` feature
+    #[cfg(feature = "uuid")]
     ProjectId {
         id: String,
         guid: Uuid,
         revision: String,
     },
+    /// The project id with a GUID string, e.g.
+    /// `5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c`
+    ProjectIdText {
+        id: String,
+        guid: String,
+        revision: String,
+    },
     Md5(String),
+    /// The file has the same coordinates as other files with this attribute
+    /// and the same identifier, e.g. the layers of a fabrication data set
+    SameCoordinates(Option<String>),
     UserDefined {
-        name: String,
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::fuzzing::arbitrary_cow))]
+        name: Cow<'static, str>,
         value: Vec<String>,
     },
 }
 
+impl FileAttribute {
+    /// The name of the attribute, e.g. `.FileFunction`.
+    pub fn name(&self) -> &str {
+        match *self {
+            FileAttribute::Part(_) => ".Part",
+            FileAttribute::FileFunction(_) => ".FileFunction",
+            FileAttribute::FilePolarity(_) => ".FilePolarity",
+            FileAttribute::GenerationSoftware(_) => ".GenerationSoftware",
+            #[cfg(feature = "chrono")]
+            FileAttribute::CreationDate(_) | FileAttribute::CreationDateWithOffset(_) => {
+                ".CreationDate"
+            }
+            FileAttribute::CreationDateText(_) => ".CreationDate",
+            #[cfg(feature = "uuid")]
+            FileAttribute::ProjectId { .. } => ".ProjectId",
+            FileAttribute::ProjectIdText { .. } => ".ProjectId",
+            FileAttribute::Md5(_) => ".MD5",
+            FileAttribute::SameCoordinates(_) => ".SameCoordinates",
+            FileAttribute::UserDefined { ref name, .. } => name,
+        }
+    }
+
+    /// Create a project id attribute from a GUID.
+    #[cfg(feature = "uuid")]
+    pub fn project_id<I: Into<String>, R: Into<String>>(
+        id: I,
+        guid: Uuid,
+        revision: R,
+    ) -> GerberResult<Self> {
+        let (id, revision) = project_fields(id.into(), revision.into())?;
+        Ok(FileAttribute::ProjectId { id, guid, revision })
+    }
+
+    /// Create a project id attribute from a GUID string in the hyphenated
+    /// format, e.g. `5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c`.
+    ///
+    /// This returns a `ProjectIdText` with the GUID in lowercase, with and
+    /// without the `uuid` feature.
+    pub fn project_id_from_str<I: Into<String>, R: Into<String>>(
+        id: I,
+        guid: &str,
+        revision: R,
+    ) -> GerberResult<Self> {
+        if !is_guid(guid) {
+            return Err(GerberError::ConversionError(format!(
+                "{:?} is not a GUID",
+                guid
+            )));
+        }
+        let (id, revision) = project_fields(id.into(), revision.into())?;
+        let guid = guid.to_ascii_lowercase();
+        Ok(FileAttribute::ProjectIdText { id, guid, revision })
+    }
+}
+
+/// Check the id and revision fields of a project id.
+fn project_fields(id: String, revision: String) -> GerberResult<(String, String)> {
+    if id.is_empty() || revision.is_empty() {
+        return Err(GerberError::MissingDataError(
+            "The project id and revision must not be empty".into(),
+        ));
+    }
+    Ok((id, revision))
+}
+
+/// Whether a string is a GUID like `5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c`.
+pub(crate) fn is_guid(guid: &str) -> bool {
+    let groups: Vec<&str> = guid.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
 impl<W: Write> PartialGerberCode<W> for FileAttribute {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             FileAttribute::Part(ref part) => {
                 write!(writer, "Part,")?;
-                part.serialize_partial(writer)?;
+                part.serialize_partial_with_context(writer, ctx)?;
             }
             FileAttribute::FileFunction(ref function) => {
                 write!(writer, "FileFunction,")?;
-                match function {
-                    FileFunction::Copper {
-                        ref layer,
-                        ref pos,
-                        ref copper_type,
-                    } => {
-                        write!(writer, "Copper,L{},", layer)?;
-                        pos.serialize_partial(writer)?;
-                        if let Some(ref t) = *copper_type {
-                            write!(writer, ",")?;
-                            t.serialize_partial(writer)?;
-                        }
-                    }
-                    FileFunction::Profile(ref plating) => {
-                        write!(writer, "Profile,")?;
-                        plating.serialize_partial(writer)?;
-                    }
-                    FileFunction::Soldermask { ref pos, ref index } => {
-                        write!(writer, "Soldermask,")?;
-                        pos.serialize_partial(writer)?;
-                        if let Some(ref i) = index {
-                            write!(writer, ",{}", *i)?;
-                        }
-                    }
-                    FileFunction::Legend { ref pos, ref index } => {
-                        write!(writer, "Legend,")?;
-                        pos.serialize_partial(writer)?;
-                        if let Some(ref i) = index {
-                            write!(writer, ",{}", *i)?;
-                        }
-                    }
-                    _ => unimplemented!(),
-                }
+                function.serialize_partial_with_context(writer, ctx)?;
             }
             FileAttribute::GenerationSoftware(ref gs) => {
                 write!(writer, "GenerationSoftware,")?;
-                gs.serialize_partial(writer)?;
+                gs.serialize_partial_with_context(writer, ctx)?;
             }
             FileAttribute::FilePolarity(ref p) => {
                 write!(writer, "FilePolarity,")?;
-                p.serialize_partial(writer)?;
+                p.serialize_partial_with_context(writer, ctx)?;
             }
             FileAttribute::Md5(ref hash) => write!(writer, "MD5,{}", hash)?,
-            _ => unimplemented!(),
+            FileAttribute::SameCoordinates(None) => write!(writer, "SameCoordinates")?,
+            FileAttribute::SameCoordinates(Some(ref ident)) => {
+                write!(writer, "SameCoordinates,{}", escape_field(ident))?
+            }
+            #[cfg(feature = "chrono")]
+            FileAttribute::CreationDate(ref date) => write!(
+                writer,
+                "CreationDate,{}",
+                date.to_rfc3339_opts(SecondsFormat::Secs, false)
+            )?,
+            #[cfg(feature = "chrono")]
+            FileAttribute::CreationDateWithOffset(ref date) => write!(
+                writer,
+                "CreationDate,{}",
+                date.to_rfc3339_opts(SecondsFormat::Secs, false)
+            )?,
+            FileAttribute::CreationDateText(ref date) => {
+                write!(writer, "CreationDate,{}", escape_field(date))?
+            }
+            #[cfg(feature = "uuid")]
+            FileAttribute::ProjectId {
+                ref id,
+                ref guid,
+                ref revision,
+            } => write!(
+                writer,
+                "ProjectId,{},{},{}",
+                escape_field(id),
+                guid.hyphenated(),
+                escape_field(revision)
+            )?,
+            FileAttribute::ProjectIdText {
+                ref id,
+                ref guid,
+                ref revision,
+            } => write!(
+                writer,
+                "ProjectId,{},{},{}",
+                escape_field(id),
+                escape_field(guid),
+                escape_field(revision)
+            )?,
+            FileAttribute::UserDefined {
+                ref name,
+                ref value,
+            } => {
+                // Names starting with a dot are reserved for standard attributes
+                validate_name(name)?;
+                if name.starts_with('.') {
+                    return Err(GerberError::NameError(name.to_string()));
+                }
+                write!(writer, "{}", name)?;
+                for field in value {
+                    write!(writer, ",{}", escape_field(field))?;
+                }
+            }
         };
         Ok(())
     }
@@ -90,14 +225,56 @@ impl<W: Write> PartialGerberCode<W> for FileAttribute {
 // ApertureAttribute
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum ApertureAttribute {
     ApertureFunction(ApertureFunction),
-    DrillTolerance { plus: f64, minus: f64 },
+    DrillTolerance { plus: Decimal, minus: Decimal },
+}
+
+impl ApertureAttribute {
+    /// The name of the attribute, e.g. `.AperFunction`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ApertureAttribute::ApertureFunction(_) => ".AperFunction",
+            ApertureAttribute::DrillTolerance { .. } => ".DrillTolerance",
+        }
+    }
+}
+
+impl<W: Write> PartialGerberCode<W> for ApertureAttribute {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            ApertureAttribute::ApertureFunction(ref function) => {
+                write!(writer, "AperFunction,")?;
+                function.serialize_partial_with_context(writer, ctx)?;
+            }
+            ApertureAttribute::DrillTolerance { plus, minus } => {
+                write!(writer, "DrillTolerance,")?;
+                plus.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, ",")?;
+                minus.serialize_partial_with_context(writer, ctx)?;
+            }
+        };
+        Ok(())
+    }
 }
 
 // Part
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum Part {
     /// Single PCB
     Single,
@@ -112,13 +289,17 @@ pub enum Part {
 }
 
 impl<W: Write> PartialGerberCode<W> for Part {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             Part::Single => write!(writer, "Single")?,
             Part::Array => write!(writer, "Array")?,
             Part::FabricationPanel => write!(writer, "FabricationPanel")?,
             Part::Coupon => write!(writer, "Coupon")?,
-            Part::Other(ref description) => write!(writer, "Other,{}", description)?,
+            Part::Other(ref description) => write!(writer, "Other,{}", escape_field(description))?,
         };
         Ok(())
     }
@@ -127,13 +308,21 @@ impl<W: Write> PartialGerberCode<W> for Part {
 // Position
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Position {
     Top,
     Bottom,
 }
 
 impl<W: Write> PartialGerberCode<W> for Position {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             Position::Top => write!(writer, "Top")?,
             Position::Bottom => write!(writer, "Bot")?,
@@ -145,6 +334,10 @@ impl<W: Write> PartialGerberCode<W> for Position {
 // ExtendedPosition
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum ExtendedPosition {
     Top,
     Inner,
@@ -152,7 +345,11 @@ pub enum ExtendedPosition {
 }
 
 impl<W: Write> PartialGerberCode<W> for ExtendedPosition {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             ExtendedPosition::Top => write!(writer, "Top")?,
             ExtendedPosition::Inner => write!(writer, "Inr")?,
@@ -165,6 +362,11 @@ impl<W: Write> PartialGerberCode<W> for ExtendedPosition {
 // CopperType
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum CopperType {
     Plane,
     Signal,
@@ -173,7 +375,11 @@ pub enum CopperType {
 }
 
 impl<W: Write> PartialGerberCode<W> for CopperType {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             CopperType::Plane => write!(writer, "Plane")?,
             CopperType::Signal => write!(writer, "Signal")?,
@@ -187,31 +393,77 @@ impl<W: Write> PartialGerberCode<W> for CopperType {
 // Drill
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Drill {
     ThroughHole,
     Blind,
     Buried,
 }
 
+impl<W: Write> PartialGerberCode<W> for Drill {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            Drill::ThroughHole => write!(writer, "PTH")?,
+            Drill::Blind => write!(writer, "Blind")?,
+            Drill::Buried => write!(writer, "Buried")?,
+        };
+        Ok(())
+    }
+}
+
 // DrillRouteType
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum DrillRouteType {
     Drill,
     Route,
     Mixed,
 }
 
+impl<W: Write> PartialGerberCode<W> for DrillRouteType {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            DrillRouteType::Drill => write!(writer, "Drill")?,
+            DrillRouteType::Route => write!(writer, "Rout")?,
+            DrillRouteType::Mixed => write!(writer, "Mixed")?,
+        };
+        Ok(())
+    }
+}
+
 // Profile
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Profile {
     Plated,
     NonPlated,
 }
 
 impl<W: Write> PartialGerberCode<W> for Profile {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             Profile::Plated => write!(writer, "P")?,
             Profile::NonPlated => write!(writer, "NP")?,
@@ -223,6 +475,11 @@ impl<W: Write> PartialGerberCode<W> for Profile {
 // FileFunction
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum FileFunction {
     Copper {
         layer: i32,
@@ -253,6 +510,7 @@ pub enum FileFunction {
         pos: Position,
         index: Option<i32>,
     },
+    /// Written as `Peelablemask`
     Peelablesoldermask {
         pos: Position,
         index: Option<i32>,
@@ -263,10 +521,13 @@ pub enum FileFunction {
     },
     Viatenting(Position),
     Viafill,
+    /// Written as `Heatsinkmask`
     Heatsink(Position),
     Paste(Position),
+    /// Written as `Keep-out`
     KeepOut(Position),
     Pads(Position),
+    /// V-cut scoring lines, written as `Vcut`
     Scoring(Position),
     Plated {
         from_layer: i32,
@@ -285,20 +546,189 @@ pub enum FileFunction {
     FabricationDrawing,
     ArrayDrawing,
     AssemblyDrawing(Position),
+    /// The components on the outer copper layer, added in X3
+    Component {
+        layer: i32,
+        pos: Position,
+    },
     Drawing(String),
     Other(String),
 }
 
+impl<W: Write> PartialGerberCode<W> for FileFunction {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            FileFunction::Copper {
+                ref layer,
+                ref pos,
+                ref copper_type,
+            } => {
+                write!(writer, "Copper,L{},", layer)?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref t) = *copper_type {
+                    write!(writer, ",")?;
+                    t.serialize_partial_with_context(writer, ctx)?;
+                }
+            }
+            FileFunction::Profile(ref plating) => {
+                write!(writer, "Profile,")?;
+                plating.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::Soldermask { ref pos, ref index } => {
+                write!(writer, "Soldermask,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref i) = index {
+                    write!(writer, ",{}", *i)?;
+                }
+            }
+            FileFunction::Legend { ref pos, ref index } => {
+                write!(writer, "Legend,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref i) = index {
+                    write!(writer, ",{}", *i)?;
+                }
+            }
+            FileFunction::Goldmask { ref pos, ref index } => {
+                write!(writer, "Goldmask,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref i) = index {
+                    write!(writer, ",{}", *i)?;
+                }
+            }
+            FileFunction::Silvermask { ref pos, ref index } => {
+                write!(writer, "Silvermask,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref i) = index {
+                    write!(writer, ",{}", *i)?;
+                }
+            }
+            FileFunction::Tinmask { ref pos, ref index } => {
+                write!(writer, "Tinmask,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref i) = index {
+                    write!(writer, ",{}", *i)?;
+                }
+            }
+            FileFunction::Carbonmask { ref pos, ref index } => {
+                write!(writer, "Carbonmask,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref i) = index {
+                    write!(writer, ",{}", *i)?;
+                }
+            }
+            FileFunction::Peelablesoldermask { ref pos, ref index } => {
+                write!(writer, "Peelablemask,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref i) = index {
+                    write!(writer, ",{}", *i)?;
+                }
+            }
+            FileFunction::Glue { ref pos, ref index } => {
+                write!(writer, "Glue,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref i) = index {
+                    write!(writer, ",{}", *i)?;
+                }
+            }
+            FileFunction::Viatenting(ref pos) => {
+                write!(writer, "Viatenting,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::Heatsink(ref pos) => {
+                write!(writer, "Heatsinkmask,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::Paste(ref pos) => {
+                write!(writer, "Paste,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::KeepOut(ref pos) => {
+                write!(writer, "Keep-out,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::Pads(ref pos) => {
+                write!(writer, "Pads,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::Scoring(ref pos) => {
+                write!(writer, "Vcut,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::Viafill => write!(writer, "Viafill")?,
+            FileFunction::Plated {
+                from_layer,
+                to_layer,
+                ref drill,
+                ref label,
+            } => {
+                write!(writer, "Plated,{},{},", from_layer, to_layer)?;
+                drill.serialize_partial_with_context(writer, ctx)?;
+                if let Some(ref l) = *label {
+                    write!(writer, ",")?;
+                    l.serialize_partial_with_context(writer, ctx)?;
+                }
+            }
+            FileFunction::NonPlated {
+                from_layer,
+                to_layer,
+                ref drill,
+                ref label,
+            } => {
+                write!(writer, "NonPlated,{},{},", from_layer, to_layer)?;
+                match *drill {
+                    Drill::ThroughHole => write!(writer, "NPTH")?,
+                    _ => drill.serialize_partial_with_context(writer, ctx)?,
+                }
+                if let Some(ref l) = *label {
+                    write!(writer, ",")?;
+                    l.serialize_partial_with_context(writer, ctx)?;
+                }
+            }
+            FileFunction::Drillmap => write!(writer, "Drillmap")?,
+            FileFunction::FabricationDrawing => write!(writer, "FabricationDrawing")?,
+            FileFunction::ArrayDrawing => write!(writer, "ArrayDrawing")?,
+            FileFunction::AssemblyDrawing(ref pos) => {
+                write!(writer, "AssemblyDrawing,")?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::Component { layer, ref pos } => {
+                check_component_attributes(ctx)?;
+                write!(writer, "Component,L{},", layer)?;
+                pos.serialize_partial_with_context(writer, ctx)?;
+            }
+            FileFunction::Drawing(ref description) => {
+                write!(writer, "OtherDrawing,{}", escape_field(description))?;
+            }
+            FileFunction::Other(ref description) => {
+                write!(writer, "Other,{}", escape_field(description))?;
+            }
+        }
+        Ok(())
+    }
+}
+
 // FilePolarity
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum FilePolarity {
     Positive,
     Negative,
 }
 
 impl<W: Write> PartialGerberCode<W> for FilePolarity {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             FilePolarity::Positive => write!(writer, "Positive")?,
             FilePolarity::Negative => write!(writer, "Negative")?,
@@ -310,6 +740,10 @@ impl<W: Write> PartialGerberCode<W> for FilePolarity {
 // GenerationSoftware
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct GenerationSoftware {
     pub vendor: String,
     pub application: String,
@@ -327,11 +761,20 @@ impl GenerationSoftware {
 }
 
 impl<W: Write> PartialGerberCode<W> for GenerationSoftware {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
-        match self.version {
-            Some(ref v) => write!(writer, "{},{},{}", self.vendor, self.application, v)?,
-            None => write!(writer, "{},{}", self.vendor, self.application)?,
-        };
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        write!(
+            writer,
+            "{},{}",
+            escape_field(&self.vendor),
+            escape_field(&self.application)
+        )?;
+        if let Some(ref v) = self.version {
+            write!(writer, ",{}", escape_field(v))?;
+        }
         Ok(())
     }
 }
@@ -339,6 +782,11 @@ impl<W: Write> PartialGerberCode<W> for GenerationSoftware {
 // ApertureFunction
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum ApertureFunction {
     // Only valid for layers with file function plated or non-plated
     ViaDrill,
@@ -377,6 +825,12 @@ pub enum ApertureFunction {
     Border,
     OtherCopper(String),
 
+    // Only valid for layers with file function component, added in X3
+    /// The component reference point
+    ComponentMain,
+    /// The reference points of the component pins
+    ComponentPin,
+
     // All layers
     Profile,
     NonMaterial,
@@ -384,27 +838,175 @@ pub enum ApertureFunction {
     Other(String),
 }
 
+impl<W: Write> PartialGerberCode<W> for ApertureFunction {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            ApertureFunction::ViaDrill => write!(writer, "ViaDrill")?,
+            ApertureFunction::BackDrill => write!(writer, "BackDrill")?,
+            ApertureFunction::ComponentDrill { press_fit } => {
+                write!(writer, "ComponentDrill")?;
+                if press_fit == Some(true) {
+                    write!(writer, ",PressFit")?;
+                }
+            }
+            ApertureFunction::CastellatedDrill => write!(writer, "CastellatedDrill")?,
+            ApertureFunction::MechanicalDrill { ref function } => {
+                write!(writer, "MechanicalDrill")?;
+                if let Some(ref function) = *function {
+                    write!(writer, ",")?;
+                    function.serialize_partial_with_context(writer, ctx)?;
+                }
+            }
+            ApertureFunction::Slot => write!(writer, "Slot")?,
+            ApertureFunction::CutOut => write!(writer, "CutOut")?,
+            ApertureFunction::Cavity => write!(writer, "Cavity")?,
+            ApertureFunction::OtherDrill(ref s) => {
+                write!(writer, "OtherDrill,{}", escape_field(s))?
+            }
+            ApertureFunction::ComponentPad { press_fit } => {
+                write!(writer, "ComponentPad")?;
+                if press_fit == Some(true) {
+                    write!(writer, ",PressFit")?;
+                }
+            }
+            ApertureFunction::SmdPad(ref t) => {
+                write!(writer, "SMDPad,")?;
+                t.serialize_partial_with_context(writer, ctx)?;
+            }
+            ApertureFunction::BgaPad(ref t) => {
+                write!(writer, "BGAPad,")?;
+                t.serialize_partial_with_context(writer, ctx)?;
+            }
+            ApertureFunction::ConnectorPad => write!(writer, "ConnectorPad")?,
+            ApertureFunction::HeatsinkPad => write!(writer, "HeatsinkPad")?,
+            ApertureFunction::ViaPad => write!(writer, "ViaPad")?,
+            ApertureFunction::TestPad => write!(writer, "TestPad")?,
+            ApertureFunction::CastellatedPad => write!(writer, "CastellatedPad")?,
+            ApertureFunction::FiducialPad(ref scope) => {
+                write!(writer, "FiducialPad,")?;
+                scope.serialize_partial_with_context(writer, ctx)?;
+            }
+            ApertureFunction::ThermalReliefPad => write!(writer, "ThermalReliefPad")?,
+            ApertureFunction::WasherPad => write!(writer, "WasherPad")?,
+            ApertureFunction::AntiPad => write!(writer, "AntiPad")?,
+            ApertureFunction::OtherPad(ref s) => write!(writer, "OtherPad,{}", escape_field(s))?,
+            ApertureFunction::Conductor => write!(writer, "Conductor")?,
+            ApertureFunction::NonConductor => write!(writer, "NonConductor")?,
+            ApertureFunction::CopperBalancing => write!(writer, "CopperBalancing")?,
+            ApertureFunction::Border => write!(writer, "Border")?,
+            ApertureFunction::OtherCopper(ref s) => {
+                write!(writer, "OtherCopper,{}", escape_field(s))?
+            }
+            ApertureFunction::ComponentMain => {
+                check_component_attributes(ctx)?;
+                write!(writer, "ComponentMain")?;
+            }
+            ApertureFunction::ComponentPin => {
+                check_component_attributes(ctx)?;
+                write!(writer, "ComponentPin")?;
+            }
+            ApertureFunction::Profile => write!(writer, "Profile")?,
+            ApertureFunction::NonMaterial => write!(writer, "NonMaterial")?,
+            ApertureFunction::Material => write!(writer, "Material")?,
+            ApertureFunction::Other(ref s) => write!(writer, "Other,{}", escape_field(s))?,
+        };
+        Ok(())
+    }
+}
+
+/// Return an error if the spec target does not support the component
+/// attributes of X3.
+fn check_component_attributes(ctx: &SerializationContext) -> GerberResult<()> {
+    if ctx.spec_target.supports_component_attributes() {
+        Ok(())
+    } else {
+        Err(GerberError::UnsupportedError(
+            "Component attributes require X3".into(),
+        ))
+    }
+}
+
 // DrillFunction
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum DrillFunction {
     BreakOut,
     Tooling,
     Other,
 }
 
+impl<W: Write> PartialGerberCode<W> for DrillFunction {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            DrillFunction::BreakOut => write!(writer, "BreakOut")?,
+            DrillFunction::Tooling => write!(writer, "Tooling")?,
+            DrillFunction::Other => write!(writer, "Other")?,
+        };
+        Ok(())
+    }
+}
+
 // SmdPadType
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum SmdPadType {
     CopperDefined,
     SoldermaskDefined,
 }
 
+impl<W: Write> PartialGerberCode<W> for SmdPadType {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            SmdPadType::CopperDefined => write!(writer, "CuDef")?,
+            SmdPadType::SoldermaskDefined => write!(writer, "SMDef")?,
+        };
+        Ok(())
+    }
+}
+
 // FiducialScope
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum FiducialScope {
     Global,
     Local,
 }
+
+impl<W: Write> PartialGerberCode<W> for FiducialScope {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            FiducialScope::Global => write!(writer, "Global")?,
+            FiducialScope::Local => write!(writer, "Local")?,
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,436 @@
+//! Bounding boxes of apertures and aperture macros.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::decimal::from_decimal;
+use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::Aperture;
+use crate::macros::{ApertureMacro, MacroContent, MacroDecimal};
+
+/// An axis aligned bounding box, in the unit of the file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl BoundingBox {
+    pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        BoundingBox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        }
+    }
+
+    /// The empty box at a single point.
+    pub fn point(x: f64, y: f64) -> Self {
+        BoundingBox::new(x, y, x, y)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    /// The smallest box that contains both boxes.
+    pub fn union(&self, other: &BoundingBox) -> Self {
+        BoundingBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    /// The box moved by the given offset, e.g. to the position of a flash.
+    pub fn translated(&self, dx: f64, dy: f64) -> Self {
+        BoundingBox::new(
+            self.min_x + dx,
+            self.min_y + dy,
+            self.max_x + dx,
+            self.max_y + dy,
+        )
+    }
+}
+
+/// Collects points into a bounding box.
+#[derive(Default)]
+struct Extents(Option<BoundingBox>);
+
+impl Extents {
+    fn point(&mut self, (x, y): (f64, f64)) {
+        let point = BoundingBox::point(x, y);
+        self.0 = Some(self.0.map_or(point, |b| b.union(&point)));
+    }
+
+    fn circle(&mut self, (x, y): (f64, f64), diameter: f64) {
+        let r = diameter.abs() / 2.0;
+        self.point((x - r, y - r));
+        self.point((x + r, y + r));
+    }
+
+    /// The vertices of a regular polygon, the first one at `angle` degrees.
+    fn polygon(&mut self, center: (f64, f64), diameter: f64, vertices: u8, angle: f64) {
+        let r = diameter / 2.0;
+        for k in 0..vertices {
+            let a = angle.to_radians() + 2.0 * PI * f64::from(k) / f64::from(vertices);
+            self.point((center.0 + r * a.cos(), center.1 + r * a.sin()));
+        }
+    }
+}
+
+/// Rotate a point around the origin, counterclockwise by degrees.
+fn rotate((x, y): (f64, f64), degrees: f64) -> (f64, f64) {
+    if degrees == 0.0 {
+        return (x, y);
+    }
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+impl Aperture {
+    /// The bounding box of a standard aperture, relative to the flash
+    /// position. Holes are ignored.
+    ///
+    /// Macro apertures return `None`, their extents are computed by
+    /// [`ApertureMacro::bounding_box`] with the arguments of the aperture.
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        let mut extents = Extents::default();
+        match *self {
+            Aperture::Circle(ref circle) => {
+                extents.circle((0.0, 0.0), from_decimal(circle.diameter));
+            }
+            Aperture::Rectangle(ref rect) => {
+                let (x, y) = (from_decimal(rect.x) / 2.0, from_decimal(rect.y) / 2.0);
+                extents.point((-x, -y));
+                extents.point((x, y));
+            }
+            Aperture::Obround(ref obround) => {
+                let (x, y) = (from_decimal(obround.x) / 2.0, from_decimal(obround.y) / 2.0);
+                extents.point((-x, -y));
+                extents.point((x, y));
+            }
+            Aperture::Polygon(ref polygon) => extents.polygon(
+                (0.0, 0.0),
+                from_decimal(polygon.diameter),
+                polygon.vertices,
+                polygon.rotation.map_or(0.0, from_decimal),
+            ),
+            Aperture::Other(_) => {}
+        }
+        extents.0
+    }
+}
+
+impl ApertureMacro {
+    /// The bounding box of the macro with the given arguments, relative to
+    /// the flash position.
+    ///
+    /// The arguments set the variables `$1`, `$2` and so on. Only primitives
+    /// with exposure on are taken into account, and the box of a thermal is
+    /// the box of its outer circle. Returns `None` if the macro has no
+    /// exposed primitives, and a `MissingDataError` if it uses a variable
+    /// that is not set.
+    pub fn bounding_box(&self, args: &[f64]) -> GerberResult<Option<BoundingBox>> {
+        let mut variables: HashMap<u32, f64> = (1..).zip(args.iter().cloned()).collect();
+        let mut extents = Extents::default();
+        for content in &self.content {
+            let value = |decimal: &MacroDecimal| resolve(decimal, &variables);
+            let point = |(x, y): &(MacroDecimal, MacroDecimal)| -> GerberResult<(f64, f64)> {
+                Ok((value(x)?, value(y)?))
+            };
+            match content {
+                MacroContent::Circle(c) if c.exposure => {
+                    let angle = c.angle.as_ref().map_or(Ok(0.0), value)?;
+                    extents.circle(rotate(point(&c.center)?, angle), value(&c.diameter)?);
+                }
+                MacroContent::VectorLine(l) if l.exposure => {
+                    let ((sx, sy), (ex, ey)) = (point(&l.start)?, point(&l.end)?);
+                    let length = (ex - sx).hypot(ey - sy);
+                    if length > 0.0 {
+                        let half = value(&l.width)? / 2.0;
+                        let (nx, ny) = (-(ey - sy) / length * half, (ex - sx) / length * half);
+                        let angle = value(&l.angle)?;
+                        for (x, y) in [(sx, sy), (ex, ey)] {
+                            extents.point(rotate((x + nx, y + ny), angle));
+                            extents.point(rotate((x - nx, y - ny), angle));
+                        }
+                    }
+                }
+                MacroContent::CenterLine(l) if l.exposure => {
+                    let (w, h) = point(&l.dimensions)?;
+                    let (cx, cy) = point(&l.center)?;
+                    let angle = value(&l.angle)?;
+                    for (dx, dy) in [(-w, -h), (w, -h), (w, h), (-w, h)] {
+                        extents.point(rotate((cx + dx / 2.0, cy + dy / 2.0), angle));
+                    }
+                }
+                MacroContent::Outline(o) if o.exposure => {
+                    let angle = value(&o.angle)?;
+                    for p in o.points.iter() {
+                        extents.point(rotate(point(p)?, angle));
+                    }
+                }
+                MacroContent::Polygon(p) if p.exposure => {
+                    // The center is at the origin if the polygon is rotated
+                    let angle = value(&p.angle)?;
+                    let center = rotate(point(&p.center)?, angle);
+                    extents.polygon(center, value(&p.diameter)?, p.vertices, angle);
+                }
+                MacroContent::Moire(m) => {
+                    let (cx, cy) = point(&m.center)?;
+                    let angle = value(&m.angle)?;
+                    extents.circle(rotate((cx, cy), angle), value(&m.diameter)?);
+                    let length = value(&m.cross_hair_length)? / 2.0;
+                    let thickness = value(&m.cross_hair_thickness)? / 2.0;
+                    for (dx, dy) in [(length, thickness), (thickness, length)] {
+                        extents.point(rotate((cx - dx, cy - dy), angle));
+                        extents.point(rotate((cx + dx, cy - dy), angle));
+                        extents.point(rotate((cx + dx, cy + dy), angle));
+                        extents.point(rotate((cx - dx, cy + dy), angle));
+                    }
+                }
+                MacroContent::Thermal(t) => {
+                    let angle = value(&t.angle)?;
+                    extents.circle(rotate(point(&t.center)?, angle), value(&t.outer_diameter)?);
+                }
+                MacroContent::VariableDefinition(v) => {
+                    let result = evaluate(v.expression(), &variables)?;
+                    variables.insert(v.number(), result);
+                }
+                _ => {}
+            }
+        }
+        Ok(extents.0)
+    }
+}
+
+fn resolve(decimal: &MacroDecimal, variables: &HashMap<u32, f64>) -> GerberResult<f64> {
+    match *decimal {
+        MacroDecimal::Value(v) => Ok(from_decimal(v)),
+        MacroDecimal::Variable(n) => variable(n, variables),
+    }
+}
+
+fn variable(number: u32, variables: &HashMap<u32, f64>) -> GerberResult<f64> {
+    variables.get(&number).cloned().ok_or_else(|| {
+        GerberError::MissingDataError(format!("Macro variable ${} is not set", number))
+    })
+}
+
+/// Evaluate an arithmetic expression of a variable definition, e.g.
+/// `$1x2+(0.5-$2)/4`.
+fn evaluate(expression: &str, variables: &HashMap<u32, f64>) -> GerberResult<f64> {
+    let mut parser = Parser {
+        chars: expression.chars().peekable(),
+        variables,
+        expression,
+    };
+    let value = parser.sum()?;
+    match parser.chars.next() {
+        None => Ok(value),
+        Some(_) => Err(parser.invalid()),
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    variables: &'a HashMap<u32, f64>,
+    expression: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn invalid(&self) -> GerberError {
+        GerberError::ConversionError(format!("Invalid macro expression {:?}", self.expression))
+    }
+
+    fn sum(&mut self) -> GerberResult<f64> {
+        let mut value = self.product()?;
+        loop {
+            match self.chars.peek() {
+                Some('+') => {
+                    self.chars.next();
+                    value += self.product()?;
+                }
+                Some('-') => {
+                    self.chars.next();
+                    value -= self.product()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn product(&mut self) -> GerberResult<f64> {
+        let mut value = self.factor()?;
+        loop {
+            match self.chars.peek() {
+                Some('x') | Some('X') => {
+                    self.chars.next();
+                    value *= self.factor()?;
+                }
+                Some('/') => {
+                    self.chars.next();
+                    value /= self.factor()?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn factor(&mut self) -> GerberResult<f64> {
+        match self.chars.next() {
+            Some('+') => self.factor(),
+            Some('-') => self.factor().map(|v| -v),
+            Some('(') => {
+                let value = self.sum()?;
+                match self.chars.next() {
+                    Some(')') => Ok(value),
+                    _ => Err(self.invalid()),
+                }
+            }
+            Some('$') => {
+                let number = self.take_while(|c| c.is_ascii_digit());
+                let number = number.parse().map_err(|_| self.invalid())?;
+                variable(number, self.variables)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = c.to_string();
+                number.push_str(&self.take_while(|c| c.is_ascii_digit() || c == '.'));
+                number.parse().map_err(|_| self.invalid())
+            }
+            _ => Err(self.invalid()),
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> String {
+        let mut taken = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !predicate(c) {
+                break;
+            }
+            taken.push(c);
+            self.chars.next();
+        }
+        taken
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::extended_codes::{Circle, Polygon, Rectangular};
+    use crate::macros::{
+        CenterLinePrimitive, CirclePrimitive, OutlinePrimitive, VariableDefinition,
+        VectorLinePrimitive,
+    };
+
+    fn assert_close(actual: BoundingBox, expected: BoundingBox) {
+        for (a, e) in [
+            (actual.min_x, expected.min_x),
+            (actual.min_y, expected.min_y),
+            (actual.max_x, expected.max_x),
+            (actual.max_y, expected.max_y),
+        ] {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_aperture_bounding_box() {
+        assert_eq!(
+            Aperture::Circle(Circle::with_hole(1.0, 0.5)).bounding_box(),
+            Some(BoundingBox::new(-0.5, -0.5, 0.5, 0.5))
+        );
+        assert_eq!(
+            Aperture::Rectangle(Rectangular::new(2.0, 1.0)).bounding_box(),
+            Some(BoundingBox::new(-1.0, -0.5, 1.0, 0.5))
+        );
+        // A square with its vertices on the axes
+        assert_close(
+            Aperture::Polygon(Polygon::new(2.0, 4))
+                .bounding_box()
+                .unwrap(),
+            BoundingBox::new(-1.0, -1.0, 1.0, 1.0),
+        );
+        let rotated = Aperture::Polygon(Polygon::new(2.0, 4).with_rotation(45.0));
+        let half = 0.5f64.sqrt();
+        assert_close(
+            rotated.bounding_box().unwrap(),
+            BoundingBox::new(-half, -half, half, half),
+        );
+        assert_eq!(Aperture::Other("RR".into()).bounding_box(), None);
+    }
+
+    #[test]
+    fn test_macro_bounding_box() {
+        let am = ApertureMacro::new("M")
+            .add_content(
+                CirclePrimitive::new(MacroDecimal::Variable(1))
+                    .centered_at((1.0.into(), 0.0.into())),
+            )
+            .add_content(VariableDefinition::new(3, "$2x2"))
+            .add_content(CenterLinePrimitive::new((
+                MacroDecimal::Variable(3),
+                1.0.into(),
+            )))
+            .add_content(CirclePrimitive::new(10.0.into()).exposure_on(false));
+        assert_close(
+            am.bounding_box(&[1.0, 1.5]).unwrap().unwrap(),
+            BoundingBox::new(-1.5, -0.5, 1.5, 0.5),
+        );
+        assert!(matches!(
+            am.bounding_box(&[1.0]),
+            Err(GerberError::MissingDataError(_))
+        ));
+
+        // Rotated by 90 degrees around the origin
+        let am = ApertureMacro::new("L").add_content(
+            VectorLinePrimitive::new((0.0.into(), 0.0.into()), (2.0.into(), 0.0.into()))
+                .with_width(0.5.into())
+                .with_angle(90.0.into()),
+        );
+        assert_close(
+            am.bounding_box(&[]).unwrap().unwrap(),
+            BoundingBox::new(-0.25, 0.0, 0.25, 2.0),
+        );
+
+        let am = ApertureMacro::new("O").add_content(
+            OutlinePrimitive::new()
+                .add_point((0.0.into(), 0.0.into()))
+                .add_point((1.0.into(), 2.0.into()))
+                .add_point((0.0.into(), 0.0.into())),
+        );
+        assert_eq!(
+            am.bounding_box(&[]).unwrap(),
+            Some(BoundingBox::new(0.0, 0.0, 1.0, 2.0))
+        );
+        assert_eq!(ApertureMacro::new("E").bounding_box(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let variables: HashMap<u32, f64> = [(1, 2.0), (2, 0.5)].iter().cloned().collect();
+        let eval = |expression| evaluate(expression, &variables);
+        assert_eq!(eval("1.5").unwrap(), 1.5);
+        assert_eq!(eval("$1x2+$2").unwrap(), 4.5);
+        assert_eq!(eval("$1+2x$2").unwrap(), 3.0);
+        assert_eq!(eval("($1+2)x$2").unwrap(), 2.0);
+        assert_eq!(eval("-$1/4").unwrap(), -0.5);
+        assert_eq!(eval("1-2-3").unwrap(), -4.0);
+        assert!(eval("$3").is_err());
+        assert!(eval("(1+2").is_err());
+        assert!(eval("1+").is_err());
+    }
+}
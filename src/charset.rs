@@ -0,0 +1,134 @@
+//! Validation and escaping of names and strings according to the character
+//! set rules of the spec.
+
+use std::borrow::Cow;
+
+use crate::errors::{GerberError, GerberResult};
+
+/// The maximum length of a name in bytes.
+pub const MAX_NAME_LENGTH: usize = 127;
+
+/// Check whether a string is a valid name, e.g. an aperture macro or
+/// attribute name.
+///
+/// Names start with a letter, `.`, `_` or `$`, followed by letters, digits,
+/// `.` or `_`. They are at most 127 bytes long.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    let first_valid = match chars.next() {
+        Some(c) => c.is_ascii_alphabetic() || c == '.' || c == '_' || c == '$',
+        None => false,
+    };
+    first_valid
+        && name.len() <= MAX_NAME_LENGTH
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+}
+
+/// Return an error if the string is not a valid name.
+pub fn validate_name(name: &str) -> GerberResult<()> {
+    if is_valid_name(name) {
+        Ok(())
+    } else {
+        Err(GerberError::NameError(name.into()))
+    }
+}
+
+/// Whether a character must be escaped in a field.
+fn needs_escape(c: char) -> bool {
+    matches!(c, '%' | '*' | ',' | '\\') || c.is_control()
+}
+
+/// Escape a string for use as a field, e.g. an attribute value.
+///
+/// The reserved characters `%` and `*`, the field separator `,`, the escape
+/// character `\` and control characters are replaced by `\uXXXX` escapes.
+/// All other characters, including those outside of the basic multilingual
+/// plane, are kept as UTF-8. Strings without characters to escape are
+/// returned unchanged.
+pub fn escape_field(value: &str) -> Cow<'_, str> {
+    if !value.chars().any(needs_escape) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        if needs_escape(c) {
+            escaped.push_str(&format!("\\u{:04X}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Reverse [`escape_field`], replacing `\uXXXX` escapes by the characters.
+///
+/// Returns a `ConversionError` for incomplete escapes or unpaired
+/// surrogates.
+pub fn unescape_field(value: &str) -> GerberResult<Cow<'_, str>> {
+    if !value.contains('\\') {
+        return Ok(Cow::Borrowed(value));
+    }
+    let invalid = || GerberError::ConversionError(format!("Invalid escape in {:?}", value));
+    let mut units: Vec<u16> = Vec::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(c) = rest.chars().next() {
+        if c == '\\' {
+            let unit = rest
+                .get(2..6)
+                .filter(|h| rest[1..].starts_with('u') && h.chars().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|h| u16::from_str_radix(h, 16).ok())
+                .ok_or_else(invalid)?;
+            units.push(unit);
+            rest = &rest[6..];
+        } else {
+            let mut buf = [0; 2];
+            units.extend_from_slice(c.encode_utf16(&mut buf));
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    String::from_utf16(&units)
+        .map(Cow::Owned)
+        .map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("DOT"));
+        assert!(is_valid_name(".AperFunction"));
+        assert!(is_valid_name("_custom.attr_2"));
+        assert!(is_valid_name("$1"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("1ABC"));
+        assert!(!is_valid_name("A-B"));
+        assert!(!is_valid_name("A$"));
+        assert!(!is_valid_name("Ä"));
+        assert!(is_valid_name(&"A".repeat(127)));
+        assert!(!is_valid_name(&"A".repeat(128)));
+        assert!(validate_name("A B").is_err());
+    }
+
+    #[test]
+    fn test_escape_field() {
+        assert!(matches!(escape_field("plain text"), Cow::Borrowed(_)));
+        assert_eq!(escape_field("Grüße"), "Grüße");
+        assert_eq!(escape_field("🎉,🎉"), "🎉\\u002C🎉");
+        assert_eq!(escape_field("a,b"), "a\\u002Cb");
+        assert_eq!(escape_field("50%*"), "50\\u0025\\u002A");
+        assert_eq!(escape_field("C:\\tmp\n"), "C:\\u005Ctmp\\u000A");
+    }
+
+    #[test]
+    fn test_unescape_field() {
+        assert!(matches!(unescape_field("plain").unwrap(), Cow::Borrowed(_)));
+        for value in ["a,b", "50%*", "C:\\tmp\n", "Grüße 🎉"] {
+            assert_eq!(unescape_field(&escape_field(value)).unwrap(), value);
+        }
+        assert!(unescape_field("a\\u00").is_err());
+        assert!(unescape_field("a\\x0041").is_err());
+        assert!(unescape_field("\\uD83C").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Consistency checks over command streams.
+
+use std::fmt;
+use std::io;
+
+use crate::coordinates::{
+    CoordinateFormat, CoordinateNumber, CoordinateOffset, Coordinates, ZeroSuppression,
+};
+use crate::deprecated::DeprecatedCode;
+use crate::function_codes::{DCode, Operation};
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// A coordinate format problem found by [`check_coordinate_format`] or
+/// [`check_coordinate_overflow`].
+///
+/// The index refers to the offending command in the checked stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FormatIssue {
+    /// An operation appears before the FS command.
+    MissingFormat { index: usize },
+    /// There is more than one FS command.
+    DuplicateFormat { index: usize },
+    /// An operation uses a different format than the FS command.
+    MismatchedFormat {
+        index: usize,
+        expected: CoordinateFormat,
+        found: CoordinateFormat,
+    },
+    /// A coordinate or offset of an operation does not fit into the format.
+    Overflow {
+        index: usize,
+        value: CoordinateNumber,
+    },
+}
+
+impl FormatIssue {
+    pub fn index(&self) -> usize {
+        match *self {
+            FormatIssue::MissingFormat { index }
+            | FormatIssue::DuplicateFormat { index }
+            | FormatIssue::MismatchedFormat { index, .. }
+            | FormatIssue::Overflow { index, .. } => index,
+        }
+    }
+}
+
+impl fmt::Display for FormatIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FormatIssue::MissingFormat { index } => {
+                write!(f, "Command {}: operation before the FS command", index)
+            }
+            FormatIssue::DuplicateFormat { index } => {
+                write!(f, "Command {}: duplicate FS command", index)
+            }
+            FormatIssue::MismatchedFormat {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Command {}: format {}.{} differs from FS format {}.{}",
+                index, found.integer, found.decimal, expected.integer, expected.decimal
+            ),
+            FormatIssue::Overflow { index, value } => write!(
+                f,
+                "Command {}: {} does not fit into the coordinate format",
+                index, value
+            ),
+        }
+    }
+}
+
+/// Check that a command stream contains exactly one FS command before the
+/// first operation, and that all coordinates and offsets use its format.
+///
+/// Returns all issues found, an empty list means the stream is consistent.
+pub fn check_coordinate_format(commands: &[Command]) -> Vec<FormatIssue> {
+    let mut issues = Vec::new();
+    let mut format: Option<CoordinateFormat> = None;
+    for (index, command) in commands.iter().enumerate() {
+        match command {
+            Command::ExtendedCode(ExtendedCode::CoordinateFormat(cf)) => {
+                check_format(index, *cf, &mut format, &mut issues);
+            }
+            Command::ExtendedCodeBlock(block) => {
+                for code in &block.codes {
+                    if let ExtendedCode::CoordinateFormat(cf) = code {
+                        check_format(index, *cf, &mut format, &mut issues);
+                    }
+                }
+            }
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) => {
+                let (coords, offset) = match op {
+                    Operation::Interpolate(coords, offset) => (coords, offset.as_ref()),
+                    Operation::Move(coords) | Operation::Flash(coords) => (coords, None),
+                };
+                check_operation(index, format, coords, offset, &mut issues);
+            }
+            Command::Deprecated(DeprecatedCode::CoordinatesOnly(coords)) => {
+                check_operation(index, format, coords, None, &mut issues);
+            }
+            Command::Deprecated(DeprecatedCode::CoordinatesWithOffset(coords, offset)) => {
+                check_operation(index, format, coords, Some(offset), &mut issues);
+            }
+            _ => {}
+        }
+    }
+    issues
+}
+
+/// Check that all coordinates and offsets of operations fit into the given
+/// format, before anything is written.
+///
+/// Returns an `Overflow` issue for the first value out of range of each
+/// offending command, an empty list means the stream can be serialized.
+pub fn check_coordinate_overflow(
+    commands: &[Command],
+    format: &CoordinateFormat,
+) -> Vec<FormatIssue> {
+    let mut issues = Vec::new();
+    for (index, command) in commands.iter().enumerate() {
+        let (coords, offset) = match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) => match op {
+                Operation::Interpolate(coords, offset) => (coords, offset.as_ref()),
+                Operation::Move(coords) | Operation::Flash(coords) => (coords, None),
+            },
+            Command::Deprecated(DeprecatedCode::CoordinatesOnly(coords)) => (coords, None),
+            Command::Deprecated(DeprecatedCode::CoordinatesWithOffset(coords, offset)) => {
+                (coords, Some(offset))
+            }
+            _ => continue,
+        };
+        let values = [
+            coords.x,
+            coords.y,
+            offset.and_then(|o| o.x),
+            offset.and_then(|o| o.y),
+        ];
+        let overflow = values.iter().flatten().copied().find(|value| {
+            value
+                .write_gerber(&mut io::sink(), format, ZeroSuppression::Leading)
+                .is_err()
+        });
+        if let Some(value) = overflow {
+            issues.push(FormatIssue::Overflow { index, value });
+        }
+    }
+    issues
+}
+
+fn check_format(
+    index: usize,
+    cf: CoordinateFormat,
+    format: &mut Option<CoordinateFormat>,
+    issues: &mut Vec<FormatIssue>,
+) {
+    if format.is_some() {
+        issues.push(FormatIssue::DuplicateFormat { index });
+    } else {
+        *format = Some(cf);
+    }
+}
+
+fn check_operation(
+    index: usize,
+    format: Option<CoordinateFormat>,
+    coords: &Coordinates,
+    offset: Option<&CoordinateOffset>,
+    issues: &mut Vec<FormatIssue>,
+) {
+    let expected = match format {
+        Some(cf) => cf,
+        None => {
+            issues.push(FormatIssue::MissingFormat { index });
+            return;
+        }
+    };
+    let found = std::iter::once(coords.format)
+        .chain(offset.map(|o| o.format))
+        .find(|f| *f != expected);
+    if let Some(found) = found {
+        issues.push(FormatIssue::MismatchedFormat {
+            index,
+            expected,
+            found,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_consistent_format() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(0, 0, cf))).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, 1, cf),
+                Some(CoordinateOffset::new(1, 0, cf)),
+            ))
+            .into(),
+        ];
+        assert!(check_coordinate_format(&commands).is_empty());
+    }
+
+    #[test]
+    fn test_inconsistent_format() {
+        let cf = CoordinateFormat::new(2, 4);
+        let other = CoordinateFormat::new(3, 5);
+        let commands: Vec<Command> = vec![
+            DCode::Operation(Operation::Move(Coordinates::new(0, 0, cf))).into(),
+            ExtendedCode::CoordinateFormat(cf).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, 1, cf),
+                Some(CoordinateOffset::new(1, 0, other)),
+            ))
+            .into(),
+            ExtendedCode::CoordinateFormat(cf).into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(0, 0, cf))).into(),
+        ];
+        let issues = check_coordinate_format(&commands);
+        assert_eq!(
+            issues,
+            vec![
+                FormatIssue::MissingFormat { index: 0 },
+                FormatIssue::MismatchedFormat {
+                    index: 2,
+                    expected: cf,
+                    found: other,
+                },
+                FormatIssue::DuplicateFormat { index: 3 },
+            ]
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "Command 2: format 3.5 differs from FS format 2.4"
+        );
+    }
+
+    #[test]
+    fn test_coordinate_overflow() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(99, 0, cf))).into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(0, 100, cf))).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(0, 0, cf),
+                Some(CoordinateOffset::new(-120, 0, cf)),
+            ))
+            .into(),
+        ];
+        let issues = check_coordinate_overflow(&commands, &cf);
+        assert_eq!(
+            issues,
+            vec![
+                FormatIssue::Overflow {
+                    index: 2,
+                    value: 100.into(),
+                },
+                FormatIssue::Overflow {
+                    index: 3,
+                    value: (-120).into(),
+                },
+            ]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "Command 2: 100 does not fit into the coordinate format"
+        );
+        assert!(check_coordinate_overflow(&commands, &CoordinateFormat::new(3, 4)).is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+//! MD5 checksums for the `.MD5` file attribute, requires the `md5` feature.
+
+use std::io::Write;
+
+use crate::attributes::FileAttribute;
+use crate::context::SerializationContext;
+use crate::errors::GerberResult;
+use crate::function_codes::MCode;
+use crate::traits::GerberCode;
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// Compute the MD5 checksum of serialized Gerber code as defined by the spec
+/// for the `.MD5` file attribute. Line endings (CR and LF) are not part of
+/// the checksum.
+pub fn gerber_md5(code: &[u8]) -> String {
+    let mut context = md5::Context::new();
+    for chunk in code.split(|b| *b == b'\r' || *b == b'\n') {
+        context.consume(chunk);
+    }
+    format!("{:x}", context.compute())
+}
+
+/// Serialize the commands and add a `%TF.MD5*%` attribute with the checksum
+/// of all code before it.
+///
+/// The attribute is written before a final `M02`, or at the end if there is
+/// none. Existing MD5 attributes in the commands are left out.
+pub fn serialize_with_md5<W: Write>(
+    commands: &[Command],
+    writer: &mut W,
+    ctx: &SerializationContext,
+) -> GerberResult<()> {
+    let (body, end_of_file) = match commands.split_last() {
+        Some((Command::FunctionCode(FunctionCode::MCode(MCode::EndOfFile)), body)) => (body, true),
+        _ => (commands, false),
+    };
+    let mut buf = Vec::new();
+    for command in body {
+        if !matches!(
+            command,
+            Command::ExtendedCode(ExtendedCode::FileAttribute(FileAttribute::Md5(_)))
+        ) {
+            command.serialize_with_context(&mut buf, ctx)?;
+        }
+    }
+    writer.write_all(&buf)?;
+    let md5 = ExtendedCode::FileAttribute(FileAttribute::Md5(gerber_md5(&buf)));
+    md5.serialize_with_context(writer, ctx)?;
+    if end_of_file {
+        MCode::EndOfFile.serialize_with_context(writer, ctx)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::context::LineEnding;
+    use crate::extended_codes::Unit;
+    use crate::function_codes::GCode;
+
+    #[test]
+    fn test_gerber_md5() {
+        let expected = "30a0e058180082713f77d37211cb4a24";
+        assert_eq!(gerber_md5(b"G04 test*\n%MOMM*%\n"), expected);
+        assert_eq!(gerber_md5(b"G04 test*\r\n%MOMM*%\r\n"), expected);
+    }
+
+    #[test]
+    fn test_serialize_with_md5() {
+        let commands: Vec<Command> = vec![
+            GCode::Comment("test".into()).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            ExtendedCode::FileAttribute(FileAttribute::Md5("outdated".into())).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let mut buf = Vec::new();
+        serialize_with_md5(&commands, &mut buf, &SerializationContext::new()).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "G04 test*\n%MOMM*%\n%TF.MD5,30a0e058180082713f77d37211cb4a24*%\nM02*\n"
+        );
+
+        let mut codegen = SerializationContext::new().codegen;
+        codegen.line_ending = LineEnding::CrLf;
+        let ctx = SerializationContext::new().with_codegen(codegen);
+        let mut buf = Vec::new();
+        serialize_with_md5(&commands[..2], &mut buf, &ctx).unwrap();
+        assert!(String::from_utf8(buf)
+            .unwrap()
+            .ends_with("%TF.MD5,30a0e058180082713f77d37211cb4a24*%\r\n"));
+    }
+}
@@ -2,14 +2,26 @@
 //! bool or Vec<G: GerberCode>.
 
 use std::io::Write;
+use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::errors::GerberResult;
-use crate::traits::{GerberCode, PartialGerberCode};
+use crate::attributes::FileAttribute;
+use crate::charset::validate_name;
+use crate::compat::{attribute_comment, attributes_to_comments};
+use crate::context::SerializationContext;
+use crate::decimal::write_decimal;
+use crate::errors::{GerberError, GerberResult};
+use crate::function_codes::write_comment;
+use crate::traits::{CodeSequence, GerberCode, PartialGerberCode};
 use crate::types::*;
 
 /// Implement `PartialGerberCode` for booleans
 impl<W: Write> PartialGerberCode<W> for bool {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         if *self {
             write!(writer, "1")?;
         } else {
@@ -19,11 +31,90 @@ impl<W: Write> PartialGerberCode<W> for bool {
     }
 }
 
+/// Implement `PartialGerberCode` for decimals, in plain decimal notation
+impl<W: Write> PartialGerberCode<W> for f64 {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        write_decimal(writer, *self, ctx.decimal_places)
+    }
+}
+
+/// Implement `GerberCode` for slices of types that are `GerberCode`.
+impl<W: Write, G: GerberCode<W>> GerberCode<W> for [G] {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        for item in self.iter() {
+            item.serialize_with_context(writer, ctx)?;
+        }
+        Ok(())
+    }
+}
+
 /// Implement `GerberCode` for Vectors of types that are `GerberCode`.
 impl<W: Write, G: GerberCode<W>> GerberCode<W> for Vec<G> {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
-        for item in self.iter() {
-            item.serialize(writer)?;
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.as_slice().serialize_with_context(writer, ctx)
+    }
+}
+
+/// Implement `GerberCode` for pointer types, e.g. `&T` or `Rc<T>`.
+macro_rules! impl_gerbercode_for_pointer {
+    ($($pointer:ty),*) => {
+        $(
+            impl<W: Write, G: GerberCode<W> + ?Sized> GerberCode<W> for $pointer {
+                fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+                    self.serialize_with_context(writer, &SerializationContext::new())
+                }
+
+                fn serialize_with_context(
+                    &self,
+                    writer: &mut W,
+                    ctx: &SerializationContext,
+                ) -> GerberResult<()> {
+                    (**self).serialize_with_context(writer, ctx)
+                }
+            }
+        )*
+    };
+}
+
+impl_gerbercode_for_pointer!(&G, Box<G>, Rc<G>, Arc<G>);
+
+/// Implement `GerberCode` for the items of a [`CodeSequence`].
+impl<W: Write, I> GerberCode<W> for CodeSequence<I>
+where
+    I: IntoIterator + Clone,
+    I::Item: GerberCode<W>,
+{
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        for item in self.0.clone() {
+            item.serialize_with_context(writer, ctx)?;
         }
         Ok(())
     }
@@ -31,9 +122,13 @@ impl<W: Write, G: GerberCode<W>> GerberCode<W> for Vec<G> {
 
 /// Implement `PartialGerberCode` for `Option<T: PartialGerberCode>`
 impl<T: PartialGerberCode<W>, W: Write> PartialGerberCode<W> for Option<T> {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         if let Some(ref val) = *self {
-            val.serialize_partial(writer)?;
+            val.serialize_partial_with_context(writer, ctx)?;
         }
         Ok(())
     }
@@ -41,20 +136,174 @@ impl<T: PartialGerberCode<W>, W: Write> PartialGerberCode<W> for Option<T> {
 
 impl<W: Write> GerberCode<W> for Command {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        if let Some(max_line_length) = ctx.codegen.max_line_length {
+            let mut buf = Vec::new();
+            let wrappable = match *self {
+                Command::FunctionCode(ref code) => {
+                    code.serialize_with_context(&mut buf, ctx)?;
+                    false
+                }
+                Command::ExtendedCode(ref code) => {
+                    code.serialize_with_context(&mut buf, ctx)?;
+                    is_wrappable(code)
+                }
+                Command::ExtendedCodeBlock(ref block) => {
+                    block.serialize_with_context(&mut buf, ctx)?;
+                    block.codes.iter().all(is_wrappable)
+                }
+                Command::Deprecated(ref code) => {
+                    code.serialize_with_context(&mut buf, ctx)?;
+                    false
+                }
+            };
+            return write_wrapped(writer, &buf, max_line_length, wrappable, ctx);
+        }
         match *self {
-            Command::FunctionCode(ref code) => code.serialize(writer)?,
-            Command::ExtendedCode(ref code) => code.serialize(writer)?,
+            Command::FunctionCode(ref code) => code.serialize_with_context(writer, ctx)?,
+            Command::ExtendedCode(ref code) => code.serialize_with_context(writer, ctx)?,
+            Command::ExtendedCodeBlock(ref block) => block.serialize_with_context(writer, ctx)?,
+            Command::Deprecated(ref code) => code.serialize_with_context(writer, ctx)?,
         };
         Ok(())
     }
 }
 
+impl<'a, W: Write> GerberCode<W> for CommandRef<'a> {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        let (string, attribute) = match *self {
+            CommandRef::Command(ref command) => return command.serialize_with_context(writer, ctx),
+            CommandRef::Comment(comment) => (comment, false),
+            CommandRef::DeleteAttribute(name) => (name, true),
+        };
+        if let Some(max_line_length) = ctx.codegen.max_line_length {
+            let mut buf = Vec::new();
+            write_borrowed(&mut buf, string, attribute, ctx)?;
+            return write_wrapped(writer, &buf, max_line_length, false, ctx);
+        }
+        write_borrowed(writer, string, attribute, ctx)
+    }
+}
+
+/// Write a comment, or a TD command if `attribute` is set.
+fn write_borrowed<W: Write>(
+    writer: &mut W,
+    string: &str,
+    attribute: bool,
+    ctx: &SerializationContext,
+) -> GerberResult<()> {
+    if !attribute {
+        return write_comment(writer, string, ctx);
+    }
+    if !ctx.spec_target.supports_attributes() {
+        let code = ExtendedCode::DeleteAttribute(string.to_owned().into());
+        return attribute_comment(&code)?.serialize_with_context(writer, ctx);
+    }
+    write!(writer, "%")?;
+    write_delete_attribute(writer, string)?;
+    write!(writer, "%")?;
+    ctx.end_extended_code(writer)
+}
+
+/// Whether a line break may be inserted between the fields of an extended
+/// code. Only aperture and macro definitions are wrappable, a line break
+/// within an attribute would become part of the attribute value.
+fn is_wrappable(code: &ExtendedCode) -> bool {
+    matches!(
+        *code,
+        ExtendedCode::ApertureDefinition(_) | ExtendedCode::ApertureMacro(_)
+    )
+}
+
+/// Find the position after the last `,` within the first `limit` bytes of
+/// a line that separates two fields, i.e. that is not part of the text of a
+/// macro comment.
+fn find_field_break(line: &[u8], limit: usize) -> Option<usize> {
+    let mut start = 0;
+    let mut result = None;
+    for segment in line.split_inclusive(|b| *b == b'*') {
+        if start >= limit {
+            break;
+        }
+        let content = segment.strip_prefix(b"%").unwrap_or(segment);
+        if !content.starts_with(b"0 ") {
+            let end = segment.len().min(limit - start);
+            if let Some(pos) = segment[..end].iter().rposition(|b| *b == b',') {
+                result = Some(start + pos + 1);
+            }
+        }
+        start += segment.len();
+    }
+    result
+}
+
+/// Write serialized code, wrapping lines that are longer than
+/// `max_line_length` after a `,` between two fields. Function codes and
+/// attributes are not wrappable, see [`is_wrappable`].
+fn write_wrapped<W: Write>(
+    writer: &mut W,
+    code: &[u8],
+    max_line_length: usize,
+    wrappable: bool,
+    ctx: &SerializationContext,
+) -> GerberResult<()> {
+    let mut lines = code.split(|b| *b == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        let terminated = lines.peek().is_some();
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let mut rest = line;
+        while rest.len() > max_line_length {
+            let split = match find_field_break(rest, max_line_length) {
+                Some(split) if wrappable => split,
+                _ => {
+                    return Err(GerberError::RangeError(format!(
+                        "Line of {} characters exceeds the maximum line length of {}",
+                        line.len(),
+                        max_line_length
+                    )))
+                }
+            };
+            writer.write_all(&rest[..split])?;
+            ctx.write_line_ending(writer)?;
+            rest = &rest[split..];
+        }
+        writer.write_all(rest)?;
+        if terminated {
+            ctx.write_line_ending(writer)?;
+        }
+    }
+    Ok(())
+}
+
 impl<W: Write> GerberCode<W> for FunctionCode {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
-            FunctionCode::DCode(ref code) => code.serialize(writer)?,
-            FunctionCode::GCode(ref code) => code.serialize(writer)?,
-            FunctionCode::MCode(ref code) => code.serialize(writer)?,
+            FunctionCode::DCode(ref code) => code.serialize_with_context(writer, ctx)?,
+            FunctionCode::GCode(ref code) => code.serialize_with_context(writer, ctx)?,
+            FunctionCode::MCode(ref code) => code.serialize_with_context(writer, ctx)?,
         };
         Ok(())
     }
@@ -62,45 +311,120 @@ impl<W: Write> GerberCode<W> for FunctionCode {
 
 impl<W: Write> GerberCode<W> for ExtendedCode {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        if self.is_attribute() && !ctx.spec_target.supports_attributes() {
+            // X1 has no attributes, they are written as comments instead
+            return attribute_comment(self)?.serialize_with_context(writer, ctx);
+        }
+        write!(writer, "%")?;
+        self.serialize_partial_with_context(writer, ctx)?;
+        write!(writer, "%")?;
+        ctx.end_extended_code(writer)?;
+        Ok(())
+    }
+}
+
+/// Implement `PartialGerberCode` for the content of an extended code, without
+/// the `%` delimiters.
+impl<W: Write> PartialGerberCode<W> for ExtendedCode {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             ExtendedCode::CoordinateFormat(ref cf) => {
-                writeln!(writer, "%FSLAX{0}{1}Y{0}{1}*%", cf.integer, cf.decimal)?;
+                // The coordinates are written in the context format
+                let cf = ctx.format.as_ref().unwrap_or(cf);
+                write!(
+                    writer,
+                    "FS{0}AX{1}{2}Y{1}{2}*",
+                    ctx.zero_suppression.fs_letter(),
+                    cf.integer,
+                    cf.decimal
+                )?;
             }
             ExtendedCode::Unit(ref unit) => {
-                write!(writer, "%MO")?;
-                unit.serialize_partial(writer)?;
-                writeln!(writer, "*%")?;
+                write!(writer, "MO")?;
+                unit.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*")?;
             }
             ExtendedCode::ApertureDefinition(ref def) => {
-                write!(writer, "%ADD")?;
-                def.serialize_partial(writer)?;
-                writeln!(writer, "*%")?;
+                write!(writer, "ADD")?;
+                def.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*")?;
             }
             ExtendedCode::ApertureMacro(ref am) => {
-                write!(writer, "%")?;
-                am.serialize_partial(writer)?;
-                writeln!(writer, "%")?;
+                am.serialize_partial_with_context(writer, ctx)?;
             }
             ExtendedCode::LoadPolarity(ref polarity) => {
-                write!(writer, "%LP")?;
-                polarity.serialize_partial(writer)?;
-                writeln!(writer, "*%")?;
+                write!(writer, "LP")?;
+                polarity.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*")?;
             }
             ExtendedCode::StepAndRepeat(ref sar) => {
-                write!(writer, "%SR")?;
-                sar.serialize_partial(writer)?;
-                writeln!(writer, "*%")?;
+                write!(writer, "SR")?;
+                sar.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*")?;
             }
             ExtendedCode::FileAttribute(ref attr) => {
-                write!(writer, "%TF.")?;
-                attr.serialize_partial(writer)?;
-                writeln!(writer, "*%")?;
+                // User defined attribute names have no leading dot
+                match *attr {
+                    FileAttribute::UserDefined { .. } => write!(writer, "TF")?,
+                    _ => write!(writer, "TF.")?,
+                }
+                attr.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*")?;
             }
-            ExtendedCode::DeleteAttribute(ref attr) => {
-                writeln!(writer, "%TD{}*%", attr)?;
+            ExtendedCode::ApertureAttribute(ref attr) => {
+                write!(writer, "TA.")?;
+                attr.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*")?;
             }
-            _ => unimplemented!(),
+            ExtendedCode::DeleteAttribute(ref attr) => write_delete_attribute(writer, attr)?,
         };
         Ok(())
     }
 }
+
+/// Write the content of a TD command.
+fn write_delete_attribute<W: Write>(writer: &mut W, name: &str) -> GerberResult<()> {
+    if !name.is_empty() {
+        validate_name(name)?;
+    }
+    write!(writer, "TD{}*", name)?;
+    Ok(())
+}
+
+impl<W: Write> GerberCode<W> for ExtendedCodeBlock {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        if !ctx.spec_target.supports_attributes()
+            && self.codes.iter().any(ExtendedCode::is_attribute)
+        {
+            let commands = attributes_to_comments(&[self.clone().into()])?;
+            return commands.serialize_with_context(writer, ctx);
+        }
+        write!(writer, "%")?;
+        for code in &self.codes {
+            code.serialize_partial_with_context(writer, ctx)?;
+        }
+        write!(writer, "%")?;
+        ctx.end_extended_code(writer)?;
+        Ok(())
+    }
+}
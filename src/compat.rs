@@ -0,0 +1,340 @@
+//! Transformations between legacy and modern constructs, for compatibility
+//! with older software.
+
+use std::mem;
+
+use crate::context::SerializationContext;
+use crate::coordinates::{CoordinateNumber, Coordinates};
+use crate::decimal::from_decimal;
+use crate::deprecated::{DeprecatedCode, ImagePolarity};
+use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::Unit;
+use crate::function_codes::{DCode, GCode, MCode, Operation};
+use crate::traits::GerberCodeExt;
+use crate::types::{Command, ExtendedCode, ExtendedCodeBlock, FunctionCode};
+
+/// The prefix of comments that carry attributes in files for software
+/// without attribute support.
+pub const ATTRIBUTE_COMMENT_PREFIX: &str = "#@! ";
+
+/// Convert all attribute commands (TF, TA and TD) into `G04 #@!` comments.
+///
+/// The spec allows attributes to be written as comments with this prefix, so
+/// that older software ignores them while newer software can still read the
+/// metadata. TD commands are converted as well, so that attributes are
+/// deleted at the same place when the comments are interpreted.
+pub fn attributes_to_comments(commands: &[Command]) -> GerberResult<Vec<Command>> {
+    let mut result = Vec::with_capacity(commands.len());
+    for command in commands {
+        match command {
+            Command::ExtendedCode(code) if code.is_attribute() => {
+                result.push(attribute_comment(code)?.into());
+            }
+            Command::ExtendedCodeBlock(block) if block.codes.iter().any(|c| c.is_attribute()) => {
+                // Split the block around the attributes
+                let mut rest = Vec::new();
+                for code in &block.codes {
+                    if code.is_attribute() {
+                        if !rest.is_empty() {
+                            result.push(ExtendedCodeBlock::new(rest.split_off(0)).into());
+                        }
+                        result.push(attribute_comment(code)?.into());
+                    } else {
+                        rest.push(code.clone());
+                    }
+                }
+                if !rest.is_empty() {
+                    result.push(ExtendedCodeBlock::new(rest).into());
+                }
+            }
+            _ => result.push(command.clone()),
+        }
+    }
+    Ok(result)
+}
+
+/// Convert an attribute command into a `G04 #@!` comment.
+pub(crate) fn attribute_comment(code: &ExtendedCode) -> GerberResult<GCode> {
+    let ctx = SerializationContext::new();
+    let serialized = code.to_code_string_with_context(&ctx)?;
+    let body = serialized
+        .trim_end_matches(ctx.codegen.line_ending.as_str())
+        .trim_start_matches('%')
+        .trim_end_matches("*%");
+    Ok(GCode::Comment(
+        format!("{}{}", ATTRIBUTE_COMMENT_PREFIX, body).into(),
+    ))
+}
+
+/// Replace deprecated codes by their modern equivalents.
+///
+/// - G54 aperture selections become plain aperture selections.
+/// - G70 and G71 become MO commands.
+/// - Coordinates without an operation code get the previous operation code.
+/// - An image offset (OF) is added to all following coordinates.
+/// - IP, MI and SF commands that do not change the image are removed.
+/// - M00 becomes M02 and ends the file, the commands after it are removed.
+/// - M01 is removed.
+///
+/// Negative image polarity, mirroring and scaling cannot be expressed with
+/// modern constructs and result in an error, as do coordinates without an
+/// operation code before the first operation.
+pub fn upgrade_deprecated(commands: &[Command]) -> GerberResult<Vec<Command>> {
+    let mut result = Vec::with_capacity(commands.len());
+    let mut previous: Option<Operation> = None;
+    let mut offset: Option<(CoordinateNumber, CoordinateNumber)> = None;
+    for command in commands {
+        let mut operation = match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) => op.clone(),
+            Command::Deprecated(DeprecatedCode::CoordinatesOnly(coords)) => match previous {
+                Some(Operation::Interpolate(..)) => Operation::Interpolate(coords.clone(), None),
+                Some(Operation::Move(_)) => Operation::Move(coords.clone()),
+                Some(Operation::Flash(_)) => Operation::Flash(coords.clone()),
+                None => {
+                    return Err(GerberError::UnsupportedError(
+                        "Coordinates without operation code before the first operation".into(),
+                    ))
+                }
+            },
+            Command::Deprecated(DeprecatedCode::CoordinatesWithOffset(coords, offset)) => {
+                match previous {
+                    Some(Operation::Interpolate(..)) => {
+                        Operation::Interpolate(coords.clone(), Some(offset.clone()))
+                    }
+                    _ => {
+                        return Err(GerberError::UnsupportedError(
+                            "Arc offset without operation code must follow an interpolation".into(),
+                        ))
+                    }
+                }
+            }
+            Command::Deprecated(code) => {
+                offset = upgrade_code(code, offset, &mut result)?;
+                continue;
+            }
+            Command::FunctionCode(FunctionCode::MCode(MCode::ProgramStop)) => {
+                result.push(MCode::EndOfFile.into());
+                break;
+            }
+            Command::FunctionCode(FunctionCode::MCode(MCode::OptionalStop)) => continue,
+            _ => {
+                result.push(command.clone());
+                continue;
+            }
+        };
+        previous = Some(operation.clone());
+        if let Some((dx, dy)) = offset {
+            let coords = match operation {
+                Operation::Interpolate(ref mut coords, _)
+                | Operation::Move(ref mut coords)
+                | Operation::Flash(ref mut coords) => coords,
+            };
+            apply_offset(coords, dx, dy)?;
+        }
+        result.push(DCode::Operation(operation).into());
+    }
+    Ok(result)
+}
+
+/// Upgrade a deprecated code other than coordinates, returning the image
+/// offset that applies after it.
+fn upgrade_code(
+    code: &DeprecatedCode,
+    offset: Option<(CoordinateNumber, CoordinateNumber)>,
+    result: &mut Vec<Command>,
+) -> GerberResult<Option<(CoordinateNumber, CoordinateNumber)>> {
+    let unsupported = |what: &str| {
+        Err(GerberError::UnsupportedError(format!(
+            "{} cannot be expressed without deprecated codes",
+            what
+        )))
+    };
+    match *code {
+        DeprecatedCode::SelectAperture(code) => result.push(DCode::SelectAperture(code).into()),
+        DeprecatedCode::UnitInches => result.push(ExtendedCode::Unit(Unit::Inches).into()),
+        DeprecatedCode::UnitMillimeters => {
+            result.push(ExtendedCode::Unit(Unit::Millimeters).into())
+        }
+        DeprecatedCode::ImagePolarity(ImagePolarity::Positive) => {}
+        DeprecatedCode::ImagePolarity(ImagePolarity::Negative) => {
+            return unsupported("Negative image polarity")
+        }
+        DeprecatedCode::MirrorImage { a: false, b: false } => {}
+        DeprecatedCode::MirrorImage { .. } => return unsupported("Mirroring"),
+        DeprecatedCode::ScaleFactor { a, b }
+            if from_decimal(a) == 1.0 && from_decimal(b) == 1.0 => {}
+        DeprecatedCode::ScaleFactor { .. } => return unsupported("Scaling"),
+        DeprecatedCode::Offset { a, b } => {
+            if from_decimal(a) == 0.0 && from_decimal(b) == 0.0 {
+                return Ok(None);
+            }
+            return Ok(Some((
+                CoordinateNumber::from_f64_rounded(from_decimal(a))?,
+                CoordinateNumber::from_f64_rounded(from_decimal(b))?,
+            )));
+        }
+        DeprecatedCode::CoordinatesOnly(_) | DeprecatedCode::CoordinatesWithOffset(..) => {
+            unreachable!()
+        }
+    }
+    Ok(offset)
+}
+
+/// Omit the operation code of operations that repeat the previous one, as
+/// written by older software that relies on modal operation codes.
+///
+/// This is the inverse of the coordinate handling of
+/// [`upgrade_deprecated`](fn.upgrade_deprecated.html). Only consecutive
+/// operations are affected, the operation code is kept after any other
+/// command. Modal operation codes are deprecated, only use this for readers
+/// that require them.
+pub fn omit_modal_operations(commands: &[Command]) -> Vec<Command> {
+    let mut result = Vec::with_capacity(commands.len());
+    let mut previous: Option<mem::Discriminant<Operation>> = None;
+    for command in commands {
+        let op = match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) => op,
+            _ => {
+                previous = None;
+                result.push(command.clone());
+                continue;
+            }
+        };
+        let kind = mem::discriminant(op);
+        if previous != Some(kind) {
+            previous = Some(kind);
+            result.push(command.clone());
+            continue;
+        }
+        let code = match op {
+            Operation::Interpolate(coords, Some(offset)) => {
+                DeprecatedCode::CoordinatesWithOffset(coords.clone(), offset.clone())
+            }
+            Operation::Interpolate(coords, None)
+            | Operation::Move(coords)
+            | Operation::Flash(coords) => DeprecatedCode::CoordinatesOnly(coords.clone()),
+        };
+        result.push(code.into());
+    }
+    result
+}
+
+fn apply_offset(
+    coords: &mut Coordinates,
+    dx: CoordinateNumber,
+    dy: CoordinateNumber,
+) -> GerberResult<()> {
+    let overflow = || GerberError::RangeError("Image offset is too large".into());
+    if let Some(x) = coords.x {
+        coords.x = Some(x.checked_add(dx).ok_or_else(overflow)?);
+    }
+    if let Some(y) = coords.y {
+        coords.y = Some(y.checked_add(dy).ok_or_else(overflow)?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::{ApertureAttribute, ApertureFunction, FileAttribute, Part};
+    use crate::coordinates::{CoordinateFormat, CoordinateOffset};
+    use crate::decimal::to_decimal;
+
+    #[test]
+    fn test_attributes_to_comments() {
+        let commands: Vec<Command> = vec![
+            ExtendedCode::FileAttribute(FileAttribute::Part(Part::Single)).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            ExtendedCode::ApertureAttribute(ApertureAttribute::ApertureFunction(
+                ApertureFunction::ViaPad,
+            ))
+            .into(),
+            ExtendedCode::DeleteAttribute(".AperFunction".into()).into(),
+            ExtendedCode::DeleteAttribute("".into()).into(),
+        ];
+        let converted = attributes_to_comments(&commands).unwrap();
+        assert_eq!(
+            converted.to_code_string().unwrap(),
+            "G04 #@! TF.Part,Single*\n\
+             %MOMM*%\n\
+             G04 #@! TA.AperFunction,ViaPad*\n\
+             G04 #@! TD.AperFunction*\n\
+             G04 #@! TD*\n"
+        );
+    }
+
+    #[test]
+    fn test_upgrade_deprecated() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            DeprecatedCode::UnitMillimeters.into(),
+            DeprecatedCode::ImagePolarity(ImagePolarity::Positive).into(),
+            DeprecatedCode::Offset {
+                a: to_decimal(1.0),
+                b: to_decimal(0.5),
+            }
+            .into(),
+            DeprecatedCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(0, 0, cf))).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::at_x(2, cf), None)).into(),
+            DeprecatedCode::CoordinatesOnly(Coordinates::at_y(2, cf)).into(),
+            MCode::OptionalStop.into(),
+            MCode::ProgramStop.into(),
+            // Ignored after the program stop
+            DCode::Operation(Operation::Flash(Coordinates::new(0, 0, cf))).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let upgraded = upgrade_deprecated(&commands).unwrap();
+        assert_eq!(
+            upgraded.to_code_string().unwrap(),
+            "%MOMM*%\nD10*\nX10000Y5000D02*\nX30000D01*\nY25000D01*\nM02*\n"
+        );
+    }
+
+    #[test]
+    fn test_omit_modal_operations() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            DCode::Operation(Operation::Move(Coordinates::new(0, 0, cf))).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::at_x(2, cf), None)).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::at_y(2, cf),
+                Some(CoordinateOffset::new(0, 1, cf)),
+            ))
+            .into(),
+            DCode::SelectAperture(11).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::at_x(0, cf), None)).into(),
+        ];
+        let legacy = omit_modal_operations(&commands);
+        assert_eq!(
+            legacy.to_code_string().unwrap(),
+            "X0Y0D02*\nX20000D01*\nY20000I0J10000*\nD11*\nX0D01*\n"
+        );
+        assert_eq!(upgrade_deprecated(&legacy).unwrap(), commands);
+    }
+
+    #[test]
+    fn test_upgrade_deprecated_errors() {
+        let cf = CoordinateFormat::new(2, 4);
+        let unsupported: Vec<Command> = vec![
+            DeprecatedCode::ImagePolarity(ImagePolarity::Negative).into(),
+            DeprecatedCode::MirrorImage { a: true, b: false }.into(),
+            DeprecatedCode::ScaleFactor {
+                a: to_decimal(2.0),
+                b: to_decimal(2.0),
+            }
+            .into(),
+            DeprecatedCode::CoordinatesOnly(Coordinates::at_y(2, cf)).into(),
+            DeprecatedCode::CoordinatesWithOffset(
+                Coordinates::at_y(2, cf),
+                CoordinateOffset::new(0, 1, cf),
+            )
+            .into(),
+        ];
+        for command in unsupported {
+            assert!(upgrade_deprecated(&[command]).is_err());
+        }
+    }
+}
@@ -0,0 +1,404 @@
+//! Conformance of command streams to a revision of the Gerber spec, e.g. as
+//! a gate in CAM pipelines.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::attributes::FileAttribute;
+use crate::context::SpecTarget;
+use crate::deprecated::DeprecatedCode;
+use crate::function_codes::{GCode, MCode, QuadrantMode};
+use crate::macros::MacroContent;
+use crate::optimize::extended_codes;
+use crate::types::{Command, ExtendedCode, FunctionCode};
+use crate::validate::Severity;
+
+/// A revision of the Gerber spec to check against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpecRevision {
+    /// Revision 2020.09, which still allows the legacy deprecated codes.
+    Revision2020_09,
+    /// Revision 2021.02, which removed the legacy deprecated codes and
+    /// deprecated single quadrant mode and the Moiré primitive.
+    Revision2021_02,
+    /// Revision 2023.08.
+    Revision2023_08,
+}
+
+impl SpecRevision {
+    /// Whether the legacy deprecated codes, e.g. G54 or IP, are no longer
+    /// part of the spec.
+    pub fn removes_deprecated(self) -> bool {
+        self >= SpecRevision::Revision2021_02
+    }
+}
+
+/// A construct that is deprecated since a revision, and possibly removed in
+/// a later one.
+struct Construct {
+    name: String,
+    deprecated: SpecRevision,
+    removed: Option<SpecRevision>,
+}
+
+impl Construct {
+    /// A legacy code, deprecated before the first known revision.
+    fn legacy(name: &str) -> Self {
+        Construct {
+            name: name.into(),
+            deprecated: SpecRevision::Revision2020_09,
+            removed: Some(SpecRevision::Revision2021_02),
+        }
+    }
+
+    /// A construct deprecated in revision 2021.02, but not removed.
+    fn deprecated_2021(name: String) -> Self {
+        Construct {
+            name,
+            deprecated: SpecRevision::Revision2021_02,
+            removed: None,
+        }
+    }
+
+    fn classify(&self, revision: SpecRevision) -> Option<(ConformanceRule, Severity)> {
+        if matches!(self.removed, Some(removed) if revision >= removed) {
+            Some((ConformanceRule::Removed, Severity::Error))
+        } else if revision >= self.deprecated {
+            Some((ConformanceRule::Deprecated, Severity::Warning))
+        } else {
+            None
+        }
+    }
+}
+
+/// The kind of a [`ConformanceIssue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ConformanceRule {
+    /// A construct that is deprecated, but still part of the revision.
+    Deprecated,
+    /// A construct that was removed from the revision.
+    Removed,
+    /// An attribute that X2 files must contain is missing.
+    MissingAttribute,
+}
+
+impl ConformanceRule {
+    /// A stable identifier of the rule, e.g. for reports in CI.
+    pub fn code(self) -> &'static str {
+        match self {
+            ConformanceRule::Deprecated => "deprecated",
+            ConformanceRule::Removed => "removed",
+            ConformanceRule::MissingAttribute => "missing-attribute",
+        }
+    }
+}
+
+/// A problem found by [`conformance_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConformanceIssue {
+    pub rule: ConformanceRule,
+    pub severity: Severity,
+    /// The index of the offending command, `None` for issues of the whole
+    /// stream.
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ConformanceIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}[{}]", severity, self.rule.code())?;
+        if let Some(index) = self.index {
+            write!(f, " command {}", index)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// The result of checking a command stream against a spec revision.
+///
+/// With the `serde` feature, the report can be serialized, e.g. to JSON.
+/// Its `Display` implementation writes one issue per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConformanceReport {
+    pub revision: SpecRevision,
+    pub target: SpecTarget,
+    pub issues: Vec<ConformanceIssue>,
+}
+
+impl ConformanceReport {
+    /// Whether the stream conforms to the revision, i.e. there are no
+    /// errors. Warnings are allowed.
+    pub fn is_conformant(&self) -> bool {
+        self.issues.iter().all(|i| i.severity != Severity::Error)
+    }
+
+    /// Whether the stream uses deprecated or removed constructs.
+    pub fn uses_deprecated(&self) -> bool {
+        self.has_rule(ConformanceRule::Deprecated) || self.has_rule(ConformanceRule::Removed)
+    }
+
+    /// Whether the stream uses constructs removed from the revision.
+    pub fn uses_removed(&self) -> bool {
+        self.has_rule(ConformanceRule::Removed)
+    }
+
+    /// Whether attributes that X2 files must contain are missing.
+    pub fn missing_attributes(&self) -> bool {
+        self.has_rule(ConformanceRule::MissingAttribute)
+    }
+
+    fn has_rule(&self, rule: ConformanceRule) -> bool {
+        self.issues.iter().any(|i| i.rule == rule)
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check a command stream against a spec revision.
+///
+/// Each construct is classified by the revision that deprecated it and the
+/// one that removed it: it is a warning in revisions that deprecate it, and
+/// an error in revisions that removed it.
+///
+/// - The legacy deprecated codes, M00 and M01 are removed in 2021.02.
+/// - Single quadrant mode (G74) and the Moiré primitive (code 6) in aperture
+///   macros are deprecated since 2021.02. Aperture definitions that use a
+///   macro with a Moiré primitive are reported as well.
+///
+/// For the X2 and X3 targets, a missing `.FileFunction` or `.FilePolarity`
+/// file attribute is an error, since these files must contain both. Plain
+/// X1 files have no attributes, so they are not checked.
+pub fn conformance_report(
+    commands: &[Command],
+    revision: SpecRevision,
+    target: SpecTarget,
+) -> ConformanceReport {
+    let mut issues = Vec::new();
+    // The names of the macros with a Moiré primitive
+    let mut moire_macros = HashSet::new();
+    for (index, command) in commands.iter().enumerate() {
+        let mut constructs = Vec::new();
+        match command {
+            Command::Deprecated(code) => constructs.push(Construct::legacy(deprecated_name(code))),
+            Command::FunctionCode(FunctionCode::GCode(GCode::QuadrantMode(
+                QuadrantMode::Single,
+            ))) => constructs.push(Construct::deprecated_2021(
+                "Single quadrant mode (G74)".into(),
+            )),
+            Command::FunctionCode(FunctionCode::MCode(MCode::ProgramStop)) => {
+                constructs.push(Construct::legacy("Program stop (M00)"))
+            }
+            Command::FunctionCode(FunctionCode::MCode(MCode::OptionalStop)) => {
+                constructs.push(Construct::legacy("Optional stop (M01)"))
+            }
+            _ => {}
+        }
+        for code in codes(command) {
+            match code {
+                ExtendedCode::ApertureMacro(am)
+                    if am
+                        .content
+                        .iter()
+                        .any(|c| matches!(c, MacroContent::Moire(_))) =>
+                {
+                    moire_macros.insert(am.name.as_ref());
+                    constructs.push(Construct::deprecated_2021(format!(
+                        "Moiré primitive in aperture macro {}",
+                        am.name
+                    )));
+                }
+                ExtendedCode::ApertureDefinition(ad) => match ad.aperture.macro_name() {
+                    Some(name) if moire_macros.contains(name) => {
+                        constructs.push(Construct::deprecated_2021(format!(
+                            "Aperture D{} uses the Moiré primitive of macro {}",
+                            ad.code, name
+                        )))
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        for construct in constructs {
+            if let Some((rule, severity)) = construct.classify(revision) {
+                issues.push(ConformanceIssue {
+                    rule,
+                    severity,
+                    index: Some(index),
+                    message: construct.name,
+                });
+            }
+        }
+    }
+
+    if target.supports_attributes() {
+        let (mut function, mut polarity) = (false, false);
+        for code in extended_codes(commands) {
+            match code {
+                ExtendedCode::FileAttribute(FileAttribute::FileFunction(_)) => function = true,
+                ExtendedCode::FileAttribute(FileAttribute::FilePolarity(_)) => polarity = true,
+                _ => {}
+            }
+        }
+        for (present, name) in [(function, ".FileFunction"), (polarity, ".FilePolarity")] {
+            if !present {
+                issues.push(ConformanceIssue {
+                    rule: ConformanceRule::MissingAttribute,
+                    severity: Severity::Error,
+                    index: None,
+                    message: format!("Missing {} file attribute", name),
+                });
+            }
+        }
+    }
+    ConformanceReport {
+        revision,
+        target,
+        issues,
+    }
+}
+
+fn codes(command: &Command) -> &[ExtendedCode] {
+    match command {
+        Command::ExtendedCode(code) => std::slice::from_ref(code),
+        Command::ExtendedCodeBlock(block) => &block.codes,
+        _ => &[],
+    }
+}
+
+fn deprecated_name(code: &DeprecatedCode) -> &'static str {
+    match code {
+        DeprecatedCode::SelectAperture(_) => "Aperture selection with G54",
+        DeprecatedCode::UnitInches | DeprecatedCode::UnitMillimeters => "Unit with G70/G71",
+        DeprecatedCode::ImagePolarity(_) => "Image polarity (IP)",
+        DeprecatedCode::MirrorImage { .. } => "Mirror image (MI)",
+        DeprecatedCode::Offset { .. } => "Image offset (OF)",
+        DeprecatedCode::ScaleFactor { .. } => "Scale factor (SF)",
+        DeprecatedCode::CoordinatesOnly(_) | DeprecatedCode::CoordinatesWithOffset(..) => {
+            "Coordinates without an operation code"
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::{FileFunction, FilePolarity, Profile};
+    use crate::coordinates::{CoordinateFormat, Coordinates};
+    use crate::function_codes::MCode;
+
+    #[test]
+    fn test_conformance_report() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::FileAttribute(FileAttribute::FileFunction(FileFunction::Profile(
+                Profile::NonPlated,
+            )))
+            .into(),
+            DeprecatedCode::SelectAperture(10).into(),
+            GCode::QuadrantMode(QuadrantMode::Single).into(),
+            DeprecatedCode::CoordinatesOnly(Coordinates::new(1, 1, cf)).into(),
+            MCode::OptionalStop.into(),
+            MCode::EndOfFile.into(),
+        ];
+
+        // Single quadrant mode is not deprecated yet
+        let report = conformance_report(&commands, SpecRevision::Revision2020_09, SpecTarget::X2);
+        assert_eq!(report.issues.len(), 4);
+        assert!(report.uses_deprecated());
+        assert!(!report.uses_removed());
+        assert!(report.missing_attributes());
+        assert!(!report.is_conformant());
+        assert_eq!(
+            report.to_string(),
+            "warning[deprecated] command 1: Aperture selection with G54\n\
+             warning[deprecated] command 3: Coordinates without an operation code\n\
+             warning[deprecated] command 4: Optional stop (M01)\n\
+             error[missing-attribute]: Missing .FilePolarity file attribute\n"
+        );
+
+        let report = conformance_report(&commands, SpecRevision::Revision2021_02, SpecTarget::X3);
+        assert!(report.uses_removed());
+        assert_eq!(
+            report.to_string(),
+            "error[removed] command 1: Aperture selection with G54\n\
+             warning[deprecated] command 2: Single quadrant mode (G74)\n\
+             error[removed] command 3: Coordinates without an operation code\n\
+             error[removed] command 4: Optional stop (M01)\n\
+             error[missing-attribute]: Missing .FilePolarity file attribute\n"
+        );
+    }
+
+    #[test]
+    fn test_x1_attributes() {
+        let commands: Vec<Command> = vec![MCode::EndOfFile.into()];
+        let report = conformance_report(&commands, SpecRevision::Revision2023_08, SpecTarget::X1);
+        assert!(report.issues.is_empty());
+        let report = conformance_report(&commands, SpecRevision::Revision2023_08, SpecTarget::X2);
+        assert_eq!(report.issues.len(), 2);
+        assert!(report.missing_attributes());
+    }
+
+    #[test]
+    fn test_moire() {
+        use crate::extended_codes::{Aperture, ApertureDefinition};
+        use crate::macros::{ApertureMacro, MoirePrimitive};
+
+        let commands: Vec<Command> = vec![
+            ExtendedCode::FileAttribute(FileAttribute::FileFunction(FileFunction::Profile(
+                Profile::NonPlated,
+            )))
+            .into(),
+            ExtendedCode::FileAttribute(FileAttribute::FilePolarity(FilePolarity::Positive)).into(),
+            ApertureMacro::new("TARGET")
+                .add_content(MoirePrimitive::new())
+                .into(),
+            ApertureDefinition::new(10, Aperture::Other("TARGET".into())).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let report = conformance_report(&commands, SpecRevision::Revision2020_09, SpecTarget::X2);
+        assert!(report.issues.is_empty());
+        for &revision in &[SpecRevision::Revision2021_02, SpecRevision::Revision2023_08] {
+            let report = conformance_report(&commands, revision, SpecTarget::X3);
+            assert!(report.is_conformant());
+            assert_eq!(
+                report.to_string(),
+                "warning[deprecated] command 2: Moiré primitive in aperture macro TARGET\n\
+                 warning[deprecated] command 3: Aperture D10 uses the Moiré primitive of macro TARGET\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_conformant() {
+        let commands: Vec<Command> = vec![
+            ExtendedCode::FileAttribute(FileAttribute::FileFunction(FileFunction::Profile(
+                Profile::NonPlated,
+            )))
+            .into(),
+            ExtendedCode::FileAttribute(FileAttribute::FilePolarity(FilePolarity::Positive)).into(),
+            GCode::QuadrantMode(QuadrantMode::Multi).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let report = conformance_report(&commands, SpecRevision::Revision2023_08, SpecTarget::X3);
+        assert!(report.issues.is_empty());
+        assert!(report.is_conformant());
+    }
+}
@@ -0,0 +1,238 @@
+//! Settings that apply to a whole serialization run.
+
+use std::io::Write;
+
+use crate::coordinates::{CoordinateFormat, ZeroSuppression};
+use crate::decimal::DEFAULT_DECIMAL_PLACES;
+use crate::errors::GerberResult;
+
+/// The line ending written after each line of code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum LineEnding {
+    /// Unix style line ending (`\n`).
+    #[default]
+    Lf,
+    /// Windows style line ending (`\r\n`).
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Settings for the layout of the generated code.
+///
+/// None of these settings change the meaning of the code, but some legacy
+/// CAM software is picky about them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct CodegenSettings {
+    pub line_ending: LineEnding,
+    /// Whether each statement is followed by a line break. If disabled, all
+    /// statements are packed into a single line, only the end of file
+    /// command is followed by a line break.
+    pub line_breaks: bool,
+    /// Whether extended code blocks (`%...*%`) are followed by a line break.
+    /// If disabled, the next command follows on the same line.
+    pub extended_code_newline: bool,
+    /// Whether a space is written between `G04` and the comment text.
+    pub comment_space: bool,
+    /// Maximum length of a line, without the line ending. Aperture and
+    /// macro definitions are wrapped after a `,` between two fields and
+    /// comments are split into multiple comments to stay within the limit,
+    /// other lines that are too long result in an error. Attributes are
+    /// never wrapped, since a line break would change their value. The
+    /// limit applies to commands, it is checked for the output of each
+    /// command separately.
+    pub max_line_length: Option<usize>,
+}
+
+impl CodegenSettings {
+    pub fn new() -> Self {
+        CodegenSettings {
+            line_ending: LineEnding::Lf,
+            line_breaks: true,
+            extended_code_newline: true,
+            comment_space: true,
+            max_line_length: None,
+        }
+    }
+
+    /// Settings that produce the smallest possible output, with all
+    /// statements on one line and no optional whitespace.
+    pub fn compact() -> Self {
+        CodegenSettings {
+            line_ending: LineEnding::Lf,
+            line_breaks: false,
+            extended_code_newline: false,
+            comment_space: false,
+            max_line_length: None,
+        }
+    }
+
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    pub fn with_line_breaks(mut self, enabled: bool) -> Self {
+        self.line_breaks = enabled;
+        self
+    }
+
+    pub fn with_extended_code_newline(mut self, enabled: bool) -> Self {
+        self.extended_code_newline = enabled;
+        self
+    }
+
+    pub fn with_comment_space(mut self, enabled: bool) -> Self {
+        self.comment_space = enabled;
+        self
+    }
+
+    pub fn with_max_line_length(mut self, max_line_length: usize) -> Self {
+        self.max_line_length = Some(max_line_length);
+        self
+    }
+}
+
+impl Default for CodegenSettings {
+    fn default() -> Self {
+        CodegenSettings::new()
+    }
+}
+
+/// The revision of the Gerber format that the output must conform to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum SpecTarget {
+    /// Plain extended Gerber (RS-274X) without attributes. Attribute
+    /// commands (TF, TA, TD) are written as `G04 #@!` comments, like
+    /// [`attributes_to_comments`] does, so that older software ignores them.
+    ///
+    /// [`attributes_to_comments`]: crate::attributes_to_comments
+    X1,
+    /// Gerber X2 with file and aperture attributes. The component attributes
+    /// added by X3 result in an `UnsupportedError`.
+    X2,
+    /// Gerber X3, everything is written as is.
+    #[default]
+    X3,
+}
+
+impl SpecTarget {
+    /// Whether attribute commands can be written.
+    pub fn supports_attributes(self) -> bool {
+        self != SpecTarget::X1
+    }
+
+    /// Whether the component attributes of X3 can be written, e.g. the
+    /// `Component` file function.
+    pub fn supports_component_attributes(self) -> bool {
+        self == SpecTarget::X3
+    }
+}
+
+/// The context passed through serialization.
+///
+/// The context makes it possible to supply settings once for a whole file.
+/// For example, if a coordinate format is set, it is used for the FS command
+/// and all coordinates and offsets instead of the format stored in them,
+/// which rules out mixed formats within a file. The values still store
+/// their own format, which is used without a context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct SerializationContext {
+    /// Coordinate format that overrides the format stored in coordinates and
+    /// FS commands.
+    pub format: Option<CoordinateFormat>,
+    /// Zero suppression of coordinate numbers, also written to the FS command.
+    pub zero_suppression: ZeroSuppression,
+    /// Maximum number of decimal places of decimal values, e.g. aperture
+    /// sizes, step and repeat distances and macro parameters.
+    pub decimal_places: usize,
+    /// Layout of the generated code.
+    pub codegen: CodegenSettings,
+    /// The revision of the Gerber format to write.
+    pub spec_target: SpecTarget,
+}
+
+impl SerializationContext {
+    pub fn new() -> Self {
+        SerializationContext {
+            format: None,
+            zero_suppression: ZeroSuppression::Leading,
+            decimal_places: DEFAULT_DECIMAL_PLACES,
+            codegen: CodegenSettings::new(),
+            spec_target: SpecTarget::X3,
+        }
+    }
+
+    pub fn with_format(mut self, format: CoordinateFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn with_zero_suppression(mut self, zero_suppression: ZeroSuppression) -> Self {
+        self.zero_suppression = zero_suppression;
+        self
+    }
+
+    pub fn with_decimal_places(mut self, decimal_places: usize) -> Self {
+        self.decimal_places = decimal_places;
+        self
+    }
+
+    pub fn with_codegen(mut self, codegen: CodegenSettings) -> Self {
+        self.codegen = codegen;
+        self
+    }
+
+    pub fn with_spec_target(mut self, spec_target: SpecTarget) -> Self {
+        self.spec_target = spec_target;
+        self
+    }
+
+    /// Write the end of a line of code, unless line breaks are disabled.
+    pub fn end_line<W: Write>(&self, writer: &mut W) -> GerberResult<()> {
+        if self.codegen.line_breaks {
+            self.write_line_ending(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Write the end of an extended code block, after the closing `%`.
+    pub fn end_extended_code<W: Write>(&self, writer: &mut W) -> GerberResult<()> {
+        if self.codegen.extended_code_newline {
+            self.end_line(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Write the line ending regardless of the line break setting.
+    pub fn write_line_ending<W: Write>(&self, writer: &mut W) -> GerberResult<()> {
+        writer.write_all(self.codegen.line_ending.as_str().as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Default for SerializationContext {
+    fn default() -> Self {
+        SerializationContext::new()
+    }
+}
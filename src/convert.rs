@@ -0,0 +1,406 @@
+//! Conversion of command streams between millimeters and inches and between
+//! coordinate formats.
+
+use crate::attributes::ApertureAttribute;
+use crate::coordinates::{convert_f64_unit, CoordinateFormat, CoordinateNumber};
+use crate::decimal::{from_decimal, to_decimal, Decimal};
+use crate::deprecated::DeprecatedCode;
+use crate::errors::{deprecated_error, GerberError, GerberResult};
+use crate::extended_codes::{Aperture, StepAndRepeat, Unit};
+use crate::function_codes::{DCode, Operation};
+use crate::macros::{ApertureMacro, MacroContent, MacroDecimal};
+use crate::types::{Command, ExtendedCode, FunctionCode};
+use crate::validate::validated;
+
+/// Convert a command stream into another unit.
+///
+/// The unit of the stream is taken from its `%MO` commands, which are
+/// rewritten to the target unit. Coordinates are converted in fixed point,
+/// aperture sizes, aperture macro primitives and step and repeat distances are
+/// converted as well. The coordinate format is not changed, it may need more
+/// integer places after converting from inches to millimeters.
+///
+/// Aperture macros with variables and macro apertures with parameters cannot
+/// be converted and result in an error, as does any length that appears
+/// before the unit is set.
+pub fn convert_unit(commands: &[Command], target: Unit) -> GerberResult<Vec<Command>> {
+    let mut unit = None;
+    let mut result = Vec::with_capacity(commands.len());
+    for command in commands {
+        let mut command = command.clone();
+        match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(ref mut op))) => {
+                convert_operation(op, source_unit(unit)?, target)?;
+            }
+            Command::ExtendedCode(ref mut code) => convert_extended(code, &mut unit, target)?,
+            Command::ExtendedCodeBlock(ref mut block) => {
+                for code in block.codes.iter_mut() {
+                    convert_extended(code, &mut unit, target)?;
+                }
+            }
+            Command::Deprecated(_) => return Err(deprecated_error()),
+            Command::FunctionCode(_) => {}
+        }
+        result.push(command);
+    }
+    Ok(result)
+}
+
+/// Convert a command stream into another coordinate format, e.g. from 2.4 to
+/// 4.6.
+///
+/// The `%FS` commands are rewritten to the target format and all coordinates
+/// and arc offsets are rounded to its decimal places, including those of
+/// deprecated coordinate-only codes. A coordinate that does not fit into the
+/// integer places of the target format results in a `RangeError`.
+pub fn convert_format(
+    commands: &[Command],
+    target: CoordinateFormat,
+) -> GerberResult<Vec<Command>> {
+    let target = validated(target)?;
+    let mut result = Vec::with_capacity(commands.len());
+    for (index, command) in commands.iter().enumerate() {
+        let mut command = command.clone();
+        let reformat = |x: &mut Option<CoordinateNumber>,
+                        y: &mut Option<CoordinateNumber>,
+                        format: &mut CoordinateFormat| {
+            for number in x.iter_mut().chain(y.iter_mut()) {
+                *number = number.round_to_format(&target).map_err(|_| {
+                    GerberError::RangeError(format!(
+                        "Coordinate {} at index {} does not fit into the format {}.{}",
+                        number, index, target.integer, target.decimal
+                    ))
+                })?;
+            }
+            *format = target;
+            Ok::<(), GerberError>(())
+        };
+        match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(ref mut op))) => match op {
+                Operation::Interpolate(coords, offset) => {
+                    reformat(&mut coords.x, &mut coords.y, &mut coords.format)?;
+                    if let Some(offset) = offset {
+                        reformat(&mut offset.x, &mut offset.y, &mut offset.format)?;
+                    }
+                }
+                Operation::Move(coords) | Operation::Flash(coords) => {
+                    reformat(&mut coords.x, &mut coords.y, &mut coords.format)?;
+                }
+            },
+            Command::ExtendedCode(ExtendedCode::CoordinateFormat(ref mut format)) => {
+                *format = target;
+            }
+            Command::ExtendedCodeBlock(ref mut block) => {
+                for code in block.codes.iter_mut() {
+                    if let ExtendedCode::CoordinateFormat(format) = code {
+                        *format = target;
+                    }
+                }
+            }
+            Command::Deprecated(DeprecatedCode::CoordinatesOnly(ref mut coords)) => {
+                reformat(&mut coords.x, &mut coords.y, &mut coords.format)?;
+            }
+            Command::Deprecated(DeprecatedCode::CoordinatesWithOffset(
+                ref mut coords,
+                ref mut offset,
+            )) => {
+                reformat(&mut coords.x, &mut coords.y, &mut coords.format)?;
+                reformat(&mut offset.x, &mut offset.y, &mut offset.format)?;
+            }
+            _ => {}
+        }
+        result.push(command);
+    }
+    Ok(result)
+}
+
+fn convert_decimal(value: Decimal, from: Unit, to: Unit) -> Decimal {
+    to_decimal(convert_f64_unit(from_decimal(value), from, to))
+}
+
+fn source_unit(unit: Option<Unit>) -> GerberResult<Unit> {
+    unit.ok_or_else(|| {
+        GerberError::MissingDataError("The unit must be set before the first length".into())
+    })
+}
+
+fn convert_operation(op: &mut Operation, from: Unit, to: Unit) -> GerberResult<()> {
+    match op {
+        Operation::Interpolate(coords, offset) => {
+            *coords = coords.convert_unit(from, to)?;
+            if let Some(offset) = offset {
+                *offset = offset.convert_unit(from, to)?;
+            }
+        }
+        Operation::Move(coords) | Operation::Flash(coords) => {
+            *coords = coords.convert_unit(from, to)?;
+        }
+    }
+    Ok(())
+}
+
+fn convert_extended(
+    code: &mut ExtendedCode,
+    unit: &mut Option<Unit>,
+    target: Unit,
+) -> GerberResult<()> {
+    match code {
+        ExtendedCode::Unit(u) => {
+            *unit = Some(*u);
+            *u = target;
+        }
+        ExtendedCode::ApertureDefinition(def) => {
+            convert_aperture(&mut def.aperture, source_unit(*unit)?, target)?;
+        }
+        ExtendedCode::ApertureMacro(am) => {
+            convert_macro(am, source_unit(*unit)?, target)?;
+        }
+        ExtendedCode::StepAndRepeat(StepAndRepeat::Open {
+            distance_x,
+            distance_y,
+            ..
+        }) => {
+            let from = source_unit(*unit)?;
+            *distance_x = convert_decimal(*distance_x, from, target);
+            *distance_y = convert_decimal(*distance_y, from, target);
+        }
+        ExtendedCode::ApertureAttribute(ApertureAttribute::DrillTolerance { plus, minus }) => {
+            let from = source_unit(*unit)?;
+            *plus = convert_decimal(*plus, from, target);
+            *minus = convert_decimal(*minus, from, target);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn convert_aperture(aperture: &mut Aperture, from: Unit, to: Unit) -> GerberResult<()> {
+    let convert = |value: &mut Decimal| *value = convert_decimal(*value, from, to);
+    match aperture {
+        Aperture::Circle(circle) => {
+            convert(&mut circle.diameter);
+            if let Some(hole_diameter) = circle.hole_diameter.as_mut() {
+                convert(hole_diameter);
+            }
+        }
+        Aperture::Rectangle(rect) => {
+            convert(&mut rect.x);
+            convert(&mut rect.y);
+            if let Some(hole_diameter) = rect.hole_diameter.as_mut() {
+                convert(hole_diameter);
+            }
+        }
+        Aperture::Obround(obround) => {
+            convert(&mut obround.x);
+            convert(&mut obround.y);
+            if let Some(hole_diameter) = obround.hole_diameter.as_mut() {
+                convert(hole_diameter);
+            }
+        }
+        Aperture::Polygon(polygon) => {
+            convert(&mut polygon.diameter);
+            if let Some(hole_diameter) = polygon.hole_diameter.as_mut() {
+                convert(hole_diameter);
+            }
+        }
+        Aperture::Other(name) => {
+            if name.contains(',') {
+                return Err(GerberError::ConversionError(format!(
+                    "Cannot convert the parameters of macro aperture {}",
+                    name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn convert_macro(am: &mut ApertureMacro, from: Unit, to: Unit) -> GerberResult<()> {
+    let ApertureMacro { name, content } = am;
+    let variables_error = || {
+        GerberError::ConversionError(format!(
+            "Cannot convert aperture macro {} with variables",
+            name
+        ))
+    };
+    let convert = |decimal: &mut MacroDecimal| match decimal {
+        MacroDecimal::Value(value) => {
+            *value = convert_decimal(*value, from, to);
+            Ok(())
+        }
+        MacroDecimal::Variable(_) => Err(variables_error()),
+    };
+    for content in content.iter_mut() {
+        match content {
+            MacroContent::Circle(c) => {
+                convert(&mut c.diameter)?;
+                convert(&mut c.center.0)?;
+                convert(&mut c.center.1)?;
+            }
+            MacroContent::VectorLine(vl) => {
+                convert(&mut vl.width)?;
+                convert(&mut vl.start.0)?;
+                convert(&mut vl.start.1)?;
+                convert(&mut vl.end.0)?;
+                convert(&mut vl.end.1)?;
+            }
+            MacroContent::CenterLine(cl) => {
+                convert(&mut cl.dimensions.0)?;
+                convert(&mut cl.dimensions.1)?;
+                convert(&mut cl.center.0)?;
+                convert(&mut cl.center.1)?;
+            }
+            MacroContent::Outline(o) => {
+                for point in o.points.iter_mut() {
+                    convert(&mut point.0)?;
+                    convert(&mut point.1)?;
+                }
+            }
+            MacroContent::Polygon(p) => {
+                convert(&mut p.diameter)?;
+                convert(&mut p.center.0)?;
+                convert(&mut p.center.1)?;
+            }
+            MacroContent::Moire(m) => {
+                convert(&mut m.center.0)?;
+                convert(&mut m.center.1)?;
+                convert(&mut m.diameter)?;
+                convert(&mut m.ring_thickness)?;
+                convert(&mut m.gap)?;
+                convert(&mut m.cross_hair_thickness)?;
+                convert(&mut m.cross_hair_length)?;
+            }
+            MacroContent::Thermal(t) => {
+                convert(&mut t.center.0)?;
+                convert(&mut t.center.1)?;
+                convert(&mut t.outer_diameter)?;
+                convert(&mut t.inner_diameter)?;
+                convert(&mut t.gap)?;
+            }
+            MacroContent::VariableDefinition(_) => return Err(variables_error()),
+            MacroContent::Comment(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::{CoordinateFormat, CoordinateNumber, Coordinates};
+    use crate::extended_codes::{ApertureDefinition, Circle};
+    use crate::macros::CirclePrimitive;
+
+    #[test]
+    fn test_convert_unit() {
+        let cf = CoordinateFormat::new(3, 5);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::Unit(Unit::Inches).into(),
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Circle(Circle::new(0.5)),
+            ))
+            .into(),
+            ExtendedCode::ApertureMacro(
+                ApertureMacro::new("DOT")
+                    .add_content(CirclePrimitive::new(MacroDecimal::from(1.0))),
+            )
+            .into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(1, 2, cf))).into(),
+        ];
+        let converted = convert_unit(&commands, Unit::Millimeters).unwrap();
+        assert_eq!(converted[0], ExtendedCode::Unit(Unit::Millimeters).into());
+        assert_eq!(
+            converted[1],
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Circle(Circle::new(12.7)),
+            ))
+            .into()
+        );
+        assert_eq!(
+            converted[2],
+            ExtendedCode::ApertureMacro(
+                ApertureMacro::new("DOT")
+                    .add_content(CirclePrimitive::new(MacroDecimal::from(25.4)))
+            )
+            .into()
+        );
+        assert_eq!(
+            converted[3],
+            DCode::Operation(Operation::Flash(Coordinates::new(
+                CoordinateNumber::from(254).checked_div(10).unwrap(),
+                CoordinateNumber::from(508).checked_div(10).unwrap(),
+                cf
+            )))
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_convert_format() {
+        use crate::coordinates::CoordinateOffset;
+        use crate::traits::GerberCodeExt;
+
+        let cf = CoordinateFormat::new(3, 6);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(
+                    CoordinateNumber::from(1234567)
+                        .checked_div(1_000_000)
+                        .unwrap(),
+                    12,
+                    cf,
+                ),
+                Some(CoordinateOffset::at_x(-1, cf)),
+            ))
+            .into(),
+        ];
+        let target = CoordinateFormat::new(2, 4);
+        let converted = convert_format(&commands, target).unwrap();
+        assert_eq!(
+            converted.to_code_string().unwrap(),
+            "%FSLAX24Y24*%\nX12346Y120000I-10000D01*\n"
+        );
+        assert_eq!(
+            converted[1],
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(
+                    CoordinateNumber::from(12346).checked_div(10_000).unwrap(),
+                    12,
+                    target
+                ),
+                Some(CoordinateOffset::at_x(-1, target)),
+            ))
+            .into()
+        );
+
+        let large: Vec<Command> =
+            vec![DCode::Operation(Operation::Flash(Coordinates::new(100, 0, cf))).into()];
+        assert!(matches!(
+            convert_format(&large, target),
+            Err(GerberError::RangeError(_))
+        ));
+        assert!(convert_format(&commands, CoordinateFormat::new(2, 8)).is_err());
+    }
+
+    #[test]
+    fn test_convert_unit_errors() {
+        let cf = CoordinateFormat::new(3, 5);
+        let no_unit: Vec<Command> =
+            vec![DCode::Operation(Operation::Flash(Coordinates::new(1, 2, cf))).into()];
+        assert!(convert_unit(&no_unit, Unit::Inches).is_err());
+
+        let variables: Vec<Command> = vec![
+            ExtendedCode::Unit(Unit::Inches).into(),
+            ExtendedCode::ApertureMacro(
+                ApertureMacro::new("VAR")
+                    .add_content(CirclePrimitive::new(MacroDecimal::Variable(1))),
+            )
+            .into(),
+        ];
+        assert!(convert_unit(&variables, Unit::Millimeters).is_err());
+    }
+}
@@ -1,14 +1,16 @@
 //! Types for Gerber code generation related to coordinates.
 
 use std::convert::{From, Into};
-use std::i64;
+use std::fmt;
 use std::io::Write;
 use std::num::FpCategory;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
 
 use conv::TryFrom;
-use num_rational::Ratio;
 
+use crate::context::SerializationContext;
 use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::Unit;
 use crate::traits::PartialGerberCode;
 
 // Helper macros
@@ -18,12 +20,19 @@ use crate::traits::PartialGerberCode;
 macro_rules! impl_xy_partial_gerbercode {
     ($class:ty, $x:expr, $y: expr) => {
         impl<W: Write> PartialGerberCode<W> for $class {
-            fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+            fn serialize_partial_with_context(
+                &self,
+                writer: &mut W,
+                ctx: &SerializationContext,
+            ) -> GerberResult<()> {
+                let format = ctx.format.unwrap_or(self.format);
                 if let Some(x) = self.x {
-                    write!(writer, "{}{}", $x, x.gerber(&self.format)?)?;
+                    writer.write_all($x.as_bytes())?;
+                    x.write_gerber(writer, &format, ctx.zero_suppression)?;
                 }
                 if let Some(y) = self.y {
-                    write!(writer, "{}{}", $y, y.gerber(&self.format)?)?;
+                    writer.write_all($y.as_bytes())?;
+                    y.write_gerber(writer, &format, ctx.zero_suppression)?;
                 }
                 Ok(())
             }
@@ -31,6 +40,72 @@ macro_rules! impl_xy_partial_gerbercode {
     };
 }
 
+/// Implement constructors for struct types based on `x` and `y` attributes
+/// that take values with a known unit and convert them into the unit of the
+/// file, as well as the conversion of existing values.
+macro_rules! impl_xy_unit_conversions {
+    ($class:ident) => {
+        impl $class {
+            /// Create an instance from values in millimeters. The values are
+            /// converted into `file_unit` and rounded to the precision of
+            /// the coordinate number.
+            pub fn from_mm(
+                x: f64,
+                y: f64,
+                file_unit: Unit,
+                format: CoordinateFormat,
+            ) -> GerberResult<Self> {
+                Self::from_unit(x, y, Unit::Millimeters, file_unit, format)
+            }
+
+            /// Create an instance from values in inches. The values are
+            /// converted into `file_unit` and rounded to the precision of
+            /// the coordinate number.
+            pub fn from_inches(
+                x: f64,
+                y: f64,
+                file_unit: Unit,
+                format: CoordinateFormat,
+            ) -> GerberResult<Self> {
+                Self::from_unit(x, y, Unit::Inches, file_unit, format)
+            }
+
+            fn from_unit(
+                x: f64,
+                y: f64,
+                unit: Unit,
+                file_unit: Unit,
+                format: CoordinateFormat,
+            ) -> GerberResult<Self> {
+                Ok($class::new(
+                    CoordinateNumber::from_f64_rounded(convert_f64_unit(x, unit, file_unit))?,
+                    CoordinateNumber::from_f64_rounded(convert_f64_unit(y, unit, file_unit))?,
+                    format,
+                ))
+            }
+
+            /// Convert the values from one unit into another, see
+            /// [`CoordinateNumber::convert_unit`].
+            pub fn convert_unit(&self, from: Unit, to: Unit) -> GerberResult<Self> {
+                Ok($class {
+                    x: self.x.map(|x| x.convert_unit(from, to)).transpose()?,
+                    y: self.y.map(|y| y.convert_unit(from, to)).transpose()?,
+                    format: self.format,
+                })
+            }
+        }
+    };
+}
+
+/// Convert a length from one unit into another.
+pub(crate) fn convert_f64_unit(value: f64, from: Unit, to: Unit) -> f64 {
+    match (from, to) {
+        (Unit::Millimeters, Unit::Inches) => value / 25.4,
+        (Unit::Inches, Unit::Millimeters) => value * 25.4,
+        _ => value,
+    }
+}
+
 // Types
 
 /// The coordinate format specifies the number of integer and decimal places in
@@ -39,15 +114,107 @@ macro_rules! impl_xy_partial_gerbercode {
 /// of integer places must be not more than 6. Thus the longest representable
 /// coordinate number is `nnnnnn.nnnnnn`.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct CoordinateFormat {
     pub integer: u8,
     pub decimal: u8,
 }
 
+impl Default for CoordinateFormat {
+    /// The 4.6 format, which the specification recommends for files in
+    /// millimeters.
+    fn default() -> Self {
+        CoordinateFormat::new(4, 6)
+    }
+}
+
 impl CoordinateFormat {
     pub fn new(integer: u8, decimal: u8) -> Self {
         CoordinateFormat { integer, decimal }
     }
+
+    /// Create a format that the spec allows: 1 to 6 integer digits and 4 to
+    /// 6 decimal digits. Returns a `CoordinateFormatError` otherwise.
+    pub fn try_new(integer: u8, decimal: u8) -> GerberResult<Self> {
+        if !(4..=6).contains(&decimal) {
+            return Err(GerberError::CoordinateFormatError(format!(
+                "{} decimal digits, the spec requires 4 to 6",
+                decimal
+            )));
+        }
+        if !(1..=6).contains(&integer) {
+            return Err(GerberError::CoordinateFormatError(format!(
+                "{} integer digits, the spec requires 1 to 6",
+                integer
+            )));
+        }
+        Ok(CoordinateFormat::new(integer, decimal))
+    }
+}
+
+/// Which zeros are omitted from coordinate numbers.
+///
+/// The setting is part of the FS command. Omitting leading zeros is the
+/// only style allowed by the current spec, trailing zero omission is
+/// deprecated but still expected by some legacy consumers.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum ZeroSuppression {
+    /// Omit leading zeros (FS `L`).
+    #[default]
+    Leading,
+    /// Omit trailing zeros (FS `T`).
+    Trailing,
+    /// Print all digits of the coordinate format (FS `L`).
+    Disabled,
+}
+
+impl ZeroSuppression {
+    /// The letter used in the FS command.
+    pub fn fs_letter(self) -> char {
+        match self {
+            ZeroSuppression::Leading | ZeroSuppression::Disabled => 'L',
+            ZeroSuppression::Trailing => 'T',
+        }
+    }
+}
+
+/// How to round floats that fall between two representable coordinate
+/// numbers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum RoundingMode {
+    /// Round to the nearest value, halfway cases away from zero.
+    HalfUp,
+    /// Round to the nearest value, halfway cases to the even neighbour.
+    HalfEven,
+    /// Round towards zero.
+    Truncate,
+}
+
+impl RoundingMode {
+    fn round(self, val: f64) -> f64 {
+        match self {
+            RoundingMode::HalfUp => val.round(),
+            RoundingMode::Truncate => val.trunc(),
+            RoundingMode::HalfEven => {
+                let floor = val.floor();
+                let diff = val - floor;
+                if diff > 0.5 || (diff == 0.5 && floor % 2.0 != 0.0) {
+                    floor + 1.0
+                } else {
+                    floor
+                }
+            }
+        }
+    }
 }
 
 /// Coordinate numbers are integers conforming to the rules set by the FS
@@ -58,8 +225,15 @@ impl CoordinateFormat {
 /// A coordinate number must have at least one character. Zero therefore must
 /// be encoded as `0`.
 ///
-/// The value is stored as a 64 bit integer with 6 decimal places.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The value is stored as a 64 bit integer with 6 decimal places, or with 9
+/// decimal places if the `high-resolution` feature is enabled. The higher
+/// resolution reduces rounding errors in intermediate computations, at the
+/// cost of a smaller range of about ±9.2 million units.
+///
+/// With the `serde` feature, coordinate numbers are represented as decimal
+/// strings like `"-1.25"`, independent of the resolution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct CoordinateNumber {
     nano: i64,
 }
@@ -68,10 +242,250 @@ impl CoordinateNumber {
     pub fn new(nano: i64) -> Self {
         CoordinateNumber { nano }
     }
+
+    /// The raw fixed point value.
+    pub(crate) fn nano(self) -> i64 {
+        self.nano
+    }
+
+    /// Convert a float, rounding with the given mode.
+    ///
+    /// Note that the `TryFrom<f64>` implementation always truncates.
+    pub fn from_f64(val: f64, mode: RoundingMode) -> GerberResult<Self> {
+        if !val.is_finite() {
+            return Err(GerberError::ConversionError(
+                "Value is not a finite number".into(),
+            ));
+        }
+        let multiplied = mode.round(val * DECIMAL_PLACES_FACTOR as f64);
+        if (multiplied > i64::MAX as f64) || (multiplied < i64::MIN as f64) {
+            return Err(GerberError::ConversionError(
+                "Value is out of bounds".into(),
+            ));
+        }
+        Ok(CoordinateNumber {
+            nano: multiplied as i64,
+        })
+    }
+
+    /// Convert a float, rounding to the nearest representable value.
+    pub(crate) fn from_f64_rounded(val: f64) -> GerberResult<Self> {
+        CoordinateNumber::from_f64(val, RoundingMode::HalfUp)
+    }
+
+    /// Checked addition. Returns `None` on overflow.
+    pub fn checked_add(self, other: CoordinateNumber) -> Option<Self> {
+        self.nano.checked_add(other.nano).map(CoordinateNumber::new)
+    }
+
+    /// Checked subtraction. Returns `None` on overflow.
+    pub fn checked_sub(self, other: CoordinateNumber) -> Option<Self> {
+        self.nano.checked_sub(other.nano).map(CoordinateNumber::new)
+    }
+
+    /// Checked negation. Returns `None` on overflow.
+    pub fn checked_neg(self) -> Option<Self> {
+        self.nano.checked_neg().map(CoordinateNumber::new)
+    }
+
+    /// Wrapping addition.
+    pub fn wrapping_add(self, other: CoordinateNumber) -> Self {
+        CoordinateNumber::new(self.nano.wrapping_add(other.nano))
+    }
+
+    /// Wrapping subtraction.
+    pub fn wrapping_sub(self, other: CoordinateNumber) -> Self {
+        CoordinateNumber::new(self.nano.wrapping_sub(other.nano))
+    }
+
+    /// Wrapping negation.
+    pub fn wrapping_neg(self) -> Self {
+        CoordinateNumber::new(self.nano.wrapping_neg())
+    }
+
+    /// Convert a length from one unit into another.
+    ///
+    /// The conversion is done in fixed point. Converting from inches to
+    /// millimeters is exact, the other direction rounds to the nearest
+    /// representable value.
+    pub fn convert_unit(self, from: Unit, to: Unit) -> GerberResult<Self> {
+        let (numer, denom) = match (from, to) {
+            (Unit::Inches, Unit::Millimeters) => (254, 10),
+            (Unit::Millimeters, Unit::Inches) => (10, 254),
+            _ => return Ok(self),
+        };
+        self.checked_scale(numer, denom)
+            .ok_or_else(|| GerberError::ConversionError("Value is out of bounds".into()))
+    }
+
+    /// Checked multiplication with an integer. Returns `None` on overflow.
+    pub fn checked_mul(self, factor: i64) -> Option<Self> {
+        self.nano.checked_mul(factor).map(CoordinateNumber::new)
+    }
+
+    /// Checked division by an integer, rounding to the nearest representable
+    /// value. Returns `None` if the divisor is zero or on overflow.
+    pub fn checked_div(self, divisor: i64) -> Option<Self> {
+        self.checked_scale(1, divisor)
+    }
+
+    /// Scale by the exact ratio `numer / denom`, rounding to the nearest
+    /// representable value (halfway cases away from zero). The intermediate
+    /// product cannot overflow. Returns `None` if `denom` is zero or the
+    /// result is out of range.
+    pub fn checked_scale(self, numer: i64, denom: i64) -> Option<Self> {
+        if denom == 0 {
+            return None;
+        }
+        let product = i128::from(self.nano) * i128::from(numer);
+        let denom = i128::from(denom);
+        let mut quotient = product / denom;
+        let remainder = product % denom;
+        if 2 * remainder.abs() >= denom.abs() {
+            quotient += product.signum() * denom.signum();
+        }
+        <i64 as std::convert::TryFrom<i128>>::try_from(quotient)
+            .ok()
+            .map(CoordinateNumber::new)
+    }
+}
+
+// Arithmetic operators. Like the integer operators, these panic on overflow
+// in debug builds and wrap in release builds.
+
+impl Add for CoordinateNumber {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        CoordinateNumber::new(self.nano + other.nano)
+    }
+}
+
+impl Sub for CoordinateNumber {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        CoordinateNumber::new(self.nano - other.nano)
+    }
+}
+
+impl Neg for CoordinateNumber {
+    type Output = Self;
+    fn neg(self) -> Self {
+        CoordinateNumber::new(-self.nano)
+    }
+}
+
+impl Mul<i64> for CoordinateNumber {
+    type Output = Self;
+    fn mul(self, factor: i64) -> Self {
+        CoordinateNumber::new(self.nano * factor)
+    }
+}
+
+/// Division rounds to the nearest representable value.
+///
+/// Panics if the divisor is zero or the result overflows.
+impl Div<i64> for CoordinateNumber {
+    type Output = Self;
+    fn div(self, divisor: i64) -> Self {
+        self.checked_div(divisor)
+            .expect("Division by zero or overflow")
+    }
+}
+
+impl AddAssign for CoordinateNumber {
+    fn add_assign(&mut self, other: Self) {
+        self.nano += other.nano;
+    }
+}
+
+impl SubAssign for CoordinateNumber {
+    fn sub_assign(&mut self, other: Self) {
+        self.nano -= other.nano;
+    }
+}
+
+#[cfg(not(feature = "high-resolution"))]
+pub(crate) const DECIMAL_PLACES_CHARS: u8 = 6;
+#[cfg(not(feature = "high-resolution"))]
+pub(crate) const DECIMAL_PLACES_FACTOR: i64 = 1_000_000;
+
+#[cfg(feature = "high-resolution")]
+pub(crate) const DECIMAL_PLACES_CHARS: u8 = 9;
+#[cfg(feature = "high-resolution")]
+pub(crate) const DECIMAL_PLACES_FACTOR: i64 = 1_000_000_000;
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CoordinateNumber {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sign = if self.nano < 0 { "-" } else { "" };
+        let abs = self.nano.unsigned_abs();
+        let factor = DECIMAL_PLACES_FACTOR as u64;
+        let fraction = format!(
+            "{:0width$}",
+            abs % factor,
+            width = DECIMAL_PLACES_CHARS as usize
+        );
+        let fraction = fraction.trim_end_matches('0');
+        let value = if fraction.is_empty() {
+            format!("{}{}", sign, abs / factor)
+        } else {
+            format!("{}{}.{}", sign, abs / factor, fraction)
+        };
+        serializer.serialize_str(&value)
+    }
 }
 
-const DECIMAL_PLACES_CHARS: u8 = 6;
-const DECIMAL_PLACES_FACTOR: i64 = 1_000_000;
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for CoordinateNumber {
+    fn schema_name() -> String {
+        "CoordinateNumber".into()
+    }
+
+    fn json_schema(_gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        schemars::schema::SchemaObject {
+            instance_type: Some(schemars::schema::InstanceType::String.into()),
+            string: Some(Box::new(schemars::schema::StringValidation {
+                pattern: Some("^-?[0-9]+(\\.[0-9]+)?$".into()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CoordinateNumber {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let value = String::deserialize(deserializer)?;
+        let invalid = || D::Error::custom(format!("invalid coordinate number {:?}", value));
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value.as_str()),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let places = DECIMAL_PLACES_CHARS as usize;
+        if integer.is_empty()
+            || fraction.len() > places
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let fraction = format!("{:0<width$}", fraction, width = places);
+        let nano = integer
+            .parse::<i64>()
+            .ok()
+            .and_then(|i| i.checked_mul(DECIMAL_PLACES_FACTOR))
+            .and_then(|i| i.checked_add(fraction.parse::<i64>().ok()?))
+            .ok_or_else(invalid)?;
+        Ok(CoordinateNumber::new(if negative { -nano } else { nano }))
+    }
+}
 
 impl TryFrom<f64> for CoordinateNumber {
     type Err = GerberError;
@@ -96,9 +510,29 @@ impl TryFrom<f64> for CoordinateNumber {
     }
 }
 
-impl Into<f64> for CoordinateNumber {
-    fn into(self) -> f64 {
-        (self.nano as f64) / DECIMAL_PLACES_FACTOR as f64
+/// Render the value as a decimal number with trailing zeros removed, e.g.
+/// `1.375` or `-2`.
+impl fmt::Display for CoordinateNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.nano < 0 { "-" } else { "" };
+        let abs = self.nano.unsigned_abs();
+        let factor = DECIMAL_PLACES_FACTOR as u64;
+        let (integer, fraction) = (abs / factor, abs % factor);
+        if fraction == 0 {
+            return write!(f, "{}{}", sign, integer);
+        }
+        let fraction = format!(
+            "{:0width$}",
+            fraction,
+            width = DECIMAL_PLACES_CHARS as usize
+        );
+        write!(f, "{}{}.{}", sign, integer, fraction.trim_end_matches('0'))
+    }
+}
+
+impl From<CoordinateNumber> for f64 {
+    fn from(val: CoordinateNumber) -> Self {
+        (val.nano as f64) / DECIMAL_PLACES_FACTOR as f64
     }
 }
 
@@ -122,30 +556,134 @@ impl_from_integer!(i32);
 impl_from_integer!(u8);
 impl_from_integer!(u16);
 
+macro_rules! impl_try_from_integer {
+    ($class:ty) => {
+        impl TryFrom<$class> for CoordinateNumber {
+            type Err = GerberError;
+            fn try_from(val: $class) -> Result<Self, Self::Err> {
+                <i64 as std::convert::TryFrom<$class>>::try_from(val)
+                    .ok()
+                    .and_then(|val| val.checked_mul(DECIMAL_PLACES_FACTOR))
+                    .map(CoordinateNumber::new)
+                    .ok_or_else(|| GerberError::ConversionError("Value is out of bounds".into()))
+            }
+        }
+    };
+}
+
+// These types may overflow when multiplied with DECIMAL_PLACES_FACTOR.
+impl_try_from_integer!(i64);
+impl_try_from_integer!(u64);
+
 impl CoordinateNumber {
     pub fn gerber(&self, format: &CoordinateFormat) -> Result<String, GerberError> {
+        self.gerber_with_zero_suppression(format, ZeroSuppression::Leading)
+    }
+
+    /// Format the number with the given zero suppression.
+    pub fn gerber_with_zero_suppression(
+        &self,
+        format: &CoordinateFormat,
+        zero_suppression: ZeroSuppression,
+    ) -> Result<String, GerberError> {
+        let mut buf = Vec::with_capacity(16);
+        self.write_gerber(&mut buf, format, zero_suppression)?;
+        Ok(String::from_utf8(buf).expect("Gerber numbers are ASCII"))
+    }
+
+    /// Write the number with the given zero suppression, without allocating.
+    pub(crate) fn write_gerber<W: Write>(
+        &self,
+        writer: &mut W,
+        format: &CoordinateFormat,
+        zero_suppression: ZeroSuppression,
+    ) -> GerberResult<()> {
         if format.decimal > DECIMAL_PLACES_CHARS {
             return Err(GerberError::CoordinateFormatError(
                 "Invalid precision: Too high!".into(),
             ));
         }
-        if self.nano.abs() >= 10_i64.pow((format.integer + DECIMAL_PLACES_CHARS) as u32) {
+        let limit = 10_u64.checked_pow(u32::from(format.integer) + u32::from(DECIMAL_PLACES_CHARS));
+        if matches!(limit, Some(limit) if self.nano.unsigned_abs() >= limit) {
             return Err(GerberError::CoordinateFormatError(
                 "Number is too large for chosen format!".into(),
             ));
         }
 
-        let divisor: i64 = 10_i64.pow((DECIMAL_PLACES_CHARS - format.decimal) as u32);
-        let number: i64 = Ratio::new(self.nano, divisor).round().to_integer();
-        Ok(number.to_string())
+        let number = self.round_to_decimal_places(format.decimal);
+        let mut buffer = itoa::Buffer::new();
+        if zero_suppression == ZeroSuppression::Leading {
+            writer.write_all(buffer.format(number).as_bytes())?;
+            return Ok(());
+        }
+
+        let digits = buffer.format(number.unsigned_abs());
+        let width = usize::from(format.integer) + usize::from(format.decimal);
+        let padding = width.saturating_sub(digits.len());
+        match zero_suppression {
+            ZeroSuppression::Trailing if number == 0 => writer.write_all(b"0")?,
+            ZeroSuppression::Trailing => {
+                if number < 0 {
+                    writer.write_all(b"-")?;
+                }
+                write_zeros(writer, padding)?;
+                writer.write_all(digits.trim_end_matches('0').as_bytes())?;
+            }
+            _ => {
+                if number < 0 {
+                    writer.write_all(b"-")?;
+                }
+                write_zeros(writer, padding)?;
+                writer.write_all(digits.as_bytes())?;
+            }
+        }
+        Ok(())
     }
 }
 
+impl CoordinateNumber {
+    /// Round half away from zero to the decimal places, returns the number
+    /// in units of the last decimal place.
+    fn round_to_decimal_places(self, decimal: u8) -> i64 {
+        let divisor: i64 = 10_i64.pow((DECIMAL_PLACES_CHARS - decimal) as u32);
+        let (quotient, remainder) = (self.nano / divisor, self.nano % divisor);
+        if remainder.unsigned_abs() * 2 >= divisor as u64 {
+            quotient + self.nano.signum()
+        } else {
+            quotient
+        }
+    }
+
+    /// Round the number to the decimal places of the format, returning an
+    /// error if it does not fit into the format.
+    pub(crate) fn round_to_format(self, format: &CoordinateFormat) -> GerberResult<Self> {
+        self.write_gerber(&mut std::io::sink(), format, ZeroSuppression::Leading)?;
+        let units = self.round_to_decimal_places(format.decimal);
+        let factor = 10_i64.pow((DECIMAL_PLACES_CHARS - format.decimal) as u32);
+        Ok(CoordinateNumber::new(units * factor))
+    }
+}
+
+fn write_zeros<W: Write>(writer: &mut W, count: usize) -> GerberResult<()> {
+    const ZEROS: &[u8] = b"0000000000000000";
+    let mut remaining = count;
+    while remaining > 0 {
+        let n = remaining.min(ZEROS.len());
+        writer.write_all(&ZEROS[..n])?;
+        remaining -= n;
+    }
+    Ok(())
+}
+
 /// Coordinates are part of an operation.
 ///
 /// Coordinates are modal. If an X is omitted, the X coordinate of the
 /// current point is used. Similar for Y.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Coordinates {
     pub x: Option<CoordinateNumber>,
     pub y: Option<CoordinateNumber>,
@@ -186,13 +724,26 @@ impl Coordinates {
             format,
         }
     }
+
+    pub(crate) fn from_f64_rounded(x: f64, y: f64, format: CoordinateFormat) -> GerberResult<Self> {
+        Ok(Coordinates::new(
+            CoordinateNumber::from_f64_rounded(x)?,
+            CoordinateNumber::from_f64_rounded(y)?,
+            format,
+        ))
+    }
 }
 
 impl_xy_partial_gerbercode!(Coordinates, "X", "Y");
+impl_xy_unit_conversions!(Coordinates);
 
 /// Coordinate offsets can be used for interpolate operations in circular
 /// interpolation mode.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct CoordinateOffset {
     pub x: Option<CoordinateNumber>,
     pub y: Option<CoordinateNumber>,
@@ -236,6 +787,7 @@ impl CoordinateOffset {
 }
 
 impl_xy_partial_gerbercode!(CoordinateOffset, "I", "J");
+impl_xy_unit_conversions!(CoordinateOffset);
 
 #[cfg(test)]
 mod test {
@@ -248,14 +800,48 @@ mod test {
 
     use crate::traits::PartialGerberCode;
 
+    /// Create a coordinate number from a value with 6 decimal places,
+    /// independent of the internal resolution.
+    fn micro(value: i64) -> CoordinateNumber {
+        CoordinateNumber::new(value * (DECIMAL_PLACES_FACTOR / 1_000_000))
+    }
+
+    #[test]
+    fn test_coordinate_format_try_new() {
+        assert_eq!(
+            CoordinateFormat::try_new(2, 4).unwrap(),
+            CoordinateFormat::new(2, 4)
+        );
+        assert!(CoordinateFormat::try_new(6, 6).is_ok());
+        for (integer, decimal) in [(0, 0), (0, 4), (2, 3), (2, 7), (7, 6)] {
+            assert!(matches!(
+                CoordinateFormat::try_new(integer, decimal),
+                Err(GerberError::CoordinateFormatError(_))
+            ));
+        }
+
+        // The same formats pass validation
+        use crate::validate::Validate;
+        for integer in 0..8 {
+            for decimal in 0..8 {
+                assert_eq!(
+                    CoordinateFormat::try_new(integer, decimal).is_ok(),
+                    CoordinateFormat::new(integer, decimal)
+                        .validate()
+                        .is_empty()
+                );
+            }
+        }
+    }
+
     #[test]
     /// Test integer to coordinate number conversion
     fn test_from_i8() {
-        let a = CoordinateNumber { nano: 13000000 };
+        let a = micro(13000000);
         let b = CoordinateNumber::from(13i8);
         assert_eq!(a, b);
 
-        let c = CoordinateNumber { nano: -99000000 };
+        let c = micro(-99000000);
         let d = CoordinateNumber::from(-99i8);
         assert_eq!(c, d);
     }
@@ -263,11 +849,11 @@ mod test {
     #[test]
     /// Test integer to coordinate number conversion
     fn test_from_i32() {
-        let a = CoordinateNumber { nano: 13000000 };
+        let a = micro(13000000);
         let b = CoordinateNumber::from(13);
         assert_eq!(a, b);
 
-        let c = CoordinateNumber { nano: -998000000 };
+        let c = micro(-998000000);
         let d = CoordinateNumber::from(-998);
         assert_eq!(c, d);
     }
@@ -275,13 +861,11 @@ mod test {
     #[test]
     /// Test float to coordinate number conversion
     fn test_try_from_f64_success() {
-        let a = CoordinateNumber { nano: 1375000i64 };
+        let a = micro(1375000i64);
         let b = CoordinateNumber::try_from(1.375f64).unwrap();
         assert_eq!(a, b);
 
-        let c = CoordinateNumber {
-            nano: 123456888888i64,
-        };
+        let c = micro(123456888888i64);
         let d = CoordinateNumber::try_from(123456.888888f64).unwrap();
         assert_eq!(c, d);
 
@@ -289,7 +873,7 @@ mod test {
         let f = CoordinateNumber::try_from(0f64).unwrap();
         assert_eq!(e, f);
 
-        let g = CoordinateNumber { nano: -12345678 };
+        let g = micro(-12345678);
         let h = CoordinateNumber::try_from(-12.345678).unwrap();
         assert_eq!(g, h);
     }
@@ -310,17 +894,177 @@ mod test {
         assert!(cn4.is_err());
     }
 
+    #[test]
+    fn test_arithmetic() {
+        let a = CoordinateNumber::new(1_500_000);
+        let b = CoordinateNumber::new(250_000);
+        assert_eq!(a + b, CoordinateNumber::new(1_750_000));
+        assert_eq!(a - b, CoordinateNumber::new(1_250_000));
+        assert_eq!(-a, CoordinateNumber::new(-1_500_000));
+        let mut c = a;
+        c += b;
+        c -= a;
+        assert_eq!(c, b);
+
+        let max = CoordinateNumber::new(i64::MAX);
+        let min = CoordinateNumber::new(i64::MIN);
+        assert_eq!(a.checked_sub(b), Some(a - b));
+        assert_eq!(max.checked_add(b), None);
+        assert_eq!(min.checked_sub(b), None);
+        assert_eq!(min.checked_neg(), None);
+        assert_eq!(max.wrapping_add(CoordinateNumber::new(1)), min);
+        assert_eq!(min.wrapping_sub(CoordinateNumber::new(1)), max);
+        assert_eq!(min.wrapping_neg(), min);
+    }
+
+    #[test]
+    fn test_scaling() {
+        let a = CoordinateNumber::new(1_500_000);
+        assert_eq!(a * 3, CoordinateNumber::new(4_500_000));
+        assert_eq!(a / 4, CoordinateNumber::new(375_000));
+        assert_eq!(CoordinateNumber::new(5) / 2, CoordinateNumber::new(3));
+        assert_eq!(CoordinateNumber::new(-5) / 2, CoordinateNumber::new(-3));
+        assert_eq!(CoordinateNumber::new(4) / 3, CoordinateNumber::new(1));
+        assert_eq!(a.checked_div(0), None);
+        assert_eq!(CoordinateNumber::new(i64::MAX).checked_mul(2), None);
+
+        // 25.4 mm per inch
+        let inch = CoordinateNumber::from(1);
+        assert_eq!(inch.checked_scale(254, 10), Some(micro(25_400_000)));
+        let mm = CoordinateNumber::from(1);
+        #[cfg(not(feature = "high-resolution"))]
+        assert_eq!(
+            mm.checked_scale(10, 254),
+            Some(CoordinateNumber::new(39_370))
+        );
+        #[cfg(feature = "high-resolution")]
+        assert_eq!(
+            mm.checked_scale(10, 254),
+            Some(CoordinateNumber::new(39_370_079))
+        );
+        // The intermediate product does not overflow
+        let max = CoordinateNumber::new(i64::MAX);
+        assert_eq!(max.checked_scale(1000, 1000), Some(max));
+        assert_eq!(max.checked_scale(2, 1), None);
+        assert_eq!(max.checked_scale(1, 0), None);
+    }
+
+    #[test]
+    fn test_ordering() {
+        let mut numbers = vec![
+            CoordinateNumber::from(2),
+            CoordinateNumber::new(-1),
+            CoordinateNumber::new(1_500_000),
+        ];
+        numbers.sort();
+        assert_eq!(
+            numbers,
+            vec![
+                CoordinateNumber::new(-1),
+                CoordinateNumber::new(1_500_000),
+                CoordinateNumber::from(2),
+            ]
+        );
+        assert_eq!(numbers.iter().max(), Some(&CoordinateNumber::from(2)));
+        assert!(CoordinateNumber::from(-3) < CoordinateNumber::from(0));
+    }
+
+    #[test]
+    #[cfg(feature = "high-resolution")]
+    fn test_high_resolution() {
+        assert_eq!(CoordinateNumber::new(1).to_string(), "0.000000001");
+        let cf = CoordinateFormat::new(2, 6);
+        let cn = CoordinateNumber::from_f64(0.000_000_4, RoundingMode::HalfUp).unwrap();
+        assert_eq!(cn, CoordinateNumber::new(400));
+        assert_eq!(cn.gerber(&cf).unwrap(), "0");
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(micro(1_375_000).to_string(), "1.375");
+        assert_eq!(micro(-500_000).to_string(), "-0.5");
+        assert_eq!(micro(1).to_string(), "0.000001");
+        assert_eq!(CoordinateNumber::from(-2).to_string(), "-2");
+        assert_eq!(CoordinateNumber::new(0).to_string(), "0");
+        #[cfg(not(feature = "high-resolution"))]
+        assert_eq!(
+            CoordinateNumber::new(i64::MIN).to_string(),
+            "-9223372036854.775808"
+        );
+    }
+
+    #[test]
+    fn test_convert_unit() {
+        let inch = CoordinateNumber::from(1);
+        let mm = inch.convert_unit(Unit::Inches, Unit::Millimeters).unwrap();
+        assert_eq!(mm, micro(25_400_000));
+        assert_eq!(
+            mm.convert_unit(Unit::Millimeters, Unit::Inches).unwrap(),
+            inch
+        );
+        assert_eq!(
+            mm.convert_unit(Unit::Millimeters, Unit::Millimeters)
+                .unwrap(),
+            mm
+        );
+        let big = CoordinateNumber::new(i64::MAX);
+        assert!(big.convert_unit(Unit::Inches, Unit::Millimeters).is_err());
+
+        let cf = CoordinateFormat::new(3, 4);
+        let coords = Coordinates::at_x(2, cf)
+            .convert_unit(Unit::Inches, Unit::Millimeters)
+            .unwrap();
+        assert_partial_code!(coords, "X508000");
+    }
+
+    #[test]
+    #[cfg(not(feature = "high-resolution"))]
+    fn test_from_f64_rounding_modes() {
+        let cases = [
+            (0.0000025, 3, 2, 2),
+            (0.0000035, 4, 4, 3),
+            (-0.0000025, -3, -2, -2),
+            (0.0000026, 3, 3, 2),
+            (1.0000004, 1_000_000, 1_000_000, 1_000_000),
+        ];
+        for &(val, half_up, half_even, truncate) in &cases {
+            let convert = |mode| CoordinateNumber::from_f64(val, mode).unwrap();
+            assert_eq!(
+                convert(RoundingMode::HalfUp),
+                CoordinateNumber::new(half_up)
+            );
+            assert_eq!(
+                convert(RoundingMode::HalfEven),
+                CoordinateNumber::new(half_even)
+            );
+            assert_eq!(
+                convert(RoundingMode::Truncate),
+                CoordinateNumber::new(truncate)
+            );
+        }
+        assert!(CoordinateNumber::from_f64(f64::NAN, RoundingMode::HalfEven).is_err());
+    }
+
+    #[test]
+    /// Test checked conversion from 64 bit integers
+    fn test_try_from_i64() {
+        let cn = CoordinateNumber::try_from(-42i64).unwrap();
+        assert_eq!(cn, CoordinateNumber::from(-42));
+        let cn = CoordinateNumber::try_from(42u64).unwrap();
+        assert_eq!(cn, CoordinateNumber::from(42));
+        assert!(CoordinateNumber::try_from(i64::MAX / 1_000).is_err());
+        assert!(CoordinateNumber::try_from(i64::MIN).is_err());
+        assert!(CoordinateNumber::try_from(u64::MAX).is_err());
+    }
+
     #[test]
     /// Test coordinate number to float conversion
     fn test_into_f64() {
-        let a: f64 = CoordinateNumber { nano: 1375000i64 }.into();
+        let a: f64 = micro(1375000i64).into();
         let b = 1.375f64;
         assert_eq!(a, b);
 
-        let c: f64 = CoordinateNumber {
-            nano: 123456888888i64,
-        }
-        .into();
+        let c: f64 = micro(123456888888i64).into();
         let d = 123456.888888f64;
         assert_eq!(c, d);
 
@@ -356,8 +1100,8 @@ mod test {
         let cf1 = CoordinateFormat::new(6, 6);
         let cf2 = CoordinateFormat::new(2, 4);
 
-        let a = CoordinateNumber { nano: 10000000 }.gerber(&cf1).unwrap();
-        let b = CoordinateNumber { nano: 20000000 }.gerber(&cf2).unwrap();
+        let a = micro(10000000).gerber(&cf1).unwrap();
+        let b = micro(20000000).gerber(&cf2).unwrap();
         assert_eq!(a, "10000000".to_string());
         assert_eq!(b, "200000".to_string());
     }
@@ -366,7 +1110,7 @@ mod test {
     /// Test coordinate number to string conversion
     fn test_formatted_65() {
         let cf = CoordinateFormat::new(6, 5);
-        let d = CoordinateNumber { nano: 123456789012 }.gerber(&cf).unwrap();
+        let d = micro(123456789012).gerber(&cf).unwrap();
         assert_eq!(d, "12345678901".to_string());
     }
 
@@ -374,7 +1118,7 @@ mod test {
     /// Test coordinate number to string conversion
     fn test_formatted_54() {
         let cf = CoordinateFormat::new(5, 4);
-        let d = CoordinateNumber { nano: 12345678901 }.gerber(&cf).unwrap();
+        let d = micro(12345678901).gerber(&cf).unwrap();
         assert_eq!(d, "123456789".to_string());
     }
 
@@ -382,7 +1126,7 @@ mod test {
     /// Test coordinate number to string conversion failure
     fn test_formatted_number_too_large() {
         let cf = CoordinateFormat::new(4, 5);
-        let d = CoordinateNumber { nano: 12345000000 }.gerber(&cf);
+        let d = micro(12345000000).gerber(&cf);
         assert!(d.is_err());
     }
 
@@ -390,15 +1134,26 @@ mod test {
     /// Test coordinate number to string conversion failure
     fn test_formatted_negative_number_too_large() {
         let cf = CoordinateFormat::new(4, 5);
-        let d = CoordinateNumber { nano: -12345000000 }.gerber(&cf);
+        let d = micro(-12345000000).gerber(&cf);
         assert!(d.is_err());
     }
 
+    #[test]
+    /// Test coordinate number to string conversion with huge formats, e.g.
+    /// from `Arbitrary`
+    fn test_formatted_huge_format() {
+        let cf = CoordinateFormat::new(255, 6);
+        let d = micro(1).gerber_with_zero_suppression(&cf, ZeroSuppression::Disabled);
+        assert_eq!(d.unwrap().len(), 261);
+        let cf = CoordinateFormat::new(255, 255);
+        assert!(micro(1).gerber(&cf).is_err());
+    }
+
     #[test]
     /// Test coordinate number to string conversion (rounding of decimal part)
     fn test_formatted_44_rounding() {
         let cf = CoordinateFormat::new(4, 4);
-        let d = CoordinateNumber { nano: 1234432199 }.gerber(&cf).unwrap();
+        let d = micro(1234432199).gerber(&cf).unwrap();
         assert_eq!(d, "12344322".to_string());
     }
 
@@ -406,14 +1161,34 @@ mod test {
     /// Test negative coordinate number to string conversion
     fn test_formatted_negative_rounding() {
         let cf = CoordinateFormat::new(6, 4);
-        let d = CoordinateNumber {
-            nano: -123456789099,
-        }
-        .gerber(&cf)
-        .unwrap();
+        let d = micro(-123456789099).gerber(&cf).unwrap();
         assert_eq!(d, "-1234567891".to_string());
     }
 
+    #[test]
+    /// Test that halfway values are rounded away from zero
+    fn test_formatted_halfway_rounding() {
+        let cf = CoordinateFormat::new(2, 4);
+        assert_eq!(micro(1_234_450).gerber(&cf).unwrap(), "12345");
+        assert_eq!(micro(-1_234_450).gerber(&cf).unwrap(), "-12345");
+        assert_eq!(micro(1_234_449).gerber(&cf).unwrap(), "12344");
+        assert_eq!(micro(-50).gerber(&cf).unwrap(), "-1");
+    }
+
+    #[test]
+    /// Test coordinate number formatting with zero suppression
+    fn test_formatted_zero_suppression() {
+        let cf = CoordinateFormat::new(2, 4);
+        let format = |value, zs| micro(value).gerber_with_zero_suppression(&cf, zs).unwrap();
+        assert_eq!(format(1_500_000, ZeroSuppression::Leading), "15000");
+        assert_eq!(format(1_500_000, ZeroSuppression::Trailing), "015");
+        assert_eq!(format(1_500_000, ZeroSuppression::Disabled), "015000");
+        assert_eq!(format(-250_000, ZeroSuppression::Trailing), "-0025");
+        assert_eq!(format(-250_000, ZeroSuppression::Disabled), "-002500");
+        assert_eq!(format(0, ZeroSuppression::Trailing), "0");
+        assert_eq!(format(0, ZeroSuppression::Disabled), "000000");
+    }
+
     #[test]
     fn test_coordinates_into() {
         let cf = CoordinateFormat::new(2, 4);
@@ -476,4 +1251,17 @@ mod test {
         assert_coords!(CoordinateOffset::at_y(20, cf55), "J2000000");
         assert_coords!(CoordinateOffset::new(0, -400, cf44), "I0J-4000000");
     }
+
+    #[test]
+    fn test_unit_constructors() {
+        let cf = CoordinateFormat::new(2, 4);
+        let c = Coordinates::from_mm(25.4, 1.27, Unit::Inches, cf).unwrap();
+        assert_eq!(c, Coordinates::new(1, micro(50_000), cf));
+        let c = Coordinates::from_inches(1.0, 0.5, Unit::Millimeters, cf).unwrap();
+        assert_partial_code!(c, "X254000Y127000");
+        let c = Coordinates::from_mm(1.5, 2.0, Unit::Millimeters, cf).unwrap();
+        assert_partial_code!(c, "X15000Y20000");
+        let o = CoordinateOffset::from_inches(0.1, 0.0, Unit::Millimeters, cf).unwrap();
+        assert_partial_code!(o, "I25400J0");
+    }
 }
@@ -0,0 +1,230 @@
+//! Formatting of decimal values, e.g. aperture sizes or macro parameters.
+
+#[cfg(feature = "ordered-float")]
+use std::cmp::Ordering;
+use std::fmt;
+#[cfg(feature = "ordered-float")]
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::ops::Deref;
+
+use crate::errors::{GerberError, GerberResult};
+
+/// The default number of decimal places of decimal values.
+pub const DEFAULT_DECIMAL_PLACES: usize = 6;
+
+/// A decimal value of a command, e.g. an aperture size.
+///
+/// Decimals compare like `f64`. With the `ordered-float` feature, they
+/// compare like ordered floats instead: NaN equals NaN and is greater than
+/// all other values. This makes it possible to implement `Eq`, `Ord` and
+/// `Hash`, so that commands implement them as well.
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(not(feature = "ordered-float"), derive(PartialEq, PartialOrd))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Decimal(f64);
+
+impl Decimal {
+    pub fn new(value: f64) -> Self {
+        Decimal(value)
+    }
+
+    pub fn into_inner(self) -> f64 {
+        self.0
+    }
+}
+
+impl From<f64> for Decimal {
+    fn from(value: f64) -> Self {
+        Decimal(value)
+    }
+}
+
+impl From<Decimal> for f64 {
+    fn from(value: Decimal) -> Self {
+        value.0
+    }
+}
+
+impl Deref for Decimal {
+    type Target = f64;
+
+    fn deref(&self) -> &f64 {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(feature = "ordered-float")]
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        ordered_float::OrderedFloat(self.0) == ordered_float::OrderedFloat(other.0)
+    }
+}
+
+#[cfg(feature = "ordered-float")]
+impl Eq for Decimal {}
+
+#[cfg(feature = "ordered-float")]
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "ordered-float")]
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        ordered_float::OrderedFloat(self.0).cmp(&ordered_float::OrderedFloat(other.0))
+    }
+}
+
+#[cfg(feature = "ordered-float")]
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ordered_float::OrderedFloat(self.0).hash(state)
+    }
+}
+
+/// Convert a float into a decimal.
+pub fn to_decimal(value: f64) -> Decimal {
+    Decimal(value)
+}
+
+/// Convert a decimal into a float.
+pub fn from_decimal(value: Decimal) -> f64 {
+    value.0
+}
+
+/// Values below this limit, scaled by the decimal places, have a precision of
+/// less than half a decimal place (2^51).
+const FAST_PATH_LIMIT: f64 = 2_251_799_813_685_248.0;
+
+/// Format a decimal value in plain decimal notation.
+///
+/// The value is rounded to at most `decimal_places` decimal places and
+/// trailing zeros are stripped, so `0.1 + 0.2` is written as `0.3`. NaN and
+/// infinite values cannot be represented in Gerber and result in an error.
+pub fn format_decimal(value: f64, decimal_places: usize) -> GerberResult<String> {
+    let mut buf = Vec::with_capacity(24);
+    write_decimal(&mut buf, value, decimal_places)?;
+    Ok(String::from_utf8(buf).expect("Decimals are ASCII"))
+}
+
+/// Write a decimal value like [`format_decimal`](fn.format_decimal.html),
+/// without allocating in the common case.
+pub(crate) fn write_decimal<W: Write>(
+    writer: &mut W,
+    value: f64,
+    decimal_places: usize,
+) -> GerberResult<()> {
+    if !value.is_finite() {
+        return Err(GerberError::RangeError(format!(
+            "Cannot format non-finite decimal {}",
+            value
+        )));
+    }
+
+    // Fast path: If the shortest representation of the value fits into the
+    // decimal places, it is also the rounded value. This only holds as long
+    // as the precision of the value is finer than the decimal places.
+    if decimal_places <= 15 && value.abs() < FAST_PATH_LIMIT / 10_f64.powi(decimal_places as i32) {
+        let mut buffer = ryu::Buffer::new();
+        let shortest = buffer.format_finite(value);
+        if let Some((int, frac)) = shortest.split_once('.') {
+            if frac == "0" {
+                let int = if int == "-0" { "0" } else { int };
+                writer.write_all(int.as_bytes())?;
+                return Ok(());
+            }
+            if !frac.contains('e') && frac.len() <= decimal_places {
+                writer.write_all(shortest.as_bytes())?;
+                return Ok(());
+            }
+        }
+    }
+
+    let mut formatted = format!("{:.*}", decimal_places, value);
+    if formatted.contains('.') {
+        let trimmed = formatted.trim_end_matches('0').trim_end_matches('.').len();
+        formatted.truncate(trimmed);
+    }
+    if formatted == "-0" {
+        formatted.remove(0);
+    }
+    writer.write_all(formatted.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_decimal() {
+        let format = |value| format_decimal(value, DEFAULT_DECIMAL_PLACES).unwrap();
+        assert_eq!(format(1.0), "1");
+        assert_eq!(format(-2.5), "-2.5");
+        assert_eq!(format(100.0), "100");
+        assert_eq!(format(0.1 + 0.2), "0.3");
+        assert_eq!(format(1e-7), "0");
+        assert_eq!(format(-1e-7), "0");
+        assert_eq!(format(1e-6), "0.000001");
+        assert_eq!(format(1e10), "10000000000");
+        assert_eq!(format_decimal(1.23456, 2).unwrap(), "1.23");
+        assert_eq!(format_decimal(7.5, 0).unwrap(), "8");
+        assert_eq!(format_decimal(-0.0, 3).unwrap(), "0");
+        assert_eq!(format_decimal(-0.25, 3).unwrap(), "-0.25");
+        assert_eq!(
+            format_decimal(1e15 + 0.125, 6).unwrap(),
+            "1000000000000000.125"
+        );
+    }
+
+    #[test]
+    fn test_format_decimal_non_finite() {
+        assert!(format_decimal(f64::NAN, DEFAULT_DECIMAL_PLACES).is_err());
+        assert!(format_decimal(f64::INFINITY, DEFAULT_DECIMAL_PLACES).is_err());
+        assert!(format_decimal(f64::NEG_INFINITY, DEFAULT_DECIMAL_PLACES).is_err());
+    }
+
+    #[test]
+    fn test_decimal_conversion() {
+        assert_eq!(format!("{:?}", Decimal::from(1.5)), "1.5");
+        assert_eq!(f64::from(Decimal::new(2.5)), 2.5);
+        assert_eq!(*to_decimal(0.5) * 2.0, 1.0);
+        assert_eq!(from_decimal(Decimal::new(3.0)), 3.0);
+        assert!(Decimal::new(1.0) < Decimal::new(2.0));
+        assert_eq!(Decimal::new(-0.0), Decimal::new(0.0));
+    }
+
+    #[test]
+    #[cfg(not(feature = "ordered-float"))]
+    fn test_decimal_comparison() {
+        let nan = Decimal::new(f64::NAN);
+        assert_ne!(nan, nan);
+        assert_eq!(nan.partial_cmp(&Decimal::new(f64::INFINITY)), None);
+    }
+
+    #[test]
+    #[cfg(feature = "ordered-float")]
+    fn test_decimal_comparison() {
+        let nan = Decimal::new(f64::NAN);
+        assert_eq!(nan, nan);
+        assert!(nan > Decimal::new(f64::INFINITY));
+    }
+}
@@ -0,0 +1,171 @@
+//! Deprecated codes.
+//!
+//! These codes are no longer part of the spec, but still appear in files
+//! written by older software. They can be converted into their modern
+//! equivalents with [`upgrade_deprecated`](fn.upgrade_deprecated.html).
+
+use std::io::Write;
+
+use crate::context::SerializationContext;
+use crate::coordinates::{CoordinateOffset, Coordinates};
+use crate::decimal::Decimal;
+use crate::errors::GerberResult;
+use crate::traits::{GerberCode, PartialGerberCode};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum DeprecatedCode {
+    /// G54 followed by an aperture selection, e.g. `G54D10*`
+    SelectAperture(i32),
+    /// G70, set the unit to inches
+    UnitInches,
+    /// G71, set the unit to millimeters
+    UnitMillimeters,
+    /// IP, image polarity
+    ImagePolarity(ImagePolarity),
+    /// MI, mirror the image along the A (X) and/or B (Y) axis
+    MirrorImage { a: bool, b: bool },
+    /// OF, offset of the image along the A (X) and B (Y) axis
+    Offset { a: Decimal, b: Decimal },
+    /// SF, scale factor of the image along the A (X) and B (Y) axis
+    ScaleFactor { a: Decimal, b: Decimal },
+    /// Coordinates without an operation code, the previous operation code
+    /// is used
+    CoordinatesOnly(Coordinates),
+    /// Coordinates and the offset (I/J) of an arc center without an
+    /// operation code, the previous operation code must be D01
+    CoordinatesWithOffset(Coordinates, CoordinateOffset),
+}
+
+impl<W: Write> GerberCode<W> for DeprecatedCode {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            DeprecatedCode::SelectAperture(code) => {
+                write!(writer, "G54D{}*", code)?;
+                ctx.end_line(writer)?;
+            }
+            DeprecatedCode::UnitInches => {
+                write!(writer, "G70*")?;
+                ctx.end_line(writer)?;
+            }
+            DeprecatedCode::UnitMillimeters => {
+                write!(writer, "G71*")?;
+                ctx.end_line(writer)?;
+            }
+            DeprecatedCode::ImagePolarity(ref polarity) => {
+                write!(writer, "%IP")?;
+                polarity.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*%")?;
+                ctx.end_extended_code(writer)?;
+            }
+            DeprecatedCode::MirrorImage { a, b } => {
+                write!(writer, "%MIA{}B{}*%", a as u8, b as u8)?;
+                ctx.end_extended_code(writer)?;
+            }
+            DeprecatedCode::Offset { a, b } => {
+                write!(writer, "%OFA")?;
+                a.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "B")?;
+                b.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*%")?;
+                ctx.end_extended_code(writer)?;
+            }
+            DeprecatedCode::ScaleFactor { a, b } => {
+                write!(writer, "%SFA")?;
+                a.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "B")?;
+                b.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*%")?;
+                ctx.end_extended_code(writer)?;
+            }
+            DeprecatedCode::CoordinatesOnly(ref coords) => {
+                coords.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*")?;
+                ctx.end_line(writer)?;
+            }
+            DeprecatedCode::CoordinatesWithOffset(ref coords, ref offset) => {
+                coords.serialize_partial_with_context(writer, ctx)?;
+                offset.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "*")?;
+                ctx.end_line(writer)?;
+            }
+        };
+        Ok(())
+    }
+}
+
+// ImagePolarity
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum ImagePolarity {
+    Positive,
+    Negative,
+}
+
+impl<W: Write> PartialGerberCode<W> for ImagePolarity {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            ImagePolarity::Positive => write!(writer, "POS")?,
+            ImagePolarity::Negative => write!(writer, "NEG")?,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::CoordinateFormat;
+    use crate::decimal::to_decimal;
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_deprecated_serialize() {
+        let cf = CoordinateFormat::new(2, 4);
+        let codes = vec![
+            DeprecatedCode::SelectAperture(10),
+            DeprecatedCode::UnitInches,
+            DeprecatedCode::UnitMillimeters,
+            DeprecatedCode::ImagePolarity(ImagePolarity::Negative),
+            DeprecatedCode::MirrorImage { a: true, b: false },
+            DeprecatedCode::Offset {
+                a: to_decimal(1.5),
+                b: to_decimal(0.0),
+            },
+            DeprecatedCode::ScaleFactor {
+                a: to_decimal(1.0),
+                b: to_decimal(2.0),
+            },
+            DeprecatedCode::CoordinatesOnly(Coordinates::at_x(1, cf)),
+            DeprecatedCode::CoordinatesWithOffset(
+                Coordinates::at_y(1, cf),
+                CoordinateOffset::new(0, 1, cf),
+            ),
+        ];
+        assert_eq!(
+            codes.to_code_string().unwrap(),
+            "G54D10*\nG70*\nG71*\n%IPNEG*%\n%MIA1B0*%\n%OFA1.5B0*%\n%SFA1B2*%\nX10000*\nY10000I0J10000*\n"
+        );
+    }
+}
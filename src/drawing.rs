@@ -0,0 +1,162 @@
+//! Helpers to draw polylines and filled polygons from a list of points.
+//!
+//! The points are given in the unit of the file and converted with the
+//! provided coordinate format. The generated operations assume that linear
+//! interpolation mode (G01) is active, and leave it active.
+
+use crate::coordinates::{CoordinateFormat, CoordinateOffset, Coordinates};
+use crate::errors::{GerberError, GerberResult};
+use crate::function_codes::{DCode, GCode, InterpolationMode, Operation, QuadrantMode, RegionMode};
+use crate::types::Command;
+
+/// Stroke a polyline with the current aperture.
+///
+/// This generates a move (D02) to the first point and an interpolation (D01)
+/// to each of the following points.
+pub fn polyline(points: &[(f64, f64)], format: CoordinateFormat) -> GerberResult<Vec<Command>> {
+    if points.len() < 2 {
+        return Err(GerberError::MissingDataError(
+            "A polyline needs at least two points".into(),
+        ));
+    }
+    let mut commands = Vec::with_capacity(points.len());
+    trace(points, format, &mut commands)?;
+    Ok(commands)
+}
+
+/// Stroke the outline of a closed polygon with the current aperture.
+///
+/// The outline is closed automatically if the last point differs from the
+/// first one.
+pub fn polygon(points: &[(f64, f64)], format: CoordinateFormat) -> GerberResult<Vec<Command>> {
+    check_polygon(points)?;
+    let mut commands = Vec::with_capacity(points.len() + 1);
+    trace(&closed(points), format, &mut commands)?;
+    Ok(commands)
+}
+
+/// Fill a polygon using a region statement (G36/G37).
+///
+/// The contour is closed automatically if the last point differs from the
+/// first one.
+pub fn region(points: &[(f64, f64)], format: CoordinateFormat) -> GerberResult<Vec<Command>> {
+    check_polygon(points)?;
+    let mut commands = Vec::with_capacity(points.len() + 3);
+    commands.push(GCode::RegionMode(RegionMode::Begin).into());
+    trace(&closed(points), format, &mut commands)?;
+    commands.push(GCode::RegionMode(RegionMode::End).into());
+    Ok(commands)
+}
+
+/// Stroke a circular arc from the current point `start` to `end` with the
+/// current aperture.
+///
+/// The center is chosen from the radius: a positive radius draws the
+/// shorter arc, a negative radius the longer one. The arc is drawn in the
+/// given circular interpolation mode, which is switched back to linear
+/// afterwards. Multi quadrant mode (G75) must be active.
+pub fn arc(
+    start: (f64, f64),
+    end: (f64, f64),
+    radius: f64,
+    direction: InterpolationMode,
+    format: CoordinateFormat,
+) -> GerberResult<Vec<Command>> {
+    let start = Coordinates::from_f64_rounded(start.0, start.1, format)?;
+    let end = Coordinates::from_f64_rounded(end.0, end.1, format)?;
+    let offset =
+        CoordinateOffset::from_arc_radius(&start, &end, radius, direction, QuadrantMode::Multi)?;
+    Ok(vec![
+        GCode::InterpolationMode(direction).into(),
+        DCode::Operation(Operation::Interpolate(end, Some(offset))).into(),
+        GCode::InterpolationMode(InterpolationMode::Linear).into(),
+    ])
+}
+
+fn check_polygon(points: &[(f64, f64)]) -> GerberResult<()> {
+    let distinct = if points.first() == points.last() {
+        points.len().saturating_sub(1)
+    } else {
+        points.len()
+    };
+    if distinct < 3 {
+        return Err(GerberError::MissingDataError(
+            "A polygon needs at least three points".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn closed(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut points = points.to_vec();
+    if points.first() != points.last() {
+        points.push(points[0]);
+    }
+    points
+}
+
+fn trace(
+    points: &[(f64, f64)],
+    format: CoordinateFormat,
+    commands: &mut Vec<Command>,
+) -> GerberResult<()> {
+    for (i, &(x, y)) in points.iter().enumerate() {
+        let coords = Coordinates::from_f64_rounded(x, y, format)?;
+        let operation = if i == 0 {
+            Operation::Move(coords)
+        } else {
+            Operation::Interpolate(coords, None)
+        };
+        commands.push(DCode::Operation(operation).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::BufWriter;
+
+    use crate::traits::GerberCode;
+
+    #[test]
+    fn test_polyline() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands = polyline(&[(0.0, 0.0), (1.5, 0.0), (1.5, 2.25)], cf).unwrap();
+        assert_code!(commands, "X0Y0D02*\nX15000Y0D01*\nX15000Y22500D01*\n");
+        assert!(polyline(&[(0.0, 0.0)], cf).is_err());
+    }
+
+    #[test]
+    fn test_polygon() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands = polygon(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], cf).unwrap();
+        assert_code!(commands, "X0Y0D02*\nX10000Y0D01*\nX0Y10000D01*\nX0Y0D01*\n");
+        assert!(polygon(&[(0.0, 0.0), (1.0, 0.0), (0.0, 0.0)], cf).is_err());
+    }
+
+    #[test]
+    fn test_arc() {
+        let cf = CoordinateFormat::new(2, 4);
+        let ccw = InterpolationMode::CounterclockwiseCircular;
+        let commands = arc((1.0, 0.0), (0.0, 1.0), 1.0, ccw, cf).unwrap();
+        assert_code!(commands, "G03*\nX0Y10000I-10000J0D01*\nG01*\n");
+        let cw = InterpolationMode::ClockwiseCircular;
+        let commands = arc((1.0, 0.0), (0.0, 1.0), -1.0, cw, cf).unwrap();
+        assert_code!(commands, "G02*\nX0Y10000I-10000J0D01*\nG01*\n");
+        assert!(arc((1.0, 0.0), (-1.0, 0.0), 0.5, ccw, cf).is_err());
+        assert!(arc((1.0, 0.0), (0.0, 1.0), 1.0, InterpolationMode::Linear, cf).is_err());
+    }
+
+    #[test]
+    fn test_region() {
+        let cf = CoordinateFormat::new(2, 4);
+        let points = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 0.0)];
+        let commands = region(&points, cf).unwrap();
+        assert_code!(
+            commands,
+            "G36*\nX0Y0D02*\nX10000Y0D01*\nX10000Y10000D01*\nX0Y0D01*\nG37*\n"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! A declarative macro to write Gerber code with little boilerplate.
+
+/// Build a `Vec<Command>` from terse statements.
+///
+/// The macro takes the coordinate format followed by a block of statements,
+/// each terminated by a semicolon, and evaluates to a
+/// `GerberResult<Vec<Command>>`. Coordinates are given as `f64` values and
+/// converted with the coordinate format.
+///
+/// Supported statements:
+///
+/// - `comment "text";`
+/// - `format;` (FS with the given coordinate format)
+/// - `unit mm;` / `unit inch;`
+/// - `aperture 10, circle 0.5;` / `aperture 11, rect 1.0, 2.0;` /
+///   `aperture 12, obround 1.0, 2.0;`
+/// - `select 10;`
+/// - `dark;` / `clear;`
+/// - `linear;` / `cw;` / `ccw;` / `single_quadrant;` / `multi_quadrant;`
+/// - `move 1.0, 2.0;` / `draw 1.0, 2.0;` / `flash 1.0, 2.0;`
+/// - `arc 1.0, 2.0, 0.5, 0.0;` (end point and center offset)
+/// - `region { ... }`
+/// - `command <expr>;` (any value that converts into a `Command`)
+/// - `eof;`
+///
+/// Example:
+///
+/// ```
+/// use gerber_types::{gerber, CoordinateFormat};
+///
+/// let cf = CoordinateFormat::new(2, 4);
+/// let commands = gerber!(cf => {
+///     comment "A line";
+///     aperture 10, circle 0.1;
+///     select 10;
+///     linear;
+///     move 0.0, 0.0;
+///     draw 1.0, 0.0;
+///     eof;
+/// })
+/// .unwrap();
+/// assert_eq!(commands.len(), 7);
+/// ```
+#[macro_export]
+macro_rules! gerber {
+    ($cf:expr => { $($body:tt)* }) => {{
+        #[allow(clippy::redundant_closure_call, clippy::vec_init_then_push)]
+        let result = (|| -> $crate::GerberResult<::std::vec::Vec<$crate::Command>> {
+            let cf: $crate::CoordinateFormat = $cf;
+            let mut commands: ::std::vec::Vec<$crate::Command> = ::std::vec::Vec::new();
+            $crate::gerber!(@stmts commands, cf; $($body)*);
+            Ok(commands)
+        })();
+        result
+    }};
+
+    (@stmts $c:ident, $cf:ident; ) => {};
+    (@stmts $c:ident, $cf:ident; comment $text:expr; $($rest:tt)*) => {
+        $c.push($crate::GCode::Comment(::std::string::String::from($text).into()).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; format; $($rest:tt)*) => {
+        $c.push($crate::ExtendedCode::CoordinateFormat($cf).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; unit mm; $($rest:tt)*) => {
+        $c.push($crate::ExtendedCode::Unit($crate::Unit::Millimeters).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; unit inch; $($rest:tt)*) => {
+        $c.push($crate::ExtendedCode::Unit($crate::Unit::Inches).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; aperture $code:expr, circle $d:expr; $($rest:tt)*) => {
+        $c.push($crate::ExtendedCode::ApertureDefinition($crate::ApertureDefinition::new(
+            $code,
+            $crate::Aperture::Circle($crate::Circle::new($d)),
+        )).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; aperture $code:expr, rect $x:expr, $y:expr; $($rest:tt)*) => {
+        $c.push($crate::ExtendedCode::ApertureDefinition($crate::ApertureDefinition::new(
+            $code,
+            $crate::Aperture::Rectangle($crate::Rectangular::new($x, $y)),
+        )).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; aperture $code:expr, obround $x:expr, $y:expr; $($rest:tt)*) => {
+        $c.push($crate::ExtendedCode::ApertureDefinition($crate::ApertureDefinition::new(
+            $code,
+            $crate::Aperture::Obround($crate::Obround::new($x, $y)),
+        )).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; select $code:expr; $($rest:tt)*) => {
+        $c.push($crate::DCode::SelectAperture($code).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; dark; $($rest:tt)*) => {
+        $c.push($crate::ExtendedCode::LoadPolarity($crate::Polarity::Dark).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; clear; $($rest:tt)*) => {
+        $c.push($crate::ExtendedCode::LoadPolarity($crate::Polarity::Clear).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; linear; $($rest:tt)*) => {
+        $c.push($crate::GCode::InterpolationMode($crate::InterpolationMode::Linear).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; cw; $($rest:tt)*) => {
+        $c.push(
+            $crate::GCode::InterpolationMode($crate::InterpolationMode::ClockwiseCircular).into(),
+        );
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; ccw; $($rest:tt)*) => {
+        $c.push(
+            $crate::GCode::InterpolationMode(
+                $crate::InterpolationMode::CounterclockwiseCircular,
+            )
+            .into(),
+        );
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; single_quadrant; $($rest:tt)*) => {
+        $c.push($crate::GCode::QuadrantMode($crate::QuadrantMode::Single).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; multi_quadrant; $($rest:tt)*) => {
+        $c.push($crate::GCode::QuadrantMode($crate::QuadrantMode::Multi).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; move $x:expr, $y:expr; $($rest:tt)*) => {
+        $c.push($crate::DCode::Operation($crate::Operation::move_xy($x, $y, $cf)?).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; draw $x:expr, $y:expr; $($rest:tt)*) => {
+        $c.push(
+            $crate::DCode::Operation($crate::Operation::interpolate_xy($x, $y, $cf)?).into(),
+        );
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; arc $x:expr, $y:expr, $i:expr, $j:expr; $($rest:tt)*) => {
+        $c.push(
+            $crate::DCode::Operation($crate::Operation::interpolate_arc_xy($x, $y, $i, $j, $cf)?)
+                .into(),
+        );
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; flash $x:expr, $y:expr; $($rest:tt)*) => {
+        $c.push($crate::DCode::Operation($crate::Operation::flash_xy($x, $y, $cf)?).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; region { $($inner:tt)* } $($rest:tt)*) => {
+        $c.push($crate::GCode::RegionMode($crate::RegionMode::Begin).into());
+        $crate::gerber!(@stmts $c, $cf; $($inner)*);
+        $c.push($crate::GCode::RegionMode($crate::RegionMode::End).into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; command $command:expr; $($rest:tt)*) => {
+        $c.push($command.into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+    (@stmts $c:ident, $cf:ident; eof; $($rest:tt)*) => {
+        $c.push($crate::MCode::EndOfFile.into());
+        $crate::gerber!(@stmts $c, $cf; $($rest)*);
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::BufWriter;
+
+    use crate::coordinates::CoordinateFormat;
+    use crate::extended_codes::StepAndRepeat;
+    use crate::traits::GerberCode;
+
+    #[test]
+    fn test_gerber_macro() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands = gerber!(cf => {
+            comment "Test";
+            format;
+            unit mm;
+            aperture 10, circle 0.5;
+            aperture 11, rect 1.0, 2.0;
+            select 10;
+            linear;
+            move 0.0, 0.0;
+            draw 1.5, 0.0;
+            ccw;
+            arc 2.0, 0.5, 0.0, 0.5;
+            select 11;
+            flash 1.0, 1.0;
+            clear;
+            command StepAndRepeat::Close;
+            eof;
+        })
+        .unwrap();
+        assert_code!(
+            commands,
+            "G04 Test*\n\
+             %FSLAX24Y24*%\n\
+             %MOMM*%\n\
+             %ADD10C,0.5*%\n\
+             %ADD11R,1X2*%\n\
+             D10*\n\
+             G01*\n\
+             X0Y0D02*\n\
+             X15000Y0D01*\n\
+             G03*\n\
+             X20000Y5000I0J5000D01*\n\
+             D11*\n\
+             X10000Y10000D03*\n\
+             %LPC*%\n\
+             %SR*%\n\
+             M02*\n"
+        );
+    }
+
+    #[test]
+    fn test_gerber_macro_region() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands = gerber!(cf => {
+            region {
+                move 0.0, 0.0;
+                draw 1.0, 0.0;
+                draw 0.0, 1.0;
+                draw 0.0, 0.0;
+            }
+            eof;
+        })
+        .unwrap();
+        assert_code!(
+            commands,
+            "G36*\nX0Y0D02*\nX10000Y0D01*\nX0Y10000D01*\nX0Y0D01*\nG37*\nM02*\n"
+        );
+    }
+
+    #[test]
+    fn test_gerber_macro_error() {
+        let cf = CoordinateFormat::new(2, 4);
+        assert!(gerber!(cf => { move f64::NAN, 0.0; }).is_err());
+    }
+}
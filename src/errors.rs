@@ -5,6 +5,7 @@ use std::io::Error as IoError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum GerberError {
     #[error("Conversion between two types failed: {0}")]
     ConversionError(String),
@@ -18,12 +19,28 @@ pub enum GerberError {
     #[error("Required data is missing: {0}")]
     MissingDataError(String),
 
+    #[error("Invalid name: {0:?}")]
+    NameError(String),
+
+    #[error("Unsupported: {0}")]
+    UnsupportedError(String),
+
+    #[error("Command out of order: {0}")]
+    OrderError(String),
+
     #[error("I/O error during code generation")]
     IoError(#[from] IoError),
 }
 
 pub type GerberResult<T> = Result<T, GerberError>;
 
+/// The error returned by transformations that do not support deprecated codes.
+pub(crate) fn deprecated_error() -> GerberError {
+    GerberError::UnsupportedError(
+        "Deprecated codes are not supported, use upgrade_deprecated first".into(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
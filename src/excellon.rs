@@ -0,0 +1,161 @@
+//! Types and code generation for Excellon (NC drill) files, requires the
+//! `excellon` feature.
+//!
+//! Like the Gerber types, these are low level and stateless. Coordinates are
+//! written with the zero suppression of the serialization context, which
+//! must match the zeros declared in the header.
+
+use std::io::Write;
+
+use crate::context::SerializationContext;
+use crate::coordinates::{Coordinates, ZeroSuppression};
+use crate::decimal::write_decimal;
+use crate::errors::GerberResult;
+use crate::extended_codes::Unit;
+use crate::traits::{GerberCode, PartialGerberCode};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
+pub enum ExcellonCommand {
+    /// Start of the header (M48)
+    HeaderStart,
+    /// The unit and the zeros of the coordinates, e.g. `METRIC,TZ`
+    Units {
+        unit: Unit,
+        zero_suppression: ZeroSuppression,
+    },
+    /// A tool with its diameter, e.g. `T01C0.8`
+    ToolDefinition { tool: u32, diameter: f64 },
+    /// End of the header (%)
+    HeaderEnd,
+    /// Absolute coordinates (G90)
+    Absolute,
+    /// Select a tool, e.g. `T01`
+    SelectTool(u32),
+    /// Drill mode (G05)
+    DrillMode,
+    /// Drill a hole at the coordinates
+    Drill(Coordinates),
+    /// Rout mode, moving to the coordinates (G00)
+    RoutMove(Coordinates),
+    /// Lower the tool (M15)
+    Plunge,
+    /// Rout linearly to the coordinates (G01)
+    RoutLinear(Coordinates),
+    /// Retract the tool (M16)
+    Retract,
+    /// A comment, e.g. `;text`
+    Comment(String),
+    /// End of the program (M30)
+    EndOfProgram,
+}
+
+impl<W: Write> GerberCode<W> for ExcellonCommand {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            ExcellonCommand::HeaderStart => writer.write_all(b"M48")?,
+            ExcellonCommand::Units {
+                unit,
+                zero_suppression,
+            } => {
+                let unit = match unit {
+                    Unit::Millimeters => "METRIC",
+                    Unit::Inches => "INCH",
+                };
+                // Excellon names the zeros that are kept
+                let zeros = match zero_suppression {
+                    ZeroSuppression::Leading => "TZ",
+                    ZeroSuppression::Trailing | ZeroSuppression::Disabled => "LZ",
+                };
+                write!(writer, "{},{}", unit, zeros)?;
+            }
+            ExcellonCommand::ToolDefinition { tool, diameter } => {
+                write!(writer, "T{:02}C", tool)?;
+                write_decimal(writer, diameter, ctx.decimal_places)?;
+            }
+            ExcellonCommand::HeaderEnd => writer.write_all(b"%")?,
+            ExcellonCommand::Absolute => writer.write_all(b"G90")?,
+            ExcellonCommand::SelectTool(tool) => write!(writer, "T{:02}", tool)?,
+            ExcellonCommand::DrillMode => writer.write_all(b"G05")?,
+            ExcellonCommand::Drill(ref coords) => {
+                coords.serialize_partial_with_context(writer, ctx)?
+            }
+            ExcellonCommand::RoutMove(ref coords) => {
+                writer.write_all(b"G00")?;
+                coords.serialize_partial_with_context(writer, ctx)?;
+            }
+            ExcellonCommand::Plunge => writer.write_all(b"M15")?,
+            ExcellonCommand::RoutLinear(ref coords) => {
+                writer.write_all(b"G01")?;
+                coords.serialize_partial_with_context(writer, ctx)?;
+            }
+            ExcellonCommand::Retract => writer.write_all(b"M16")?,
+            ExcellonCommand::Comment(ref comment) => write!(writer, ";{}", comment)?,
+            ExcellonCommand::EndOfProgram => writer.write_all(b"M30")?,
+        };
+        ctx.end_line(writer)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::BufWriter;
+
+    use crate::coordinates::CoordinateFormat;
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_drill_file() {
+        let cf = CoordinateFormat::new(3, 3);
+        let commands = vec![
+            ExcellonCommand::HeaderStart,
+            ExcellonCommand::Comment("FILE_FORMAT=3:3".into()),
+            ExcellonCommand::Units {
+                unit: Unit::Millimeters,
+                zero_suppression: ZeroSuppression::Leading,
+            },
+            ExcellonCommand::ToolDefinition {
+                tool: 1,
+                diameter: 0.8,
+            },
+            ExcellonCommand::HeaderEnd,
+            ExcellonCommand::Absolute,
+            ExcellonCommand::DrillMode,
+            ExcellonCommand::SelectTool(1),
+            ExcellonCommand::Drill(Coordinates::new(1, 2, cf)),
+            ExcellonCommand::RoutMove(Coordinates::new(0, 0, cf)),
+            ExcellonCommand::Plunge,
+            ExcellonCommand::RoutLinear(Coordinates::at_x(10, cf)),
+            ExcellonCommand::Retract,
+            ExcellonCommand::EndOfProgram,
+        ];
+        assert_eq!(
+            commands.to_code_string().unwrap(),
+            "M48\n;FILE_FORMAT=3:3\nMETRIC,TZ\nT01C0.8\n%\nG90\nG05\nT01\n\
+             X1000Y2000\nG00X0Y0\nM15\nG01X10000\nM16\nM30\n"
+        );
+    }
+
+    #[test]
+    fn test_units_zeros() {
+        let units = ExcellonCommand::Units {
+            unit: Unit::Inches,
+            zero_suppression: ZeroSuppression::Trailing,
+        };
+        assert_code!(units, "INCH,LZ\n");
+    }
+}
@@ -1,20 +1,35 @@
 //! Extended code types.
 
+use std::borrow::Cow;
 use std::io::Write;
+use std::mem;
 
-use crate::errors::GerberResult;
+use crate::attributes::ApertureAttribute;
+use crate::context::SerializationContext;
+use crate::decimal::{from_decimal, to_decimal, Decimal};
+use crate::errors::{GerberError, GerberResult};
 use crate::traits::PartialGerberCode;
+use crate::types::{Command, ExtendedCode};
+use crate::validate::validated;
 
 // Unit
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Unit {
     Inches,
     Millimeters,
 }
 
 impl<W: Write> PartialGerberCode<W> for Unit {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             Unit::Millimeters => write!(writer, "MM")?,
             Unit::Inches => write!(writer, "IN")?,
@@ -26,6 +41,10 @@ impl<W: Write> PartialGerberCode<W> for Unit {
 // ApertureDefinition
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ApertureDefinition {
     pub code: i32,
     pub aperture: Aperture,
@@ -35,12 +54,70 @@ impl ApertureDefinition {
     pub fn new(code: i32, aperture: Aperture) -> Self {
         ApertureDefinition { code, aperture }
     }
+
+    /// Create an aperture definition, checking that the code is in the valid
+    /// range. Codes 0 to 9 are reserved, the highest code is `i32::MAX`.
+    pub fn try_new(code: i32, aperture: Aperture) -> GerberResult<Self> {
+        if code < 10 {
+            return Err(GerberError::RangeError(format!(
+                "Aperture code must be at least 10, not {}",
+                code
+            )));
+        }
+        Ok(ApertureDefinition::new(code, aperture))
+    }
+
+    /// Start building an aperture definition with attached aperture
+    /// attributes.
+    pub fn builder(code: i32, aperture: Aperture) -> ApertureDefinitionBuilder {
+        ApertureDefinitionBuilder {
+            definition: ApertureDefinition::new(code, aperture),
+            attributes: Vec::new(),
+        }
+    }
+}
+
+/// A builder for an aperture definition together with the aperture
+/// attributes (TA) that apply to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApertureDefinitionBuilder {
+    definition: ApertureDefinition,
+    attributes: Vec<ApertureAttribute>,
+}
+
+impl ApertureDefinitionBuilder {
+    /// Attach an aperture attribute, replacing an earlier one with the same
+    /// name.
+    pub fn with_attribute(mut self, attribute: ApertureAttribute) -> Self {
+        self.attributes
+            .retain(|a| mem::discriminant(a) != mem::discriminant(&attribute));
+        self.attributes.push(attribute);
+        self
+    }
+
+    /// Return the attribute commands followed by the aperture definition.
+    ///
+    /// The attributes stay in the attribute dictionary and also apply to
+    /// subsequent aperture definitions, use
+    /// [`with_aperture_attributes`](fn.with_aperture_attributes.html) to
+    /// delete them again.
+    pub fn build(self) -> Vec<Command> {
+        self.attributes
+            .into_iter()
+            .map(|attribute| ExtendedCode::ApertureAttribute(attribute).into())
+            .chain(std::iter::once(self.definition.into()))
+            .collect()
+    }
 }
 
 impl<W: Write> PartialGerberCode<W> for ApertureDefinition {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         write!(writer, "{}", self.code)?;
-        self.aperture.serialize_partial(writer)?;
+        self.aperture.serialize_partial_with_context(writer, ctx)?;
         Ok(())
     }
 }
@@ -48,32 +125,55 @@ impl<W: Write> PartialGerberCode<W> for ApertureDefinition {
 // Aperture
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum Aperture {
     Circle(Circle),
     Rectangle(Rectangular),
-    Obround(Rectangular),
+    Obround(Obround),
     Polygon(Polygon),
-    Other(String),
+    Other(
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::fuzzing::arbitrary_cow))]
+        Cow<'static, str>,
+    ),
+}
+
+impl Aperture {
+    /// The name of the aperture macro used by a macro aperture, e.g. `DOT`
+    /// for `DOT,1.5`.
+    pub fn macro_name(&self) -> Option<&str> {
+        match *self {
+            Aperture::Other(ref string) => string.split(',').next(),
+            _ => None,
+        }
+    }
 }
 
 impl<W: Write> PartialGerberCode<W> for Aperture {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             Aperture::Circle(ref circle) => {
                 write!(writer, "C,")?;
-                circle.serialize_partial(writer)?;
+                circle.serialize_partial_with_context(writer, ctx)?;
             }
             Aperture::Rectangle(ref rectangular) => {
                 write!(writer, "R,")?;
-                rectangular.serialize_partial(writer)?;
+                rectangular.serialize_partial_with_context(writer, ctx)?;
             }
-            Aperture::Obround(ref rectangular) => {
+            Aperture::Obround(ref obround) => {
                 write!(writer, "O,")?;
-                rectangular.serialize_partial(writer)?;
+                obround.serialize_partial_with_context(writer, ctx)?;
             }
             Aperture::Polygon(ref polygon) => {
                 write!(writer, "P,")?;
-                polygon.serialize_partial(writer)?;
+                polygon.serialize_partial_with_context(writer, ctx)?;
             }
             Aperture::Other(ref string) => write!(writer, "{}", string)?,
         };
@@ -84,35 +184,51 @@ impl<W: Write> PartialGerberCode<W> for Aperture {
 // Circle
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Circle {
-    pub diameter: f64,
-    pub hole_diameter: Option<f64>,
+    pub diameter: Decimal,
+    pub hole_diameter: Option<Decimal>,
 }
 
 impl Circle {
     pub fn new(diameter: f64) -> Self {
         Circle {
-            diameter,
+            diameter: to_decimal(diameter),
             hole_diameter: None,
         }
     }
 
     pub fn with_hole(diameter: f64, hole_diameter: f64) -> Self {
         Circle {
-            diameter,
-            hole_diameter: Some(hole_diameter),
+            diameter: to_decimal(diameter),
+            hole_diameter: Some(to_decimal(hole_diameter)),
         }
     }
+
+    /// Create a circle, returning a `RangeError` if the diameter is negative
+    /// or not finite, or if the hole does not fit.
+    pub fn try_new(diameter: f64, hole_diameter: Option<f64>) -> GerberResult<Self> {
+        validated(Circle {
+            diameter: to_decimal(diameter),
+            hole_diameter: hole_diameter.map(to_decimal),
+        })
+    }
 }
 
 impl<W: Write> PartialGerberCode<W> for Circle {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
-        match self.hole_diameter {
-            Some(hole_diameter) => {
-                write!(writer, "{}X{}", self.diameter, hole_diameter)?;
-            }
-            None => write!(writer, "{}", self.diameter)?,
-        };
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.diameter.serialize_partial_with_context(writer, ctx)?;
+        if let Some(hole_diameter) = self.hole_diameter {
+            write!(writer, "X")?;
+            hole_diameter.serialize_partial_with_context(writer, ctx)?;
+        }
         Ok(())
     }
 }
@@ -120,36 +236,139 @@ impl<W: Write> PartialGerberCode<W> for Circle {
 // Rectangular
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Rectangular {
-    pub x: f64,
-    pub y: f64,
-    pub hole_diameter: Option<f64>,
+    pub x: Decimal,
+    pub y: Decimal,
+    pub hole_diameter: Option<Decimal>,
 }
 
 impl Rectangular {
     pub fn new(x: f64, y: f64) -> Self {
         Rectangular {
-            x,
-            y,
+            x: to_decimal(x),
+            y: to_decimal(y),
             hole_diameter: None,
         }
     }
 
     pub fn with_hole(x: f64, y: f64, hole_diameter: f64) -> Self {
         Rectangular {
-            x,
-            y,
-            hole_diameter: Some(hole_diameter),
+            x: to_decimal(x),
+            y: to_decimal(y),
+            hole_diameter: Some(to_decimal(hole_diameter)),
         }
     }
+
+    /// Create a rectangle, returning a `RangeError` if a side is not positive
+    /// and finite, or if the hole does not fit.
+    pub fn try_new(x: f64, y: f64, hole_diameter: Option<f64>) -> GerberResult<Self> {
+        validated(Rectangular {
+            x: to_decimal(x),
+            y: to_decimal(y),
+            hole_diameter: hole_diameter.map(to_decimal),
+        })
+    }
 }
 
 impl<W: Write> PartialGerberCode<W> for Rectangular {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
-        match self.hole_diameter {
-            Some(hole_diameter) => write!(writer, "{}X{}X{}", self.x, self.y, hole_diameter)?,
-            None => write!(writer, "{}X{}", self.x, self.y)?,
-        };
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.x.serialize_partial_with_context(writer, ctx)?;
+        write!(writer, "X")?;
+        self.y.serialize_partial_with_context(writer, ctx)?;
+        if let Some(hole_diameter) = self.hole_diameter {
+            write!(writer, "X")?;
+            hole_diameter.serialize_partial_with_context(writer, ctx)?;
+        }
+        Ok(())
+    }
+}
+
+// Obround
+
+/// A rectangle with semicircles at its shorter sides, like a slot.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Obround {
+    pub x: Decimal,
+    pub y: Decimal,
+    pub hole_diameter: Option<Decimal>,
+}
+
+impl Obround {
+    pub fn new(x: f64, y: f64) -> Self {
+        Obround {
+            x: to_decimal(x),
+            y: to_decimal(y),
+            hole_diameter: None,
+        }
+    }
+
+    pub fn with_hole(x: f64, y: f64, hole_diameter: f64) -> Self {
+        Obround {
+            x: to_decimal(x),
+            y: to_decimal(y),
+            hole_diameter: Some(to_decimal(hole_diameter)),
+        }
+    }
+
+    /// Create a horizontal obround with the given overall length in X and
+    /// width in Y, returning a `RangeError` if the width exceeds the length.
+    pub fn from_length_and_width(length: f64, width: f64) -> GerberResult<Self> {
+        if width > length {
+            return Err(GerberError::RangeError(format!(
+                "The width {} of an obround must not exceed its length {}",
+                width, length
+            )));
+        }
+        Obround::try_new(length, width, None)
+    }
+
+    /// Create an obround, returning a `RangeError` if a side is not positive
+    /// and finite, or if the hole does not fit.
+    pub fn try_new(x: f64, y: f64, hole_diameter: Option<f64>) -> GerberResult<Self> {
+        validated(Obround {
+            x: to_decimal(x),
+            y: to_decimal(y),
+            hole_diameter: hole_diameter.map(to_decimal),
+        })
+    }
+
+    /// The length of the obround, i.e. its longer side.
+    pub fn length(&self) -> f64 {
+        from_decimal(self.x).max(from_decimal(self.y))
+    }
+
+    /// The width of the obround, i.e. its shorter side and the diameter of
+    /// the rounded ends.
+    pub fn width(&self) -> f64 {
+        from_decimal(self.x).min(from_decimal(self.y))
+    }
+}
+
+impl<W: Write> PartialGerberCode<W> for Obround {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.x.serialize_partial_with_context(writer, ctx)?;
+        write!(writer, "X")?;
+        self.y.serialize_partial_with_context(writer, ctx)?;
+        if let Some(hole_diameter) = self.hole_diameter {
+            write!(writer, "X")?;
+            hole_diameter.serialize_partial_with_context(writer, ctx)?;
+        }
         Ok(())
     }
 }
@@ -157,17 +376,21 @@ impl<W: Write> PartialGerberCode<W> for Rectangular {
 // Polygon
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Polygon {
-    pub diameter: f64,
+    pub diameter: Decimal,
     pub vertices: u8, // 3--12
-    pub rotation: Option<f64>,
-    pub hole_diameter: Option<f64>,
+    pub rotation: Option<Decimal>,
+    pub hole_diameter: Option<Decimal>,
 }
 
 impl Polygon {
     pub fn new(diameter: f64, vertices: u8) -> Self {
         Polygon {
-            diameter,
+            diameter: to_decimal(diameter),
             vertices,
             rotation: None,
             hole_diameter: None,
@@ -175,25 +398,52 @@ impl Polygon {
     }
 
     pub fn with_rotation(mut self, angle: f64) -> Self {
-        self.rotation = Some(angle);
+        self.rotation = Some(to_decimal(angle));
         self
     }
 
     pub fn with_diameter(mut self, diameter: f64) -> Self {
-        self.diameter = diameter;
+        self.diameter = to_decimal(diameter);
         self
     }
+
+    /// Create a polygon, returning a `RangeError` if the diameter is not
+    /// positive and finite, the number of vertices is not 3 to 12, or the
+    /// hole does not fit.
+    pub fn try_new(diameter: f64, vertices: u8, hole_diameter: Option<f64>) -> GerberResult<Self> {
+        validated(Polygon {
+            diameter: to_decimal(diameter),
+            vertices,
+            rotation: None,
+            hole_diameter: hole_diameter.map(to_decimal),
+        })
+    }
 }
 
 impl<W: Write> PartialGerberCode<W> for Polygon {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.diameter.serialize_partial_with_context(writer, ctx)?;
+        write!(writer, "X{}", self.vertices)?;
         match (self.rotation, self.hole_diameter) {
             (Some(rot), Some(hd)) => {
-                write!(writer, "{}X{}X{}X{}", self.diameter, self.vertices, rot, hd)?
+                write!(writer, "X")?;
+                rot.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "X")?;
+                hd.serialize_partial_with_context(writer, ctx)?;
+            }
+            (Some(rot), None) => {
+                write!(writer, "X")?;
+                rot.serialize_partial_with_context(writer, ctx)?;
+            }
+            (None, Some(hd)) => {
+                write!(writer, "X0X")?;
+                hd.serialize_partial_with_context(writer, ctx)?;
             }
-            (Some(rot), None) => write!(writer, "{}X{}X{}", self.diameter, self.vertices, rot)?,
-            (None, Some(hd)) => write!(writer, "{}X{}X0X{}", self.diameter, self.vertices, hd)?,
-            (None, None) => write!(writer, "{}X{}", self.diameter, self.vertices)?,
+            (None, None) => {}
         };
         Ok(())
     }
@@ -201,14 +451,24 @@ impl<W: Write> PartialGerberCode<W> for Polygon {
 
 // Polarity
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Polarity {
     Clear,
+    /// The initial polarity of a Gerber file.
+    #[default]
     Dark,
 }
 
 impl<W: Write> PartialGerberCode<W> for Polarity {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             Polarity::Clear => write!(writer, "C")?,
             Polarity::Dark => write!(writer, "D")?,
@@ -220,25 +480,38 @@ impl<W: Write> PartialGerberCode<W> for Polarity {
 // StepAndRepeat
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum StepAndRepeat {
     Open {
         repeat_x: u32,
         repeat_y: u32,
-        distance_x: f64,
-        distance_y: f64,
+        distance_x: Decimal,
+        distance_y: Decimal,
     },
     Close,
 }
 
 impl<W: Write> PartialGerberCode<W> for StepAndRepeat {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             StepAndRepeat::Open {
                 repeat_x: rx,
                 repeat_y: ry,
                 distance_x: dx,
                 distance_y: dy,
-            } => write!(writer, "X{}Y{}I{}J{}", rx, ry, dx, dy)?,
+            } => {
+                write!(writer, "X{}Y{}I", rx, ry)?;
+                dx.serialize_partial_with_context(writer, ctx)?;
+                write!(writer, "J")?;
+                dy.serialize_partial_with_context(writer, ctx)?;
+            }
             StepAndRepeat::Close => {}
         };
         Ok(())
@@ -259,12 +532,80 @@ mod test {
         assert_eq!(ad1, ad2);
     }
 
+    #[test]
+    fn test_aperture_definition_try_new() {
+        let aperture = Aperture::Circle(Circle::new(3.0));
+        let ad = ApertureDefinition::try_new(10, aperture.clone()).unwrap();
+        assert_eq!(ad, ApertureDefinition::new(10, aperture.clone()));
+        assert!(ApertureDefinition::try_new(i32::MAX, aperture.clone()).is_ok());
+        assert!(ApertureDefinition::try_new(9, aperture.clone()).is_err());
+        assert!(ApertureDefinition::try_new(-1, aperture).is_err());
+    }
+
+    #[test]
+    fn test_aperture_definition_builder() {
+        use crate::attributes::ApertureFunction;
+        use crate::traits::GerberCodeExt;
+
+        let commands = ApertureDefinition::builder(10, Aperture::Circle(Circle::new(0.5)))
+            .with_attribute(ApertureAttribute::ApertureFunction(
+                ApertureFunction::ComponentPad { press_fit: None },
+            ))
+            .with_attribute(ApertureAttribute::ApertureFunction(
+                ApertureFunction::ViaPad,
+            ))
+            .build();
+        assert_eq!(
+            commands.to_code_string().unwrap(),
+            "%TA.AperFunction,ViaPad*%\n%ADD10C,0.5*%\n"
+        );
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert_eq!(Circle::try_new(0.0, None).unwrap(), Circle::new(0.0));
+        assert_eq!(
+            Circle::try_new(1.0, Some(0.5)).unwrap(),
+            Circle::with_hole(1.0, 0.5)
+        );
+        assert!(Circle::try_new(-1.0, None).is_err());
+        assert!(Circle::try_new(f64::NAN, None).is_err());
+        assert!(Circle::try_new(1.0, Some(1.0)).is_err());
+
+        assert!(Rectangular::try_new(2.0, 1.0, Some(0.5)).is_ok());
+        assert!(Rectangular::try_new(0.0, 1.0, None).is_err());
+        assert!(Rectangular::try_new(2.0, 1.0, Some(1.5)).is_err());
+
+        assert_eq!(
+            Polygon::try_new(1.0, 6, None).unwrap(),
+            Polygon::new(1.0, 6)
+        );
+        assert!(Polygon::try_new(1.0, 2, None).is_err());
+        assert!(Polygon::try_new(1.0, 13, None).is_err());
+        assert!(matches!(
+            Polygon::try_new(1.0, 6, Some(2.0)),
+            Err(GerberError::RangeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_obround() {
+        let obround = Obround::from_length_and_width(2.0, 1.0).unwrap();
+        assert_eq!(obround, Obround::new(2.0, 1.0));
+        assert_eq!(obround.length(), 2.0);
+        assert_eq!(Obround::new(1.0, 3.0).width(), 1.0);
+        assert!(Obround::from_length_and_width(1.0, 2.0).is_err());
+        assert!(Obround::from_length_and_width(2.0, 0.0).is_err());
+        assert!(Obround::try_new(2.0, 1.0, Some(0.8)).is_ok());
+        assert!(Obround::try_new(2.0, 1.0, Some(1.0)).is_err());
+    }
+
     #[test]
     fn test_rectangular_new() {
         let r1 = Rectangular::new(2.0, 3.0);
         let r2 = Rectangular {
-            x: 2.0,
-            y: 3.0,
+            x: to_decimal(2.0),
+            y: to_decimal(3.0),
             hole_diameter: None,
         };
         assert_eq!(r1, r2);
@@ -274,9 +615,9 @@ mod test {
     fn test_rectangular_with_hole() {
         let r1 = Rectangular::with_hole(3.0, 2.0, 1.0);
         let r2 = Rectangular {
-            x: 3.0,
-            y: 2.0,
-            hole_diameter: Some(1.0),
+            x: to_decimal(3.0),
+            y: to_decimal(2.0),
+            hole_diameter: Some(to_decimal(1.0)),
         };
         assert_eq!(r1, r2);
     }
@@ -285,7 +626,7 @@ mod test {
     fn test_circle_new() {
         let c1 = Circle::new(3.0);
         let c2 = Circle {
-            diameter: 3.0,
+            diameter: to_decimal(3.0),
             hole_diameter: None,
         };
         assert_eq!(c1, c2);
@@ -295,8 +636,8 @@ mod test {
     fn test_circle_with_hole() {
         let c1 = Circle::with_hole(3.0, 1.0);
         let c2 = Circle {
-            diameter: 3.0,
-            hole_diameter: Some(1.0),
+            diameter: to_decimal(3.0),
+            hole_diameter: Some(to_decimal(1.0)),
         };
         assert_eq!(c1, c2);
     }
@@ -305,9 +646,9 @@ mod test {
     fn test_polygon_new() {
         let p1 = Polygon::new(3.0, 4).with_rotation(45.0);
         let p2 = Polygon {
-            diameter: 3.0,
+            diameter: to_decimal(3.0),
             vertices: 4,
-            rotation: Some(45.0),
+            rotation: Some(to_decimal(45.0)),
             hole_diameter: None,
         };
         assert_eq!(p1, p2);
@@ -0,0 +1,140 @@
+//! Helpers to write Gerber files.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::errors::{GerberError, GerberResult};
+use crate::function_codes::MCode;
+use crate::traits::GerberCode;
+use crate::types::{Command, FunctionCode};
+
+/// Options for [`write_gerber_file_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Write into a temporary file next to the target and rename it when
+    /// done, so that readers never see a partially written file.
+    pub atomic: bool,
+    /// Append an `M02` command if the commands do not end with one.
+    pub append_end_of_file: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriteOptions {
+    pub fn new() -> Self {
+        WriteOptions {
+            atomic: false,
+            append_end_of_file: false,
+        }
+    }
+
+    pub fn with_atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    pub fn with_append_end_of_file(mut self, append: bool) -> Self {
+        self.append_end_of_file = append;
+        self
+    }
+}
+
+/// Write the commands into a file, replacing it if it already exists.
+pub fn write_gerber_file<P: AsRef<Path>>(path: P, commands: &[Command]) -> GerberResult<()> {
+    write_gerber_file_with_options(path, commands, &WriteOptions::new())
+}
+
+/// Write the commands into a file with the given options.
+pub fn write_gerber_file_with_options<P: AsRef<Path>>(
+    path: P,
+    commands: &[Command],
+    options: &WriteOptions,
+) -> GerberResult<()> {
+    let path = path.as_ref();
+    if !options.atomic {
+        return write_to(path, commands, options);
+    }
+    let tmp_path = temporary_path(path)?;
+    let result = write_to(&tmp_path, commands, options)
+        .and_then(|_| fs::rename(&tmp_path, path).map_err(GerberError::from));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn write_to(path: &Path, commands: &[Command], options: &WriteOptions) -> GerberResult<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for command in commands {
+        command.serialize(&mut writer)?;
+    }
+    let ends_with_eof = matches!(
+        commands.last(),
+        Some(Command::FunctionCode(FunctionCode::MCode(MCode::EndOfFile)))
+    );
+    if options.append_end_of_file && !ends_with_eof {
+        MCode::EndOfFile.serialize(&mut writer)?;
+    }
+    writer.flush()?;
+    writer
+        .into_inner()
+        .map_err(|e| GerberError::from(e.into_error()))?
+        .sync_all()?;
+    Ok(())
+}
+
+fn temporary_path(path: &Path) -> GerberResult<PathBuf> {
+    let file_name = path.file_name().ok_or_else(|| {
+        GerberError::MissingDataError(format!("Path {} has no file name", path.display()))
+    })?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".tmp");
+    Ok(path.with_file_name(tmp_name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::function_codes::GCode;
+
+    fn test_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gerber-types-{}-{}", std::process::id(), name))
+    }
+
+    fn commands() -> Vec<Command> {
+        vec![GCode::Comment("test".into()).into()]
+    }
+
+    #[test]
+    fn test_write_gerber_file() {
+        let path = test_path("plain.gbr");
+        write_gerber_file(&path, &commands()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "G04 test*\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_gerber_file_atomic_with_eof() {
+        let path = test_path("atomic.gbr");
+        let options = WriteOptions::new()
+            .with_atomic(true)
+            .with_append_end_of_file(true);
+        write_gerber_file_with_options(&path, &commands(), &options).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "G04 test*\nM02*\n");
+        assert!(!temporary_path(&path).unwrap().exists());
+
+        // An existing M02 is not duplicated
+        let mut with_eof = commands();
+        with_eof.push(MCode::EndOfFile.into());
+        write_gerber_file_with_options(&path, &with_eof, &options).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "G04 test*\nM02*\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,14 +1,20 @@
 //! Function code types.
 
+use std::borrow::Cow;
 use std::io::Write;
 
-use crate::coordinates::{CoordinateOffset, Coordinates};
-use crate::errors::GerberResult;
+use crate::context::{SerializationContext, SpecTarget};
+use crate::coordinates::{CoordinateFormat, CoordinateNumber, CoordinateOffset, Coordinates};
+use crate::errors::{GerberError, GerberResult};
 use crate::traits::{GerberCode, PartialGerberCode};
 
 // DCode
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum DCode {
     Operation(Operation),
     SelectAperture(i32),
@@ -16,9 +22,22 @@ pub enum DCode {
 
 impl<W: Write> GerberCode<W> for DCode {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
-            DCode::Operation(ref operation) => operation.serialize(writer)?,
-            DCode::SelectAperture(code) => writeln!(writer, "D{}*", code)?,
+            DCode::Operation(ref operation) => operation.serialize_with_context(writer, ctx)?,
+            DCode::SelectAperture(code) => {
+                writer.write_all(b"D")?;
+                writer.write_all(itoa::Buffer::new().format(code).as_bytes())?;
+                writer.write_all(b"*")?;
+                ctx.end_line(writer)?;
+            }
         };
         Ok(())
     }
@@ -27,43 +46,142 @@ impl<W: Write> GerberCode<W> for DCode {
 // GCode
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum GCode {
     InterpolationMode(InterpolationMode),
-    RegionMode(bool),
+    RegionMode(RegionMode),
     QuadrantMode(QuadrantMode),
-    Comment(String),
+    Comment(
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::fuzzing::arbitrary_cow))]
+        Cow<'static, str>,
+    ),
 }
 
 impl<W: Write> GerberCode<W> for GCode {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
-            GCode::InterpolationMode(ref mode) => mode.serialize(writer)?,
-            GCode::RegionMode(enabled) => {
-                if enabled {
-                    writeln!(writer, "G36*")?;
-                } else {
-                    writeln!(writer, "G37*")?;
-                }
-            }
-            GCode::QuadrantMode(ref mode) => mode.serialize(writer)?,
-            GCode::Comment(ref comment) => writeln!(writer, "G04 {}*", comment)?,
+            GCode::InterpolationMode(ref mode) => mode.serialize_with_context(writer, ctx)?,
+            GCode::RegionMode(ref mode) => mode.serialize_with_context(writer, ctx)?,
+            GCode::QuadrantMode(ref mode) => mode.serialize_with_context(writer, ctx)?,
+            GCode::Comment(ref comment) => write_comment(writer, comment, ctx)?,
         };
         Ok(())
     }
 }
 
+/// Write a comment (G04).
+///
+/// Each line of a multi-line comment is written as a separate comment. If a
+/// maximum line length is set, longer lines are split as well, preferably at
+/// a space.
+pub(crate) fn write_comment<W: Write>(
+    writer: &mut W,
+    comment: &str,
+    ctx: &SerializationContext,
+) -> GerberResult<()> {
+    let space = if ctx.codegen.comment_space { " " } else { "" };
+    // The text must fit between `G04 ` and `*`
+    let width = match ctx.codegen.max_line_length {
+        Some(max) => match max.checked_sub(4 + space.len()) {
+            Some(width) if width > 0 => Some(width),
+            _ => {
+                return Err(GerberError::RangeError(format!(
+                    "A maximum line length of {} is too short for comments",
+                    max
+                )))
+            }
+        },
+        None => None,
+    };
+    for line in comment.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        for chunk in split_comment_line(line, width.unwrap_or(usize::MAX)) {
+            write!(writer, "G04{}{}*", space, chunk)?;
+            ctx.end_line(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Split a line of a comment into chunks of at most `width` bytes.
+fn split_comment_line(line: &str, width: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = line;
+    while rest.len() > width {
+        let mut end = width;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            // A single character wider than the limit
+            end = rest.chars().next().map_or(0, char::len_utf8);
+        }
+        let (chunk, next) = if rest[end..].starts_with(' ') {
+            (&rest[..end], &rest[end + 1..])
+        } else {
+            match rest[..end].rfind(' ') {
+                Some(pos) if pos > 0 => (&rest[..pos], &rest[pos + 1..]),
+                _ => rest.split_at(end),
+            }
+        };
+        chunks.push(chunk);
+        rest = next;
+    }
+    chunks.push(rest);
+    chunks
+}
+
 // MCode
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum MCode {
     EndOfFile,
+    /// M00, deprecated, equivalent to M02. Only written for the X1 target,
+    /// serializing it for any other target fails with an `UnsupportedError`.
+    ProgramStop,
+    /// M01, deprecated, without effect. Only written for the X1 target,
+    /// serializing it for any other target fails with an `UnsupportedError`.
+    OptionalStop,
 }
 
 impl<W: Write> GerberCode<W> for MCode {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
-            MCode::EndOfFile => writeln!(writer, "M02*")?,
+            MCode::EndOfFile => write!(writer, "M02*")?,
+            MCode::ProgramStop | MCode::OptionalStop if ctx.spec_target != SpecTarget::X1 => {
+                return Err(GerberError::UnsupportedError(
+                    "M00 and M01 are deprecated and only written for the X1 target".into(),
+                ))
+            }
+            MCode::ProgramStop => write!(writer, "M00*")?,
+            MCode::OptionalStop => write!(writer, "M01*")?,
         };
+        ctx.write_line_ending(writer)?;
         Ok(())
     }
 }
@@ -71,6 +189,10 @@ impl<W: Write> GerberCode<W> for MCode {
 // Operation
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Operation {
     /// D01 Command
     Interpolate(Coordinates, Option<CoordinateOffset>),
@@ -80,23 +202,74 @@ pub enum Operation {
     Flash(Coordinates),
 }
 
+impl Operation {
+    /// Create a D01 operation from floating point coordinates, rounded to
+    /// the precision of the coordinate number.
+    pub fn interpolate_xy(x: f64, y: f64, format: CoordinateFormat) -> GerberResult<Self> {
+        Ok(Operation::Interpolate(xy(x, y, format)?, None))
+    }
+
+    /// Create a circular D01 operation from floating point coordinates and
+    /// the offset (I/J) of the arc center, rounded to the precision of the
+    /// coordinate number.
+    pub fn interpolate_arc_xy(
+        x: f64,
+        y: f64,
+        i: f64,
+        j: f64,
+        format: CoordinateFormat,
+    ) -> GerberResult<Self> {
+        let offset = CoordinateOffset::new(
+            CoordinateNumber::from_f64_rounded(i)?,
+            CoordinateNumber::from_f64_rounded(j)?,
+            format,
+        );
+        Ok(Operation::Interpolate(xy(x, y, format)?, Some(offset)))
+    }
+
+    /// Create a D02 operation from floating point coordinates, rounded to
+    /// the precision of the coordinate number.
+    pub fn move_xy(x: f64, y: f64, format: CoordinateFormat) -> GerberResult<Self> {
+        Ok(Operation::Move(xy(x, y, format)?))
+    }
+
+    /// Create a D03 operation from floating point coordinates, rounded to
+    /// the precision of the coordinate number.
+    pub fn flash_xy(x: f64, y: f64, format: CoordinateFormat) -> GerberResult<Self> {
+        Ok(Operation::Flash(xy(x, y, format)?))
+    }
+}
+
+fn xy(x: f64, y: f64, format: CoordinateFormat) -> GerberResult<Coordinates> {
+    Coordinates::from_f64_rounded(x, y, format)
+}
+
 impl<W: Write> GerberCode<W> for Operation {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
             Operation::Interpolate(ref coords, ref offset) => {
-                coords.serialize_partial(writer)?;
-                offset.serialize_partial(writer)?;
-                writeln!(writer, "D01*")?;
+                coords.serialize_partial_with_context(writer, ctx)?;
+                offset.serialize_partial_with_context(writer, ctx)?;
+                writer.write_all(b"D01*")?;
             }
             Operation::Move(ref coords) => {
-                coords.serialize_partial(writer)?;
-                writeln!(writer, "D02*")?;
+                coords.serialize_partial_with_context(writer, ctx)?;
+                writer.write_all(b"D02*")?;
             }
             Operation::Flash(ref coords) => {
-                coords.serialize_partial(writer)?;
-                writeln!(writer, "D03*")?;
+                coords.serialize_partial_with_context(writer, ctx)?;
+                writer.write_all(b"D03*")?;
             }
         };
+        ctx.end_line(writer)?;
         Ok(())
     }
 }
@@ -104,6 +277,10 @@ impl<W: Write> GerberCode<W> for Operation {
 // InterpolationMode
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum InterpolationMode {
     Linear,
     ClockwiseCircular,
@@ -112,18 +289,109 @@ pub enum InterpolationMode {
 
 impl<W: Write> GerberCode<W> for InterpolationMode {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        match *self {
+            InterpolationMode::Linear => write!(writer, "G01*")?,
+            InterpolationMode::ClockwiseCircular => write!(writer, "G02*")?,
+            InterpolationMode::CounterclockwiseCircular => write!(writer, "G03*")?,
+        };
+        ctx.end_line(writer)?;
+        Ok(())
+    }
+}
+
+// RegionMode
+
+/// The beginning (G36) or end (G37) of a region statement.
+///
+/// For compatibility with earlier versions, the mode is serialized with serde
+/// as a boolean, `true` for the beginning of a region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum RegionMode {
+    Begin,
+    End,
+}
+
+impl From<bool> for RegionMode {
+    fn from(begin: bool) -> Self {
+        if begin {
+            RegionMode::Begin
+        } else {
+            RegionMode::End
+        }
+    }
+}
+
+impl From<RegionMode> for bool {
+    fn from(mode: RegionMode) -> Self {
+        mode == RegionMode::Begin
+    }
+}
+
+impl<W: Write> GerberCode<W> for RegionMode {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
-            InterpolationMode::Linear => writeln!(writer, "G01*")?,
-            InterpolationMode::ClockwiseCircular => writeln!(writer, "G02*")?,
-            InterpolationMode::CounterclockwiseCircular => writeln!(writer, "G03*")?,
+            RegionMode::Begin => write!(writer, "G36*")?,
+            RegionMode::End => write!(writer, "G37*")?,
         };
+        ctx.end_line(writer)?;
         Ok(())
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for RegionMode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bool(bool::from(*self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RegionMode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        bool::deserialize(deserializer).map(RegionMode::from)
+    }
+}
+
+#[cfg(feature = "schemars")]
+impl schemars::JsonSchema for RegionMode {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        bool::schema_name()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        bool::json_schema(gen)
+    }
+}
+
 // QuadrantMode
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum QuadrantMode {
     Single,
     Multi,
@@ -131,13 +399,33 @@ pub enum QuadrantMode {
 
 impl<W: Write> GerberCode<W> for QuadrantMode {
     fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
-            QuadrantMode::Single => writeln!(writer, "G74*")?,
-            QuadrantMode::Multi => writeln!(writer, "G75*")?,
+            QuadrantMode::Single => write!(writer, "G74*")?,
+            QuadrantMode::Multi => write!(writer, "G75*")?,
         };
+        ctx.end_line(writer)?;
         Ok(())
     }
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_comment_line() {
+        assert_eq!(split_comment_line("", 5), [""]);
+        assert_eq!(split_comment_line("a b c d", 3), ["a b", "c d"]);
+        assert_eq!(split_comment_line("abc def", 5), ["abc", "def"]);
+        assert_eq!(split_comment_line("abcdefg", 3), ["abc", "def", "g"]);
+        assert_eq!(split_comment_line("äöü", 3), ["ä", "ö", "ü"]);
+    }
+}
@@ -0,0 +1,31 @@
+//! Helpers for the `Arbitrary` implementations, requires the `arbitrary`
+//! feature.
+
+use std::borrow::Cow;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+/// Generate an owned string, since the input data does not live for
+/// `'static`.
+pub(crate) fn arbitrary_cow(u: &mut Unstructured) -> Result<Cow<'static, str>> {
+    Ok(Cow::Owned(String::arbitrary(u)?))
+}
+
+#[cfg(test)]
+mod test {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use crate::traits::GerberCodeExt;
+    use crate::types::Command;
+
+    #[test]
+    fn test_arbitrary_commands() {
+        let data: Vec<u8> = (0..4096u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let mut u = Unstructured::new(&data);
+        for _ in 0..64 {
+            let command = Command::arbitrary(&mut u).unwrap();
+            // Arbitrary commands may be invalid, but must not panic
+            let _ = command.to_code_string();
+        }
+    }
+}
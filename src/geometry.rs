@@ -0,0 +1,557 @@
+//! Conversion of the image drawn by a command stream into `geo` geometries,
+//! e.g. for clipping or offsetting with the Rust geometry ecosystem.
+//!
+//! Coordinates are in the unit of the file. Arcs are flattened into line
+//! segments that deviate at most by the given tolerance from the arc, circles
+//! into polygons with vertices on the circle.
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use geo::{BooleanOps, Coord, LineString, MultiPolygon, Polygon};
+
+use crate::coordinates::CoordinateNumber;
+use crate::decimal::from_decimal;
+use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::{Aperture, Polarity, StepAndRepeat};
+use crate::function_codes::{DCode, GCode, InterpolationMode, Operation, RegionMode};
+use crate::quadrants::{sweep, to_multi_quadrant};
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// The center lines of all draws (D01) outside of regions, one line string
+/// for each connected path.
+///
+/// A path ends at a move (D02), a flash (D03), a region statement or a change
+/// of the aperture or polarity.
+pub fn to_line_strings(commands: &[Command], tolerance: f64) -> GerberResult<Vec<LineString>> {
+    Ok(objects(commands, tolerance)?
+        .into_iter()
+        .filter_map(|object| match object.shape {
+            Shape::Draw(path, _) => Some(LineString::new(path)),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The image of a command stream, with the polarity applied: dark objects are
+/// added to the image and clear objects are removed from it, in the order of
+/// the stream.
+///
+/// Flashes of the standard apertures, draws with circular apertures and
+/// regions are supported. Apertures defined by macros, draws with other
+/// apertures and step and repeat blocks result in an `UnsupportedError`.
+pub fn to_multi_polygon(commands: &[Command], tolerance: f64) -> GerberResult<MultiPolygon> {
+    let mut apertures = HashMap::new();
+    for command in commands {
+        for code in codes(command) {
+            if let ExtendedCode::ApertureDefinition(definition) = code {
+                apertures.insert(definition.code, &definition.aperture);
+            }
+        }
+    }
+    let aperture = |code: Option<i32>| {
+        code.and_then(|code| apertures.get(&code).copied())
+            .ok_or_else(|| {
+                GerberError::MissingDataError("Operation without a defined aperture".into())
+            })
+    };
+
+    let mut image = MultiPolygon::new(Vec::new());
+    for object in objects(commands, tolerance)? {
+        let shape = match object.shape {
+            Shape::Flash(point, code) => {
+                translated(&aperture_shape(aperture(code)?, tolerance)?, point)
+            }
+            Shape::Draw(path, code) => match aperture(code)? {
+                Aperture::Circle(circle) => {
+                    stroke(&path, from_decimal(circle.diameter) / 2.0, tolerance)
+                }
+                _ => {
+                    return Err(GerberError::UnsupportedError(
+                        "Only draws with circular apertures can be converted".into(),
+                    ))
+                }
+            },
+            Shape::Region(contour) => {
+                MultiPolygon::new(vec![Polygon::new(LineString::new(contour), Vec::new())])
+            }
+        };
+        image = match object.polarity {
+            Polarity::Dark => image.union(&shape),
+            Polarity::Clear => image.difference(&shape),
+        };
+    }
+    Ok(image)
+}
+
+type XY = (Option<CoordinateNumber>, Option<CoordinateNumber>);
+
+/// A graphical object, with the aperture code of flashes and draws.
+enum Shape {
+    Flash(Coord, Option<i32>),
+    Draw(Vec<Coord>, Option<i32>),
+    Region(Vec<Coord>),
+}
+
+struct Object {
+    shape: Shape,
+    polarity: Polarity,
+}
+
+fn codes(command: &Command) -> &[ExtendedCode] {
+    match command {
+        Command::ExtendedCode(code) => std::slice::from_ref(code),
+        Command::ExtendedCodeBlock(block) => &block.codes,
+        _ => &[],
+    }
+}
+
+/// The graphics state while collecting the objects of a command stream.
+struct Walker {
+    objects: Vec<Object>,
+    aperture: Option<i32>,
+    polarity: Polarity,
+    region: bool,
+    /// The points of the path or contour that is being drawn
+    path: Vec<Coord>,
+}
+
+impl Walker {
+    /// End the current path or contour.
+    fn finish(&mut self) {
+        if self.path.len() >= 2 {
+            let path = std::mem::take(&mut self.path);
+            let shape = if self.region {
+                Shape::Region(path)
+            } else {
+                Shape::Draw(path, self.aperture)
+            };
+            self.objects.push(Object {
+                shape,
+                polarity: self.polarity,
+            });
+        }
+        self.path.clear();
+    }
+}
+
+/// The graphical objects of a command stream, with flattened arcs.
+fn objects(commands: &[Command], tolerance: f64) -> GerberResult<Vec<Object>> {
+    if tolerance.is_nan() || tolerance <= 0.0 {
+        return Err(GerberError::RangeError(
+            "The tolerance must be positive".into(),
+        ));
+    }
+    let mut walker = Walker {
+        objects: Vec::new(),
+        aperture: None,
+        polarity: Polarity::Dark,
+        region: false,
+        path: Vec::new(),
+    };
+    let mut point: XY = (None, None);
+    let mut interpolation = None;
+
+    // All arcs are converted to multi quadrant mode, with a signed offset
+    for command in &to_multi_quadrant(commands)? {
+        for code in codes(command) {
+            match code {
+                ExtendedCode::LoadPolarity(polarity) => {
+                    walker.finish();
+                    walker.polarity = *polarity;
+                }
+                ExtendedCode::StepAndRepeat(StepAndRepeat::Open { .. }) => {
+                    return Err(GerberError::UnsupportedError(
+                        "Step and repeat blocks cannot be converted".into(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        let function = match command {
+            Command::FunctionCode(function) => function,
+            _ => continue,
+        };
+        match function {
+            FunctionCode::GCode(GCode::InterpolationMode(mode)) => interpolation = Some(*mode),
+            FunctionCode::GCode(GCode::RegionMode(mode)) => {
+                walker.finish();
+                walker.region = *mode == RegionMode::Begin;
+            }
+            FunctionCode::DCode(DCode::SelectAperture(code)) => {
+                walker.finish();
+                walker.aperture = Some(*code);
+            }
+            FunctionCode::DCode(DCode::Operation(operation)) => {
+                let coords = match operation {
+                    Operation::Interpolate(coords, _)
+                    | Operation::Move(coords)
+                    | Operation::Flash(coords) => coords,
+                };
+                let end = end_point(point, (coords.x, coords.y))?;
+                match operation {
+                    Operation::Interpolate(_, offset) => {
+                        let start = end_point(point, (None, None))?;
+                        if walker.path.is_empty() {
+                            walker.path.push(start);
+                        }
+                        match interpolation {
+                            Some(InterpolationMode::Linear) => walker.path.push(end),
+                            Some(mode) => {
+                                let zero = CoordinateNumber::new(0);
+                                let (i, j) = offset.as_ref().map_or((zero, zero), |o| {
+                                    (o.x.unwrap_or(zero), o.y.unwrap_or(zero))
+                                });
+                                let center = Coord {
+                                    x: start.x + f64::from(i),
+                                    y: start.y + f64::from(j),
+                                };
+                                let clockwise = mode == InterpolationMode::ClockwiseCircular;
+                                walker
+                                    .path
+                                    .extend(arc(start, end, center, clockwise, tolerance)?);
+                            }
+                            None => {
+                                return Err(GerberError::MissingDataError(
+                                    "Interpolation before the interpolation mode is set".into(),
+                                ))
+                            }
+                        }
+                    }
+                    Operation::Move(_) => walker.finish(),
+                    Operation::Flash(_) => {
+                        walker.finish();
+                        walker.objects.push(Object {
+                            shape: Shape::Flash(end, walker.aperture),
+                            polarity: walker.polarity,
+                        });
+                    }
+                }
+                point = (coords.x.or(point.0), coords.y.or(point.1));
+            }
+            _ => {}
+        }
+    }
+    walker.finish();
+    Ok(walker.objects)
+}
+
+/// The point of an operation, missing coordinates are the ones of the
+/// current point.
+fn end_point(point: XY, (x, y): XY) -> GerberResult<Coord> {
+    match (x.or(point.0), y.or(point.1)) {
+        (Some(x), Some(y)) => Ok(Coord {
+            x: x.into(),
+            y: y.into(),
+        }),
+        _ => Err(GerberError::MissingDataError(
+            "Operation before the current point is set".into(),
+        )),
+    }
+}
+
+/// The largest angle of an arc segment that deviates at most by the
+/// tolerance from a circle with the radius.
+fn max_step(radius: f64, tolerance: f64) -> f64 {
+    2.0 * (1.0 - tolerance / radius).max(-1.0).acos()
+}
+
+/// The points of an arc after its start point.
+fn arc(
+    start: Coord,
+    end: Coord,
+    center: Coord,
+    clockwise: bool,
+    tolerance: f64,
+) -> GerberResult<Vec<Coord>> {
+    let sweep = sweep(start.x_y(), end.x_y(), center.x_y(), clockwise)?;
+    let radius = (start.x - center.x).hypot(start.y - center.y);
+    let steps = (sweep / max_step(radius, tolerance)).ceil().max(1.0) as usize;
+    let start_angle = (start.y - center.y).atan2(start.x - center.x);
+    let direction = if clockwise { -1.0 } else { 1.0 };
+    let mut points: Vec<Coord> = (1..steps)
+        .map(|k| {
+            let angle = start_angle + direction * sweep * k as f64 / steps as f64;
+            Coord {
+                x: center.x + radius * angle.cos(),
+                y: center.y + radius * angle.sin(),
+            }
+        })
+        .collect();
+    points.push(end);
+    Ok(points)
+}
+
+/// A circle around the origin.
+fn circle(diameter: f64, tolerance: f64) -> Polygon {
+    let radius = diameter / 2.0;
+    let steps = (2.0 * PI / max_step(radius, tolerance)).ceil().max(3.0) as usize;
+    regular_polygon(radius, steps, 0.0)
+}
+
+/// A regular polygon around the origin, with the first vertex at the angle in
+/// radians.
+fn regular_polygon(radius: f64, vertices: usize, rotation: f64) -> Polygon {
+    let points = (0..vertices)
+        .map(|k| {
+            let angle = rotation + 2.0 * PI * k as f64 / vertices as f64;
+            Coord {
+                x: radius * angle.cos(),
+                y: radius * angle.sin(),
+            }
+        })
+        .collect();
+    Polygon::new(LineString::new(points), Vec::new())
+}
+
+fn rectangle(min: Coord, max: Coord) -> Polygon {
+    let points = vec![
+        min,
+        Coord { x: max.x, y: min.y },
+        max,
+        Coord { x: min.x, y: max.y },
+    ];
+    Polygon::new(LineString::new(points), Vec::new())
+}
+
+/// An obround around the origin, with semicircles at the ends of its longer
+/// side.
+fn obround(x: f64, y: f64, tolerance: f64) -> GerberResult<Polygon> {
+    let radius = x.min(y) / 2.0;
+    let (dx, dy) = if x >= y {
+        (x / 2.0 - radius, 0.0)
+    } else {
+        (0.0, y / 2.0 - radius)
+    };
+    let mut points = Vec::new();
+    // The semicircles, counterclockwise from the end of the straight sides
+    for &(cx, cy, sign) in &[(dx, dy, 1.0), (-dx, -dy, -1.0)] {
+        let center = Coord { x: cx, y: cy };
+        let (sx, sy) = if x >= y {
+            (cx, cy - sign * radius)
+        } else {
+            (cx + sign * radius, cy)
+        };
+        let start = Coord { x: sx, y: sy };
+        let end = Coord {
+            x: 2.0 * cx - sx,
+            y: 2.0 * cy - sy,
+        };
+        points.push(start);
+        points.extend(arc(start, end, center, false, tolerance)?);
+    }
+    Ok(Polygon::new(LineString::new(points), Vec::new()))
+}
+
+/// The shape of a standard aperture around the origin.
+fn aperture_shape(aperture: &Aperture, tolerance: f64) -> GerberResult<MultiPolygon> {
+    let (shape, hole) = match aperture {
+        Aperture::Circle(c) => (circle(from_decimal(c.diameter), tolerance), c.hole_diameter),
+        Aperture::Rectangle(r) => {
+            let (x, y) = (from_decimal(r.x) / 2.0, from_decimal(r.y) / 2.0);
+            let shape = rectangle(Coord { x: -x, y: -y }, Coord { x, y });
+            (shape, r.hole_diameter)
+        }
+        Aperture::Obround(o) => (
+            obround(from_decimal(o.x), from_decimal(o.y), tolerance)?,
+            o.hole_diameter,
+        ),
+        Aperture::Polygon(p) => {
+            let rotation = p.rotation.map_or(0.0, from_decimal).to_radians();
+            let radius = from_decimal(p.diameter) / 2.0;
+            let shape = regular_polygon(radius, usize::from(p.vertices), rotation);
+            (shape, p.hole_diameter)
+        }
+        Aperture::Other(_) => {
+            return Err(GerberError::UnsupportedError(
+                "Apertures defined by macros cannot be converted".into(),
+            ))
+        }
+    };
+    let shape = MultiPolygon::new(vec![shape]);
+    Ok(match hole {
+        Some(hole) => shape.difference(&circle(from_decimal(hole), tolerance)),
+        None => shape,
+    })
+}
+
+fn translated(shape: &MultiPolygon, offset: Coord) -> MultiPolygon {
+    use geo::Translate;
+
+    shape.translate(offset.x, offset.y)
+}
+
+/// The area covered by a circle with the radius moved along the path.
+fn stroke(path: &[Coord], radius: f64, tolerance: f64) -> MultiPolygon {
+    let dot = circle(2.0 * radius, tolerance);
+    let mut parts: Vec<Polygon> = path
+        .iter()
+        .map(|&point| {
+            use geo::Translate;
+
+            dot.translate(point.x, point.y)
+        })
+        .collect();
+    for segment in path.windows(2) {
+        let (dx, dy) = (segment[1].x - segment[0].x, segment[1].y - segment[0].y);
+        let length = dx.hypot(dy);
+        if length == 0.0 {
+            continue;
+        }
+        // The normal of the segment, with the length of the radius
+        let (nx, ny) = (-dy / length * radius, dx / length * radius);
+        let points = vec![
+            Coord {
+                x: segment[0].x + nx,
+                y: segment[0].y + ny,
+            },
+            Coord {
+                x: segment[0].x - nx,
+                y: segment[0].y - ny,
+            },
+            Coord {
+                x: segment[1].x - nx,
+                y: segment[1].y - ny,
+            },
+            Coord {
+                x: segment[1].x + nx,
+                y: segment[1].y + ny,
+            },
+        ];
+        parts.push(Polygon::new(LineString::new(points), Vec::new()));
+    }
+    parts
+        .iter()
+        .fold(MultiPolygon::new(Vec::new()), |stroke, part| {
+            stroke.union(part)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use geo::Area;
+
+    use crate::coordinates::{CoordinateFormat, CoordinateOffset, Coordinates};
+    use crate::extended_codes::{ApertureDefinition, Circle, Rectangular};
+    use crate::function_codes::QuadrantMode;
+
+    fn point(x: i32, y: i32) -> Coordinates {
+        Coordinates::new(x, y, CoordinateFormat::new(2, 4))
+    }
+
+    #[test]
+    fn test_to_line_strings() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Circle(Circle::new(0.1)),
+            ))
+            .into(),
+            DCode::SelectAperture(10).into(),
+            GCode::InterpolationMode(InterpolationMode::Linear).into(),
+            GCode::QuadrantMode(QuadrantMode::Multi).into(),
+            DCode::Operation(Operation::Move(point(0, 0))).into(),
+            DCode::Operation(Operation::Interpolate(point(2, 0), None)).into(),
+            GCode::InterpolationMode(InterpolationMode::CounterclockwiseCircular).into(),
+            DCode::Operation(Operation::Interpolate(
+                point(2, 2),
+                Some(CoordinateOffset::new(0, 1, cf)),
+            ))
+            .into(),
+            DCode::Operation(Operation::Move(point(5, 5))).into(),
+            GCode::InterpolationMode(InterpolationMode::Linear).into(),
+            DCode::Operation(Operation::Interpolate(point(5, 6), None)).into(),
+        ];
+        let lines = to_line_strings(&commands, 0.01).unwrap();
+        assert_eq!(lines.len(), 2);
+        let first = &lines[0].0;
+        assert_eq!(first[0], Coord { x: 0.0, y: 0.0 });
+        assert_eq!(first[1], Coord { x: 2.0, y: 0.0 });
+        assert_eq!(first.last(), Some(&Coord { x: 2.0, y: 2.0 }));
+        // The semicircle bulges to the right and its points lie on the circle
+        assert!(first.len() > 10);
+        assert!(first[2..]
+            .iter()
+            .all(|c| ((c.x - 2.0).hypot(c.y - 1.0) - 1.0).abs() < 1e-9 && c.x >= 2.0));
+        assert_eq!(
+            lines[1].0,
+            vec![Coord { x: 5.0, y: 5.0 }, Coord { x: 5.0, y: 6.0 }]
+        );
+    }
+
+    #[test]
+    fn test_to_multi_polygon() {
+        let commands: Vec<Command> = vec![
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Rectangle(Rectangular::new(4.0, 2.0)),
+            ))
+            .into(),
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                11,
+                Aperture::Circle(Circle::new(1.0)),
+            ))
+            .into(),
+            DCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Flash(point(0, 0))).into(),
+            // A region next to the flash
+            GCode::RegionMode(RegionMode::Begin).into(),
+            GCode::InterpolationMode(InterpolationMode::Linear).into(),
+            DCode::Operation(Operation::Move(point(5, 0))).into(),
+            DCode::Operation(Operation::Interpolate(point(7, 0), None)).into(),
+            DCode::Operation(Operation::Interpolate(point(7, 2), None)).into(),
+            DCode::Operation(Operation::Interpolate(point(5, 2), None)).into(),
+            DCode::Operation(Operation::Interpolate(point(5, 0), None)).into(),
+            GCode::RegionMode(RegionMode::End).into(),
+            // A clear draw across the middle of the flash
+            ExtendedCode::LoadPolarity(Polarity::Clear).into(),
+            DCode::SelectAperture(11).into(),
+            DCode::Operation(Operation::Move(point(0, -2))).into(),
+            DCode::Operation(Operation::Interpolate(point(0, 2), None)).into(),
+        ];
+        let image = to_multi_polygon(&commands, 0.001).unwrap();
+        assert_eq!(image.0.len(), 3);
+        assert!((image.unsigned_area() - (8.0 - 2.0 + 4.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_aperture_shapes() {
+        let area = |aperture: Aperture| aperture_shape(&aperture, 1e-4).unwrap().unsigned_area();
+        assert!((area(Aperture::Circle(Circle::with_hole(2.0, 1.0))) - 0.75 * PI).abs() < 1e-3);
+        assert!(
+            (area(Aperture::Obround(crate::extended_codes::Obround::new(
+                1.0, 3.0
+            ))) - (2.0 + 0.25 * PI))
+                .abs()
+                < 1e-3
+        );
+        let square = Aperture::Polygon(crate::extended_codes::Polygon::new(2.0, 4));
+        assert!((area(square) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let commands: Vec<Command> = vec![
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Other("THERMAL".into()),
+            ))
+            .into(),
+            DCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Flash(point(0, 0))).into(),
+        ];
+        assert!(matches!(
+            to_multi_polygon(&commands, 0.01),
+            Err(GerberError::UnsupportedError(_))
+        ));
+        // The center lines don't need the aperture
+        assert!(to_line_strings(&commands, 0.01).unwrap().is_empty());
+        assert!(matches!(
+            to_line_strings(&commands, 0.0),
+            Err(GerberError::RangeError(_))
+        ));
+    }
+}
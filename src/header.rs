@@ -0,0 +1,103 @@
+//! Splitting of command streams into header and body.
+
+use crate::deprecated::DeprecatedCode;
+use crate::errors::{GerberError, GerberResult};
+use crate::function_codes::GCode;
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// Split a command stream into its header and its body.
+///
+/// The header is the leading run of format, unit, attribute, aperture and
+/// macro definitions, including comments between them. The body starts with
+/// the first other command, e.g. an operation, a mode change or `M02`.
+///
+/// Aperture definitions, macros and aperture attributes may also appear in
+/// the body, but a coordinate format, unit or file attribute after the start
+/// of the body results in an `OrderError`.
+pub fn split_header(commands: &[Command]) -> GerberResult<(&[Command], &[Command])> {
+    let split = commands
+        .iter()
+        .position(|command| !is_header_command(command))
+        .unwrap_or(commands.len());
+    let (header, body) = commands.split_at(split);
+    for (index, command) in body.iter().enumerate() {
+        let codes: &[ExtendedCode] = match command {
+            Command::ExtendedCode(code) => std::slice::from_ref(code),
+            Command::ExtendedCodeBlock(block) => &block.codes,
+            _ => &[],
+        };
+        let misplaced = codes.iter().any(|code| {
+            matches!(
+                code,
+                ExtendedCode::CoordinateFormat(_)
+                    | ExtendedCode::Unit(_)
+                    | ExtendedCode::FileAttribute(_)
+            )
+        });
+        if misplaced {
+            return Err(GerberError::OrderError(format!(
+                "Header command at index {} after the start of the body",
+                split + index
+            )));
+        }
+    }
+    Ok((header, body))
+}
+
+fn is_header_command(command: &Command) -> bool {
+    match command {
+        Command::ExtendedCode(code) => code.is_header_code(),
+        Command::ExtendedCodeBlock(block) => block.codes.iter().all(ExtendedCode::is_header_code),
+        Command::FunctionCode(FunctionCode::GCode(GCode::Comment(_))) => true,
+        Command::FunctionCode(_) => false,
+        Command::Deprecated(code) => !matches!(
+            code,
+            DeprecatedCode::SelectAperture(_)
+                | DeprecatedCode::CoordinatesOnly(_)
+                | DeprecatedCode::CoordinatesWithOffset(..)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::{CoordinateFormat, Coordinates};
+    use crate::extended_codes::{Aperture, ApertureDefinition, Circle, Unit};
+    use crate::function_codes::{DCode, MCode, Operation};
+
+    #[test]
+    fn test_split_header() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            GCode::Comment("header".into()).into(),
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            ApertureDefinition::new(10, Aperture::Circle(Circle::new(0.1))).into(),
+            DCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(1, 1, cf))).into(),
+            ApertureDefinition::new(11, Aperture::Circle(Circle::new(0.2))).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let (header, body) = split_header(&commands).unwrap();
+        assert_eq!(header, &commands[..4]);
+        assert_eq!(body, &commands[4..]);
+
+        let (header, body) = split_header(&commands[..4]).unwrap();
+        assert_eq!(header.len(), 4);
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn test_split_header_order() {
+        let commands: Vec<Command> = vec![
+            DCode::SelectAperture(10).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+        ];
+        assert!(matches!(
+            split_header(&commands),
+            Err(GerberError::OrderError(_))
+        ));
+    }
+}
@@ -0,0 +1,231 @@
+//! Types for Gerber job files (`.gbrjob`), requires the `job` feature.
+//!
+//! A job file is a JSON document that describes a set of Gerber files that
+//! together make up a PCB fabrication job. The types serialize with serde into
+//! the structure of the specification, use e.g. `serde_json` to write them.
+//! Only the most common properties are supported.
+
+use serde::{Deserialize, Serialize};
+
+use crate::attributes::{FileFunction, FilePolarity};
+use crate::context::SerializationContext;
+use crate::errors::GerberResult;
+use crate::traits::PartialGerberCode;
+
+/// A Gerber job file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GerberJob {
+    pub header: JobHeader,
+    pub general_specs: GeneralSpecs,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub design_rules: Vec<DesignRule>,
+    pub files_attributes: Vec<JobFile>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub material_stackup: Vec<StackupLayer>,
+}
+
+impl GerberJob {
+    pub fn new(header: JobHeader, general_specs: GeneralSpecs) -> Self {
+        GerberJob {
+            header,
+            general_specs,
+            design_rules: Vec::new(),
+            files_attributes: Vec::new(),
+            material_stackup: Vec::new(),
+        }
+    }
+
+    pub fn with_file(mut self, file: JobFile) -> Self {
+        self.files_attributes.push(file);
+        self
+    }
+
+    pub fn with_design_rule(mut self, rule: DesignRule) -> Self {
+        self.design_rules.push(rule);
+        self
+    }
+
+    pub fn with_stackup_layer(mut self, layer: StackupLayer) -> Self {
+        self.material_stackup.push(layer);
+        self
+    }
+}
+
+/// The header of a job file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JobHeader {
+    pub generation_software: JobGenerationSoftware,
+    /// The creation date as an ISO 8601 string, e.g.
+    /// `2015-02-23T15:59:51+01:00`
+    pub creation_date: String,
+}
+
+/// The software that generated the job file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JobGenerationSoftware {
+    pub vendor: String,
+    pub application: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// General specifications of the board.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GeneralSpecs {
+    pub project_id: JobProjectId,
+    /// The size of the bounding box of the board in mm
+    pub size: JobSize,
+    /// The number of copper layers
+    pub layer_number: u32,
+    /// The thickness of the board in mm
+    pub board_thickness: f64,
+    /// The surface finish, e.g. `ENIG`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish: Option<String>,
+}
+
+/// The project a job belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JobProjectId {
+    pub name: String,
+    #[serde(rename = "GUID")]
+    pub guid: String,
+    pub revision: String,
+}
+
+/// A size in mm.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JobSize {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Design rules that apply to a set of layers, all distances in mm.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DesignRule {
+    /// The layers the rule applies to, e.g. `Outer` or `Inner`
+    pub layers: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pad_to_pad: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pad_to_track: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_to_track: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_line_width: Option<f64>,
+}
+
+/// A file of the job with its attributes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct JobFile {
+    pub path: String,
+    /// The file function in the same notation as the `.FileFunction`
+    /// attribute, e.g. `Copper,L1,Top`
+    pub file_function: String,
+    pub file_polarity: FilePolarity,
+}
+
+impl JobFile {
+    /// Create a job file entry from a file function.
+    pub fn new<S: Into<String>>(
+        path: S,
+        function: &FileFunction,
+        polarity: FilePolarity,
+    ) -> GerberResult<Self> {
+        let mut buf = Vec::new();
+        function.serialize_partial_with_context(&mut buf, &SerializationContext::new())?;
+        Ok(JobFile {
+            path: path.into(),
+            file_function: String::from_utf8(buf).expect("Generated code is not UTF-8"),
+            file_polarity: polarity,
+        })
+    }
+}
+
+/// A layer of the material stackup, from top to bottom.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StackupLayer {
+    /// The layer type, e.g. `Copper`, `Dielectric` or `Legend`
+    #[serde(rename = "Type")]
+    pub layer_type: String,
+    /// The thickness in mm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thickness: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub material: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::ExtendedPosition;
+
+    #[test]
+    fn test_job_json() {
+        let header = JobHeader {
+            generation_software: JobGenerationSoftware {
+                vendor: "Vendor".into(),
+                application: "App".into(),
+                version: None,
+            },
+            creation_date: "2015-02-23T15:59:51+01:00".into(),
+        };
+        let specs = GeneralSpecs {
+            project_id: JobProjectId {
+                name: "Board".into(),
+                guid: "5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c".into(),
+                revision: "1".into(),
+            },
+            size: JobSize { x: 100.0, y: 50.5 },
+            layer_number: 2,
+            board_thickness: 1.6,
+            finish: None,
+        };
+        let top = FileFunction::Copper {
+            layer: 1,
+            pos: ExtendedPosition::Top,
+            copper_type: None,
+        };
+        let job = GerberJob::new(header, specs)
+            .with_file(JobFile::new("top.gbr", &top, FilePolarity::Positive).unwrap());
+        let json = serde_json::to_value(&job).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "Header": {
+                    "GenerationSoftware": {"Vendor": "Vendor", "Application": "App"},
+                    "CreationDate": "2015-02-23T15:59:51+01:00"
+                },
+                "GeneralSpecs": {
+                    "ProjectId": {
+                        "Name": "Board",
+                        "GUID": "5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c",
+                        "Revision": "1"
+                    },
+                    "Size": {"X": 100.0, "Y": 50.5},
+                    "LayerNumber": 2,
+                    "BoardThickness": 1.6
+                },
+                "FilesAttributes": [{
+                    "Path": "top.gbr",
+                    "FileFunction": "Copper,L1,Top",
+                    "FilePolarity": "Positive"
+                }]
+            })
+        );
+        let parsed: GerberJob = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, job);
+    }
+}
@@ -0,0 +1,265 @@
+//! Sets of layers that belong to the same board.
+
+use crate::attributes::{FileAttribute, FileFunction};
+use crate::coordinates::CoordinateFormat;
+use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::Unit;
+use crate::optimize::extended_codes;
+use crate::types::{Command, ExtendedCode};
+
+/// Attributes that describe the whole set rather than a single layer. They
+/// are copied to the layers that do not have them.
+const SHARED_ATTRIBUTES: [&str; 4] = [
+    ".Part",
+    ".GenerationSoftware",
+    ".CreationDate",
+    ".ProjectId",
+];
+
+/// A layer of a [`LayerSet`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub function: FileFunction,
+    pub commands: Vec<Command>,
+}
+
+/// The layers of a board, e.g. the Gerber files of a fabrication data set.
+///
+/// All layers must use the same unit and coordinate format and, since they
+/// share the same coordinates, the same `.SameCoordinates` identifier. Use
+/// [`complete_attributes`](#method.complete_attributes) to add the
+/// attributes that are missing in some of the layers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayerSet {
+    pub layers: Vec<Layer>,
+}
+
+impl LayerSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_layer(mut self, function: FileFunction, commands: Vec<Command>) -> Self {
+        self.layers.push(Layer { function, commands });
+        self
+    }
+
+    /// Check that the layers are consistent.
+    ///
+    /// Every layer must set the unit and the coordinate format, and they
+    /// must be the same for all layers. The `.FileFunction` attribute of a
+    /// layer, if present, must match the function of the layer, and all
+    /// `.SameCoordinates` identifiers must be the same.
+    pub fn check(&self) -> GerberResult<()> {
+        let mut first: Option<(Unit, CoordinateFormat)> = None;
+        let mut same_coordinates: Option<&Option<String>> = None;
+        for (index, layer) in self.layers.iter().enumerate() {
+            let mut unit = None;
+            let mut format = None;
+            for code in extended_codes(&layer.commands) {
+                match code {
+                    ExtendedCode::Unit(u) => unit = unit.or(Some(*u)),
+                    ExtendedCode::CoordinateFormat(cf) => format = format.or(Some(*cf)),
+                    ExtendedCode::FileAttribute(FileAttribute::FileFunction(function))
+                        if *function != layer.function =>
+                    {
+                        return Err(inconsistent(format!(
+                            "Layer {} has a different .FileFunction attribute than the layer",
+                            index
+                        )));
+                    }
+                    ExtendedCode::FileAttribute(FileAttribute::SameCoordinates(ident)) => {
+                        match same_coordinates {
+                            Some(first) if first != ident => {
+                                return Err(inconsistent(format!(
+                                    "Layer {} has a different .SameCoordinates identifier",
+                                    index
+                                )));
+                            }
+                            _ => same_coordinates = Some(ident),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let missing = |what: &str| {
+                GerberError::MissingDataError(format!("Layer {} does not set the {}", index, what))
+            };
+            let unit = unit.ok_or_else(|| missing("unit"))?;
+            let format = format.ok_or_else(|| missing("coordinate format"))?;
+            match first {
+                None => first = Some((unit, format)),
+                Some((first_unit, _)) if first_unit != unit => {
+                    return Err(inconsistent(format!(
+                        "Layer {} uses a different unit than the first layer",
+                        index
+                    )));
+                }
+                Some((_, first_format)) if first_format != format => {
+                    return Err(inconsistent(format!(
+                        "Layer {} uses a different coordinate format than the first layer",
+                        index
+                    )));
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the layers and add missing file attributes to the start of
+    /// each layer.
+    ///
+    /// Every layer gets its `.FileFunction` and a `.SameCoordinates`
+    /// attribute, with the identifier used by the other layers. The
+    /// `.Part`, `.GenerationSoftware`, `.CreationDate` and `.ProjectId`
+    /// attributes are copied from the first layer that has them.
+    pub fn complete_attributes(&mut self) -> GerberResult<()> {
+        self.check()?;
+        let attributes: Vec<Vec<FileAttribute>> = self
+            .layers
+            .iter()
+            .map(|layer| file_attributes(&layer.commands).cloned().collect())
+            .collect();
+        let same_coordinates = attributes
+            .iter()
+            .flatten()
+            .find(|a| matches!(a, FileAttribute::SameCoordinates(_)))
+            .cloned()
+            .unwrap_or(FileAttribute::SameCoordinates(None));
+        let shared: Vec<&FileAttribute> = SHARED_ATTRIBUTES
+            .iter()
+            .filter_map(|name| attributes.iter().flatten().find(|a| a.name() == *name))
+            .collect();
+
+        for (layer, present) in self.layers.iter_mut().zip(&attributes) {
+            let has = |name: &str| present.iter().any(|a| a.name() == name);
+            let mut missing = Vec::new();
+            if !has(".FileFunction") {
+                missing.push(FileAttribute::FileFunction(layer.function.clone()));
+            }
+            if !has(".SameCoordinates") {
+                missing.push(same_coordinates.clone());
+            }
+            for attribute in &shared {
+                if !has(attribute.name()) {
+                    missing.push((*attribute).clone());
+                }
+            }
+            layer.commands.splice(
+                0..0,
+                missing
+                    .into_iter()
+                    .map(|a| Command::from(ExtendedCode::FileAttribute(a))),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn inconsistent(message: String) -> GerberError {
+    GerberError::UnsupportedError(message)
+}
+
+fn file_attributes(commands: &[Command]) -> impl Iterator<Item = &FileAttribute> {
+    extended_codes(commands).filter_map(|code| match code {
+        ExtendedCode::FileAttribute(attribute) => Some(attribute),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::{CopperType, ExtendedPosition, Part, Position};
+    use crate::traits::GerberCodeExt;
+
+    fn layer(unit: Unit) -> Vec<Command> {
+        vec![
+            ExtendedCode::CoordinateFormat(CoordinateFormat::new(2, 4)).into(),
+            ExtendedCode::Unit(unit).into(),
+        ]
+    }
+
+    fn copper(layer: i32, pos: ExtendedPosition) -> FileFunction {
+        FileFunction::Copper {
+            layer,
+            pos,
+            copper_type: Some(CopperType::Signal),
+        }
+    }
+
+    #[test]
+    fn test_layer_set_complete_attributes() {
+        let mut top = layer(Unit::Millimeters);
+        top.insert(
+            0,
+            ExtendedCode::FileAttribute(FileAttribute::Part(Part::Single)).into(),
+        );
+        let mut set = LayerSet::new()
+            .with_layer(copper(1, ExtendedPosition::Top), top)
+            .with_layer(
+                FileFunction::Legend {
+                    pos: Position::Top,
+                    index: None,
+                },
+                layer(Unit::Millimeters),
+            );
+        set.complete_attributes().unwrap();
+        assert_eq!(
+            set.layers[0].commands.to_code_string().unwrap(),
+            "%TF.FileFunction,Copper,L1,Top,Signal*%\n\
+             %TF.SameCoordinates*%\n\
+             %TF.Part,Single*%\n\
+             %FSLAX24Y24*%\n%MOMM*%\n"
+        );
+        assert_eq!(
+            set.layers[1].commands.to_code_string().unwrap(),
+            "%TF.FileFunction,Legend,Top*%\n\
+             %TF.SameCoordinates*%\n\
+             %TF.Part,Single*%\n\
+             %FSLAX24Y24*%\n%MOMM*%\n"
+        );
+
+        // Completing again does not change anything
+        let completed = set.clone();
+        set.complete_attributes().unwrap();
+        assert_eq!(set, completed);
+    }
+
+    #[test]
+    fn test_layer_set_check() {
+        let set = LayerSet::new()
+            .with_layer(copper(1, ExtendedPosition::Top), layer(Unit::Millimeters))
+            .with_layer(copper(2, ExtendedPosition::Bottom), layer(Unit::Inches));
+        assert!(matches!(set.check(), Err(GerberError::UnsupportedError(_))));
+
+        let set = LayerSet::new().with_layer(copper(1, ExtendedPosition::Top), vec![]);
+        assert!(matches!(set.check(), Err(GerberError::MissingDataError(_))));
+
+        let mut commands = layer(Unit::Millimeters);
+        commands.push(
+            ExtendedCode::FileAttribute(FileAttribute::FileFunction(copper(
+                2,
+                ExtendedPosition::Bottom,
+            )))
+            .into(),
+        );
+        let set = LayerSet::new().with_layer(copper(1, ExtendedPosition::Top), commands);
+        assert!(set.check().is_err());
+
+        let same = |ident: &str| -> Vec<Command> {
+            let mut commands = layer(Unit::Millimeters);
+            commands.push(
+                ExtendedCode::FileAttribute(FileAttribute::SameCoordinates(Some(ident.into())))
+                    .into(),
+            );
+            commands
+        };
+        let set = LayerSet::new()
+            .with_layer(copper(1, ExtendedPosition::Top), same("A"))
+            .with_layer(copper(2, ExtendedPosition::Bottom), same("B"));
+        assert!(set.check().is_err());
+    }
+}
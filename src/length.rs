@@ -0,0 +1,146 @@
+//! Conversions from and to `uom` lengths, requires the `uom` feature.
+//!
+//! Gerber values have no unit of their own, they are interpreted in the unit
+//! of the file (`%MO`). All conversions therefore take the file unit.
+
+use uom::si::f64::Length;
+use uom::si::length::{inch, millimeter};
+
+use crate::coordinates::{CoordinateFormat, CoordinateNumber, CoordinateOffset, Coordinates};
+use crate::errors::GerberResult;
+use crate::extended_codes::{Circle, Obround, Polygon, Rectangular, Unit};
+
+impl Unit {
+    /// The value of a length in this unit.
+    pub fn value_of(self, length: Length) -> f64 {
+        match self {
+            Unit::Millimeters => length.get::<millimeter>(),
+            Unit::Inches => length.get::<inch>(),
+        }
+    }
+
+    /// A length from a value in this unit.
+    pub fn length(self, value: f64) -> Length {
+        match self {
+            Unit::Millimeters => Length::new::<millimeter>(value),
+            Unit::Inches => Length::new::<inch>(value),
+        }
+    }
+}
+
+impl CoordinateNumber {
+    /// Create a coordinate number from a length, converted into `file_unit`
+    /// and rounded to the nearest representable value.
+    pub fn from_length(length: Length, file_unit: Unit) -> GerberResult<Self> {
+        CoordinateNumber::from_f64_rounded(file_unit.value_of(length))
+    }
+
+    /// The length of this coordinate number in a file with `file_unit`.
+    pub fn to_length(self, file_unit: Unit) -> Length {
+        file_unit.length(f64::from(self))
+    }
+}
+
+macro_rules! impl_xy_from_lengths {
+    ($class:ident) => {
+        impl $class {
+            /// Create an instance from lengths, converted into `file_unit`.
+            pub fn from_lengths(
+                x: Length,
+                y: Length,
+                file_unit: Unit,
+                format: CoordinateFormat,
+            ) -> GerberResult<Self> {
+                Ok($class::new(
+                    CoordinateNumber::from_length(x, file_unit)?,
+                    CoordinateNumber::from_length(y, file_unit)?,
+                    format,
+                ))
+            }
+        }
+    };
+}
+
+impl_xy_from_lengths!(Coordinates);
+impl_xy_from_lengths!(CoordinateOffset);
+
+impl Circle {
+    /// Create a circle from a diameter length, converted into `file_unit`.
+    pub fn from_length(diameter: Length, file_unit: Unit) -> Self {
+        Circle::new(file_unit.value_of(diameter))
+    }
+}
+
+impl Rectangular {
+    /// Create a rectangle from lengths, converted into `file_unit`.
+    pub fn from_lengths(x: Length, y: Length, file_unit: Unit) -> Self {
+        Rectangular::new(file_unit.value_of(x), file_unit.value_of(y))
+    }
+}
+
+impl Obround {
+    /// Create an obround from lengths, converted into `file_unit`.
+    pub fn from_lengths(x: Length, y: Length, file_unit: Unit) -> Self {
+        Obround::new(file_unit.value_of(x), file_unit.value_of(y))
+    }
+}
+
+impl Polygon {
+    /// Create a polygon from a diameter length, converted into `file_unit`.
+    pub fn from_length(diameter: Length, vertices: u8, file_unit: Unit) -> Self {
+        Polygon::new(file_unit.value_of(diameter), vertices)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use conv::TryFrom;
+
+    #[test]
+    fn test_coordinate_number_from_length() {
+        let length = Length::new::<inch>(1.0);
+        assert_eq!(
+            CoordinateNumber::from_length(length, Unit::Millimeters).unwrap(),
+            CoordinateNumber::try_from(25.4).unwrap()
+        );
+        assert_eq!(
+            CoordinateNumber::from_length(length, Unit::Inches).unwrap(),
+            CoordinateNumber::try_from(1.0).unwrap()
+        );
+        let number = CoordinateNumber::try_from(2.54).unwrap();
+        let back = number.to_length(Unit::Millimeters).get::<inch>();
+        assert!((back - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_coordinates_from_lengths() {
+        let cf = CoordinateFormat::new(2, 4);
+        let coords = Coordinates::from_lengths(
+            Length::new::<millimeter>(25.4),
+            Length::new::<inch>(-0.5),
+            Unit::Inches,
+            cf,
+        )
+        .unwrap();
+        assert_eq!(
+            coords,
+            Coordinates::new(1, CoordinateNumber::try_from(-0.5).unwrap(), cf)
+        );
+    }
+
+    #[test]
+    fn test_apertures_from_lengths() {
+        let circle = Circle::from_length(Length::new::<inch>(0.1), Unit::Millimeters);
+        assert!((f64::from(circle.diameter) - 2.54).abs() < 1e-12);
+        let rect = Rectangular::from_lengths(
+            Length::new::<millimeter>(1.0),
+            Length::new::<millimeter>(2.0),
+            Unit::Millimeters,
+        );
+        assert_eq!(rect, Rectangular::new(1.0, 2.0));
+        let polygon = Polygon::from_length(Length::new::<millimeter>(3.0), 6, Unit::Millimeters);
+        assert_eq!(polygon, Polygon::new(3.0, 6));
+    }
+}
@@ -16,59 +16,180 @@
 //!   terminated with a newline character.
 //! - `PartialGerberCode` (internal only) generates Gerber representation of a
 //!   value, but does not represent a full line of code.
+//!
+//! Both traits can serialize with a
+//! [`SerializationContext`](struct.SerializationContext.html), which holds
+//! settings for a whole file, for example the coordinate format.
+
 #![allow(clippy::new_without_default)]
 
 #[cfg(test)]
 #[macro_use]
 mod test_macros;
 
+mod arc;
+#[cfg(feature = "zip")]
+mod archive;
+#[cfg(feature = "tokio")]
+mod asynchronous;
 mod attributes;
+mod bounds;
+mod charset;
+mod checks;
+#[cfg(feature = "md5")]
+mod checksum;
 mod codegen;
+mod compat;
+mod conformance;
+mod context;
+mod convert;
 mod coordinates;
+mod decimal;
+mod deprecated;
+mod drawing;
+mod dsl;
 mod errors;
+#[cfg(feature = "excellon")]
+mod excellon;
 mod extended_codes;
+mod file;
 mod function_codes;
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
+#[cfg(feature = "geo")]
+mod geometry;
+mod header;
+#[cfg(feature = "job")]
+mod job;
+mod layers;
+#[cfg(feature = "uom")]
+mod length;
 mod macros;
+mod merge;
+mod mirror;
+mod naming;
+mod optimize;
+mod pads;
+mod panelize;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod pretty;
+mod profile;
+mod quadrants;
+mod scaffold;
+mod size;
+#[cfg(feature = "proptest")]
+mod strategies;
+mod stream;
+mod text;
+mod tokens;
 mod traits;
+mod transform;
 mod types;
+mod validate;
+mod variables;
+#[cfg(feature = "serde")]
+mod versioned;
+mod visit;
+mod winding;
+mod writer;
 
+#[cfg(feature = "zip")]
+pub use crate::archive::*;
+#[cfg(feature = "tokio")]
+pub use crate::asynchronous::*;
 pub use crate::attributes::*;
-pub use crate::codegen::*;
+pub use crate::bounds::*;
+pub use crate::charset::*;
+pub use crate::checks::*;
+#[cfg(feature = "md5")]
+pub use crate::checksum::*;
+pub use crate::compat::*;
+pub use crate::conformance::*;
+pub use crate::context::*;
+pub use crate::convert::*;
 pub use crate::coordinates::*;
+pub use crate::decimal::*;
+pub use crate::deprecated::*;
+pub use crate::drawing::*;
 pub use crate::errors::*;
+#[cfg(feature = "excellon")]
+pub use crate::excellon::*;
 pub use crate::extended_codes::*;
+pub use crate::file::*;
 pub use crate::function_codes::*;
+#[cfg(feature = "geo")]
+pub use crate::geometry::*;
+pub use crate::header::*;
+#[cfg(feature = "job")]
+pub use crate::job::*;
+pub use crate::layers::*;
 pub use crate::macros::*;
-pub use crate::traits::GerberCode;
+pub use crate::merge::*;
+pub use crate::mirror::*;
+pub use crate::naming::*;
+pub use crate::optimize::*;
+pub use crate::pads::*;
+pub use crate::panelize::*;
+#[cfg(feature = "rayon")]
+pub use crate::parallel::*;
+pub use crate::pretty::*;
+pub use crate::profile::*;
+pub use crate::quadrants::*;
+pub use crate::scaffold::*;
+pub use crate::size::*;
+#[cfg(feature = "proptest")]
+pub use crate::strategies::*;
+pub use crate::stream::*;
+pub use crate::text::*;
+pub use crate::traits::{CodeSequence, DynGerberCode, GerberCode, GerberCodeExt};
+pub use crate::transform::*;
 pub use crate::types::*;
+pub use crate::validate::*;
+#[cfg(feature = "serde")]
+pub use crate::versioned::*;
+pub use crate::visit::*;
+pub use crate::winding::*;
+pub use crate::writer::*;
 
 #[cfg(test)]
 mod test {
     use std::io::BufWriter;
 
+    use super::decimal::to_decimal;
     use super::traits::PartialGerberCode;
     use super::*;
 
     #[test]
     fn test_serialize() {
         //! The serialize method of the GerberCode trait should generate strings.
-        let comment = GCode::Comment("testcomment".to_string());
+        let comment = GCode::Comment("testcomment".into());
         assert_code!(comment, "G04 testcomment*\n");
     }
 
+    #[test]
+    fn test_serialize_multiline_comment() {
+        let comment = GCode::Comment("first line\r\nsecond line\n\nlast".into());
+        assert_code!(
+            comment,
+            "G04 first line*\nG04 second line*\nG04 *\nG04 last*\n"
+        );
+    }
+
     #[test]
     fn test_vec_serialize() {
         //! A `Vec<T: GerberCode>` should also implement `GerberCode`.
-        let mut v = Vec::new();
-        v.push(GCode::Comment("comment 1".to_string()));
-        v.push(GCode::Comment("another one".to_string()));
+        let v = vec![
+            GCode::Comment("comment 1".into()),
+            GCode::Comment("another one".into()),
+        ];
         assert_code!(v, "G04 comment 1*\nG04 another one*\n");
     }
 
     #[test]
     fn test_command_serialize() {
         //! A `Command` should implement `GerberCode`
-        let c = Command::FunctionCode(FunctionCode::GCode(GCode::Comment("comment".to_string())));
+        let c = Command::FunctionCode(FunctionCode::GCode(GCode::Comment("comment".into())));
         assert_code!(c, "G04 comment*\n");
     }
 
@@ -86,17 +207,19 @@ mod test {
 
     #[test]
     fn test_region_mode() {
-        let mut commands = Vec::new();
-        commands.push(GCode::RegionMode(true));
-        commands.push(GCode::RegionMode(false));
+        let commands = vec![
+            GCode::RegionMode(RegionMode::Begin),
+            GCode::RegionMode(RegionMode::End),
+        ];
         assert_code!(commands, "G36*\nG37*\n");
     }
 
     #[test]
     fn test_quadrant_mode() {
-        let mut commands = Vec::new();
-        commands.push(GCode::QuadrantMode(QuadrantMode::Single));
-        commands.push(GCode::QuadrantMode(QuadrantMode::Multi));
+        let commands = vec![
+            GCode::QuadrantMode(QuadrantMode::Single),
+            GCode::QuadrantMode(QuadrantMode::Multi),
+        ];
         assert_code!(commands, "G74*\nG75*\n");
     }
 
@@ -136,6 +259,284 @@ mod test {
         assert_code!(c, "X230000Y420000D03*\n");
     }
 
+    #[test]
+    fn test_serialize_with_context() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(1, 2, cf))).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::at_x(3, cf),
+                Some(CoordinateOffset::new(1, 0, cf)),
+            ))
+            .into(),
+        ];
+        let ctx = SerializationContext::new().with_format(CoordinateFormat::new(2, 6));
+        let mut buf = Vec::new();
+        commands.serialize_with_context(&mut buf, &ctx).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "%FSLAX26Y26*%\nX1000000Y2000000D02*\nX3000000I1000000J0D01*\n"
+        );
+        assert_eq!(
+            commands.to_code_string().unwrap(),
+            "%FSLAX24Y24*%\nX10000Y20000D02*\nX30000I10000J0D01*\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_zero_suppression() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(1, 0, cf))).into(),
+        ];
+        let trailing = SerializationContext::new().with_zero_suppression(ZeroSuppression::Trailing);
+        assert_eq!(
+            commands.to_code_string_with_context(&trailing).unwrap(),
+            "%FSTAX24Y24*%\nX01Y0D02*\n"
+        );
+        let disabled = SerializationContext::new().with_zero_suppression(ZeroSuppression::Disabled);
+        assert_eq!(
+            commands.to_code_string_with_context(&disabled).unwrap(),
+            "%FSLAX24Y24*%\nX010000Y000000D02*\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_codegen_settings() {
+        let commands: Vec<Command> = vec![
+            GCode::Comment("hello".into()).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            ExtendedCode::LoadPolarity(Polarity::Dark).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let codegen = CodegenSettings::new()
+            .with_line_ending(LineEnding::CrLf)
+            .with_extended_code_newline(false)
+            .with_comment_space(false);
+        let ctx = SerializationContext::new().with_codegen(codegen);
+        assert_eq!(
+            commands.to_code_string_with_context(&ctx).unwrap(),
+            "G04hello*\r\n%MOMM*%%LPD*%M02*\r\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_compact() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            GCode::Comment("hello".into()).into(),
+            ExtendedCode::CoordinateFormat(cf).into(),
+            DCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(1, 0, cf))).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::at_x(2, cf), None)).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let ctx = SerializationContext::new().with_codegen(CodegenSettings::compact());
+        assert_eq!(
+            commands.to_code_string_with_context(&ctx).unwrap(),
+            "G04hello*%FSLAX24Y24*%D10*X10000Y0D02*X20000D01*M02*\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_decimal_places() {
+        let commands: Vec<Command> = vec![
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Circle(Circle::new(0.1 + 0.2)),
+            ))
+            .into(),
+            ExtendedCode::StepAndRepeat(StepAndRepeat::Open {
+                repeat_x: 2,
+                repeat_y: 1,
+                distance_x: to_decimal(1.23456),
+                distance_y: to_decimal(0.0),
+            })
+            .into(),
+        ];
+        assert_eq!(
+            commands.to_code_string().unwrap(),
+            "%ADD10C,0.3*%\n%SRX2Y1I1.23456J0*%\n"
+        );
+        let ctx = SerializationContext::new().with_decimal_places(2);
+        assert_eq!(
+            commands.to_code_string_with_context(&ctx).unwrap(),
+            "%ADD10C,0.3*%\n%SRX2Y1I1.23J0*%\n"
+        );
+        assert_eq!(SerializationContext::default(), SerializationContext::new());
+    }
+
+    #[test]
+    fn test_serialize_max_line_length() {
+        let gensw = ExtendedCode::FileAttribute(FileAttribute::GenerationSoftware(
+            GenerationSoftware::new("Vendor", "application", Some("1.0")),
+        ));
+        let outline = ExtendedCode::ApertureMacro(
+            ApertureMacro::new("OUTLINE").add_content(OutlinePrimitive::from_points(
+                [(0.0, 0.0), (12.5, 0.0), (12.5, 1.0), (0.0, 0.0)]
+                    .iter()
+                    .map(|&(x, y)| (x.into(), y.into()))
+                    .collect(),
+            )),
+        );
+        let codegen = CodegenSettings::new().with_max_line_length(25);
+        let ctx = SerializationContext::new().with_codegen(codegen);
+        assert_eq!(
+            Command::from(outline)
+                .to_code_string_with_context(&ctx)
+                .unwrap(),
+            "%AMOUTLINE*\n4,1,3,\n0,0,\n12.5,0,\n12.5,1,\n0,0,\n0*%\n"
+        );
+        let macro_comment = ExtendedCode::ApertureMacro(
+            ApertureMacro::new("C")
+                .add_content(MacroContent::Comment("a comment, with commas".into()))
+                .add_content(CirclePrimitive::new(1.5.into())),
+        );
+        assert_eq!(
+            Command::from(macro_comment.clone())
+                .to_code_string_with_context(&ctx)
+                .unwrap(),
+            "%AMC*\n0 a comment, with commas*\n1,1,1.5,0,0*%\n"
+        );
+        // Macro comments are not wrapped
+        let narrow = SerializationContext::new()
+            .with_codegen(CodegenSettings::new().with_max_line_length(20));
+        assert!(Command::from(macro_comment)
+            .to_code_string_with_context(&narrow)
+            .is_err());
+
+        // Line breaks would become part of attribute values
+        let part = ExtendedCode::FileAttribute(FileAttribute::Part(Part::Other(
+            "Panel,with,a,very,long,description".into(),
+        )));
+        for attribute in [gensw, part] {
+            assert!(matches!(
+                Command::from(attribute).to_code_string_with_context(&ctx),
+                Err(GerberError::RangeError(_))
+            ));
+        }
+
+        let comment: Command = GCode::Comment("a long comment, with a comma, or two".into()).into();
+        assert_eq!(
+            comment.to_code_string_with_context(&ctx).unwrap(),
+            "G04 a long comment, with*\nG04 a comma, or two*\n"
+        );
+        let name: Command = ExtendedCode::DeleteAttribute("VeryLongAttributeName".into()).into();
+        assert!(name.to_code_string_with_context(&ctx).is_err());
+    }
+
+    #[test]
+    fn test_serialize_spec_target() {
+        let commands: Vec<Command> = vec![
+            ExtendedCode::FileAttribute(FileAttribute::Part(Part::Single)).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            ExtendedCode::DeleteAttribute("".into()).into(),
+            MCode::EndOfFile.into(),
+        ];
+        // X1 has no attributes, they are written as comments
+        let x1 = SerializationContext::new().with_spec_target(SpecTarget::X1);
+        let expected = "G04 #@! TF.Part,Single*\n%MOMM*%\nG04 #@! TD*\nM02*\n";
+        assert_eq!(commands.to_code_string_with_context(&x1).unwrap(), expected);
+        assert_eq!(
+            attributes_to_comments(&commands)
+                .unwrap()
+                .to_code_string_with_context(&x1)
+                .unwrap(),
+            expected
+        );
+        let x2_stream: Vec<CommandRef> = vec![
+            Command::from(ExtendedCodeBlock::new(vec![
+                ExtendedCode::FileAttribute(FileAttribute::Part(Part::Single)),
+                ExtendedCode::Unit(Unit::Millimeters),
+            ]))
+            .into(),
+            Command::from(ExtendedCode::ApertureAttribute(
+                ApertureAttribute::ApertureFunction(ApertureFunction::Conductor),
+            ))
+            .into(),
+            Command::from(ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Circle(Circle::new(0.5)),
+            )))
+            .into(),
+            CommandRef::DeleteAttribute(".AperFunction"),
+            Command::from(MCode::EndOfFile).into(),
+        ];
+        assert_eq!(
+            x2_stream.to_code_string_with_context(&x1).unwrap(),
+            "G04 #@! TF.Part,Single*\n%MOMM*%\nG04 #@! TA.AperFunction,Conductor*\n\
+             %ADD10C,0.5*%\nG04 #@! TD.AperFunction*\nM02*\n"
+        );
+        let x2 = SerializationContext::new().with_spec_target(SpecTarget::X2);
+        assert_eq!(
+            commands.to_code_string_with_context(&x2).unwrap(),
+            commands.to_code_string().unwrap()
+        );
+
+        // Component attributes were added in X3
+        let component: Vec<Command> = vec![
+            ExtendedCode::FileAttribute(FileAttribute::FileFunction(FileFunction::Component {
+                layer: 1,
+                pos: Position::Top,
+            }))
+            .into(),
+            ExtendedCode::ApertureAttribute(ApertureAttribute::ApertureFunction(
+                ApertureFunction::ComponentMain,
+            ))
+            .into(),
+        ];
+        assert_eq!(
+            component.to_code_string().unwrap(),
+            "%TF.FileFunction,Component,L1,Top*%\n%TA.AperFunction,ComponentMain*%\n"
+        );
+        for command in &component {
+            assert!(matches!(
+                command.to_code_string_with_context(&x2),
+                Err(GerberError::UnsupportedError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_deprecated_mcodes() {
+        let commands: Vec<Command> = vec![MCode::OptionalStop.into(), MCode::ProgramStop.into()];
+        let x1 = SerializationContext::new().with_spec_target(SpecTarget::X1);
+        assert_eq!(
+            commands.to_code_string_with_context(&x1).unwrap(),
+            "M01*\nM00*\n"
+        );
+        // Rejected for every other target, including the default X3
+        for target in [SpecTarget::X2, SpecTarget::X3] {
+            let ctx = SerializationContext::new().with_spec_target(target);
+            for command in &commands {
+                assert!(matches!(
+                    command.to_code_string_with_context(&ctx),
+                    Err(GerberError::UnsupportedError(_))
+                ));
+            }
+        }
+        assert!(matches!(
+            commands.to_code_string(),
+            Err(GerberError::UnsupportedError(_))
+        ));
+    }
+
+    #[test]
+    fn test_operation_from_f64() {
+        let cf = CoordinateFormat::new(2, 4);
+        let c1 = Operation::move_xy(1.5, -0.25, cf).unwrap();
+        assert_code!(c1, "X15000Y-2500D02*\n");
+        let c2 = Operation::interpolate_arc_xy(1.0, 2.0, 0.5, 0.0, cf).unwrap();
+        assert_code!(c2, "X10000Y20000I5000J0D01*\n");
+        let c3 = Operation::flash_xy(0.0, 3.0, cf).unwrap();
+        assert_eq!(c3, Operation::Flash(Coordinates::new(0, 3, cf)));
+        assert!(Operation::interpolate_xy(f64::NAN, 0.0, cf).is_err());
+        let c4 = Operation::move_xy(0.1 + 0.2, 2.9999999999, cf).unwrap();
+        assert_eq!(c4, Operation::move_xy(0.3, 3.0, cf).unwrap());
+    }
+
     #[test]
     fn test_select_aperture() {
         let c1 = DCode::SelectAperture(10);
@@ -163,14 +564,14 @@ mod test {
         let ad1 = ApertureDefinition {
             code: 10,
             aperture: Aperture::Circle(Circle {
-                diameter: 4.0,
-                hole_diameter: Some(2.0),
+                diameter: to_decimal(4.0),
+                hole_diameter: Some(to_decimal(2.0)),
             }),
         };
         let ad2 = ApertureDefinition {
             code: 11,
             aperture: Aperture::Circle(Circle {
-                diameter: 4.5,
+                diameter: to_decimal(4.5),
                 hole_diameter: None,
             }),
         };
@@ -183,24 +584,24 @@ mod test {
         let ad1 = ApertureDefinition {
             code: 12,
             aperture: Aperture::Rectangle(Rectangular {
-                x: 1.5,
-                y: 2.25,
-                hole_diameter: Some(3.8),
+                x: to_decimal(1.5),
+                y: to_decimal(2.25),
+                hole_diameter: Some(to_decimal(3.8)),
             }),
         };
         let ad2 = ApertureDefinition {
             code: 13,
             aperture: Aperture::Rectangle(Rectangular {
-                x: 1.0,
-                y: 1.0,
+                x: to_decimal(1.0),
+                y: to_decimal(1.0),
                 hole_diameter: None,
             }),
         };
         let ad3 = ApertureDefinition {
             code: 14,
-            aperture: Aperture::Obround(Rectangular {
-                x: 2.0,
-                y: 4.5,
+            aperture: Aperture::Obround(Obround {
+                x: to_decimal(2.0),
+                y: to_decimal(4.5),
                 hole_diameter: None,
             }),
         };
@@ -214,7 +615,7 @@ mod test {
         let ad1 = ApertureDefinition {
             code: 15,
             aperture: Aperture::Polygon(Polygon {
-                diameter: 4.5,
+                diameter: to_decimal(4.5),
                 vertices: 3,
                 rotation: None,
                 hole_diameter: None,
@@ -223,19 +624,19 @@ mod test {
         let ad2 = ApertureDefinition {
             code: 16,
             aperture: Aperture::Polygon(Polygon {
-                diameter: 5.0,
+                diameter: to_decimal(5.0),
                 vertices: 4,
-                rotation: Some(30.6),
+                rotation: Some(to_decimal(30.6)),
                 hole_diameter: None,
             }),
         };
         let ad3 = ApertureDefinition {
             code: 17,
             aperture: Aperture::Polygon(Polygon {
-                diameter: 5.5,
+                diameter: to_decimal(5.5),
                 vertices: 5,
                 rotation: None,
-                hole_diameter: Some(1.8),
+                hole_diameter: Some(to_decimal(1.8)),
             }),
         };
         assert_partial_code!(ad1, "15P,4.5X3");
@@ -251,23 +652,139 @@ mod test {
         assert_code!(c, "%LPC*%\n");
     }
 
+    #[test]
+    fn test_defaults() {
+        assert_eq!(Polarity::default(), Polarity::Dark);
+        assert_eq!(CoordinateFormat::default(), CoordinateFormat::new(4, 6));
+        let moire = MoirePrimitive {
+            max_rings: 3,
+            ..Default::default()
+        };
+        assert_eq!(moire, MoirePrimitive::new().with_rings_max(3));
+        assert_eq!(OutlinePrimitive::default(), OutlinePrimitive::new());
+    }
+
     #[test]
     fn test_step_and_repeat_serialize() {
         let o = ExtendedCode::StepAndRepeat(StepAndRepeat::Open {
             repeat_x: 2,
             repeat_y: 3,
-            distance_x: 2.0,
-            distance_y: 3.0,
+            distance_x: to_decimal(2.0),
+            distance_y: to_decimal(3.0),
         });
         let c = ExtendedCode::StepAndRepeat(StepAndRepeat::Close);
         assert_code!(o, "%SRX2Y3I2J3*%\n");
         assert_code!(c, "%SR*%\n");
     }
 
+    #[test]
+    fn test_aperture_attribute_serialize() {
+        let function = |f| ExtendedCode::ApertureAttribute(ApertureAttribute::ApertureFunction(f));
+        assert_code!(
+            function(ApertureFunction::ComponentDrill {
+                press_fit: Some(true)
+            }),
+            "%TA.AperFunction,ComponentDrill,PressFit*%\n"
+        );
+        assert_code!(
+            function(ApertureFunction::MechanicalDrill {
+                function: Some(DrillFunction::Tooling)
+            }),
+            "%TA.AperFunction,MechanicalDrill,Tooling*%\n"
+        );
+        assert_code!(
+            function(ApertureFunction::SmdPad(SmdPadType::CopperDefined)),
+            "%TA.AperFunction,SMDPad,CuDef*%\n"
+        );
+        assert_code!(
+            function(ApertureFunction::FiducialPad(FiducialScope::Local)),
+            "%TA.AperFunction,FiducialPad,Local*%\n"
+        );
+        assert_code!(
+            function(ApertureFunction::Other("a,b".into())),
+            "%TA.AperFunction,Other,a\\u002Cb*%\n"
+        );
+        let tolerance = ExtendedCode::ApertureAttribute(ApertureAttribute::DrillTolerance {
+            plus: to_decimal(0.01),
+            minus: to_decimal(0.005),
+        });
+        assert_code!(tolerance, "%TA.DrillTolerance,0.01,0.005*%\n");
+    }
+
+    #[test]
+    #[cfg(feature = "ordered-float")]
+    fn test_commands_hash() {
+        use std::collections::HashSet;
+
+        let circle = |diameter| -> Command {
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Circle(Circle::new(diameter)),
+            ))
+            .into()
+        };
+        let commands = [circle(0.5), circle(1.0), circle(0.5)];
+        let unique: HashSet<&Command> = commands.iter().collect();
+        assert_eq!(unique.len(), 2);
+        assert!(unique.contains(&circle(1.0)));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_roundtrip() {
+        let cf = CoordinateFormat::new(2, 5);
+        let commands: Vec<Command> = vec![
+            GCode::Comment("serde".into()).into(),
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ExtendedCode::FileAttribute(FileAttribute::GenerationSoftware(
+                GenerationSoftware::new("Vendor", "App", Some("1.0")),
+            ))
+            .into(),
+            ExtendedCode::ApertureMacro(
+                ApertureMacro::new("DOT").add_content(CirclePrimitive::new(1.0.into())),
+            )
+            .into(),
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Other("DOT".into()),
+            ))
+            .into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, 2, cf),
+                Some(CoordinateOffset::new(0, 1, cf)),
+            ))
+            .into(),
+            DeprecatedCode::UnitInches.into(),
+        ];
+        let json = serde_json::to_string(&commands).unwrap();
+        let deserialized: Vec<Command> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, commands);
+    }
+
+    #[test]
+    fn test_extended_code_block_serialize() {
+        let block = ExtendedCodeBlock::new(vec![
+            ExtendedCode::CoordinateFormat(CoordinateFormat::new(2, 5)),
+            ExtendedCode::Unit(Unit::Millimeters),
+        ])
+        .add_code(FileAttribute::Part(Part::Single));
+        assert_code!(block, "%FSLAX25Y25*MOMM*TF.Part,Single*%\n");
+
+        let x1 = SerializationContext::new().with_spec_target(SpecTarget::X1);
+        assert_eq!(
+            block.to_code_string_with_context(&x1).unwrap(),
+            "%FSLAX25Y25*MOMM*%\nG04 #@! TF.Part,Single*\n"
+        );
+    }
+
     #[test]
     fn test_delete_attribute_serialize() {
         let d = ExtendedCode::DeleteAttribute("foo".into());
         assert_code!(d, "%TDfoo*%\n");
+        let all = ExtendedCode::DeleteAttribute("".into());
+        assert_code!(all, "%TD*%\n");
+        let invalid = ExtendedCode::DeleteAttribute("foo bar".into());
+        assert!(invalid.to_code_string().is_err());
     }
 
     #[test]
@@ -275,6 +792,11 @@ mod test {
         let part = ExtendedCode::FileAttribute(FileAttribute::Part(Part::Other("foo".into())));
         assert_code!(part, "%TF.Part,Other,foo*%\n");
 
+        let same = ExtendedCode::FileAttribute(FileAttribute::SameCoordinates(None));
+        assert_code!(same, "%TF.SameCoordinates*%\n");
+        let same = ExtendedCode::FileAttribute(FileAttribute::SameCoordinates(Some("A,B".into())));
+        assert_code!(same, "%TF.SameCoordinates,A\\u002CB*%\n");
+
         let gensw1 = ExtendedCode::FileAttribute(FileAttribute::GenerationSoftware(
             GenerationSoftware::new("Vend0r", "superpcb", None),
         ));
@@ -284,5 +806,178 @@ mod test {
             GenerationSoftware::new("Vend0r", "superpcb", Some("1.2.3")),
         ));
         assert_code!(gensw2, "%TF.GenerationSoftware,Vend0r,superpcb,1.2.3*%\n");
+
+        let gensw3 = ExtendedCode::FileAttribute(FileAttribute::GenerationSoftware(
+            GenerationSoftware::new("Vendor, Inc.", "pcb*", None),
+        ));
+        assert_code!(
+            gensw3,
+            "%TF.GenerationSoftware,Vendor\\u002C Inc.,pcb\\u002A*%\n"
+        );
+    }
+
+    #[test]
+    fn test_attribute_field_escaping() {
+        let other = ExtendedCode::FileAttribute(FileAttribute::FileFunction(FileFunction::Other(
+            "50% scale, *draft*".into(),
+        )));
+        assert_code!(
+            other,
+            "%TF.FileFunction,Other,50\\u0025 scale\\u002C \\u002Adraft\\u002A*%\n"
+        );
+
+        let user_defined = ExtendedCode::FileAttribute(FileAttribute::UserDefined {
+            name: "Supplier".into(),
+            value: vec!["ACME, Inc.".into(), "Batch 7".into()],
+        });
+        assert_code!(user_defined, "%TFSupplier,ACME\\u002C Inc.,Batch 7*%\n");
+
+        let reserved = ExtendedCode::FileAttribute(FileAttribute::UserDefined {
+            name: ".Supplier".into(),
+            value: vec![],
+        });
+        assert!(matches!(
+            reserved.to_code_string(),
+            Err(GerberError::NameError(_))
+        ));
+    }
+
+    #[test]
+    fn test_file_function_serialize() {
+        let cases = vec![
+            (
+                FileFunction::Goldmask {
+                    pos: Position::Top,
+                    index: Some(2),
+                },
+                "Goldmask,Top,2",
+            ),
+            (
+                FileFunction::Peelablesoldermask {
+                    pos: Position::Bottom,
+                    index: None,
+                },
+                "Peelablemask,Bot",
+            ),
+            (FileFunction::Heatsink(Position::Top), "Heatsinkmask,Top"),
+            (FileFunction::KeepOut(Position::Bottom), "Keep-out,Bot"),
+            (FileFunction::Scoring(Position::Top), "Vcut,Top"),
+            (FileFunction::Viafill, "Viafill"),
+            (
+                FileFunction::Plated {
+                    from_layer: 1,
+                    to_layer: 4,
+                    drill: Drill::ThroughHole,
+                    label: Some(DrillRouteType::Route),
+                },
+                "Plated,1,4,PTH,Rout",
+            ),
+            (
+                FileFunction::NonPlated {
+                    from_layer: 1,
+                    to_layer: 4,
+                    drill: Drill::ThroughHole,
+                    label: None,
+                },
+                "NonPlated,1,4,NPTH",
+            ),
+            (
+                FileFunction::NonPlated {
+                    from_layer: 1,
+                    to_layer: 2,
+                    drill: Drill::Blind,
+                    label: Some(DrillRouteType::Drill),
+                },
+                "NonPlated,1,2,Blind,Drill",
+            ),
+            (FileFunction::Drillmap, "Drillmap"),
+            (
+                FileFunction::AssemblyDrawing(Position::Bottom),
+                "AssemblyDrawing,Bot",
+            ),
+        ];
+        for (function, expected) in cases {
+            let code = ExtendedCode::FileAttribute(FileAttribute::FileFunction(function));
+            assert_code!(code, &format!("%TF.FileFunction,{}*%\n", expected));
+        }
+    }
+
+    #[test]
+    fn test_creation_date_and_project_id_serialize() {
+        let date = ExtendedCode::FileAttribute(FileAttribute::CreationDateText(
+            "2015-02-23T15:59:51+01:00".into(),
+        ));
+        assert_code!(date, "%TF.CreationDate,2015-02-23T15:59:51+01:00*%\n");
+
+        let project = ExtendedCode::FileAttribute(FileAttribute::ProjectIdText {
+            id: "Board".into(),
+            guid: "5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c".into(),
+            revision: "2".into(),
+        });
+        assert_code!(
+            project,
+            "%TF.ProjectId,Board,5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c,2*%\n"
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "chrono", feature = "uuid"))]
+    fn test_creation_date_and_project_id_typed_serialize() {
+        use chrono::TimeZone;
+
+        let date = ExtendedCode::FileAttribute(FileAttribute::CreationDate(
+            chrono::Utc
+                .with_ymd_and_hms(2015, 2, 23, 14, 59, 51)
+                .unwrap(),
+        ));
+        assert_code!(date, "%TF.CreationDate,2015-02-23T14:59:51+00:00*%\n");
+
+        let local = ExtendedCode::FileAttribute(FileAttribute::CreationDateWithOffset(
+            chrono::FixedOffset::east_opt(3600)
+                .unwrap()
+                .with_ymd_and_hms(2015, 2, 23, 15, 59, 51)
+                .unwrap(),
+        ));
+        assert_code!(local, "%TF.CreationDate,2015-02-23T15:59:51+01:00*%\n");
+
+        let guid = uuid::Uuid::parse_str("5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c").unwrap();
+        let attribute = FileAttribute::project_id("Board", guid, String::from("2")).unwrap();
+        assert_eq!(
+            attribute,
+            FileAttribute::ProjectId {
+                id: "Board".into(),
+                guid,
+                revision: "2".into(),
+            }
+        );
+        let project = ExtendedCode::FileAttribute(attribute);
+        assert_code!(
+            project,
+            "%TF.ProjectId,Board,5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c,2*%\n"
+        );
+    }
+
+    #[test]
+    fn test_project_id_from_str() {
+        let project = FileAttribute::project_id_from_str(
+            "Board",
+            "5A2C1DD2-7E5F-4F2B-9D9E-7A3B4F3E9A4C",
+            String::from("2"),
+        )
+        .unwrap();
+        // The same variant with and without the `uuid` feature
+        assert!(matches!(project, FileAttribute::ProjectIdText { .. }));
+        assert_code!(
+            ExtendedCode::FileAttribute(project),
+            "%TF.ProjectId,Board,5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c,2*%\n"
+        );
+        assert!(matches!(
+            FileAttribute::project_id_from_str("Board", "5a2c1dd27e5f4f2b9d9e7a3b4f3e9a4c", "2"),
+            Err(GerberError::ConversionError(_))
+        ));
+        assert!(matches!(
+            FileAttribute::project_id_from_str("", "5a2c1dd2-7e5f-4f2b-9d9e-7a3b4f3e9a4c", "2"),
+            Err(GerberError::MissingDataError(_))
+        ));
     }
 }
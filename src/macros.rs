@@ -1,19 +1,31 @@
 //! Aperture Macros.
 
+use std::borrow::Cow;
 use std::convert::From;
+use std::fmt;
 use std::io::Write;
+use std::iter::FromIterator;
+use std::ops::{Deref, DerefMut};
 
+use crate::charset::validate_name;
+use crate::context::SerializationContext;
+use crate::decimal::{from_decimal, to_decimal, Decimal};
 use crate::errors::{GerberError, GerberResult};
 use crate::traits::PartialGerberCode;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ApertureMacro {
-    pub name: String,
+    #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::fuzzing::arbitrary_cow))]
+    pub name: Cow<'static, str>,
     pub content: Vec<MacroContent>,
 }
 
 impl ApertureMacro {
-    pub fn new<S: Into<String>>(name: S) -> Self {
+    pub fn new<S: Into<Cow<'static, str>>>(name: S) -> Self {
         ApertureMacro {
             name: name.into(),
             content: Vec::new(),
@@ -37,31 +49,41 @@ impl ApertureMacro {
 }
 
 impl<W: Write> PartialGerberCode<W> for ApertureMacro {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         if self.content.is_empty() {
             return Err(GerberError::MissingDataError(
                 "There must be at least 1 content element in an aperture macro".into(),
             ));
         }
-        writeln!(writer, "AM{}*", self.name)?;
+        validate_name(&self.name)?;
+        write!(writer, "AM{}*", self.name)?;
+        ctx.end_line(writer)?;
         let mut first = true;
         for content in &self.content {
             if first {
                 first = false;
             } else {
-                writeln!(writer)?;
+                ctx.end_line(writer)?;
             }
-            content.serialize_partial(writer)?;
+            content.serialize_partial_with_context(writer, ctx)?;
         }
         Ok(())
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
 /// A macro decimal can either be an f64 or a variable placeholder.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum MacroDecimal {
     /// A decimal value.
-    Value(f64),
+    Value(Decimal),
     /// A variable placeholder.
     Variable(u32),
 }
@@ -69,7 +91,7 @@ pub enum MacroDecimal {
 impl MacroDecimal {
     fn is_negative(&self) -> bool {
         match *self {
-            MacroDecimal::Value(v) => v < 0.0,
+            MacroDecimal::Value(v) => from_decimal(v) < 0.0,
             MacroDecimal::Variable(_) => false,
         }
     }
@@ -77,20 +99,24 @@ impl MacroDecimal {
 
 impl From<f32> for MacroDecimal {
     fn from(val: f32) -> Self {
-        MacroDecimal::Value(val as f64)
+        MacroDecimal::Value(to_decimal(val as f64))
     }
 }
 
 impl From<f64> for MacroDecimal {
     fn from(val: f64) -> Self {
-        MacroDecimal::Value(val)
+        MacroDecimal::Value(to_decimal(val))
     }
 }
 
 impl<W: Write> PartialGerberCode<W> for MacroDecimal {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
-            MacroDecimal::Value(ref v) => write!(writer, "{}", v)?,
+            MacroDecimal::Value(ref v) => v.serialize_partial_with_context(writer, ctx)?,
             MacroDecimal::Variable(ref v) => write!(writer, "${}", v)?,
         };
         Ok(())
@@ -98,6 +124,11 @@ impl<W: Write> PartialGerberCode<W> for MacroDecimal {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum MacroContent {
     // Primitives
     Circle(CirclePrimitive),
@@ -116,17 +147,23 @@ pub enum MacroContent {
 }
 
 impl<W: Write> PartialGerberCode<W> for MacroContent {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         match *self {
-            MacroContent::Circle(ref c) => c.serialize_partial(writer)?,
-            MacroContent::VectorLine(ref vl) => vl.serialize_partial(writer)?,
-            MacroContent::CenterLine(ref cl) => cl.serialize_partial(writer)?,
-            MacroContent::Outline(ref o) => o.serialize_partial(writer)?,
-            MacroContent::Polygon(ref p) => p.serialize_partial(writer)?,
-            MacroContent::Moire(ref m) => m.serialize_partial(writer)?,
-            MacroContent::Thermal(ref t) => t.serialize_partial(writer)?,
+            MacroContent::Circle(ref c) => c.serialize_partial_with_context(writer, ctx)?,
+            MacroContent::VectorLine(ref vl) => vl.serialize_partial_with_context(writer, ctx)?,
+            MacroContent::CenterLine(ref cl) => cl.serialize_partial_with_context(writer, ctx)?,
+            MacroContent::Outline(ref o) => o.serialize_partial_with_context(writer, ctx)?,
+            MacroContent::Polygon(ref p) => p.serialize_partial_with_context(writer, ctx)?,
+            MacroContent::Moire(ref m) => m.serialize_partial_with_context(writer, ctx)?,
+            MacroContent::Thermal(ref t) => t.serialize_partial_with_context(writer, ctx)?,
             MacroContent::Comment(ref s) => write!(writer, "0 {}*", &s)?,
-            MacroContent::VariableDefinition(ref v) => v.serialize_partial(writer)?,
+            MacroContent::VariableDefinition(ref v) => {
+                v.serialize_partial_with_context(writer, ctx)?
+            }
         };
         Ok(())
     }
@@ -162,6 +199,10 @@ impl<T: Into<String>> From<T> for MacroContent {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct CirclePrimitive {
     /// Exposure off/on
     pub exposure: bool,
@@ -188,7 +229,7 @@ impl CirclePrimitive {
         CirclePrimitive {
             exposure: true,
             diameter,
-            center: (MacroDecimal::Value(0.0), MacroDecimal::Value(0.0)),
+            center: (MacroDecimal::from(0.0), MacroDecimal::from(0.0)),
             angle: None,
         }
     }
@@ -210,18 +251,22 @@ impl CirclePrimitive {
 }
 
 impl<W: Write> PartialGerberCode<W> for CirclePrimitive {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         write!(writer, "1,")?;
-        self.exposure.serialize_partial(writer)?;
+        self.exposure.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.diameter.serialize_partial(writer)?;
+        self.diameter.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.center.0.serialize_partial(writer)?;
+        self.center.0.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.center.1.serialize_partial(writer)?;
+        self.center.1.serialize_partial_with_context(writer, ctx)?;
         if let Some(ref a) = self.angle {
             write!(writer, ",")?;
-            a.serialize_partial(writer)?;
+            a.serialize_partial_with_context(writer, ctx)?;
         }
         write!(writer, "*")?;
         Ok(())
@@ -229,6 +274,10 @@ impl<W: Write> PartialGerberCode<W> for CirclePrimitive {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct VectorLinePrimitive {
     /// Exposure off/on
     pub exposure: bool,
@@ -254,10 +303,10 @@ impl VectorLinePrimitive {
     pub fn new(start: (MacroDecimal, MacroDecimal), end: (MacroDecimal, MacroDecimal)) -> Self {
         VectorLinePrimitive {
             exposure: true,
-            width: MacroDecimal::Value(0.0),
+            width: MacroDecimal::from(0.0),
             start,
             end,
-            angle: MacroDecimal::Value(0.0),
+            angle: MacroDecimal::from(0.0),
         }
     }
 
@@ -278,27 +327,35 @@ impl VectorLinePrimitive {
 }
 
 impl<W: Write> PartialGerberCode<W> for VectorLinePrimitive {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         write!(writer, "20,")?;
-        self.exposure.serialize_partial(writer)?;
+        self.exposure.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.width.serialize_partial(writer)?;
+        self.width.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.start.0.serialize_partial(writer)?;
+        self.start.0.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.start.1.serialize_partial(writer)?;
+        self.start.1.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.end.0.serialize_partial(writer)?;
+        self.end.0.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.end.1.serialize_partial(writer)?;
+        self.end.1.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.angle.serialize_partial(writer)?;
+        self.angle.serialize_partial_with_context(writer, ctx)?;
         write!(writer, "*")?;
         Ok(())
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct CenterLinePrimitive {
     /// Exposure off/on (0/1)
     pub exposure: bool,
@@ -322,8 +379,8 @@ impl CenterLinePrimitive {
         CenterLinePrimitive {
             exposure: true,
             dimensions,
-            center: (MacroDecimal::Value(0.0), MacroDecimal::Value(0.0)),
-            angle: MacroDecimal::Value(0.0),
+            center: (MacroDecimal::from(0.0), MacroDecimal::from(0.0)),
+            angle: MacroDecimal::from(0.0),
         }
     }
 
@@ -344,25 +401,167 @@ impl CenterLinePrimitive {
 }
 
 impl<W: Write> PartialGerberCode<W> for CenterLinePrimitive {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         write!(writer, "21,")?;
-        self.exposure.serialize_partial(writer)?;
+        self.exposure.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.dimensions.0.serialize_partial(writer)?;
+        self.dimensions
+            .0
+            .serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.dimensions.1.serialize_partial(writer)?;
+        self.dimensions
+            .1
+            .serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.center.0.serialize_partial(writer)?;
+        self.center.0.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.center.1.serialize_partial(writer)?;
+        self.center.1.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.angle.serialize_partial(writer)?;
+        self.angle.serialize_partial_with_context(writer, ctx)?;
         write!(writer, "*")?;
         Ok(())
     }
 }
 
+#[cfg(feature = "smallvec")]
+type Points = smallvec::SmallVec<[(MacroDecimal, MacroDecimal); 8]>;
+#[cfg(not(feature = "smallvec"))]
+type Points = Vec<(MacroDecimal, MacroDecimal)>;
+
+/// The points of an outline primitive.
+///
+/// The points are accessed as a slice. With the `smallvec` feature, outlines
+/// with up to 8 points are stored without a heap allocation, the API is the
+/// same with and without the feature.
+#[derive(Clone, Default, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct OutlinePoints(Points);
+
+impl OutlinePoints {
+    pub fn new() -> Self {
+        OutlinePoints(Points::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        OutlinePoints(Points::with_capacity(capacity))
+    }
+
+    pub fn push(&mut self, point: (MacroDecimal, MacroDecimal)) {
+        self.0.push(point);
+    }
+
+    pub fn pop(&mut self) -> Option<(MacroDecimal, MacroDecimal)> {
+        self.0.pop()
+    }
+
+    pub fn insert(&mut self, index: usize, point: (MacroDecimal, MacroDecimal)) {
+        self.0.insert(index, point);
+    }
+
+    pub fn remove(&mut self, index: usize) -> (MacroDecimal, MacroDecimal) {
+        self.0.remove(index)
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&(MacroDecimal, MacroDecimal)) -> bool,
+    {
+        self.0.retain(|point| f(point));
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.0.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl fmt::Debug for OutlinePoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.iter()).finish()
+    }
+}
+
+impl Deref for OutlinePoints {
+    type Target = [(MacroDecimal, MacroDecimal)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for OutlinePoints {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<Vec<(MacroDecimal, MacroDecimal)>> for OutlinePoints {
+    fn from(points: Vec<(MacroDecimal, MacroDecimal)>) -> Self {
+        points.into_iter().collect()
+    }
+}
+
+impl From<OutlinePoints> for Vec<(MacroDecimal, MacroDecimal)> {
+    fn from(points: OutlinePoints) -> Self {
+        points.0.into_iter().collect()
+    }
+}
+
+impl FromIterator<(MacroDecimal, MacroDecimal)> for OutlinePoints {
+    fn from_iter<I: IntoIterator<Item = (MacroDecimal, MacroDecimal)>>(iter: I) -> Self {
+        OutlinePoints(iter.into_iter().collect())
+    }
+}
+
+impl Extend<(MacroDecimal, MacroDecimal)> for OutlinePoints {
+    fn extend<I: IntoIterator<Item = (MacroDecimal, MacroDecimal)>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl IntoIterator for OutlinePoints {
+    type Item = (MacroDecimal, MacroDecimal);
+    type IntoIter = <Points as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a OutlinePoints {
+    type Item = &'a (MacroDecimal, MacroDecimal);
+    type IntoIter = std::slice::Iter<'a, (MacroDecimal, MacroDecimal)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut OutlinePoints {
+    type Item = &'a mut (MacroDecimal, MacroDecimal);
+    type IntoIter = std::slice::IterMut<'a, (MacroDecimal, MacroDecimal)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter_mut()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct OutlinePrimitive {
     /// Exposure off/on (0/1)
     pub exposure: bool,
@@ -370,7 +569,7 @@ pub struct OutlinePrimitive {
     /// Vector of coordinate pairs.
     ///
     /// The last coordinate pair must be equal to the first coordinate pair!
-    pub points: Vec<(MacroDecimal, MacroDecimal)>,
+    pub points: OutlinePoints,
 
     /// Rotation angle of the outline primitive
     ///
@@ -380,18 +579,24 @@ pub struct OutlinePrimitive {
     pub angle: MacroDecimal,
 }
 
+impl Default for OutlinePrimitive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl OutlinePrimitive {
     pub fn new() -> Self {
         OutlinePrimitive {
             exposure: true,
-            points: Vec::new(),
-            angle: MacroDecimal::Value(0.0),
+            points: OutlinePoints::new(),
+            angle: MacroDecimal::from(0.0),
         }
     }
 
     pub fn from_points(points: Vec<(MacroDecimal, MacroDecimal)>) -> Self {
         let mut outline_prim = Self::new();
-        outline_prim.points = points;
+        outline_prim.points = points.into_iter().collect();
         outline_prim
     }
 
@@ -407,7 +612,11 @@ impl OutlinePrimitive {
 }
 
 impl<W: Write> PartialGerberCode<W> for OutlinePrimitive {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         // Points invariants
         if self.points.len() < 2 {
             return Err(GerberError::MissingDataError(
@@ -426,24 +635,30 @@ impl<W: Write> PartialGerberCode<W> for OutlinePrimitive {
         }
 
         write!(writer, "4,")?;
-        self.exposure.serialize_partial(writer)?;
-        writeln!(writer, ",{},", self.points.len() - 1)?;
+        self.exposure.serialize_partial_with_context(writer, ctx)?;
+        write!(writer, ",{},", self.points.len() - 1)?;
+        ctx.end_line(writer)?;
 
-        for &(ref x, ref y) in &self.points {
-            x.serialize_partial(writer)?;
+        for (x, y) in &self.points {
+            x.serialize_partial_with_context(writer, ctx)?;
             write!(writer, ",")?;
-            y.serialize_partial(writer)?;
-            writeln!(writer, ",")?;
+            y.serialize_partial_with_context(writer, ctx)?;
+            write!(writer, ",")?;
+            ctx.end_line(writer)?;
         }
-        self.angle.serialize_partial(writer)?;
+        self.angle.serialize_partial_with_context(writer, ctx)?;
         write!(writer, "*")?;
         Ok(())
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
 /// A polygon primitive is a regular polygon defined by the number of vertices,
 /// the center point and the diameter of the circumscribed circle.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct PolygonPrimitive {
     /// Exposure off/on (0/1)
     pub exposure: bool,
@@ -474,9 +689,9 @@ impl PolygonPrimitive {
         PolygonPrimitive {
             exposure: true,
             vertices,
-            center: (MacroDecimal::Value(0.0), MacroDecimal::Value(0.0)),
-            diameter: MacroDecimal::Value(0.0),
-            angle: MacroDecimal::Value(0.0),
+            center: (MacroDecimal::from(0.0), MacroDecimal::from(0.0)),
+            diameter: MacroDecimal::from(0.0),
+            angle: MacroDecimal::from(0.0),
         }
     }
 
@@ -502,7 +717,11 @@ impl PolygonPrimitive {
 }
 
 impl<W: Write> PartialGerberCode<W> for PolygonPrimitive {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         // Vertice count invariants
         if self.vertices < 3 {
             return Err(GerberError::MissingDataError(
@@ -520,15 +739,15 @@ impl<W: Write> PartialGerberCode<W> for PolygonPrimitive {
             ));
         }
         write!(writer, "5,")?;
-        self.exposure.serialize_partial(writer)?;
+        self.exposure.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",{},", self.vertices)?;
-        self.center.0.serialize_partial(writer)?;
+        self.center.0.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.center.1.serialize_partial(writer)?;
+        self.center.1.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.diameter.serialize_partial(writer)?;
+        self.diameter.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.angle.serialize_partial(writer)?;
+        self.angle.serialize_partial_with_context(writer, ctx)?;
         write!(writer, "*")?;
         Ok(())
     }
@@ -537,6 +756,10 @@ impl<W: Write> PartialGerberCode<W> for PolygonPrimitive {
 /// The moiré primitive is a cross hair centered on concentric rings (annuli).
 /// Exposure is always on.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct MoirePrimitive {
     /// X and Y coordinates of center point, decimals
     pub center: (MacroDecimal, MacroDecimal),
@@ -570,17 +793,23 @@ pub struct MoirePrimitive {
     pub angle: MacroDecimal,
 }
 
+impl Default for MoirePrimitive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MoirePrimitive {
     pub fn new() -> Self {
         MoirePrimitive {
-            center: (MacroDecimal::Value(0.0), MacroDecimal::Value(0.0)),
-            diameter: MacroDecimal::Value(0.0),
-            ring_thickness: MacroDecimal::Value(0.0),
-            gap: MacroDecimal::Value(0.0),
+            center: (MacroDecimal::from(0.0), MacroDecimal::from(0.0)),
+            diameter: MacroDecimal::from(0.0),
+            ring_thickness: MacroDecimal::from(0.0),
+            gap: MacroDecimal::from(0.0),
             max_rings: 1,
-            cross_hair_thickness: MacroDecimal::Value(0.0),
-            cross_hair_length: MacroDecimal::Value(0.0),
-            angle: MacroDecimal::Value(0.0),
+            cross_hair_thickness: MacroDecimal::from(0.0),
+            cross_hair_length: MacroDecimal::from(0.0),
+            angle: MacroDecimal::from(0.0),
         }
     }
 
@@ -626,7 +855,11 @@ impl MoirePrimitive {
 }
 
 impl<W: Write> PartialGerberCode<W> for MoirePrimitive {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         // Decimal invariants
         if self.diameter.is_negative() {
             return Err(GerberError::RangeError(
@@ -654,21 +887,24 @@ impl<W: Write> PartialGerberCode<W> for MoirePrimitive {
             ));
         }
         write!(writer, "6,")?;
-        self.center.0.serialize_partial(writer)?;
+        self.center.0.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.center.1.serialize_partial(writer)?;
+        self.center.1.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.diameter.serialize_partial(writer)?;
+        self.diameter.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.ring_thickness.serialize_partial(writer)?;
+        self.ring_thickness
+            .serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.gap.serialize_partial(writer)?;
+        self.gap.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",{},", self.max_rings)?;
-        self.cross_hair_thickness.serialize_partial(writer)?;
+        self.cross_hair_thickness
+            .serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.cross_hair_length.serialize_partial(writer)?;
+        self.cross_hair_length
+            .serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.angle.serialize_partial(writer)?;
+        self.angle.serialize_partial_with_context(writer, ctx)?;
         write!(writer, "*")?;
         Ok(())
     }
@@ -677,6 +913,10 @@ impl<W: Write> PartialGerberCode<W> for MoirePrimitive {
 /// The thermal primitive is a ring (annulus) interrupted by four gaps.
 /// Exposure is always on.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct ThermalPrimitive {
     /// X and Y coordinates of center point, decimals
     pub center: (MacroDecimal, MacroDecimal),
@@ -705,11 +945,11 @@ pub struct ThermalPrimitive {
 impl ThermalPrimitive {
     pub fn new(inner: MacroDecimal, outer: MacroDecimal, gap: MacroDecimal) -> Self {
         ThermalPrimitive {
-            center: (MacroDecimal::Value(0.0), MacroDecimal::Value(0.0)),
+            center: (MacroDecimal::from(0.0), MacroDecimal::from(0.0)),
             outer_diameter: outer,
             inner_diameter: inner,
             gap,
-            angle: MacroDecimal::Value(0.0),
+            angle: MacroDecimal::from(0.0),
         }
     }
 
@@ -725,7 +965,11 @@ impl ThermalPrimitive {
 }
 
 impl<W: Write> PartialGerberCode<W> for ThermalPrimitive {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         // Decimal invariants
         if self.inner_diameter.is_negative() {
             return Err(GerberError::RangeError(
@@ -733,23 +977,29 @@ impl<W: Write> PartialGerberCode<W> for ThermalPrimitive {
             ));
         }
         write!(writer, "7,")?;
-        self.center.0.serialize_partial(writer)?;
+        self.center.0.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.center.1.serialize_partial(writer)?;
+        self.center.1.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.outer_diameter.serialize_partial(writer)?;
+        self.outer_diameter
+            .serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.inner_diameter.serialize_partial(writer)?;
+        self.inner_diameter
+            .serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.gap.serialize_partial(writer)?;
+        self.gap.serialize_partial_with_context(writer, ctx)?;
         write!(writer, ",")?;
-        self.angle.serialize_partial(writer)?;
+        self.angle.serialize_partial_with_context(writer, ctx)?;
         write!(writer, "*")?;
         Ok(())
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct VariableDefinition {
     number: u32,
     expression: String,
@@ -762,10 +1012,22 @@ impl VariableDefinition {
             expression: expr.into(),
         }
     }
+
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
 }
 
 impl<W: Write> PartialGerberCode<W> for VariableDefinition {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
         write!(writer, "${}={}*", self.number, self.expression)?;
         Ok(())
     }
@@ -777,9 +1039,13 @@ mod test {
 
     use crate::traits::PartialGerberCode;
 
-    use super::MacroDecimal::{Value, Variable};
+    use super::MacroDecimal::Variable;
     use super::*;
 
+    fn value(value: f64) -> MacroDecimal {
+        MacroDecimal::from(value)
+    }
+
     macro_rules! assert_partial_code {
         ($obj:expr, $expected:expr) => {
             let mut buf = BufWriter::new(Vec::new());
@@ -795,15 +1061,15 @@ mod test {
     fn test_circle_primitive_codegen() {
         let with_angle = CirclePrimitive {
             exposure: true,
-            diameter: Value(1.5),
-            center: (Value(0.), Value(0.)),
-            angle: Some(Value(0.)),
+            diameter: value(1.5),
+            center: (value(0.), value(0.)),
+            angle: Some(value(0.)),
         };
         assert_partial_code!(with_angle, "1,1,1.5,0,0,0*");
         let no_angle = CirclePrimitive {
             exposure: false,
-            diameter: Value(99.9),
-            center: (Value(1.1), Value(2.2)),
+            diameter: value(99.9),
+            center: (value(1.1), value(2.2)),
             angle: None,
         };
         assert_partial_code!(no_angle, "1,0,99.9,1.1,2.2*");
@@ -813,10 +1079,10 @@ mod test {
     fn test_vector_line_primitive_codegen() {
         let line = VectorLinePrimitive {
             exposure: true,
-            width: Value(0.9),
-            start: (Value(0.), Value(0.45)),
-            end: (Value(12.), Value(0.45)),
-            angle: Value(0.),
+            width: value(0.9),
+            start: (value(0.), value(0.45)),
+            end: (value(12.), value(0.45)),
+            angle: value(0.),
         };
         assert_partial_code!(line, "20,1,0.9,0,0.45,12,0.45,0*");
     }
@@ -825,9 +1091,9 @@ mod test {
     fn test_center_line_primitive_codegen() {
         let line = CenterLinePrimitive {
             exposure: true,
-            dimensions: (Value(6.8), Value(1.2)),
-            center: (Value(3.4), Value(0.6)),
-            angle: Value(30.0),
+            dimensions: (value(6.8), value(1.2)),
+            center: (value(3.4), value(0.6)),
+            angle: value(30.0),
         };
         assert_partial_code!(line, "21,1,6.8,1.2,3.4,0.6,30*");
     }
@@ -837,13 +1103,15 @@ mod test {
         let line = OutlinePrimitive {
             exposure: true,
             points: vec![
-                (Value(0.1), Value(0.1)),
-                (Value(0.5), Value(0.1)),
-                (Value(0.5), Value(0.5)),
-                (Value(0.1), Value(0.5)),
-                (Value(0.1), Value(0.1)),
-            ],
-            angle: Value(0.0),
+                (value(0.1), value(0.1)),
+                (value(0.5), value(0.1)),
+                (value(0.5), value(0.5)),
+                (value(0.1), value(0.5)),
+                (value(0.1), value(0.1)),
+            ]
+            .into_iter()
+            .collect(),
+            angle: value(0.0),
         };
         assert_partial_code!(
             line,
@@ -851,14 +1119,45 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_outline_points() {
+        // The same with and without the `smallvec` feature
+        let mut points = OutlinePoints::from(vec![(value(0.0), value(1.0))]);
+        points.push((value(2.0), value(3.0)));
+        points.extend(vec![(value(4.0), value(5.0))]);
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[1], (value(2.0), value(3.0)));
+        points.reverse();
+        assert_eq!(points.iter().next(), Some(&(value(4.0), value(5.0))));
+        assert_eq!(format!("{:?}", OutlinePoints::new()), "[]");
+    }
+
+    #[test]
+    fn test_outline_points_vec_methods() {
+        let mut points: OutlinePoints = (0..5).map(|i| (value(i as f64), value(0.0))).collect();
+        assert_eq!(points.pop(), Some((value(4.0), value(0.0))));
+        points.insert(0, (value(9.0), value(0.0)));
+        assert_eq!(points.remove(1), (value(0.0), value(0.0)));
+        points.retain(|point| point.0 != value(2.0));
+        assert_eq!(points.len(), 3);
+        points.truncate(2);
+        let vec: Vec<(MacroDecimal, MacroDecimal)> = points.clone().into();
+        assert_eq!(
+            vec,
+            vec![(value(9.0), value(0.0)), (value(1.0), value(0.0))]
+        );
+        points.clear();
+        assert!(points.is_empty());
+    }
+
     #[test]
     fn test_polygon_primitive_codegen() {
         let line = PolygonPrimitive {
             exposure: true,
             vertices: 8,
-            center: (Value(1.5), Value(2.0)),
-            diameter: Value(8.0),
-            angle: Value(0.0),
+            center: (value(1.5), value(2.0)),
+            diameter: value(8.0),
+            angle: value(0.0),
         };
         assert_partial_code!(line, "5,1,8,1.5,2,8,0*");
     }
@@ -866,14 +1165,14 @@ mod test {
     #[test]
     fn test_moire_primitive_codegen() {
         let line = MoirePrimitive {
-            center: (Value(0.0), Value(0.0)),
-            diameter: Value(5.0),
-            ring_thickness: Value(0.5),
-            gap: Value(0.5),
+            center: (value(0.0), value(0.0)),
+            diameter: value(5.0),
+            ring_thickness: value(0.5),
+            gap: value(0.5),
             max_rings: 2,
-            cross_hair_thickness: Value(0.1),
-            cross_hair_length: Value(6.0),
-            angle: Value(0.0),
+            cross_hair_thickness: value(0.1),
+            cross_hair_length: value(6.0),
+            angle: value(0.0),
         };
         assert_partial_code!(line, "6,0,0,5,0.5,0.5,2,0.1,6,0*");
     }
@@ -881,11 +1180,11 @@ mod test {
     #[test]
     fn test_thermal_primitive_codegen() {
         let line = ThermalPrimitive {
-            center: (Value(0.0), Value(0.0)),
-            outer_diameter: Value(8.0),
-            inner_diameter: Value(6.5),
-            gap: Value(1.0),
-            angle: Value(45.0),
+            center: (value(0.0), value(0.0)),
+            outer_diameter: value(8.0),
+            inner_diameter: value(6.5),
+            gap: value(1.0),
+            angle: value(45.0),
         };
         assert_partial_code!(line, "7,0,0,8,6.5,1,45*");
     }
@@ -894,21 +1193,21 @@ mod test {
     fn test_aperture_macro_codegen() {
         let am = ApertureMacro::new("CRAZY")
             .add_content(MacroContent::Thermal(ThermalPrimitive {
-                center: (Value(0.0), Value(0.0)),
-                outer_diameter: Value(0.08),
-                inner_diameter: Value(0.055),
-                gap: Value(0.0125),
-                angle: Value(45.0),
+                center: (value(0.0), value(0.0)),
+                outer_diameter: value(0.08),
+                inner_diameter: value(0.055),
+                gap: value(0.0125),
+                angle: value(45.0),
             }))
             .add_content(MacroContent::Moire(MoirePrimitive {
-                center: (Value(0.0), Value(0.0)),
-                diameter: Value(0.125),
-                ring_thickness: Value(0.01),
-                gap: Value(0.01),
+                center: (value(0.0), value(0.0)),
+                diameter: value(0.125),
+                ring_thickness: value(0.01),
+                gap: value(0.01),
                 max_rings: 3,
-                cross_hair_thickness: Value(0.003),
-                cross_hair_length: Value(0.150),
-                angle: Value(0.0),
+                cross_hair_thickness: value(0.003),
+                cross_hair_length: value(0.150),
+                angle: value(0.0),
             }));
         assert_partial_code!(
             am,
@@ -916,13 +1215,20 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_aperture_macro_invalid_name() {
+        let am = ApertureMacro::new("NOT VALID").add_content(CirclePrimitive::new(value(1.0)));
+        let mut buf = Vec::new();
+        assert!(am.serialize_partial(&mut buf).is_err());
+    }
+
     #[test]
     fn test_codegen_with_variable() {
         let line = VectorLinePrimitive {
             exposure: true,
             width: Variable(0),
             start: (Variable(1), 0.45.into()),
-            end: (Value(12.), Variable(2)),
+            end: (value(12.), Variable(2)),
             angle: Variable(3),
         };
         assert_partial_code!(line, "20,1,$0,$1,0.45,12,$2,$3*");
@@ -930,7 +1236,7 @@ mod test {
 
     #[test]
     fn test_macro_decimal_into() {
-        let a = Value(1.0);
+        let a = value(1.0);
         let b: MacroDecimal = 1.0.into();
         assert_eq!(a, b);
         let c = Variable(1);
@@ -964,11 +1270,11 @@ mod test {
 
     #[test]
     fn test_circle_primitive_new() {
-        let c1 = CirclePrimitive::new(Value(3.0)).centered_at((Value(5.0), Value(0.0)));
+        let c1 = CirclePrimitive::new(value(3.0)).centered_at((value(5.0), value(0.0)));
         let c2 = CirclePrimitive {
             exposure: true,
-            diameter: Value(3.0),
-            center: (Value(5.0), Value(0.0)),
+            diameter: value(3.0),
+            center: (value(5.0), value(0.0)),
             angle: None,
         };
         assert_eq!(c1, c2);
@@ -976,26 +1282,26 @@ mod test {
 
     #[test]
     fn test_vectorline_primitive_new() {
-        let vl1 = VectorLinePrimitive::new((Value(0.0), Value(5.3)), (Value(3.9), Value(8.5)))
-            .with_angle(Value(38.0));
+        let vl1 = VectorLinePrimitive::new((value(0.0), value(5.3)), (value(3.9), value(8.5)))
+            .with_angle(value(38.0));
         let vl2 = VectorLinePrimitive {
             exposure: true,
-            width: Value(0.0),
-            start: (Value(0.0), Value(5.3)),
-            end: (Value(3.9), Value(8.5)),
-            angle: Value(38.0),
+            width: value(0.0),
+            start: (value(0.0), value(5.3)),
+            end: (value(3.9), value(8.5)),
+            angle: value(38.0),
         };
         assert_eq!(vl1, vl2);
     }
 
     #[test]
     fn test_centerline_primitive_new() {
-        let cl1 = CenterLinePrimitive::new((Value(3.0), Value(4.5))).exposure_on(false);
+        let cl1 = CenterLinePrimitive::new((value(3.0), value(4.5))).exposure_on(false);
         let cl2 = CenterLinePrimitive {
             exposure: false,
-            dimensions: (Value(3.0), Value(4.5)),
-            center: (Value(0.0), Value(0.0)),
-            angle: Value(0.0),
+            dimensions: (value(3.0), value(4.5)),
+            center: (value(0.0), value(0.0)),
+            angle: value(0.0),
         };
         assert_eq!(cl1, cl2);
     }
@@ -1003,22 +1309,22 @@ mod test {
     #[test]
     fn test_outline_primitive_new() {
         let op1 = OutlinePrimitive::new()
-            .add_point((Value(0.0), Value(0.0)))
-            .add_point((Value(2.0), Value(2.0)))
-            .add_point((Value(-2.0), Value(-2.0)))
-            .add_point((Value(0.0), Value(0.0)));
+            .add_point((value(0.0), value(0.0)))
+            .add_point((value(2.0), value(2.0)))
+            .add_point((value(-2.0), value(-2.0)))
+            .add_point((value(0.0), value(0.0)));
 
         let pts = vec![
-            (Value(0.0), Value(0.0)),
-            (Value(2.0), Value(2.0)),
-            (Value(-2.0), Value(-2.0)),
-            (Value(0.0), Value(0.0)),
+            (value(0.0), value(0.0)),
+            (value(2.0), value(2.0)),
+            (value(-2.0), value(-2.0)),
+            (value(0.0), value(0.0)),
         ];
 
         let op2 = OutlinePrimitive {
             exposure: true,
-            points: pts,
-            angle: Value(0.0),
+            points: pts.into_iter().collect(),
+            angle: value(0.0),
         };
         assert_eq!(op1, op2);
     }
@@ -1026,15 +1332,15 @@ mod test {
     #[test]
     fn test_polygon_primitive_new() {
         let pp1 = PolygonPrimitive::new(5)
-            .with_angle(Value(98.0))
-            .with_diameter(Value(5.3))
-            .centered_at((Value(1.0), Value(1.0)));
+            .with_angle(value(98.0))
+            .with_diameter(value(5.3))
+            .centered_at((value(1.0), value(1.0)));
         let pp2 = PolygonPrimitive {
             exposure: true,
             vertices: 5,
-            angle: Value(98.0),
-            diameter: Value(5.3),
-            center: (Value(1.0), Value(1.0)),
+            angle: value(98.0),
+            diameter: value(5.3),
+            center: (value(1.0), value(1.0)),
         };
         assert_eq!(pp1, pp2);
     }
@@ -1042,33 +1348,33 @@ mod test {
     #[test]
     fn test_moire_primitive_new() {
         let mp1 = MoirePrimitive::new()
-            .with_diameter(Value(3.0))
-            .with_ring_thickness(Value(0.05))
-            .with_cross_thickness(Value(0.01))
-            .with_cross_length(Value(0.5))
+            .with_diameter(value(3.0))
+            .with_ring_thickness(value(0.05))
+            .with_cross_thickness(value(0.01))
+            .with_cross_length(value(0.5))
             .with_rings_max(3);
         let mp2 = MoirePrimitive {
-            center: (MacroDecimal::Value(0.0), MacroDecimal::Value(0.0)),
-            diameter: MacroDecimal::Value(3.0),
-            ring_thickness: MacroDecimal::Value(0.05),
-            gap: MacroDecimal::Value(0.0),
+            center: (MacroDecimal::from(0.0), MacroDecimal::from(0.0)),
+            diameter: MacroDecimal::from(3.0),
+            ring_thickness: MacroDecimal::from(0.05),
+            gap: MacroDecimal::from(0.0),
             max_rings: 3,
-            cross_hair_thickness: MacroDecimal::Value(0.01),
-            cross_hair_length: MacroDecimal::Value(0.5),
-            angle: MacroDecimal::Value(0.0),
+            cross_hair_thickness: MacroDecimal::from(0.01),
+            cross_hair_length: MacroDecimal::from(0.5),
+            angle: MacroDecimal::from(0.0),
         };
         assert_eq!(mp1, mp2);
     }
 
     #[test]
     fn test_thermal_primitive_new() {
-        let tp1 = ThermalPrimitive::new(Value(1.0), Value(2.0), Value(1.5)).with_angle(Value(87.3));
+        let tp1 = ThermalPrimitive::new(value(1.0), value(2.0), value(1.5)).with_angle(value(87.3));
         let tp2 = ThermalPrimitive {
-            inner_diameter: Value(1.0),
-            outer_diameter: Value(2.0),
-            gap: Value(1.5),
-            angle: Value(87.3),
-            center: (Value(0.0), Value(0.0)),
+            inner_diameter: value(1.0),
+            outer_diameter: value(2.0),
+            gap: value(1.5),
+            angle: value(87.3),
+            center: (value(0.0), value(0.0)),
         };
         assert_eq!(tp1, tp2);
     }
@@ -0,0 +1,325 @@
+//! Merging of multiple command streams into one image.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::mem;
+
+use crate::attributes::ApertureAttribute;
+use crate::coordinates::CoordinateFormat;
+use crate::errors::{deprecated_error, GerberError, GerberResult};
+use crate::extended_codes::{Aperture, Polarity, Unit};
+use crate::function_codes::{DCode, MCode};
+use crate::macros::ApertureMacro;
+use crate::optimize::extended_codes;
+use crate::types::{Command, ExtendedCode, ExtendedCodeBlock, FunctionCode};
+
+/// Merge multiple command streams into a single image.
+///
+/// The streams are appended in order. Aperture codes that are already used by
+/// a previous stream are renumbered, identical aperture definitions (including
+/// their aperture attributes) and identical aperture macros are only emitted
+/// once. Macros with the same name but different content are renamed.
+///
+/// Each stream starts with dark polarity and an empty aperture attribute
+/// dictionary, like a separate file would. File attributes are taken from the
+/// first stream only. All streams must use the same coordinate format and
+/// unit, use [`convert_unit`](fn.convert_unit.html) to align them first. A
+/// single `M02` is written at the end if any of the streams contains one.
+pub fn merge<S: AsRef<[Command]>>(streams: &[S]) -> GerberResult<Vec<Command>> {
+    let mut merger = Merger::default();
+    for (index, stream) in streams.iter().enumerate() {
+        merger.add(stream.as_ref(), index == 0)?;
+    }
+    if merger.end_of_file {
+        merger.result.push(MCode::EndOfFile.into());
+    }
+    Ok(merger.result)
+}
+
+#[derive(Default)]
+struct Merger {
+    result: Vec<Command>,
+    format: Option<CoordinateFormat>,
+    unit: Option<Unit>,
+    /// Emitted aperture definitions with the attributes they were defined with.
+    apertures: Vec<(i32, Aperture, Vec<ApertureAttribute>)>,
+    macros: Vec<ApertureMacro>,
+    end_of_file: bool,
+    /// The state at the end of the previous stream, which must be reset.
+    clear_polarity: bool,
+    attributes: bool,
+}
+
+/// The state of the stream that is being added.
+struct Stream {
+    first: bool,
+    codes: HashMap<i32, i32>,
+    macro_names: HashMap<String, String>,
+    attributes: Vec<ApertureAttribute>,
+    polarity: Polarity,
+    next_code: i32,
+}
+
+impl Merger {
+    fn add(&mut self, commands: &[Command], first: bool) -> GerberResult<()> {
+        if mem::take(&mut self.attributes) {
+            self.result
+                .push(ExtendedCode::DeleteAttribute(Cow::Borrowed("")).into());
+        }
+        if mem::take(&mut self.clear_polarity) {
+            self.result
+                .push(ExtendedCode::LoadPolarity(Polarity::Dark).into());
+        }
+
+        let max_code = self
+            .apertures
+            .iter()
+            .map(|(code, _, _)| *code)
+            .chain(defined_codes(commands))
+            .max()
+            .unwrap_or(9);
+        let mut stream = Stream {
+            first,
+            codes: HashMap::new(),
+            macro_names: HashMap::new(),
+            attributes: Vec::new(),
+            polarity: Polarity::Dark,
+            next_code: max_code + 1,
+        };
+        for command in commands {
+            let command = match command {
+                Command::FunctionCode(FunctionCode::DCode(DCode::SelectAperture(code))) => {
+                    let code = stream.codes.get(code).copied().unwrap_or(*code);
+                    Some(DCode::SelectAperture(code).into())
+                }
+                Command::FunctionCode(FunctionCode::MCode(MCode::EndOfFile)) => {
+                    self.end_of_file = true;
+                    None
+                }
+                Command::FunctionCode(_) => Some(command.clone()),
+                Command::ExtendedCode(code) => self
+                    .add_extended(code.clone(), &mut stream)?
+                    .map(Command::from),
+                Command::ExtendedCodeBlock(block) => {
+                    let mut codes = Vec::with_capacity(block.codes.len());
+                    for code in &block.codes {
+                        codes.extend(self.add_extended(code.clone(), &mut stream)?);
+                    }
+                    if codes.is_empty() {
+                        None
+                    } else {
+                        Some(ExtendedCodeBlock::new(codes).into())
+                    }
+                }
+                Command::Deprecated(_) => return Err(deprecated_error()),
+            };
+            self.result.extend(command);
+        }
+        self.clear_polarity = stream.polarity == Polarity::Clear;
+        self.attributes = !stream.attributes.is_empty();
+        Ok(())
+    }
+
+    /// Process an extended code, returns `None` if it is dropped.
+    fn add_extended(
+        &mut self,
+        code: ExtendedCode,
+        stream: &mut Stream,
+    ) -> GerberResult<Option<ExtendedCode>> {
+        match code {
+            ExtendedCode::CoordinateFormat(format) => {
+                return keep_first(&mut self.format, format, "coordinate format")
+                    .map(|keep| keep.then_some(code));
+            }
+            ExtendedCode::Unit(unit) => {
+                return keep_first(&mut self.unit, unit, "unit").map(|keep| keep.then_some(code));
+            }
+            ExtendedCode::FileAttribute(_) if !stream.first => return Ok(None),
+            ExtendedCode::ApertureAttribute(ref attr) => {
+                stream
+                    .attributes
+                    .retain(|a| mem::discriminant(a) != mem::discriminant(attr));
+                stream.attributes.push(attr.clone());
+            }
+            ExtendedCode::DeleteAttribute(ref name) => {
+                let name = name.trim_start_matches('.');
+                stream
+                    .attributes
+                    .retain(|a| !name.is_empty() && a.name().trim_start_matches('.') != name);
+            }
+            ExtendedCode::LoadPolarity(polarity) => stream.polarity = polarity,
+            ExtendedCode::ApertureMacro(am) => return Ok(self.add_macro(am, stream)),
+            ExtendedCode::ApertureDefinition(mut def) => {
+                if let Aperture::Other(ref mut name) = def.aperture {
+                    rename_macro_aperture(name, &stream.macro_names);
+                }
+                let existing = self.apertures.iter().find(|(_, aperture, attributes)| {
+                    *aperture == def.aperture && *attributes == stream.attributes
+                });
+                if let Some((code, _, _)) = existing {
+                    stream.codes.insert(def.code, *code);
+                    return Ok(None);
+                }
+                if self.apertures.iter().any(|(code, _, _)| *code == def.code) {
+                    stream.codes.insert(def.code, stream.next_code);
+                    def.code = stream.next_code;
+                    stream.next_code += 1;
+                }
+                self.apertures
+                    .push((def.code, def.aperture.clone(), stream.attributes.clone()));
+                return Ok(Some(ExtendedCode::ApertureDefinition(def)));
+            }
+            _ => {}
+        }
+        Ok(Some(code))
+    }
+
+    fn add_macro(&mut self, mut am: ApertureMacro, stream: &mut Stream) -> Option<ExtendedCode> {
+        let original = am.name.to_string();
+        if let Some(existing) = self.macros.iter().find(|m| m.content == am.content) {
+            stream
+                .macro_names
+                .insert(original, existing.name.to_string());
+            return None;
+        }
+        let taken = |name: &str| self.macros.iter().any(|m| m.name == name);
+        if taken(&original) {
+            let name = (2..)
+                .map(|i| format!("{}_{}", original, i))
+                .find(|name| !taken(name))
+                .expect("Infinite iterator");
+            am.name = name.into();
+        }
+        stream.macro_names.insert(original, am.name.to_string());
+        self.macros.push(am.clone());
+        Some(ExtendedCode::ApertureMacro(am))
+    }
+}
+
+/// Keep the first value, drop identical ones and reject different ones.
+fn keep_first<T: PartialEq>(current: &mut Option<T>, value: T, what: &str) -> GerberResult<bool> {
+    match current {
+        None => {
+            *current = Some(value);
+            Ok(true)
+        }
+        Some(current) if *current == value => Ok(false),
+        Some(_) => Err(GerberError::UnsupportedError(format!(
+            "Cannot merge streams with a different {}",
+            what
+        ))),
+    }
+}
+
+fn defined_codes(commands: &[Command]) -> impl Iterator<Item = i32> + '_ {
+    extended_codes(commands).filter_map(|code| match code {
+        ExtendedCode::ApertureDefinition(def) => Some(def.code),
+        _ => None,
+    })
+}
+
+/// Replace the macro name of a macro aperture like `NAME,1.5X2`.
+fn rename_macro_aperture(aperture: &mut Cow<'static, str>, names: &HashMap<String, String>) {
+    let (name, parameters) = match aperture.find(',') {
+        Some(index) => aperture.split_at(index),
+        None => (aperture.as_ref(), ""),
+    };
+    if let Some(new_name) = names.get(name) {
+        if new_name != name {
+            *aperture = format!("{}{}", new_name, parameters).into();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::ApertureFunction;
+    use crate::coordinates::Coordinates;
+    use crate::extended_codes::{ApertureDefinition, Circle, Rectangular};
+    use crate::function_codes::Operation;
+    use crate::macros::CirclePrimitive;
+    use crate::traits::GerberCodeExt;
+
+    fn stream(aperture: Aperture, polarity: Polarity) -> Vec<Command> {
+        let cf = CoordinateFormat::new(2, 4);
+        vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            ApertureDefinition::new(10, aperture).into(),
+            ExtendedCode::LoadPolarity(polarity).into(),
+            DCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(1, 1, cf))).into(),
+            MCode::EndOfFile.into(),
+        ]
+    }
+
+    #[test]
+    fn test_merge_renumbers_and_deduplicates() {
+        let circle = Aperture::Circle(Circle::new(0.5));
+        let rect = Aperture::Rectangle(Rectangular::new(1.0, 2.0));
+        let merged = merge(&[
+            stream(circle.clone(), Polarity::Clear),
+            stream(rect, Polarity::Dark),
+            stream(circle, Polarity::Dark),
+        ])
+        .unwrap();
+        assert_eq!(
+            merged.to_code_string().unwrap(),
+            "%FSLAX24Y24*%\n%MOMM*%\n\
+             %ADD10C,0.5*%\n%LPC*%\nD10*\nX10000Y10000D03*\n\
+             %LPD*%\n%ADD11R,1X2*%\n%LPD*%\nD11*\nX10000Y10000D03*\n\
+             %LPD*%\nD10*\nX10000Y10000D03*\n\
+             M02*\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_attributes_and_macros() {
+        let dot = |diameter: f64| -> Command {
+            ApertureMacro::new("DOT")
+                .add_content(CirclePrimitive::new(diameter.into()))
+                .into()
+        };
+        let attribute: Command = ExtendedCode::ApertureAttribute(
+            ApertureAttribute::ApertureFunction(ApertureFunction::ViaPad),
+        )
+        .into();
+        let first = vec![
+            dot(1.0),
+            attribute,
+            ApertureDefinition::new(10, Aperture::Other("DOT".into())).into(),
+        ];
+        let second = vec![
+            dot(2.0),
+            ApertureDefinition::new(10, Aperture::Other("DOT".into())).into(),
+            DCode::SelectAperture(10).into(),
+        ];
+        let merged = merge(&[first, second]).unwrap();
+        assert_eq!(
+            merged.to_code_string().unwrap(),
+            "%AMDOT*\n1,1,1,0,0*%\n\
+             %TA.AperFunction,ViaPad*%\n\
+             %ADD10DOT*%\n\
+             %TD*%\n\
+             %AMDOT_2*\n1,1,2,0,0*%\n\
+             %ADD11DOT_2*%\n\
+             D11*\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_different_units() {
+        let cf = CoordinateFormat::new(2, 4);
+        let mm: Vec<Command> = vec![ExtendedCode::Unit(Unit::Millimeters).into()];
+        let inches: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ExtendedCode::Unit(Unit::Inches).into(),
+        ];
+        assert!(matches!(
+            merge(&[mm, inches]),
+            Err(GerberError::UnsupportedError(_))
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+//! Mirroring of command streams, e.g. for bottom side layers.
+
+use crate::coordinates::CoordinateNumber;
+use crate::decimal::{from_decimal, to_decimal, Decimal};
+use crate::errors::{deprecated_error, GerberError, GerberResult};
+use crate::extended_codes::{Aperture, StepAndRepeat};
+use crate::function_codes::{DCode, GCode, InterpolationMode, Operation, QuadrantMode};
+use crate::macros::{ApertureMacro, MacroContent, MacroDecimal};
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// The axis to mirror about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MirrorAxis {
+    /// Flip about the X axis, i.e. negate the Y coordinates.
+    X,
+    /// Flip about the Y axis, i.e. negate the X coordinates. This is the
+    /// usual way to view bottom side layers from the top.
+    Y,
+}
+
+/// Mirror a command stream about the X or Y axis.
+///
+/// Coordinates and arc offsets are negated and the direction of circular
+/// interpolation is reversed. Circles, rectangles and obrounds are symmetric
+/// and stay unchanged, polygon apertures and aperture macros are mirrored by
+/// adjusting their rotation and the positions of the macro primitives.
+///
+/// Aperture macros with variables cannot be mirrored and result in an error,
+/// as does a step and repeat block with more than one repetition along the
+/// mirrored axis.
+pub fn mirror(commands: &[Command], axis: MirrorAxis) -> GerberResult<Vec<Command>> {
+    let mut quadrant = None;
+    let mut result = Vec::with_capacity(commands.len());
+    for command in commands {
+        let mut command = command.clone();
+        match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(ref mut op))) => {
+                mirror_operation(op, axis, quadrant)?;
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::InterpolationMode(ref mut mode))) => {
+                *mode = match *mode {
+                    InterpolationMode::ClockwiseCircular => {
+                        InterpolationMode::CounterclockwiseCircular
+                    }
+                    InterpolationMode::CounterclockwiseCircular => {
+                        InterpolationMode::ClockwiseCircular
+                    }
+                    InterpolationMode::Linear => InterpolationMode::Linear,
+                };
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::QuadrantMode(mode))) => {
+                quadrant = Some(mode);
+            }
+            Command::ExtendedCode(ref mut code) => mirror_extended(code, axis)?,
+            Command::ExtendedCodeBlock(ref mut block) => {
+                for code in block.codes.iter_mut() {
+                    mirror_extended(code, axis)?;
+                }
+            }
+            Command::Deprecated(_) => return Err(deprecated_error()),
+            Command::FunctionCode(_) => {}
+        }
+        result.push(command);
+    }
+    Ok(result)
+}
+
+fn negate(value: &mut Option<CoordinateNumber>) -> GerberResult<()> {
+    if let Some(v) = value {
+        *v = v
+            .checked_neg()
+            .ok_or_else(|| GerberError::RangeError("Mirrored coordinate is out of range".into()))?;
+    }
+    Ok(())
+}
+
+fn mirror_operation(
+    op: &mut Operation,
+    axis: MirrorAxis,
+    quadrant: Option<QuadrantMode>,
+) -> GerberResult<()> {
+    let (coords, offset) = match op {
+        Operation::Interpolate(coords, offset) => (coords, offset.as_mut()),
+        Operation::Move(coords) | Operation::Flash(coords) => (coords, None),
+    };
+    match axis {
+        MirrorAxis::X => negate(&mut coords.y)?,
+        MirrorAxis::Y => negate(&mut coords.x)?,
+    }
+    // Offsets are unsigned in single quadrant mode, the reversed direction
+    // is enough to select the mirrored center.
+    if let Some(offset) = offset {
+        if quadrant != Some(QuadrantMode::Single) {
+            match axis {
+                MirrorAxis::X => negate(&mut offset.y)?,
+                MirrorAxis::Y => negate(&mut offset.x)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Mirror an angle in degrees, normalized to `[0, 360)`.
+///
+/// Mirroring about the Y axis is the same as mirroring about the X axis and
+/// rotating by 180 degrees.
+fn mirror_angle(angle: f64, axis: MirrorAxis) -> f64 {
+    let mirrored = match axis {
+        MirrorAxis::X => 360.0 - angle,
+        MirrorAxis::Y => 180.0 - angle,
+    };
+    mirrored.rem_euclid(360.0)
+}
+
+fn mirror_extended(code: &mut ExtendedCode, axis: MirrorAxis) -> GerberResult<()> {
+    match code {
+        ExtendedCode::ApertureDefinition(def) => {
+            if let Aperture::Polygon(polygon) = &mut def.aperture {
+                let rotation = polygon.rotation.map(from_decimal).unwrap_or(0.0);
+                polygon.rotation = Some(to_decimal(mirror_angle(rotation, axis)));
+            }
+        }
+        ExtendedCode::ApertureMacro(am) => mirror_macro(am, axis)?,
+        ExtendedCode::StepAndRepeat(StepAndRepeat::Open {
+            repeat_x, repeat_y, ..
+        }) => {
+            let repeat = match axis {
+                MirrorAxis::X => *repeat_y,
+                MirrorAxis::Y => *repeat_x,
+            };
+            if repeat > 1 {
+                return Err(GerberError::UnsupportedError(
+                    "Cannot mirror a step and repeat block along its repeat direction".into(),
+                ));
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Mirror the primitives about the X axis and, for the Y axis, rotate them
+/// by 180 degrees around the macro origin.
+fn mirror_macro(am: &mut ApertureMacro, axis: MirrorAxis) -> GerberResult<()> {
+    let ApertureMacro { name, content } = am;
+    let variables_error = || {
+        GerberError::UnsupportedError(format!(
+            "Cannot mirror aperture macro {} with variables",
+            name
+        ))
+    };
+    let value = |decimal: &MacroDecimal| -> GerberResult<Decimal> {
+        match *decimal {
+            MacroDecimal::Value(value) => Ok(value),
+            MacroDecimal::Variable(_) => Err(variables_error()),
+        }
+    };
+    let flip = |point: &mut (MacroDecimal, MacroDecimal)| -> GerberResult<()> {
+        // Adding zero avoids a negative zero
+        let y = -from_decimal(value(&point.1)?) + 0.0;
+        point.1 = MacroDecimal::Value(to_decimal(y));
+        Ok(())
+    };
+    let rotate = |angle: &mut MacroDecimal| -> GerberResult<()> {
+        let mirrored = mirror_angle(from_decimal(value(&*angle)?), axis);
+        *angle = MacroDecimal::Value(to_decimal(mirrored));
+        Ok(())
+    };
+    for content in content.iter_mut() {
+        match content {
+            MacroContent::Circle(c) => {
+                flip(&mut c.center)?;
+                let mut angle = c.angle.take().unwrap_or(MacroDecimal::from(0.0));
+                rotate(&mut angle)?;
+                c.angle = Some(angle);
+            }
+            MacroContent::VectorLine(vl) => {
+                flip(&mut vl.start)?;
+                flip(&mut vl.end)?;
+                rotate(&mut vl.angle)?;
+            }
+            MacroContent::CenterLine(cl) => {
+                flip(&mut cl.center)?;
+                rotate(&mut cl.angle)?;
+            }
+            MacroContent::Outline(o) => {
+                for point in o.points.iter_mut() {
+                    flip(point)?;
+                }
+                rotate(&mut o.angle)?;
+            }
+            MacroContent::Polygon(p) => {
+                flip(&mut p.center)?;
+                rotate(&mut p.angle)?;
+            }
+            MacroContent::Moire(m) => {
+                flip(&mut m.center)?;
+                rotate(&mut m.angle)?;
+            }
+            MacroContent::Thermal(t) => {
+                flip(&mut t.center)?;
+                rotate(&mut t.angle)?;
+            }
+            MacroContent::VariableDefinition(_) => return Err(variables_error()),
+            MacroContent::Comment(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::{CoordinateFormat, CoordinateOffset, Coordinates};
+    use crate::extended_codes::{ApertureDefinition, Polygon};
+    use crate::macros::CirclePrimitive;
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_mirror() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ApertureDefinition::new(10, Aperture::Polygon(Polygon::new(1.0, 3))).into(),
+            ApertureMacro::new("DOT")
+                .add_content(CirclePrimitive {
+                    exposure: true,
+                    diameter: 1.0.into(),
+                    center: (1.0.into(), 2.0.into()),
+                    angle: None,
+                })
+                .into(),
+            GCode::InterpolationMode(InterpolationMode::ClockwiseCircular).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, 2, cf),
+                Some(CoordinateOffset::new(1, 0, cf)),
+            ))
+            .into(),
+        ];
+        let mirrored = mirror(&commands, MirrorAxis::Y).unwrap();
+        assert_eq!(
+            mirrored.to_code_string().unwrap(),
+            "%ADD10P,1X3X180*%\n\
+             %AMDOT*\n1,1,1,1,-2,180*%\n\
+             G03*\n\
+             X-10000Y20000I-10000J0D01*\n"
+        );
+
+        let mirrored = mirror(&commands, MirrorAxis::X).unwrap();
+        assert_eq!(
+            mirrored[3],
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, -2, cf),
+                Some(CoordinateOffset::new(1, 0, cf)),
+            ))
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_mirror_errors() {
+        let commands: Vec<Command> = vec![ExtendedCode::StepAndRepeat(StepAndRepeat::Open {
+            repeat_x: 2,
+            repeat_y: 1,
+            distance_x: to_decimal(10.0),
+            distance_y: to_decimal(0.0),
+        })
+        .into()];
+        assert!(mirror(&commands, MirrorAxis::X).is_ok());
+        assert!(matches!(
+            mirror(&commands, MirrorAxis::Y),
+            Err(GerberError::UnsupportedError(_))
+        ));
+    }
+}
@@ -0,0 +1,247 @@
+//! Conventional names of Gerber files.
+
+use crate::attributes::{
+    Drill, DrillRouteType, ExtendedPosition, FileFunction, FilePolarity, Position, Profile,
+};
+use crate::errors::{GerberError, GerberResult};
+
+/// A convention for naming the files of a fabrication data set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NamingScheme {
+    /// Names with the `.gbr` extension recommended by the spec, that
+    /// describe the file function, e.g. `board-copper_l1_top.gbr`.
+    Gerber,
+    /// The classic Protel extensions, e.g. `board.GTL` for the top copper.
+    /// Only the common layers have such an extension.
+    Protel,
+}
+
+/// The conventional name of a file with the given function and polarity.
+///
+/// With the Protel scheme, negative inner copper layers are planes
+/// (`.GP1`), and functions without an extension result in an
+/// `UnsupportedError`. With the Gerber scheme, negative files get a
+/// `_negative` suffix.
+pub fn file_name(
+    prefix: &str,
+    function: &FileFunction,
+    polarity: &FilePolarity,
+    scheme: NamingScheme,
+) -> GerberResult<String> {
+    match scheme {
+        NamingScheme::Gerber => {
+            let suffix = match polarity {
+                FilePolarity::Positive => "",
+                FilePolarity::Negative => "_negative",
+            };
+            Ok(format!("{}-{}{}.gbr", prefix, stem(function), suffix))
+        }
+        NamingScheme::Protel => {
+            let extension = protel_extension(function, polarity).ok_or_else(|| {
+                GerberError::UnsupportedError(format!(
+                    "No Protel extension for file function {:?}",
+                    function
+                ))
+            })?;
+            Ok(format!("{}.{}", prefix, extension))
+        }
+    }
+}
+
+fn stem(function: &FileFunction) -> String {
+    let side = |pos: &Position| match pos {
+        Position::Top => "top",
+        Position::Bottom => "bottom",
+    };
+    let with_index = |name: &str, pos: &Position, index: &Option<i32>| match index {
+        Some(i) => format!("{}_{}_{}", name, side(pos), i),
+        None => format!("{}_{}", name, side(pos)),
+    };
+    let drill = |name: &str, from: i32, to: i32, drill: &Drill, label: &Option<DrillRouteType>| {
+        let drill = match drill {
+            Drill::ThroughHole => "th",
+            Drill::Blind => "blind",
+            Drill::Buried => "buried",
+        };
+        let label = match label {
+            Some(DrillRouteType::Drill) => "_drill",
+            Some(DrillRouteType::Route) => "_route",
+            Some(DrillRouteType::Mixed) => "_mixed",
+            None => "",
+        };
+        format!("{}_l{}_l{}_{}{}", name, from, to, drill, label)
+    };
+    match function {
+        FileFunction::Copper { layer, pos, .. } => {
+            let pos = match pos {
+                ExtendedPosition::Top => "top",
+                ExtendedPosition::Inner => "inner",
+                ExtendedPosition::Bottom => "bottom",
+            };
+            format!("copper_l{}_{}", layer, pos)
+        }
+        FileFunction::Soldermask { pos, index } => with_index("soldermask", pos, index),
+        FileFunction::Legend { pos, index } => with_index("legend", pos, index),
+        FileFunction::Goldmask { pos, index } => with_index("goldmask", pos, index),
+        FileFunction::Silvermask { pos, index } => with_index("silvermask", pos, index),
+        FileFunction::Tinmask { pos, index } => with_index("tinmask", pos, index),
+        FileFunction::Carbonmask { pos, index } => with_index("carbonmask", pos, index),
+        FileFunction::Peelablesoldermask { pos, index } => {
+            with_index("peelablesoldermask", pos, index)
+        }
+        FileFunction::Glue { pos, index } => with_index("glue", pos, index),
+        FileFunction::Viatenting(pos) => format!("viatenting_{}", side(pos)),
+        FileFunction::Viafill => "viafill".into(),
+        FileFunction::Heatsink(pos) => format!("heatsink_{}", side(pos)),
+        FileFunction::Paste(pos) => format!("paste_{}", side(pos)),
+        FileFunction::KeepOut(pos) => format!("keepout_{}", side(pos)),
+        FileFunction::Pads(pos) => format!("pads_{}", side(pos)),
+        FileFunction::Scoring(pos) => format!("scoring_{}", side(pos)),
+        FileFunction::Plated {
+            from_layer,
+            to_layer,
+            drill: d,
+            label,
+        } => drill("plated", *from_layer, *to_layer, d, label),
+        FileFunction::NonPlated {
+            from_layer,
+            to_layer,
+            drill: d,
+            label,
+        } => drill("nonplated", *from_layer, *to_layer, d, label),
+        FileFunction::Profile(Profile::Plated) => "profile_plated".into(),
+        FileFunction::Profile(Profile::NonPlated) => "profile".into(),
+        FileFunction::Drillmap => "drillmap".into(),
+        FileFunction::FabricationDrawing => "fabrication_drawing".into(),
+        FileFunction::ArrayDrawing => "array_drawing".into(),
+        FileFunction::AssemblyDrawing(pos) => format!("assembly_drawing_{}", side(pos)),
+        FileFunction::Component { layer, pos } => format!("component_l{}_{}", layer, side(pos)),
+        FileFunction::Drawing(description) => format!("drawing_{}", sanitize(description)),
+        FileFunction::Other(description) => format!("other_{}", sanitize(description)),
+    }
+}
+
+fn protel_extension(function: &FileFunction, polarity: &FilePolarity) -> Option<String> {
+    let side = |pos: &Position, top: &str, bottom: &str| match pos {
+        Position::Top => top.to_string(),
+        Position::Bottom => bottom.to_string(),
+    };
+    let extension = match function {
+        FileFunction::Copper { pos, layer, .. } => match pos {
+            ExtendedPosition::Top => "GTL".into(),
+            ExtendedPosition::Bottom => "GBL".into(),
+            ExtendedPosition::Inner if *polarity == FilePolarity::Negative => {
+                format!("GP{}", layer - 1)
+            }
+            ExtendedPosition::Inner => format!("G{}", layer - 1),
+        },
+        FileFunction::Soldermask { pos, index: None } => side(pos, "GTS", "GBS"),
+        FileFunction::Legend { pos, index: None } => side(pos, "GTO", "GBO"),
+        FileFunction::Paste(pos) => side(pos, "GTP", "GBP"),
+        FileFunction::Profile(_) => "GKO".into(),
+        FileFunction::Drillmap => "GD1".into(),
+        _ => return None,
+    };
+    Some(extension)
+}
+
+/// Lowercase letters and digits, with underscores for all other characters.
+fn sanitize(description: &str) -> String {
+    description
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::CopperType;
+
+    fn copper(layer: i32, pos: ExtendedPosition) -> FileFunction {
+        FileFunction::Copper {
+            layer,
+            pos,
+            copper_type: Some(CopperType::Plane),
+        }
+    }
+
+    #[test]
+    fn test_gerber_file_names() {
+        let name = |function: &FileFunction, polarity: &FilePolarity| {
+            file_name("board", function, polarity, NamingScheme::Gerber).unwrap()
+        };
+        let positive = FilePolarity::Positive;
+        assert_eq!(
+            name(&copper(1, ExtendedPosition::Top), &positive),
+            "board-copper_l1_top.gbr"
+        );
+        assert_eq!(
+            name(&copper(2, ExtendedPosition::Inner), &FilePolarity::Negative),
+            "board-copper_l2_inner_negative.gbr"
+        );
+        let soldermask = FileFunction::Soldermask {
+            pos: Position::Bottom,
+            index: Some(2),
+        };
+        assert_eq!(
+            name(&soldermask, &positive),
+            "board-soldermask_bottom_2.gbr"
+        );
+        let plated = FileFunction::Plated {
+            from_layer: 1,
+            to_layer: 4,
+            drill: Drill::ThroughHole,
+            label: Some(DrillRouteType::Drill),
+        };
+        assert_eq!(name(&plated, &positive), "board-plated_l1_l4_th_drill.gbr");
+        assert_eq!(
+            name(&FileFunction::Other("Test Points".into()), &positive),
+            "board-other_test_points.gbr"
+        );
+    }
+
+    #[test]
+    fn test_protel_file_names() {
+        let name = |function: &FileFunction, polarity: &FilePolarity| {
+            file_name("board", function, polarity, NamingScheme::Protel)
+        };
+        let positive = FilePolarity::Positive;
+        assert_eq!(
+            name(&copper(1, ExtendedPosition::Top), &positive).unwrap(),
+            "board.GTL"
+        );
+        assert_eq!(
+            name(&copper(4, ExtendedPosition::Bottom), &positive).unwrap(),
+            "board.GBL"
+        );
+        assert_eq!(
+            name(&copper(2, ExtendedPosition::Inner), &positive).unwrap(),
+            "board.G1"
+        );
+        assert_eq!(
+            name(&copper(3, ExtendedPosition::Inner), &FilePolarity::Negative).unwrap(),
+            "board.GP2"
+        );
+        let legend = FileFunction::Legend {
+            pos: Position::Bottom,
+            index: None,
+        };
+        assert_eq!(name(&legend, &positive).unwrap(), "board.GBO");
+        assert_eq!(
+            name(&FileFunction::Profile(Profile::NonPlated), &positive).unwrap(),
+            "board.GKO"
+        );
+        assert!(matches!(
+            name(&FileFunction::Viafill, &positive),
+            Err(GerberError::UnsupportedError(_))
+        ));
+    }
+}
@@ -0,0 +1,465 @@
+//! Optimization passes that shrink command streams without changing the
+//! image they describe.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::coordinates::{CoordinateNumber, Coordinates};
+use crate::deprecated::DeprecatedCode;
+use crate::extended_codes::Polarity;
+use crate::function_codes::{DCode, GCode, InterpolationMode, Operation, QuadrantMode};
+use crate::types::{Command, ExtendedCode, ExtendedCodeBlock, FunctionCode};
+
+/// Omit X and Y words that are unchanged from the current point.
+///
+/// Coordinates are modal: an omitted coordinate takes its value from the
+/// current point. This pass tracks the current point through the stream and
+/// removes every coordinate that equals it. The current point is treated as
+/// undefined at the start of the stream, at step and repeat boundaries and
+/// after deprecated codes, so coordinates there are always kept.
+pub fn elide_modal_coordinates(commands: &[Command]) -> Vec<Command> {
+    let mut current: (Option<CoordinateNumber>, Option<CoordinateNumber>) = (None, None);
+    let mut result = Vec::with_capacity(commands.len());
+    for command in commands {
+        let mut command = command.clone();
+        match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(ref mut op))) => {
+                let coords = match op {
+                    Operation::Interpolate(coords, _)
+                    | Operation::Move(coords)
+                    | Operation::Flash(coords) => coords,
+                };
+                elide(coords, &mut current);
+            }
+            Command::ExtendedCode(ExtendedCode::StepAndRepeat(_)) | Command::Deprecated(_) => {
+                current = (None, None);
+            }
+            Command::ExtendedCodeBlock(ref block)
+                if block
+                    .codes
+                    .iter()
+                    .any(|code| matches!(code, ExtendedCode::StepAndRepeat(_))) =>
+            {
+                current = (None, None);
+            }
+            _ => {}
+        }
+        result.push(command);
+    }
+    result
+}
+
+/// Remove commands that do not change the graphics state.
+///
+/// Interpolation mode (G01, G02, G03), quadrant mode (G74, G75), polarity
+/// (LP) and aperture selections (Dnn) that repeat the current state are
+/// removed. The state is treated as unknown at the start of the stream and
+/// after deprecated codes, so the first command of each kind is kept.
+pub fn remove_redundant_modal_commands(commands: &[Command]) -> Vec<Command> {
+    let mut interpolation: Option<InterpolationMode> = None;
+    let mut quadrant: Option<QuadrantMode> = None;
+    let mut polarity: Option<Polarity> = None;
+    let mut aperture: Option<i32> = None;
+    let mut result = Vec::with_capacity(commands.len());
+    for command in commands {
+        let redundant = match command {
+            Command::FunctionCode(FunctionCode::GCode(GCode::InterpolationMode(mode))) => {
+                interpolation.replace(*mode) == Some(*mode)
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::QuadrantMode(mode))) => {
+                quadrant.replace(*mode) == Some(*mode)
+            }
+            Command::ExtendedCode(ExtendedCode::LoadPolarity(p)) => {
+                polarity.replace(*p) == Some(*p)
+            }
+            Command::ExtendedCodeBlock(block) => {
+                for code in &block.codes {
+                    if let ExtendedCode::LoadPolarity(p) = code {
+                        polarity = Some(*p);
+                    }
+                }
+                false
+            }
+            Command::FunctionCode(FunctionCode::DCode(DCode::SelectAperture(code))) => {
+                aperture.replace(*code) == Some(*code)
+            }
+            Command::Deprecated(_) => {
+                interpolation = None;
+                quadrant = None;
+                polarity = None;
+                aperture = None;
+                false
+            }
+            _ => false,
+        };
+        if !redundant {
+            result.push(command.clone());
+        }
+    }
+    result
+}
+
+/// Merge consecutive extended codes into extended code blocks, e.g. to write
+/// the header as `%FSLAX24Y24*MOMM*%`.
+///
+/// Aperture macros are kept in their own blocks, since they span multiple
+/// lines.
+pub fn group_extended_codes(commands: &[Command]) -> Vec<Command> {
+    let mut result = Vec::with_capacity(commands.len());
+    let mut group: Vec<ExtendedCode> = Vec::new();
+    for command in commands {
+        match command {
+            Command::ExtendedCode(ExtendedCode::ApertureMacro(_)) => {}
+            Command::ExtendedCode(code) => {
+                group.push(code.clone());
+                continue;
+            }
+            Command::ExtendedCodeBlock(block) => {
+                group.extend(block.codes.iter().cloned());
+                continue;
+            }
+            _ => {}
+        }
+        flush_group(&mut group, &mut result);
+        result.push(command.clone());
+    }
+    flush_group(&mut group, &mut result);
+    result
+}
+
+fn flush_group(group: &mut Vec<ExtendedCode>, result: &mut Vec<Command>) {
+    match group.len() {
+        0 => {}
+        1 => result.push(group.remove(0).into()),
+        _ => result.push(ExtendedCodeBlock::new(group.split_off(0)).into()),
+    }
+}
+
+/// Aperture and macro definitions that are never used, see
+/// [`find_unused_apertures`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnusedApertures {
+    /// Codes of apertures that are defined but never selected.
+    pub codes: Vec<i32>,
+    /// Names of aperture macros that are not used by a selected aperture.
+    pub macros: Vec<String>,
+}
+
+impl UnusedApertures {
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty() && self.macros.is_empty()
+    }
+}
+
+/// Find the apertures that are defined but never selected, and the aperture
+/// macros that are only used by such apertures or not at all.
+pub fn find_unused_apertures(commands: &[Command]) -> UnusedApertures {
+    let mut selected = HashSet::new();
+    for command in commands {
+        match *command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::SelectAperture(code)))
+            | Command::Deprecated(DeprecatedCode::SelectAperture(code)) => {
+                selected.insert(code);
+            }
+            _ => {}
+        }
+    }
+
+    let mut unused = UnusedApertures::default();
+    let mut used_macros = HashSet::new();
+    for code in extended_codes(commands) {
+        if let ExtendedCode::ApertureDefinition(def) = code {
+            if selected.contains(&def.code) {
+                used_macros.extend(def.aperture.macro_name());
+            } else {
+                unused.codes.push(def.code);
+            }
+        }
+    }
+    for code in extended_codes(commands) {
+        if let ExtendedCode::ApertureMacro(am) = code {
+            if !used_macros.contains(am.name.as_ref()) {
+                unused.macros.push(am.name.to_string());
+            }
+        }
+    }
+    unused
+}
+
+/// Remove the aperture and macro definitions found by
+/// [`find_unused_apertures`].
+///
+/// Aperture attributes are kept, since they may also apply to following
+/// definitions.
+pub fn prune_unused_apertures(commands: &[Command]) -> Vec<Command> {
+    let unused = find_unused_apertures(commands);
+    let keep = |code: &ExtendedCode| match code {
+        ExtendedCode::ApertureDefinition(def) => !unused.codes.contains(&def.code),
+        ExtendedCode::ApertureMacro(am) => !unused.macros.iter().any(|name| *name == am.name),
+        _ => true,
+    };
+    let mut result = Vec::with_capacity(commands.len());
+    for command in commands {
+        match command {
+            Command::ExtendedCode(code) if !keep(code) => {}
+            Command::ExtendedCodeBlock(block) if !block.codes.iter().all(keep) => {
+                let codes: Vec<ExtendedCode> =
+                    block.codes.iter().filter(|c| keep(c)).cloned().collect();
+                if !codes.is_empty() {
+                    result.push(ExtendedCodeBlock::new(codes).into());
+                }
+            }
+            _ => result.push(command.clone()),
+        }
+    }
+    result
+}
+
+/// Renumber the apertures in the order of their definition, starting at
+/// D10, e.g. after merging streams or pruning unused apertures.
+///
+/// Selected codes without a definition are kept, and their numbers are not
+/// given to other apertures.
+pub fn compact_aperture_codes(commands: &[Command]) -> Vec<Command> {
+    let defined: Vec<i32> = extended_codes(commands)
+        .filter_map(|code| match code {
+            ExtendedCode::ApertureDefinition(def) => Some(def.code),
+            _ => None,
+        })
+        .collect();
+    let reserved: HashSet<i32> = commands
+        .iter()
+        .filter_map(|command| match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::SelectAperture(code)))
+            | Command::Deprecated(DeprecatedCode::SelectAperture(code)) => Some(*code),
+            _ => None,
+        })
+        .filter(|code| !defined.contains(code))
+        .collect();
+    let mut codes = HashMap::new();
+    let mut next = 10;
+    for code in defined {
+        if codes.contains_key(&code) {
+            continue;
+        }
+        while reserved.contains(&next) {
+            next += 1;
+        }
+        codes.insert(code, next);
+        next += 1;
+    }
+    remap_aperture_codes(commands, &codes)
+}
+
+/// Replace aperture codes in definitions and selections. Codes that are not
+/// in the map are kept.
+pub fn remap_aperture_codes(commands: &[Command], codes: &HashMap<i32, i32>) -> Vec<Command> {
+    let remap = |code: &mut i32| {
+        if let Some(new) = codes.get(code) {
+            *code = *new;
+        }
+    };
+    let remap_extended = |code: &mut ExtendedCode| {
+        if let ExtendedCode::ApertureDefinition(def) = code {
+            remap(&mut def.code);
+        }
+    };
+    let mut result = commands.to_vec();
+    for command in result.iter_mut() {
+        match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::SelectAperture(code)))
+            | Command::Deprecated(DeprecatedCode::SelectAperture(code)) => remap(code),
+            Command::ExtendedCode(code) => remap_extended(code),
+            Command::ExtendedCodeBlock(block) => block.codes.iter_mut().for_each(remap_extended),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// All extended codes, including the ones in extended code blocks.
+pub(crate) fn extended_codes(commands: &[Command]) -> impl Iterator<Item = &ExtendedCode> {
+    commands.iter().flat_map(|command| {
+        let codes: &[ExtendedCode] = match command {
+            Command::ExtendedCode(code) => std::slice::from_ref(code),
+            Command::ExtendedCodeBlock(block) => &block.codes,
+            _ => &[],
+        };
+        codes.iter()
+    })
+}
+
+fn elide(
+    coords: &mut Coordinates,
+    current: &mut (Option<CoordinateNumber>, Option<CoordinateNumber>),
+) {
+    if let Some(x) = coords.x {
+        if current.0 == Some(x) {
+            coords.x = None;
+        }
+        current.0 = Some(x);
+    }
+    if let Some(y) = coords.y {
+        if current.1 == Some(y) {
+            coords.y = None;
+        }
+        current.1 = Some(y);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::CoordinateFormat;
+    use crate::decimal::to_decimal;
+    use crate::extended_codes::StepAndRepeat;
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_elide_modal_coordinates() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            DCode::Operation(Operation::Move(Coordinates::new(0, 0, cf))).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::new(5, 0, cf), None)).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::new(5, 5, cf), None)).into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(5, 5, cf))).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::at_y(0, cf), None)).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::new(0, 0, cf), None)).into(),
+        ];
+        let optimized = elide_modal_coordinates(&commands);
+        assert_eq!(
+            optimized.to_code_string().unwrap(),
+            "X0Y0D02*\nX50000D01*\nY50000D01*\nD03*\nY0D01*\nX0D01*\n"
+        );
+    }
+
+    #[test]
+    fn test_elide_modal_coordinates_step_and_repeat() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            DCode::Operation(Operation::Flash(Coordinates::new(1, 1, cf))).into(),
+            ExtendedCode::StepAndRepeat(StepAndRepeat::Open {
+                repeat_x: 2,
+                repeat_y: 1,
+                distance_x: to_decimal(5.0),
+                distance_y: to_decimal(0.0),
+            })
+            .into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(1, 1, cf))).into(),
+        ];
+        let optimized = elide_modal_coordinates(&commands);
+        assert_eq!(optimized, commands);
+    }
+
+    #[test]
+    fn test_remove_redundant_modal_commands() {
+        let cf = CoordinateFormat::new(2, 4);
+        let draw: Command =
+            DCode::Operation(Operation::Interpolate(Coordinates::new(1, 1, cf), None)).into();
+        let commands: Vec<Command> = vec![
+            GCode::InterpolationMode(InterpolationMode::Linear).into(),
+            GCode::QuadrantMode(QuadrantMode::Multi).into(),
+            ExtendedCode::LoadPolarity(Polarity::Dark).into(),
+            DCode::SelectAperture(10).into(),
+            draw.clone(),
+            GCode::InterpolationMode(InterpolationMode::Linear).into(),
+            GCode::QuadrantMode(QuadrantMode::Multi).into(),
+            ExtendedCode::LoadPolarity(Polarity::Dark).into(),
+            DCode::SelectAperture(10).into(),
+            draw.clone(),
+            ExtendedCode::LoadPolarity(Polarity::Clear).into(),
+            DCode::SelectAperture(11).into(),
+            GCode::InterpolationMode(InterpolationMode::ClockwiseCircular).into(),
+            draw.clone(),
+        ];
+        let optimized = remove_redundant_modal_commands(&commands);
+        assert_eq!(
+            optimized.to_code_string().unwrap(),
+            "G01*\nG75*\n%LPD*%\nD10*\nX10000Y10000D01*\nX10000Y10000D01*\n\
+             %LPC*%\nD11*\nG02*\nX10000Y10000D01*\n"
+        );
+    }
+
+    #[test]
+    fn test_group_extended_codes() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ExtendedCode::Unit(crate::extended_codes::Unit::Millimeters).into(),
+            ExtendedCode::LoadPolarity(Polarity::Dark).into(),
+            DCode::SelectAperture(10).into(),
+            ExtendedCode::LoadPolarity(Polarity::Clear).into(),
+        ];
+        let grouped = group_extended_codes(&commands);
+        assert_eq!(grouped.len(), 3);
+        assert_eq!(
+            grouped.to_code_string().unwrap(),
+            "%FSLAX24Y24*MOMM*LPD*%\nD10*\n%LPC*%\n"
+        );
+    }
+
+    #[test]
+    fn test_prune_unused_apertures() {
+        use crate::extended_codes::{Aperture, ApertureDefinition, Circle};
+        use crate::macros::{ApertureMacro, CirclePrimitive};
+
+        let dot = |name: &'static str| -> ExtendedCode {
+            ApertureMacro::new(name)
+                .add_content(CirclePrimitive::new(1.0.into()))
+                .into()
+        };
+        let commands: Vec<Command> = vec![
+            ExtendedCodeBlock::new(vec![dot("USED"), dot("UNUSED")]).into(),
+            ApertureDefinition::new(10, Aperture::Other("USED,1".into())).into(),
+            ApertureDefinition::new(11, Aperture::Other("UNUSED".into())).into(),
+            ApertureDefinition::new(12, Aperture::Circle(Circle::new(0.1))).into(),
+            DCode::SelectAperture(10).into(),
+        ];
+        let unused = find_unused_apertures(&commands);
+        assert_eq!(
+            unused,
+            UnusedApertures {
+                codes: vec![11, 12],
+                macros: vec!["UNUSED".into()],
+            }
+        );
+
+        let pruned = prune_unused_apertures(&commands);
+        assert_eq!(
+            pruned.to_code_string().unwrap(),
+            "%AMUSED*\n1,1,1,0,0*%\n%ADD10USED,1*%\nD10*\n"
+        );
+        assert!(find_unused_apertures(&pruned).is_empty());
+    }
+
+    #[test]
+    fn test_compact_aperture_codes() {
+        use crate::extended_codes::{Aperture, ApertureDefinition, Circle};
+
+        let circle = || Aperture::Circle(Circle::new(0.1));
+        let commands: Vec<Command> = vec![
+            ExtendedCodeBlock::new(vec![ApertureDefinition::new(22, circle()).into()]).into(),
+            ApertureDefinition::new(15, circle()).into(),
+            DCode::SelectAperture(15).into(),
+            DCode::SelectAperture(22).into(),
+            DCode::SelectAperture(99).into(),
+        ];
+        assert_eq!(
+            compact_aperture_codes(&commands).to_code_string().unwrap(),
+            "%ADD10C,0.1*%\n%ADD11C,0.1*%\nD11*\nD10*\nD99*\n"
+        );
+
+        // The undefined D10 must not be merged with a renumbered aperture
+        let commands: Vec<Command> = vec![
+            ApertureDefinition::new(22, circle()).into(),
+            ApertureDefinition::new(15, circle()).into(),
+            DCode::SelectAperture(10).into(),
+            DCode::SelectAperture(22).into(),
+            DCode::SelectAperture(15).into(),
+        ];
+        assert_eq!(
+            compact_aperture_codes(&commands).to_code_string().unwrap(),
+            "%ADD11C,0.1*%\n%ADD12C,0.1*%\nD10*\nD11*\nD12*\n"
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Constructors for common PCB pad apertures.
+//!
+//! Standard apertures are returned as [`Aperture`] values, pads that need an
+//! aperture macro as [`ApertureMacro`] values. Macros must be written before
+//! they are used, an aperture definition refers to them by name with
+//! `Aperture::Other(name)`. Fiducials are returned as the commands that
+//! define and flash them.
+
+use std::borrow::Cow;
+
+use crate::attributes::{ApertureAttribute, ApertureFunction, FiducialScope};
+use crate::coordinates::{CoordinateFormat, Coordinates};
+use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::{Aperture, ApertureDefinition, Circle, Rectangular};
+use crate::function_codes::{DCode, Operation};
+use crate::macros::{
+    ApertureMacro, CenterLinePrimitive, CirclePrimitive, MacroDecimal, ThermalPrimitive,
+    VariableDefinition, VectorLinePrimitive,
+};
+use crate::types::{Command, ExtendedCode};
+use crate::validate::validated;
+use crate::writer::with_aperture_attributes;
+
+/// Check that a dimension is finite and positive.
+fn check_positive(value: f64, what: &str) -> GerberResult<()> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(GerberError::RangeError(format!(
+            "The {} must be positive, not {}",
+            what, value
+        )))
+    }
+}
+
+/// A round through hole pad with a drill hole.
+pub fn circular_pad(diameter: f64, drill: f64) -> GerberResult<Aperture> {
+    check_positive(diameter, "pad diameter")?;
+    Circle::try_new(diameter, Some(drill)).map(Aperture::Circle)
+}
+
+/// A rectangular SMD pad.
+pub fn smd_rectangle(width: f64, height: f64) -> GerberResult<Aperture> {
+    Rectangular::try_new(width, height, None).map(Aperture::Rectangle)
+}
+
+/// Circular fiducial pads flashed at the given positions.
+///
+/// This returns the aperture definition with the code and the
+/// `.AperFunction,FiducialPad` attribute of the scope, the aperture
+/// selection and a flash at each position. The clearance around a fiducial
+/// is not part of the copper layer and must be added to the soldermask.
+pub fn fiducials(
+    code: i32,
+    diameter: f64,
+    scope: FiducialScope,
+    positions: &[(f64, f64)],
+    format: CoordinateFormat,
+) -> GerberResult<Vec<Command>> {
+    check_positive(diameter, "fiducial diameter")?;
+    let definition = ApertureDefinition::try_new(code, Aperture::Circle(Circle::new(diameter)))?;
+    let mut commands = Vec::with_capacity(positions.len() + 4);
+    let function = ApertureAttribute::ApertureFunction(ApertureFunction::FiducialPad(scope));
+    with_aperture_attributes(&mut commands, &[function], |commands| {
+        commands.push(ExtendedCode::ApertureDefinition(definition).into())
+    });
+    commands.push(DCode::SelectAperture(code).into());
+    for &(x, y) in positions {
+        let coords = Coordinates::from_f64_rounded(x, y, format)?;
+        commands.push(DCode::Operation(Operation::Flash(coords)).into());
+    }
+    Ok(commands)
+}
+
+/// A rectangle with rounded corners, centered at the origin.
+///
+/// The corner radius must be positive and at most half of the shorter side.
+pub fn rounded_rectangle<S: Into<Cow<'static, str>>>(
+    name: S,
+    width: f64,
+    height: f64,
+    radius: f64,
+) -> GerberResult<ApertureMacro> {
+    check_positive(width, "width")?;
+    check_positive(height, "height")?;
+    check_positive(radius, "corner radius")?;
+    if 2.0 * radius > width.min(height) {
+        return Err(GerberError::RangeError(
+            "The corner radius must be at most half of the shorter side".into(),
+        ));
+    }
+    let inner_width = width - 2.0 * radius;
+    let inner_height = height - 2.0 * radius;
+    let mut am = ApertureMacro::new(name);
+    for (x, y) in [(width, inner_height), (inner_width, height)] {
+        if x > 0.0 && y > 0.0 {
+            am.add_content_mut(CenterLinePrimitive::new((x.into(), y.into())));
+        }
+    }
+    // Adding zero avoids a negative zero
+    let (cx, cy) = (inner_width / 2.0, inner_height / 2.0);
+    for (x, y) in [(-cx, -cy), (cx, -cy), (cx, cy), (-cx, cy)] {
+        am.add_content_mut(
+            CirclePrimitive::new((2.0 * radius).into())
+                .centered_at(((x + 0.0).into(), (y + 0.0).into())),
+        );
+    }
+    validated(am)
+}
+
+/// A ring with the given outer and inner diameter, centered at the origin.
+///
+/// Unlike a circle with a hole, the inside of the ring is not part of the
+/// pad, it is cleared within the macro.
+pub fn annular_ring<S: Into<Cow<'static, str>>>(
+    name: S,
+    outer_diameter: f64,
+    inner_diameter: f64,
+) -> GerberResult<ApertureMacro> {
+    check_positive(outer_diameter, "outer diameter")?;
+    check_positive(inner_diameter, "inner diameter")?;
+    if inner_diameter >= outer_diameter {
+        return Err(GerberError::RangeError(
+            "The inner diameter must be smaller than the outer".into(),
+        ));
+    }
+    validated(
+        ApertureMacro::new(name)
+            .add_content(CirclePrimitive::new(outer_diameter.into()))
+            .add_content(CirclePrimitive::new(inner_diameter.into()).exposure_on(false)),
+    )
+}
+
+/// A thermal relief pad: a ring with four gaps, centered at the origin.
+pub fn thermal<S: Into<Cow<'static, str>>>(
+    name: S,
+    outer_diameter: f64,
+    inner_diameter: f64,
+    gap: f64,
+) -> GerberResult<ApertureMacro> {
+    check_positive(outer_diameter, "outer diameter")?;
+    check_positive(inner_diameter, "inner diameter")?;
+    check_positive(gap, "gap")?;
+    if gap * std::f64::consts::SQRT_2 >= outer_diameter {
+        return Err(GerberError::RangeError(
+            "The gap must be smaller than the outer diameter divided by sqrt(2)".into(),
+        ));
+    }
+    validated(ApertureMacro::new(name).add_content(ThermalPrimitive::new(
+        inner_diameter.into(),
+        outer_diameter.into(),
+        gap.into(),
+    )))
+}
+
+/// A parametric thermal relief with the given number of spokes, centered at
+/// the origin.
+///
+/// The dimensions are the arguments of the macro: `$1` is the outer
+/// diameter, `$2` the inner diameter, `$3` the spoke width and `$4` the
+/// rotation in degrees, e.g. `Aperture::Other("THR,1.2X0.8X0.25X45".into())`.
+/// With four spokes the macro is a thermal primitive, otherwise a ring with
+/// a cleared line for each spoke.
+pub fn thermal_relief<S: Into<Cow<'static, str>>>(
+    name: S,
+    spokes: u8,
+) -> GerberResult<ApertureMacro> {
+    use MacroDecimal::Variable;
+
+    if spokes == 0 {
+        return Err(GerberError::RangeError(
+            "A thermal relief needs at least one spoke".into(),
+        ));
+    }
+    let mut am = ApertureMacro::new(name);
+    if spokes == 4 {
+        am.add_content_mut(
+            ThermalPrimitive::new(Variable(2), Variable(1), Variable(3)).with_angle(Variable(4)),
+        );
+        return validated(am);
+    }
+    am.add_content_mut(CirclePrimitive::new(Variable(1)));
+    am.add_content_mut(CirclePrimitive::new(Variable(2)).exposure_on(false));
+    for k in 0..u32::from(spokes) {
+        // The rotation of each spoke is a variable after the arguments
+        let angle = if k == 0 {
+            4
+        } else {
+            let angle = 360.0 * f64::from(k) / f64::from(spokes);
+            am.add_content_mut(VariableDefinition::new(4 + k, &format!("$4+{}", angle)));
+            4 + k
+        };
+        am.add_content_mut(
+            VectorLinePrimitive::new((0.0.into(), 0.0.into()), (Variable(1), 0.0.into()))
+                .with_width(Variable(3))
+                .with_angle(Variable(angle))
+                .exposure_on(false),
+        );
+    }
+    validated(am)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_standard_pads() {
+        assert_eq!(
+            circular_pad(1.0, 0.5).unwrap(),
+            Aperture::Circle(Circle::with_hole(1.0, 0.5))
+        );
+        assert!(circular_pad(0.0, 0.5).is_err());
+        assert!(circular_pad(1.0, 1.0).is_err());
+        assert_eq!(
+            smd_rectangle(1.0, 0.5).unwrap(),
+            Aperture::Rectangle(Rectangular::new(1.0, 0.5))
+        );
+        assert!(smd_rectangle(-1.0, 0.5).is_err());
+    }
+
+    #[test]
+    fn test_thermal_relief() {
+        let am = thermal_relief("THR", 4).unwrap();
+        assert_eq!(
+            Command::from(am).to_code_string().unwrap(),
+            "%AMTHR*\n7,0,0,$1,$2,$3,$4*%\n"
+        );
+
+        let am = thermal_relief("THR", 3).unwrap();
+        assert_eq!(
+            Command::from(am.clone()).to_code_string().unwrap(),
+            "%AMTHR*\n\
+             1,1,$1,0,0*\n\
+             1,0,$2,0,0*\n\
+             20,0,$3,0,0,$1,0,$4*\n\
+             $5=$4+120*\n\
+             20,0,$3,0,0,$1,0,$5*\n\
+             $6=$4+240*\n\
+             20,0,$3,0,0,$1,0,$6*%\n"
+        );
+        let bbox = am.bounding_box(&[1.2, 0.8, 0.25, 0.0]).unwrap().unwrap();
+        assert_eq!((bbox.width(), bbox.height()), (1.2, 1.2));
+        assert!(thermal_relief("THR", 0).is_err());
+    }
+
+    #[test]
+    fn test_fiducials() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands = fiducials(
+            10,
+            1.0,
+            FiducialScope::Global,
+            &[(1.0, 1.0), (9.0, 2.5)],
+            cf,
+        )
+        .unwrap();
+        assert_eq!(
+            commands.to_code_string().unwrap(),
+            "%TA.AperFunction,FiducialPad,Global*%\n\
+             %ADD10C,1*%\n\
+             %TD.AperFunction*%\n\
+             D10*\n\
+             X10000Y10000D03*\n\
+             X90000Y25000D03*\n"
+        );
+        assert!(fiducials(5, 1.0, FiducialScope::Local, &[], cf).is_err());
+        assert!(fiducials(10, 0.0, FiducialScope::Local, &[], cf).is_err());
+    }
+
+    #[test]
+    fn test_rounded_rectangle() {
+        let am = rounded_rectangle("RR", 2.0, 1.0, 0.25).unwrap();
+        assert_eq!(
+            Command::from(am).to_code_string().unwrap(),
+            "%AMRR*\n\
+             21,1,2,0.5,0,0,0*\n\
+             21,1,1.5,1,0,0,0*\n\
+             1,1,0.5,-0.75,-0.25*\n\
+             1,1,0.5,0.75,-0.25*\n\
+             1,1,0.5,0.75,0.25*\n\
+             1,1,0.5,-0.75,0.25*%\n"
+        );
+
+        // Fully rounded ends
+        let am = rounded_rectangle("RR", 2.0, 1.0, 0.5).unwrap();
+        assert_eq!(am.content.len(), 5);
+
+        assert!(rounded_rectangle("RR", 2.0, 1.0, 0.6).is_err());
+        assert!(rounded_rectangle("RR", 2.0, 1.0, 0.0).is_err());
+        assert!(rounded_rectangle("1RR", 2.0, 1.0, 0.1).is_err());
+    }
+
+    #[test]
+    fn test_ring_and_thermal() {
+        let am = annular_ring("RING", 1.0, 0.6).unwrap();
+        assert_eq!(
+            Command::from(am).to_code_string().unwrap(),
+            "%AMRING*\n1,1,1,0,0*\n1,0,0.6,0,0*%\n"
+        );
+        assert!(annular_ring("RING", 1.0, 1.0).is_err());
+
+        let am = thermal("THERMAL", 1.0, 0.6, 0.2).unwrap();
+        assert_eq!(
+            Command::from(am).to_code_string().unwrap(),
+            "%AMTHERMAL*\n7,0,0,1,0.6,0.2,0*%\n"
+        );
+        assert!(thermal("THERMAL", 1.0, 0.6, 0.8).is_err());
+    }
+}
@@ -0,0 +1,555 @@
+//! Panelization helpers built on the step and repeat (SR) command.
+
+use std::borrow::Cow;
+use std::mem;
+
+use crate::attributes::ApertureAttribute;
+use crate::coordinates::{CoordinateNumber, Coordinates};
+use crate::decimal::to_decimal;
+use crate::errors::{deprecated_error, GerberError, GerberResult};
+use crate::extended_codes::{Polarity, StepAndRepeat};
+use crate::function_codes::{DCode, MCode, Operation};
+use crate::types::{Command, ExtendedCode, ExtendedCodeBlock, FunctionCode};
+
+/// A rectangular grid of identical board images.
+///
+/// The step distances are measured from the origin of one board image to the
+/// origin of the next one, in the unit of the file. They must be finite and
+/// not negative, and positive in directions with more than one board.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PanelLayout {
+    pub columns: u32,
+    pub rows: u32,
+    pub step_x: f64,
+    pub step_y: f64,
+}
+
+impl PanelLayout {
+    pub fn new(columns: u32, rows: u32, step_x: f64, step_y: f64) -> Self {
+        PanelLayout {
+            columns,
+            rows,
+            step_x,
+            step_y,
+        }
+    }
+
+    /// Create a layout, checking the counts and step distances.
+    pub fn try_new(columns: u32, rows: u32, step_x: f64, step_y: f64) -> GerberResult<Self> {
+        let layout = PanelLayout::new(columns, rows, step_x, step_y);
+        layout.check()?;
+        Ok(layout)
+    }
+
+    /// Compute the step distances from the board size and the gap between
+    /// two adjacent boards.
+    pub fn from_board_size(
+        columns: u32,
+        rows: u32,
+        board_width: f64,
+        board_height: f64,
+        gap: f64,
+    ) -> Self {
+        PanelLayout::new(columns, rows, board_width + gap, board_height + gap)
+    }
+
+    /// Compute the largest grid of boards that fits into a panel of the given
+    /// size.
+    pub fn fit(
+        panel_width: f64,
+        panel_height: f64,
+        board_width: f64,
+        board_height: f64,
+        gap: f64,
+    ) -> GerberResult<Self> {
+        if board_width <= 0.0 || board_height <= 0.0 || gap < 0.0 {
+            return Err(GerberError::RangeError(
+                "Board size must be positive and gap must not be negative".into(),
+            ));
+        }
+        let columns = ((panel_width + gap) / (board_width + gap)).floor();
+        let rows = ((panel_height + gap) / (board_height + gap)).floor();
+        if columns < 1.0 || rows < 1.0 {
+            return Err(GerberError::RangeError(
+                "The board does not fit into the panel".into(),
+            ));
+        }
+        Ok(PanelLayout::from_board_size(
+            columns as u32,
+            rows as u32,
+            board_width,
+            board_height,
+            gap,
+        ))
+    }
+
+    /// The SR command that opens a block for this layout.
+    pub fn step_and_repeat(&self) -> StepAndRepeat {
+        StepAndRepeat::Open {
+            repeat_x: self.columns,
+            repeat_y: self.rows,
+            distance_x: to_decimal(self.step_x),
+            distance_y: to_decimal(self.step_y),
+        }
+    }
+
+    /// Wrap the image of a board in a step and repeat block.
+    ///
+    /// Definitions (format, unit, apertures, macros and file attributes) are
+    /// hoisted in front of the block, so that they are shared by all copies.
+    /// Aperture attributes (TA and TD) keep their place in the image, because
+    /// they also apply to regions. Hoisted aperture definitions get the
+    /// attributes they were defined with. A trailing `M02` is kept at the
+    /// end of the stream.
+    pub fn panelize(&self, commands: &[Command]) -> GerberResult<Vec<Command>> {
+        self.check()?;
+        let parts = Parts::split(commands)?;
+
+        let mut result = parts.definitions;
+        result.push(ExtendedCode::StepAndRepeat(self.step_and_repeat()).into());
+        result.extend(parts.body);
+        result.push(ExtendedCode::StepAndRepeat(StepAndRepeat::Close).into());
+        if parts.end_of_file {
+            result.push(MCode::EndOfFile.into());
+        }
+        Ok(result)
+    }
+
+    /// Replicate the image of a board without using SR.
+    ///
+    /// This is useful for consumers that do not support step and repeat
+    /// blocks. Definitions are emitted only once, the body is repeated for
+    /// every copy with translated coordinates.
+    pub fn explode(&self, commands: &[Command]) -> GerberResult<Vec<Command>> {
+        self.check()?;
+        let parts = Parts::split(commands)?;
+        let step_x = CoordinateNumber::from_f64_rounded(self.step_x)?;
+        let step_y = CoordinateNumber::from_f64_rounded(self.step_y)?;
+        let ends_clear = parts.body.iter().rev().find_map(|c| match c {
+            Command::ExtendedCode(ExtendedCode::LoadPolarity(p)) => Some(*p == Polarity::Clear),
+            _ => None,
+        }) == Some(true);
+
+        let mut result = parts.definitions;
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let dx = step_x
+                    .checked_mul(i64::from(column))
+                    .ok_or_else(overflow_error)?;
+                let dy = step_y
+                    .checked_mul(i64::from(row))
+                    .ok_or_else(overflow_error)?;
+                if (row, column) != (0, 0) {
+                    if ends_clear {
+                        result.push(ExtendedCode::LoadPolarity(Polarity::Dark).into());
+                    }
+                    if let Some(ref body_attributes) = parts.body_attributes {
+                        let mut codes = Vec::new();
+                        sync_attributes(&mut parts.attributes.clone(), body_attributes, &mut codes);
+                        result.extend(codes.into_iter().map(Command::from));
+                    }
+                }
+                translate_body(&parts.body, dx, dy, &mut result)?;
+            }
+        }
+        if parts.end_of_file {
+            result.push(MCode::EndOfFile.into());
+        }
+        Ok(result)
+    }
+
+    fn check(&self) -> GerberResult<()> {
+        if self.columns == 0 || self.rows == 0 {
+            return Err(GerberError::RangeError(
+                "A panel needs at least one column and one row".into(),
+            ));
+        }
+        for (step, count) in [(self.step_x, self.columns), (self.step_y, self.rows)] {
+            if !step.is_finite() || step < 0.0 || (step == 0.0 && count > 1) {
+                return Err(GerberError::RangeError(format!(
+                    "Invalid step distance {} for {} boards, it must be finite and \
+                     positive (or zero for a single board)",
+                    step, count
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A command stream split up into shared definitions and the image body.
+#[derive(Default)]
+struct Parts {
+    definitions: Vec<Command>,
+    body: Vec<Command>,
+    end_of_file: bool,
+    /// The aperture attributes of the original stream, at the end of the
+    /// body after splitting.
+    attributes: Vec<ApertureAttribute>,
+    /// The aperture attributes at the end of the definitions.
+    hoisted_attributes: Vec<ApertureAttribute>,
+    /// The aperture attributes when the body starts.
+    body_attributes: Option<Vec<ApertureAttribute>>,
+}
+
+impl Parts {
+    fn split(commands: &[Command]) -> GerberResult<Self> {
+        let mut parts = Parts::default();
+        for command in commands {
+            match command {
+                Command::ExtendedCode(code) => {
+                    let (mut definitions, mut body) = (Vec::new(), Vec::new());
+                    parts.split_code(code, &mut definitions, &mut body)?;
+                    parts
+                        .definitions
+                        .extend(definitions.into_iter().map(Command::from));
+                    parts.body.extend(body.into_iter().map(Command::from));
+                }
+                Command::ExtendedCodeBlock(block) => {
+                    let (mut definitions, mut body) = (Vec::new(), Vec::new());
+                    for code in &block.codes {
+                        parts.split_code(code, &mut definitions, &mut body)?;
+                    }
+                    if !definitions.is_empty() {
+                        parts
+                            .definitions
+                            .push(ExtendedCodeBlock::new(definitions).into());
+                    }
+                    if !body.is_empty() {
+                        parts.body.push(ExtendedCodeBlock::new(body).into());
+                    }
+                }
+                Command::FunctionCode(FunctionCode::MCode(MCode::EndOfFile)) => {
+                    parts.end_of_file = true;
+                }
+                Command::Deprecated(_) => return Err(deprecated_error()),
+                Command::FunctionCode(_) => {
+                    parts.start_body();
+                    parts.body.push(command.clone());
+                }
+            }
+        }
+        // The body expects the attributes it started with
+        if let Some(ref body_attributes) = parts.body_attributes {
+            let mut codes = Vec::new();
+            sync_attributes(&mut parts.hoisted_attributes, body_attributes, &mut codes);
+            parts
+                .definitions
+                .extend(codes.into_iter().map(Command::from));
+        }
+        Ok(parts)
+    }
+
+    fn split_code(
+        &mut self,
+        code: &ExtendedCode,
+        definitions: &mut Vec<ExtendedCode>,
+        body: &mut Vec<ExtendedCode>,
+    ) -> GerberResult<()> {
+        match code {
+            ExtendedCode::StepAndRepeat(_) => {
+                return Err(GerberError::RangeError(
+                    "Step and repeat blocks cannot be nested".into(),
+                ));
+            }
+            ExtendedCode::CoordinateFormat(_)
+            | ExtendedCode::Unit(_)
+            | ExtendedCode::ApertureMacro(_)
+            | ExtendedCode::FileAttribute(_) => definitions.push(code.clone()),
+            ExtendedCode::ApertureDefinition(_) => {
+                sync_attributes(&mut self.hoisted_attributes, &self.attributes, definitions);
+                definitions.push(code.clone());
+            }
+            ExtendedCode::ApertureAttribute(_) | ExtendedCode::DeleteAttribute(_) => {
+                apply_attribute(&mut self.attributes, code);
+                if self.body_attributes.is_none() {
+                    self.hoisted_attributes = self.attributes.clone();
+                    definitions.push(code.clone());
+                } else {
+                    body.push(code.clone());
+                }
+            }
+            _ => {
+                self.start_body();
+                body.push(code.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn start_body(&mut self) {
+        if self.body_attributes.is_none() {
+            self.body_attributes = Some(self.attributes.clone());
+        }
+    }
+}
+
+/// Apply a TA or TD command to a set of aperture attributes.
+fn apply_attribute(attributes: &mut Vec<ApertureAttribute>, code: &ExtendedCode) {
+    match code {
+        ExtendedCode::ApertureAttribute(attr) => {
+            attributes.retain(|a| mem::discriminant(a) != mem::discriminant(attr));
+            attributes.push(attr.clone());
+        }
+        ExtendedCode::DeleteAttribute(name) => {
+            let name = name.trim_start_matches('.');
+            attributes.retain(|a| !name.is_empty() && a.name().trim_start_matches('.') != name);
+        }
+        _ => {}
+    }
+}
+
+/// Write the TA and TD commands that turn the `current` aperture attributes
+/// into the `target` ones.
+fn sync_attributes(
+    current: &mut Vec<ApertureAttribute>,
+    target: &[ApertureAttribute],
+    codes: &mut Vec<ExtendedCode>,
+) {
+    for attr in current.iter() {
+        if !target.iter().any(|a| a.name() == attr.name()) {
+            codes.push(ExtendedCode::DeleteAttribute(Cow::Borrowed(attr.name())));
+        }
+    }
+    for attr in target {
+        if !current.contains(attr) {
+            codes.push(ExtendedCode::ApertureAttribute(attr.clone()));
+        }
+    }
+    *current = target.to_vec();
+}
+
+fn overflow_error() -> GerberError {
+    GerberError::RangeError("Panel offset is too large".into())
+}
+
+fn translate_body(
+    body: &[Command],
+    dx: CoordinateNumber,
+    dy: CoordinateNumber,
+    result: &mut Vec<Command>,
+) -> GerberResult<()> {
+    // Omitted coordinates are taken from the current point. This is only
+    // safe if the current point was set by the same copy.
+    let (mut x_known, mut y_known) = (false, false);
+    for command in body {
+        let mut command = command.clone();
+        if let Command::FunctionCode(FunctionCode::DCode(DCode::Operation(ref mut op))) = command {
+            let coords = match op {
+                Operation::Interpolate(coords, _) => coords,
+                Operation::Move(coords) => coords,
+                Operation::Flash(coords) => coords,
+            };
+            translate(coords, dx, dy, &mut x_known, &mut y_known)?;
+        }
+        result.push(command);
+    }
+    Ok(())
+}
+
+fn translate(
+    coords: &mut Coordinates,
+    dx: CoordinateNumber,
+    dy: CoordinateNumber,
+    x_known: &mut bool,
+    y_known: &mut bool,
+) -> GerberResult<()> {
+    for (value, delta, known) in [(&mut coords.x, dx, x_known), (&mut coords.y, dy, y_known)] {
+        match value {
+            Some(v) => {
+                *v = v.checked_add(delta).ok_or_else(overflow_error)?;
+                *known = true;
+            }
+            None if !*known => {
+                return Err(GerberError::MissingDataError(
+                    "Coordinate omitted before the current point is defined".into(),
+                ));
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::ApertureFunction;
+    use crate::coordinates::CoordinateFormat;
+    use crate::extended_codes::{Aperture, ApertureDefinition, Circle, Unit};
+    use crate::function_codes::{GCode, RegionMode};
+
+    fn board() -> Vec<Command> {
+        let cf = CoordinateFormat::new(2, 4);
+        vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Circle(Circle::new(0.5)),
+            ))
+            .into(),
+            DCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(0, 0, cf))).into(),
+            DCode::Operation(Operation::Interpolate(Coordinates::at_x(5, cf), None)).into(),
+            MCode::EndOfFile.into(),
+        ]
+    }
+
+    #[test]
+    fn test_from_board_size() {
+        let layout = PanelLayout::from_board_size(2, 3, 10.0, 20.0, 2.0);
+        assert_eq!(layout, PanelLayout::new(2, 3, 12.0, 22.0));
+    }
+
+    #[test]
+    fn test_fit() {
+        let layout = PanelLayout::fit(100.0, 50.0, 30.0, 20.0, 5.0).unwrap();
+        assert_eq!(layout, PanelLayout::new(3, 2, 35.0, 25.0));
+        assert!(PanelLayout::fit(10.0, 10.0, 30.0, 20.0, 5.0).is_err());
+    }
+
+    #[test]
+    fn test_panelize() {
+        let commands = board();
+        let panel = PanelLayout::new(2, 1, 10.0, 0.0)
+            .panelize(&commands)
+            .unwrap();
+        assert_eq!(panel.len(), commands.len() + 2);
+        assert_eq!(&panel[..3], &commands[..3]);
+        assert_eq!(
+            panel[3],
+            ExtendedCode::StepAndRepeat(StepAndRepeat::Open {
+                repeat_x: 2,
+                repeat_y: 1,
+                distance_x: to_decimal(10.0),
+                distance_y: to_decimal(0.0),
+            })
+            .into()
+        );
+        assert_eq!(&panel[4..7], &commands[3..6]);
+        assert_eq!(
+            panel[7],
+            ExtendedCode::StepAndRepeat(StepAndRepeat::Close).into()
+        );
+        assert_eq!(panel[8], MCode::EndOfFile.into());
+    }
+
+    #[test]
+    fn test_panelize_aperture_attributes() {
+        let cf = CoordinateFormat::new(2, 4);
+        let conductor: Command = ExtendedCode::ApertureAttribute(
+            ApertureAttribute::ApertureFunction(ApertureFunction::Conductor),
+        )
+        .into();
+        let delete: Command = ExtendedCode::DeleteAttribute(".AperFunction".into()).into();
+        let definition = |code| -> Command {
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                code,
+                Aperture::Circle(Circle::new(0.5)),
+            ))
+            .into()
+        };
+        let flash: Command = DCode::Operation(Operation::Flash(Coordinates::new(0, 0, cf))).into();
+        let commands: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            conductor.clone(),
+            definition(10),
+            delete.clone(),
+            DCode::SelectAperture(10).into(),
+            flash.clone(),
+            // Applies to the region and to D11
+            conductor.clone(),
+            GCode::RegionMode(RegionMode::Begin).into(),
+            GCode::RegionMode(RegionMode::End).into(),
+            definition(11),
+            DCode::SelectAperture(11).into(),
+            flash.clone(),
+        ];
+        let panel = PanelLayout::new(2, 1, 10.0, 0.0)
+            .panelize(&commands)
+            .unwrap();
+        let sr = ExtendedCode::StepAndRepeat(PanelLayout::new(2, 1, 10.0, 0.0).step_and_repeat());
+        assert_eq!(
+            panel,
+            vec![
+                ExtendedCode::CoordinateFormat(cf).into(),
+                conductor.clone(),
+                definition(10),
+                delete.clone(),
+                // D11 is defined with the attributes of its original place
+                conductor.clone(),
+                definition(11),
+                // The body starts without attributes, like the original image
+                delete.clone(),
+                sr.into(),
+                DCode::SelectAperture(10).into(),
+                flash.clone(),
+                conductor,
+                GCode::RegionMode(RegionMode::Begin).into(),
+                GCode::RegionMode(RegionMode::End).into(),
+                DCode::SelectAperture(11).into(),
+                flash,
+                ExtendedCode::StepAndRepeat(StepAndRepeat::Close).into(),
+            ]
+        );
+
+        // Every exploded copy starts without attributes
+        let exploded = PanelLayout::new(2, 1, 10.0, 0.0)
+            .explode(&commands)
+            .unwrap();
+        assert_eq!(exploded[14], delete);
+        assert_eq!(exploded[15], DCode::SelectAperture(10).into());
+    }
+
+    #[test]
+    fn test_invalid_steps() {
+        for (columns, step) in [(2, 0.0), (1, -1.0), (1, f64::NAN), (3, f64::INFINITY)] {
+            assert!(matches!(
+                PanelLayout::try_new(columns, 1, step, 0.0),
+                Err(GerberError::RangeError(_))
+            ));
+            assert!(PanelLayout::new(columns, 1, step, 0.0)
+                .panelize(&board())
+                .is_err());
+        }
+        assert!(PanelLayout::try_new(1, 1, 0.0, 0.0).is_ok());
+    }
+
+    #[test]
+    fn test_panelize_nested() {
+        let mut commands = board();
+        commands.insert(3, ExtendedCode::StepAndRepeat(StepAndRepeat::Close).into());
+        assert!(PanelLayout::new(2, 2, 1.0, 1.0)
+            .panelize(&commands)
+            .is_err());
+    }
+
+    #[test]
+    fn test_explode() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands = board();
+        let panel = PanelLayout::new(1, 2, 0.0, 10.0)
+            .explode(&commands)
+            .unwrap();
+        assert_eq!(panel.len(), 3 + 2 * 3 + 1);
+        assert_eq!(
+            panel[7],
+            DCode::Operation(Operation::Move(Coordinates::new(0, 10, cf))).into()
+        );
+        assert_eq!(
+            panel[8],
+            DCode::Operation(Operation::Interpolate(Coordinates::at_x(5, cf), None)).into()
+        );
+        assert_eq!(panel[9], MCode::EndOfFile.into());
+    }
+
+    #[test]
+    fn test_explode_undefined_current_point() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> =
+            vec![DCode::Operation(Operation::Flash(Coordinates::at_x(1, cf))).into()];
+        assert!(PanelLayout::new(2, 1, 1.0, 1.0).explode(&commands).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Parallel serialization of large command streams, requires the `rayon`
+//! feature.
+
+use std::io::Write;
+
+use rayon::prelude::*;
+
+use crate::context::SerializationContext;
+use crate::errors::GerberResult;
+use crate::size::EstimatedLen;
+use crate::traits::GerberCode;
+use crate::types::Command;
+
+/// The number of commands that are serialized into one buffer.
+const CHUNK_SIZE: usize = 8192;
+
+/// Serialize the commands on multiple threads.
+///
+/// The commands are split into chunks, which are serialized into separate
+/// buffers in parallel and then written in order. The output is identical to
+/// serializing the commands one by one, but the serialized code is held in
+/// memory until all chunks are done. If several chunks fail, the error of
+/// the first failing chunk in the stream is returned, like in sequential
+/// serialization.
+pub fn serialize_parallel<W: Write>(
+    commands: &[Command],
+    writer: &mut W,
+    ctx: &SerializationContext,
+) -> GerberResult<()> {
+    // Collect the result of every chunk, since collecting into a result
+    // returns the error of whichever chunk fails first in time
+    let results: Vec<GerberResult<Vec<u8>>> = commands
+        .par_chunks(CHUNK_SIZE)
+        .map(|chunk| {
+            let mut buf = Vec::with_capacity(chunk.estimated_len());
+            for command in chunk {
+                command.serialize_with_context(&mut buf, ctx)?;
+            }
+            Ok(buf)
+        })
+        .collect();
+    let buffers = results.into_iter().collect::<GerberResult<Vec<_>>>()?;
+    for buf in buffers {
+        writer.write_all(&buf)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::{CoordinateFormat, Coordinates};
+    use crate::function_codes::{DCode, Operation};
+
+    #[test]
+    fn test_serialize_parallel() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = (0..3 * CHUNK_SIZE as i32)
+            .map(|i| {
+                DCode::Operation(Operation::Flash(Coordinates::new(i % 100, i / 1000, cf))).into()
+            })
+            .collect();
+        let ctx = SerializationContext::new();
+        let mut parallel = Vec::new();
+        serialize_parallel(&commands, &mut parallel, &ctx).unwrap();
+        let mut sequential = Vec::new();
+        commands
+            .serialize_with_context(&mut sequential, &ctx)
+            .unwrap();
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    fn test_serialize_parallel_first_error() {
+        use crate::errors::GerberError;
+        use crate::types::ExtendedCode;
+
+        let mut commands: Vec<Command> = (0..4 * CHUNK_SIZE)
+            .map(|_| ExtendedCode::DeleteAttribute("".into()).into())
+            .collect();
+        commands[CHUNK_SIZE + 1] = ExtendedCode::DeleteAttribute("first error".into()).into();
+        commands[3 * CHUNK_SIZE] = ExtendedCode::DeleteAttribute("second error".into()).into();
+        let ctx = SerializationContext::new();
+        let mut sequential = Vec::new();
+        let expected = commands
+            .serialize_with_context(&mut sequential, &ctx)
+            .unwrap_err();
+        for _ in 0..10 {
+            let error = serialize_parallel(&commands, &mut Vec::new(), &ctx).unwrap_err();
+            assert!(matches!(
+                (&error, &expected),
+                (GerberError::NameError(a), GerberError::NameError(b)) if a == b
+            ));
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Helpers that make generated code easier to read for humans.
+
+use crate::function_codes::GCode;
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Header,
+    Macros,
+    Apertures,
+    Body,
+}
+
+impl Section {
+    fn title(self) -> &'static str {
+        match self {
+            Section::Header => "Header",
+            Section::Macros => "Aperture macros",
+            Section::Apertures => "Aperture definitions",
+            Section::Body => "Image",
+        }
+    }
+
+    /// The section of a command, or `None` for comments and aperture
+    /// attributes, which belong to the section of the following command.
+    fn of(command: &Command) -> Option<Self> {
+        match command {
+            Command::ExtendedCode(code) => Section::of_extended(code),
+            Command::ExtendedCodeBlock(block) => {
+                block.codes.iter().filter_map(Section::of_extended).next()
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::Comment(_))) => None,
+            _ => Some(Section::Body),
+        }
+    }
+
+    fn of_extended(code: &ExtendedCode) -> Option<Self> {
+        match code {
+            ExtendedCode::CoordinateFormat(_)
+            | ExtendedCode::Unit(_)
+            | ExtendedCode::FileAttribute(_) => Some(Section::Header),
+            ExtendedCode::ApertureMacro(_) => Some(Section::Macros),
+            ExtendedCode::ApertureDefinition(_) => Some(Section::Apertures),
+            ExtendedCode::ApertureAttribute(_) | ExtendedCode::DeleteAttribute(_) => None,
+            ExtendedCode::LoadPolarity(_) | ExtendedCode::StepAndRepeat(_) => Some(Section::Body),
+        }
+    }
+}
+
+/// Insert comment banners between the header, aperture macros, aperture
+/// definitions and the image.
+///
+/// A banner is inserted wherever the section changes, the order of the
+/// commands is not changed. Comments and aperture attributes belong to the
+/// section of the command that follows them.
+pub fn with_section_banners(commands: &[Command]) -> Vec<Command> {
+    let mut sections = vec![None; commands.len()];
+    let mut next = None;
+    for (i, command) in commands.iter().enumerate().rev() {
+        next = Section::of(command).or(next);
+        sections[i] = next;
+    }
+
+    let mut result = Vec::with_capacity(commands.len() + 4);
+    let mut current = None;
+    for (command, section) in commands.iter().zip(sections) {
+        if let Some(new) = section.filter(|s| current != Some(*s)) {
+            result.push(GCode::Comment(format!("--- {} ---", new.title()).into()).into());
+            current = section;
+        }
+        result.push(command.clone());
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::{ApertureAttribute, ApertureFunction};
+    use crate::coordinates::{CoordinateFormat, Coordinates};
+    use crate::extended_codes::{Aperture, ApertureDefinition, Circle, Unit};
+    use crate::function_codes::{DCode, MCode, Operation};
+    use crate::macros::{ApertureMacro, CirclePrimitive};
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_with_section_banners() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            GCode::Comment("Generated".into()).into(),
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            ExtendedCode::ApertureMacro(
+                ApertureMacro::new("DOT").add_content(CirclePrimitive::new(1.0.into())),
+            )
+            .into(),
+            ExtendedCode::ApertureAttribute(ApertureAttribute::ApertureFunction(
+                ApertureFunction::ViaPad,
+            ))
+            .into(),
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                10,
+                Aperture::Circle(Circle::new(0.5)),
+            ))
+            .into(),
+            DCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(0, 0, cf))).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let pretty = with_section_banners(&commands);
+        assert_eq!(
+            pretty.to_code_string().unwrap(),
+            "G04 --- Header ---*\n\
+             G04 Generated*\n\
+             %FSLAX24Y24*%\n\
+             %MOMM*%\n\
+             G04 --- Aperture macros ---*\n\
+             %AMDOT*\n1,1,1,0,0*%\n\
+             G04 --- Aperture definitions ---*\n\
+             %TA.AperFunction,ViaPad*%\n\
+             %ADD10C,0.5*%\n\
+             G04 --- Image ---*\n\
+             D10*\n\
+             X0Y0D03*\n\
+             M02*\n"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Board profile (outline) generator.
+
+use crate::attributes::{FileAttribute, FileFunction, Profile};
+use crate::coordinates::{CoordinateFormat, CoordinateNumber, CoordinateOffset, Coordinates};
+use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::{Aperture, ApertureDefinition, Circle};
+use crate::function_codes::{DCode, GCode, InterpolationMode, Operation, QuadrantMode};
+use crate::types::{Command, ExtendedCode};
+
+/// A rectangular board profile with optionally rounded corners.
+///
+/// The profile is drawn counterclockwise, starting at the bottom left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardProfile {
+    pub width: f64,
+    pub height: f64,
+    pub corner_radius: Option<f64>,
+    pub line_width: f64,
+    pub aperture_code: i32,
+    pub origin: (f64, f64),
+    pub plating: Profile,
+}
+
+impl BoardProfile {
+    pub fn new(width: f64, height: f64, line_width: f64) -> Self {
+        BoardProfile {
+            width,
+            height,
+            corner_radius: None,
+            line_width,
+            aperture_code: 10,
+            origin: (0.0, 0.0),
+            plating: Profile::NonPlated,
+        }
+    }
+
+    pub fn with_corner_radius(mut self, radius: f64) -> Self {
+        self.corner_radius = Some(radius);
+        self
+    }
+
+    pub fn with_aperture_code(mut self, code: i32) -> Self {
+        self.aperture_code = code;
+        self
+    }
+
+    pub fn with_origin(mut self, x: f64, y: f64) -> Self {
+        self.origin = (x, y);
+        self
+    }
+
+    pub fn with_plating(mut self, plating: Profile) -> Self {
+        self.plating = plating;
+        self
+    }
+
+    /// Generate the commands of the profile layer: the file function
+    /// attribute, the aperture definition and the draws and arcs of the
+    /// outline.
+    pub fn commands(&self, format: CoordinateFormat) -> GerberResult<Vec<Command>> {
+        if self.width <= 0.0 || self.height <= 0.0 || self.line_width <= 0.0 {
+            return Err(GerberError::RangeError(
+                "Width, height and line width of a profile must be positive".into(),
+            ));
+        }
+        let r = self.corner_radius.unwrap_or(0.0);
+        if r < 0.0 || 2.0 * r > self.width.min(self.height) {
+            return Err(GerberError::RangeError(
+                "Corner radius must not be negative or exceed half the board size".into(),
+            ));
+        }
+
+        let mut commands: Vec<Command> = vec![
+            ExtendedCode::FileAttribute(FileAttribute::FileFunction(FileFunction::Profile(
+                self.plating.clone(),
+            )))
+            .into(),
+            ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                self.aperture_code,
+                Aperture::Circle(Circle::new(self.line_width)),
+            ))
+            .into(),
+            DCode::SelectAperture(self.aperture_code).into(),
+        ];
+        if r > 0.0 {
+            commands.push(GCode::QuadrantMode(QuadrantMode::Multi).into());
+        }
+        commands.push(GCode::InterpolationMode(InterpolationMode::Linear).into());
+
+        let (x0, y0) = self.origin;
+        let (x1, y1) = (x0 + self.width, y0 + self.height);
+        commands.push(
+            DCode::Operation(Operation::Move(Coordinates::from_f64_rounded(
+                x0 + r,
+                y0,
+                format,
+            )?))
+            .into(),
+        );
+
+        // Each side is a straight line followed by a counterclockwise corner
+        // arc, given by its end point and the offset of its center.
+        let sides = [
+            ((x1 - r, y0), (x1, y0 + r), (0.0, r)),
+            ((x1, y1 - r), (x1 - r, y1), (-r, 0.0)),
+            ((x0 + r, y1), (x0, y1 - r), (0.0, -r)),
+            ((x0, y0 + r), (x0 + r, y0), (r, 0.0)),
+        ];
+        let mut linear = true;
+        for &((lx, ly), (ax, ay), (i, j)) in &sides {
+            if !linear {
+                commands.push(GCode::InterpolationMode(InterpolationMode::Linear).into());
+                linear = true;
+            }
+            commands.push(
+                DCode::Operation(Operation::Interpolate(
+                    Coordinates::from_f64_rounded(lx, ly, format)?,
+                    None,
+                ))
+                .into(),
+            );
+            if r > 0.0 {
+                commands.push(
+                    GCode::InterpolationMode(InterpolationMode::CounterclockwiseCircular).into(),
+                );
+                linear = false;
+                let offset = CoordinateOffset::new(
+                    CoordinateNumber::from_f64_rounded(i)?,
+                    CoordinateNumber::from_f64_rounded(j)?,
+                    format,
+                );
+                commands.push(
+                    DCode::Operation(Operation::Interpolate(
+                        Coordinates::from_f64_rounded(ax, ay, format)?,
+                        Some(offset),
+                    ))
+                    .into(),
+                );
+            }
+        }
+        Ok(commands)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::BufWriter;
+
+    use crate::traits::GerberCode;
+
+    #[test]
+    fn test_rectangular_profile() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands = BoardProfile::new(10.0, 5.0, 0.1).commands(cf).unwrap();
+        assert_code!(
+            commands,
+            "%TF.FileFunction,Profile,NP*%\n\
+             %ADD10C,0.1*%\n\
+             D10*\n\
+             G01*\n\
+             X0Y0D02*\n\
+             X100000Y0D01*\n\
+             X100000Y50000D01*\n\
+             X0Y50000D01*\n\
+             X0Y0D01*\n"
+        );
+    }
+
+    #[test]
+    fn test_rounded_profile() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands = BoardProfile::new(10.0, 5.0, 0.1)
+            .with_corner_radius(1.0)
+            .with_aperture_code(11)
+            .commands(cf)
+            .unwrap();
+        assert_code!(
+            commands,
+            "%TF.FileFunction,Profile,NP*%\n\
+             %ADD11C,0.1*%\n\
+             D11*\n\
+             G75*\n\
+             G01*\n\
+             X10000Y0D02*\n\
+             X90000Y0D01*\n\
+             G03*\n\
+             X100000Y10000I0J10000D01*\n\
+             G01*\n\
+             X100000Y40000D01*\n\
+             G03*\n\
+             X90000Y50000I-10000J0D01*\n\
+             G01*\n\
+             X10000Y50000D01*\n\
+             G03*\n\
+             X0Y40000I0J-10000D01*\n\
+             G01*\n\
+             X0Y10000D01*\n\
+             G03*\n\
+             X10000Y0I10000J0D01*\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_profile() {
+        let cf = CoordinateFormat::new(2, 4);
+        assert!(BoardProfile::new(-1.0, 5.0, 0.1).commands(cf).is_err());
+        assert!(BoardProfile::new(10.0, 5.0, 0.1)
+            .with_corner_radius(3.0)
+            .commands(cf)
+            .is_err());
+    }
+}
@@ -0,0 +1,441 @@
+//! Conversion of circular interpolations between single quadrant (G74) and
+//! multi quadrant (G75) mode.
+
+use std::f64::consts::{FRAC_PI_2, PI};
+
+use crate::coordinates::{CoordinateFormat, CoordinateNumber, CoordinateOffset, Coordinates};
+use crate::errors::{deprecated_error, GerberError, GerberResult};
+use crate::function_codes::{DCode, GCode, InterpolationMode, Operation, QuadrantMode};
+use crate::types::{Command, FunctionCode};
+
+/// Tolerance for rounding errors of angles (in radians) and radii, relative
+/// to the radius.
+const TOLERANCE: f64 = 1e-6;
+
+/// Split circular interpolations in multi quadrant mode into arcs of at most
+/// 90 degrees, for readers that only support single quadrant mode.
+///
+/// The arcs are split where they cross the horizontal or vertical line
+/// through their center, a full circle results in four arcs. All G75
+/// commands are replaced by G74 and the offsets are written unsigned. Arcs
+/// before the first quadrant mode command result in a `MissingDataError`,
+/// deprecated codes in an `UnsupportedError`.
+pub fn to_single_quadrant(commands: &[Command]) -> GerberResult<Vec<Command>> {
+    let mut result = Vec::with_capacity(commands.len());
+    let mut state = State::default();
+    for command in commands {
+        let (coords, offset) = match state.update(command)? {
+            Some(Arc {
+                coords,
+                offset,
+                quadrant: QuadrantMode::Multi,
+            }) => (coords, offset),
+            _ => {
+                result.push(match command {
+                    Command::FunctionCode(FunctionCode::GCode(GCode::QuadrantMode(_))) => {
+                        GCode::QuadrantMode(QuadrantMode::Single).into()
+                    }
+                    _ => command.clone(),
+                });
+                state.advance(command);
+                continue;
+            }
+        };
+        let start = state.point()?;
+        let center = (start.0 + f64::from(offset.0), start.1 + f64::from(offset.1));
+        let end = state.end_point(coords)?;
+        let radius = (start.0 - center.0).hypot(start.1 - center.1);
+        let a0 = (start.1 - center.1).atan2(start.0 - center.0);
+        let sweep = sweep(start, end, center, state.clockwise())?;
+
+        // Points where the arc crosses an axis through the center
+        let step = if state.clockwise() { -1.0 } else { 1.0 };
+        let mut k = if state.clockwise() {
+            (a0 / FRAC_PI_2 - TOLERANCE).ceil() - 1.0
+        } else {
+            (a0 / FRAC_PI_2 + TOLERANCE).floor() + 1.0
+        };
+        let mut from = start;
+        loop {
+            let travelled = step * (k * FRAC_PI_2 - a0);
+            if travelled >= sweep - TOLERANCE {
+                break;
+            }
+            let (dx, dy) = match (k as i64).rem_euclid(4) {
+                0 => (radius, 0.0),
+                1 => (0.0, radius),
+                2 => (-radius, 0.0),
+                _ => (0.0, -radius),
+            };
+            let x = rounded(center.0 + dx, coords.format)?;
+            let y = rounded(center.1 + dy, coords.format)?;
+            result.push(arc(Coordinates::new(x, y, coords.format), from, center)?);
+            from = (x.into(), y.into());
+            k += step;
+        }
+        result.push(arc(coords.clone(), from, center)?);
+        state.advance(command);
+    }
+    Ok(result)
+}
+
+/// Convert circular interpolations in single quadrant mode to multi
+/// quadrant mode, and join consecutive arcs around the same center.
+///
+/// In single quadrant mode the offset is unsigned, the center is the one of
+/// the four candidates that is equally far from the start and end point and
+/// gives an arc of at most 90 degrees. All G74 commands are replaced by G75.
+/// Arcs without such a center result in a `RangeError`, arcs before the
+/// first quadrant mode command in a `MissingDataError` and deprecated codes
+/// in an `UnsupportedError`.
+pub fn to_multi_quadrant(commands: &[Command]) -> GerberResult<Vec<Command>> {
+    let mut result: Vec<Command> = Vec::with_capacity(commands.len());
+    let mut state = State::default();
+    // The center and sweep of the last arc in the result, if it can be
+    // extended by the next one
+    let mut joinable: Option<((f64, f64), f64)> = None;
+    for command in commands {
+        let (coords, offset, quadrant) = match state.update(command)? {
+            Some(Arc {
+                coords,
+                offset,
+                quadrant,
+            }) => (coords, offset, quadrant),
+            None => {
+                result.push(match command {
+                    Command::FunctionCode(FunctionCode::GCode(GCode::QuadrantMode(_))) => {
+                        GCode::QuadrantMode(QuadrantMode::Multi).into()
+                    }
+                    _ => command.clone(),
+                });
+                state.advance(command);
+                joinable = None;
+                continue;
+            }
+        };
+        let start = state.point()?;
+        let end = state.end_point(coords)?;
+        let clockwise = state.clockwise();
+        let (i, j) = (f64::from(offset.0), f64::from(offset.1));
+        let center = match quadrant {
+            QuadrantMode::Multi => (start.0 + i, start.1 + j),
+            QuadrantMode::Single => single_quadrant_center(start, end, (i, j), clockwise)?,
+        };
+        let sweep = sweep(start, end, center, clockwise)?;
+
+        let joined = match joinable {
+            Some((previous_center, previous_sweep))
+                if same_point(previous_center, center)
+                    && previous_sweep + sweep <= 2.0 * PI + TOLERANCE =>
+            {
+                Some((previous_center, previous_sweep + sweep))
+            }
+            _ => None,
+        };
+        match (joined, result.last_mut()) {
+            (
+                Some(joined),
+                Some(Command::FunctionCode(FunctionCode::DCode(DCode::Operation(
+                    Operation::Interpolate(previous_coords, _),
+                )))),
+            ) => {
+                *previous_coords = Coordinates::new(
+                    rounded(end.0, coords.format)?,
+                    rounded(end.1, coords.format)?,
+                    coords.format,
+                );
+                joinable = Some(joined);
+            }
+            _ => {
+                let offset = CoordinateOffset::new(
+                    rounded(center.0 - start.0, coords.format)?,
+                    rounded(center.1 - start.1, coords.format)?,
+                    coords.format,
+                );
+                result.push(
+                    DCode::Operation(Operation::Interpolate(coords.clone(), Some(offset))).into(),
+                );
+                joinable = Some((center, sweep));
+            }
+        }
+        state.advance(command);
+    }
+    Ok(result)
+}
+
+/// A circular interpolation, with the offset of the center.
+struct Arc<'a> {
+    coords: &'a Coordinates,
+    offset: (CoordinateNumber, CoordinateNumber),
+    quadrant: QuadrantMode,
+}
+
+/// The graphics state needed to follow arcs.
+#[derive(Default)]
+struct State {
+    point: (Option<CoordinateNumber>, Option<CoordinateNumber>),
+    interpolation: Option<InterpolationMode>,
+    quadrant: Option<QuadrantMode>,
+}
+
+impl State {
+    /// Update the modes, and return the arc if the command is a circular
+    /// interpolation.
+    fn update<'a>(&mut self, command: &'a Command) -> GerberResult<Option<Arc<'a>>> {
+        match command {
+            Command::FunctionCode(FunctionCode::GCode(GCode::InterpolationMode(mode))) => {
+                self.interpolation = Some(*mode);
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::QuadrantMode(mode))) => {
+                self.quadrant = Some(*mode);
+            }
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(
+                Operation::Interpolate(coords, offset),
+            ))) if self.interpolation.is_some()
+                && self.interpolation != Some(InterpolationMode::Linear) =>
+            {
+                let quadrant = self.quadrant.ok_or_else(|| {
+                    GerberError::MissingDataError(
+                        "Circular interpolation before the quadrant mode is set".into(),
+                    )
+                })?;
+                let zero = CoordinateNumber::new(0);
+                let offset = offset
+                    .as_ref()
+                    .map_or((zero, zero), |o| (o.x.unwrap_or(zero), o.y.unwrap_or(zero)));
+                return Ok(Some(Arc {
+                    coords,
+                    offset,
+                    quadrant,
+                }));
+            }
+            Command::Deprecated(_) => return Err(deprecated_error()),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    /// Move the current point to the end of an operation.
+    fn advance(&mut self, command: &Command) {
+        if let Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) = command {
+            let coords = match op {
+                Operation::Interpolate(coords, _)
+                | Operation::Move(coords)
+                | Operation::Flash(coords) => coords,
+            };
+            self.point = (coords.x.or(self.point.0), coords.y.or(self.point.1));
+        }
+    }
+
+    fn clockwise(&self) -> bool {
+        self.interpolation == Some(InterpolationMode::ClockwiseCircular)
+    }
+
+    fn point(&self) -> GerberResult<(f64, f64)> {
+        match self.point {
+            (Some(x), Some(y)) => Ok((x.into(), y.into())),
+            _ => Err(GerberError::MissingDataError(
+                "Circular interpolation before the current point is set".into(),
+            )),
+        }
+    }
+
+    fn end_point(&self, coords: &Coordinates) -> GerberResult<(f64, f64)> {
+        match (coords.x.or(self.point.0), coords.y.or(self.point.1)) {
+            (Some(x), Some(y)) => Ok((x.into(), y.into())),
+            _ => Err(GerberError::MissingDataError(
+                "Circular interpolation before the current point is set".into(),
+            )),
+        }
+    }
+}
+
+/// The angle of an arc in its direction, a full circle if the start and end
+/// point are the same.
+pub(crate) fn sweep(
+    start: (f64, f64),
+    end: (f64, f64),
+    center: (f64, f64),
+    clockwise: bool,
+) -> GerberResult<f64> {
+    let radius = (start.0 - center.0).hypot(start.1 - center.1);
+    if radius == 0.0 {
+        return Err(GerberError::RangeError(
+            "The center of an arc must differ from its start".into(),
+        ));
+    }
+    let a0 = (start.1 - center.1).atan2(start.0 - center.0);
+    let a1 = (end.1 - center.1).atan2(end.0 - center.0);
+    let sweep = if clockwise { a0 - a1 } else { a1 - a0 }.rem_euclid(2.0 * PI);
+    if same_point(start, end) || sweep > 2.0 * PI - TOLERANCE {
+        Ok(2.0 * PI)
+    } else {
+        Ok(sweep)
+    }
+}
+
+fn single_quadrant_center(
+    start: (f64, f64),
+    end: (f64, f64),
+    (i, j): (f64, f64),
+    clockwise: bool,
+) -> GerberResult<(f64, f64)> {
+    let radius = i.hypot(j);
+    [(i, j), (-i, j), (i, -j), (-i, -j)]
+        .iter()
+        .map(|&(i, j)| (start.0 + i, start.1 + j))
+        .filter(|&center| {
+            let end_radius = (end.0 - center.0).hypot(end.1 - center.1);
+            (end_radius - radius).abs() <= TOLERANCE * radius.max(1.0)
+                && !same_point(start, end)
+                && matches!(sweep(start, end, center, clockwise), Ok(s) if s <= FRAC_PI_2 + TOLERANCE)
+        })
+        .min_by(|a, b| {
+            let error = |c: &(f64, f64)| ((end.0 - c.0).hypot(end.1 - c.1) - radius).abs();
+            error(a).partial_cmp(&error(b)).unwrap()
+        })
+        .ok_or_else(|| {
+            GerberError::RangeError(
+                "No center of a single quadrant arc matches its start, end and offset".into(),
+            )
+        })
+}
+
+fn same_point(a: (f64, f64), b: (f64, f64)) -> bool {
+    (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+}
+
+fn rounded(value: f64, format: CoordinateFormat) -> GerberResult<CoordinateNumber> {
+    CoordinateNumber::from_f64_rounded(value)?.round_to_format(&format)
+}
+
+/// A single quadrant arc from `from` to `to` around `center`.
+fn arc(to: Coordinates, from: (f64, f64), center: (f64, f64)) -> GerberResult<Command> {
+    let offset = CoordinateOffset::new(
+        rounded((center.0 - from.0).abs(), to.format)?,
+        rounded((center.1 - from.1).abs(), to.format)?,
+        to.format,
+    );
+    Ok(DCode::Operation(Operation::Interpolate(to, Some(offset))).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::traits::GerberCodeExt;
+
+    fn circle(quadrant: QuadrantMode) -> Vec<Command> {
+        let cf = CoordinateFormat::new(2, 4);
+        vec![
+            GCode::QuadrantMode(quadrant).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(1, 0, cf))).into(),
+            GCode::InterpolationMode(InterpolationMode::CounterclockwiseCircular).into(),
+        ]
+    }
+
+    #[test]
+    fn test_to_single_quadrant() {
+        let cf = CoordinateFormat::new(2, 4);
+        let mut commands = circle(QuadrantMode::Multi);
+        commands.push(
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, 0, cf),
+                Some(CoordinateOffset::new(-1, 0, cf)),
+            ))
+            .into(),
+        );
+        let single = to_single_quadrant(&commands).unwrap();
+        assert_eq!(
+            single.to_code_string().unwrap(),
+            "G74*\nX10000Y0D02*\nG03*\n\
+             X0Y10000I10000J0D01*\n\
+             X-10000Y0I0J10000D01*\n\
+             X0Y-10000I10000J0D01*\n\
+             X10000Y0I0J10000D01*\n"
+        );
+
+        // Joining the quarters gives the full circle again
+        assert_eq!(to_multi_quadrant(&single).unwrap(), commands);
+    }
+
+    #[test]
+    fn test_to_single_quadrant_clockwise() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            GCode::QuadrantMode(QuadrantMode::Multi).into(),
+            DCode::Operation(Operation::Move(Coordinates::new(0, 1, cf))).into(),
+            GCode::InterpolationMode(InterpolationMode::ClockwiseCircular).into(),
+            // A half circle from the top to the bottom, on the right side
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(0, -1, cf),
+                Some(CoordinateOffset::new(0, -1, cf)),
+            ))
+            .into(),
+        ];
+        assert_eq!(
+            to_single_quadrant(&commands)
+                .unwrap()
+                .to_code_string()
+                .unwrap(),
+            "G74*\nX0Y10000D02*\nG02*\n\
+             X10000Y0I0J10000D01*\n\
+             X0Y-10000I10000J0D01*\n"
+        );
+    }
+
+    #[test]
+    fn test_to_multi_quadrant() {
+        let cf = CoordinateFormat::new(2, 4);
+        let mut commands = circle(QuadrantMode::Single);
+        // A quarter to the top, then a quarter of a circle above it
+        commands.push(
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(0, 1, cf),
+                Some(CoordinateOffset::new(1, 0, cf)),
+            ))
+            .into(),
+        );
+        commands.push(
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, 2, cf),
+                Some(CoordinateOffset::new(0, 1, cf)),
+            ))
+            .into(),
+        );
+        assert_eq!(
+            to_multi_quadrant(&commands)
+                .unwrap()
+                .to_code_string()
+                .unwrap(),
+            "G75*\nX10000Y0D02*\nG03*\n\
+             X0Y10000I-10000J0D01*\n\
+             X10000Y20000I0J10000D01*\n"
+        );
+
+        // No center is equally far from both points
+        let mut commands = circle(QuadrantMode::Single);
+        commands.push(
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(0, 3, cf),
+                Some(CoordinateOffset::new(1, 0, cf)),
+            ))
+            .into(),
+        );
+        assert!(matches!(
+            to_multi_quadrant(&commands),
+            Err(GerberError::RangeError(_))
+        ));
+
+        let commands = &circle(QuadrantMode::Single)[1..];
+        assert!(to_multi_quadrant(commands).is_ok());
+        let mut commands = commands.to_vec();
+        commands.push(
+            DCode::Operation(Operation::Interpolate(Coordinates::new(0, 1, cf), None)).into(),
+        );
+        assert!(matches!(
+            to_multi_quadrant(&commands),
+            Err(GerberError::MissingDataError(_))
+        ));
+    }
+}
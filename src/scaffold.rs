@@ -0,0 +1,107 @@
+//! Skeletons of complete Gerber files.
+
+use crate::attributes::{FileAttribute, FileFunction, FilePolarity, GenerationSoftware};
+use crate::coordinates::CoordinateFormat;
+use crate::extended_codes::Unit;
+use crate::function_codes::MCode;
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// Create the smallest valid file with the given function: the standard
+/// file attributes, the coordinate format, the unit and `M02`.
+///
+/// The body of the file can be added with [`insert_body`].
+///
+/// ```
+/// use gerber_types::*;
+///
+/// let cf = CoordinateFormat::new(2, 6);
+/// let mut commands = minimal_file(
+///     FileFunction::Profile(Profile::NonPlated),
+///     Unit::Millimeters,
+///     cf,
+///     GenerationSoftware::new("Vendor", "Application", None),
+/// );
+/// insert_body(
+///     &mut commands,
+///     vec![DCode::Operation(Operation::Flash(Coordinates::new(0, 0, cf))).into()],
+/// );
+/// ```
+pub fn minimal_file(
+    function: FileFunction,
+    unit: Unit,
+    format: CoordinateFormat,
+    software: GenerationSoftware,
+) -> Vec<Command> {
+    vec![
+        ExtendedCode::FileAttribute(FileAttribute::GenerationSoftware(software)).into(),
+        ExtendedCode::FileAttribute(FileAttribute::FileFunction(function)).into(),
+        ExtendedCode::FileAttribute(FileAttribute::FilePolarity(FilePolarity::Positive)).into(),
+        ExtendedCode::CoordinateFormat(format).into(),
+        ExtendedCode::Unit(unit).into(),
+        FunctionCode::MCode(MCode::EndOfFile).into(),
+    ]
+}
+
+/// Insert body commands before the final `M02` of a file, or append them
+/// if the file does not end with `M02`.
+pub fn insert_body<I: IntoIterator<Item = Command>>(commands: &mut Vec<Command>, body: I) {
+    let index = match commands.last() {
+        Some(Command::FunctionCode(FunctionCode::MCode(MCode::EndOfFile))) => commands.len() - 1,
+        _ => commands.len(),
+    };
+    commands.splice(index..index, body);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::attributes::{CopperType, ExtendedPosition};
+    use crate::checks::check_coordinate_format;
+    use crate::coordinates::Coordinates;
+    use crate::extended_codes::{Aperture, ApertureDefinition, Circle};
+    use crate::function_codes::{DCode, Operation};
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_minimal_file() {
+        let cf = CoordinateFormat::new(2, 6);
+        let mut commands = minimal_file(
+            FileFunction::Copper {
+                layer: 1,
+                pos: ExtendedPosition::Top,
+                copper_type: Some(CopperType::Signal),
+            },
+            Unit::Millimeters,
+            cf,
+            GenerationSoftware::new("Vendor", "App", Some("1.0")),
+        );
+        assert_eq!(
+            commands.to_code_string().unwrap(),
+            "%TF.GenerationSoftware,Vendor,App,1.0*%\n\
+             %TF.FileFunction,Copper,L1,Top,Signal*%\n\
+             %TF.FilePolarity,Positive*%\n\
+             %FSLAX26Y26*%\n\
+             %MOMM*%\n\
+             M02*\n"
+        );
+
+        insert_body(
+            &mut commands,
+            vec![
+                ExtendedCode::ApertureDefinition(ApertureDefinition::new(
+                    10,
+                    Aperture::Circle(Circle::new(0.5)),
+                ))
+                .into(),
+                DCode::SelectAperture(10).into(),
+                DCode::Operation(Operation::Flash(Coordinates::new(1, 2, cf))).into(),
+            ],
+        );
+        assert!(commands.ends_with(&[
+            DCode::Operation(Operation::Flash(Coordinates::new(1, 2, cf))).into(),
+            MCode::EndOfFile.into(),
+        ]));
+        assert!(check_coordinate_format(&commands).is_empty());
+    }
+}
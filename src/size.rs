@@ -0,0 +1,163 @@
+//! Estimates of the serialized size of commands, to pre-allocate buffers.
+
+use crate::coordinates::{CoordinateFormat, CoordinateNumber};
+use crate::deprecated::DeprecatedCode;
+use crate::function_codes::{DCode, GCode, Operation};
+use crate::types::{Command, ExtendedCode, ExtendedCodeBlock, FunctionCode};
+
+/// The estimated size of a line ending.
+const LINE_ENDING: usize = 1;
+
+/// The estimated size of extended codes with variable content, like
+/// aperture definitions or attributes.
+const EXTENDED_CODE: usize = 32;
+
+/// The estimated size of a single aperture macro primitive.
+const MACRO_PRIMITIVE: usize = 24;
+
+/// An estimate of the number of bytes that serializing a value produces.
+///
+/// The estimate assumes the default serialization context. It is meant for
+/// pre-allocating buffers, e.g. with `Vec::with_capacity`, and tends to be
+/// slightly larger than the actual size.
+pub trait EstimatedLen {
+    fn estimated_len(&self) -> usize;
+}
+
+impl EstimatedLen for Command {
+    fn estimated_len(&self) -> usize {
+        match self {
+            Command::FunctionCode(code) => code.estimated_len(),
+            Command::ExtendedCode(code) => code.estimated_len(),
+            Command::ExtendedCodeBlock(block) => block.estimated_len(),
+            Command::Deprecated(code) => code.estimated_len(),
+        }
+    }
+}
+
+impl EstimatedLen for FunctionCode {
+    fn estimated_len(&self) -> usize {
+        match self {
+            FunctionCode::DCode(DCode::Operation(op)) => op.estimated_len(),
+            FunctionCode::DCode(DCode::SelectAperture(_)) => 5 + LINE_ENDING,
+            FunctionCode::GCode(GCode::Comment(comment)) => 5 + comment.len() + LINE_ENDING,
+            FunctionCode::GCode(_) | FunctionCode::MCode(_) => 4 + LINE_ENDING,
+        }
+    }
+}
+
+impl EstimatedLen for Operation {
+    fn estimated_len(&self) -> usize {
+        let (coords, offset) = match self {
+            Operation::Interpolate(coords, offset) => (coords, offset.as_ref()),
+            Operation::Move(coords) | Operation::Flash(coords) => (coords, None),
+        };
+        let mut len = 4 + LINE_ENDING;
+        len += coordinate_len(coords.x, coords.format) + coordinate_len(coords.y, coords.format);
+        if let Some(offset) = offset {
+            len += coordinate_len(offset.x, offset.format);
+            len += coordinate_len(offset.y, offset.format);
+        }
+        len
+    }
+}
+
+fn coordinate_len(number: Option<CoordinateNumber>, format: CoordinateFormat) -> usize {
+    match number {
+        // Axis letter and the digits without leading zero suppression
+        Some(_) => 1 + usize::from(format.integer) + usize::from(format.decimal),
+        None => 0,
+    }
+}
+
+impl EstimatedLen for ExtendedCode {
+    fn estimated_len(&self) -> usize {
+        let body = match self {
+            ExtendedCode::CoordinateFormat(_) => 12,
+            ExtendedCode::Unit(_) | ExtendedCode::LoadPolarity(_) => 5,
+            ExtendedCode::ApertureMacro(am) => {
+                4 + am.name.len() + am.content.len() * (MACRO_PRIMITIVE + LINE_ENDING)
+            }
+            ExtendedCode::DeleteAttribute(name) => 4 + name.len(),
+            _ => EXTENDED_CODE,
+        };
+        body + 2 + LINE_ENDING
+    }
+}
+
+impl EstimatedLen for ExtendedCodeBlock {
+    fn estimated_len(&self) -> usize {
+        // Only one pair of delimiters and one line ending for all codes
+        let codes: usize = self
+            .codes
+            .iter()
+            .map(|code| code.estimated_len() - 2 - LINE_ENDING)
+            .sum();
+        codes + 2 + LINE_ENDING
+    }
+}
+
+impl EstimatedLen for DeprecatedCode {
+    fn estimated_len(&self) -> usize {
+        match self {
+            DeprecatedCode::CoordinatesOnly(coords) => {
+                coordinate_len(coords.x, coords.format)
+                    + coordinate_len(coords.y, coords.format)
+                    + 1
+                    + LINE_ENDING
+            }
+            DeprecatedCode::CoordinatesWithOffset(coords, offset) => {
+                coordinate_len(coords.x, coords.format)
+                    + coordinate_len(coords.y, coords.format)
+                    + coordinate_len(offset.x, offset.format)
+                    + coordinate_len(offset.y, offset.format)
+                    + 1
+                    + LINE_ENDING
+            }
+            _ => 16,
+        }
+    }
+}
+
+impl EstimatedLen for [Command] {
+    fn estimated_len(&self) -> usize {
+        self.iter().map(EstimatedLen::estimated_len).sum()
+    }
+}
+
+impl EstimatedLen for Vec<Command> {
+    fn estimated_len(&self) -> usize {
+        self.as_slice().estimated_len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::Coordinates;
+    use crate::extended_codes::Unit;
+    use crate::function_codes::MCode;
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_estimated_len() {
+        let cf = CoordinateFormat::new(2, 4);
+        let mut commands: Vec<Command> = vec![
+            GCode::Comment("Estimated".into()).into(),
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            DCode::SelectAperture(10).into(),
+        ];
+        for i in 0..100 {
+            let coords = Coordinates::new(i % 10, i / 10, cf);
+            commands.push(DCode::Operation(Operation::Flash(coords)).into());
+        }
+        commands.push(MCode::EndOfFile.into());
+
+        let actual = commands.to_code_string().unwrap().len();
+        let estimated = commands.estimated_len();
+        assert!(estimated >= actual);
+        assert!(estimated < actual * 3 / 2);
+    }
+}
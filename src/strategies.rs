@@ -0,0 +1,165 @@
+//! Strategies for property based testing with `proptest`, requires the
+//! `proptest` feature.
+//!
+//! All strategies generate values that serialize without errors and without
+//! rounding, e.g. coordinate numbers fit into their coordinate format and
+//! decimals have at most four decimal places.
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::coordinates::{CoordinateFormat, CoordinateNumber, Coordinates, DECIMAL_PLACES_CHARS};
+use crate::extended_codes::{
+    Aperture, ApertureDefinition, Circle, Obround, Polygon, Rectangular, Unit,
+};
+use crate::function_codes::{DCode, GCode, InterpolationMode, MCode, Operation};
+use crate::macros::{ApertureMacro, CenterLinePrimitive, CirclePrimitive, MacroContent};
+use crate::types::{Command, ExtendedCode};
+
+/// A positive decimal with at most four decimal places.
+fn arb_size() -> impl Strategy<Value = f64> {
+    (1..100_000u32).prop_map(|n| f64::from(n) / 10_000.0)
+}
+
+/// A decimal with at most four decimal places between -100 and 100.
+fn arb_offset() -> impl Strategy<Value = f64> {
+    (-1_000_000..=1_000_000i32).prop_map(|n| f64::from(n) / 10_000.0)
+}
+
+/// A coordinate format with 1 to 6 integer and 4 to 6 decimal places.
+pub fn arb_coordinate_format() -> impl Strategy<Value = CoordinateFormat> {
+    (1..=6u8, 4..=6u8).prop_map(|(integer, decimal)| CoordinateFormat::new(integer, decimal))
+}
+
+/// A coordinate number that can be written exactly in the given format.
+pub fn arb_coordinate_number(format: CoordinateFormat) -> impl Strategy<Value = CoordinateNumber> {
+    let limit = 10_i64.pow(u32::from(format.integer) + u32::from(format.decimal));
+    let factor = 10_i64.pow(u32::from(DECIMAL_PLACES_CHARS - format.decimal));
+    (-limit + 1..limit).prop_map(move |n| CoordinateNumber::new(n * factor))
+}
+
+/// Coordinates with X and Y in the given format.
+pub fn arb_coordinates(format: CoordinateFormat) -> impl Strategy<Value = Coordinates> {
+    (arb_coordinate_number(format), arb_coordinate_number(format))
+        .prop_map(move |(x, y)| Coordinates::new(x, y, format))
+}
+
+/// A standard aperture (circle, rectangle, obround or polygon).
+pub fn arb_aperture() -> impl Strategy<Value = Aperture> {
+    prop_oneof![
+        arb_size().prop_map(|d| Aperture::Circle(Circle::new(d))),
+        (arb_size(), arb_size()).prop_map(|(x, y)| Aperture::Rectangle(Rectangular::new(x, y))),
+        (arb_size(), arb_size()).prop_map(|(x, y)| Aperture::Obround(Obround::new(x, y))),
+        (arb_size(), 3..=12u8).prop_map(|(d, v)| Aperture::Polygon(Polygon::new(d, v))),
+    ]
+}
+
+/// An aperture definition with a code of 10 or higher.
+pub fn arb_aperture_definition() -> impl Strategy<Value = ApertureDefinition> {
+    (10..10_000i32, arb_aperture())
+        .prop_map(|(code, aperture)| ApertureDefinition::new(code, aperture))
+}
+
+/// An aperture macro with a valid name and one to four circle and center
+/// line primitives.
+pub fn arb_aperture_macro() -> impl Strategy<Value = ApertureMacro> {
+    let primitive = prop_oneof![
+        (arb_size(), arb_offset(), arb_offset()).prop_map(|(d, x, y)| MacroContent::from(
+            CirclePrimitive::new(d.into()).centered_at((x.into(), y.into()))
+        )),
+        (arb_size(), arb_size(), arb_offset(), arb_offset()).prop_map(|(w, h, x, y)| {
+            MacroContent::from(
+                CenterLinePrimitive::new((w.into(), h.into())).centered_at((x.into(), y.into())),
+            )
+        }),
+    ];
+    ("[A-Z][A-Z0-9_]{0,15}", vec(primitive, 1..=4)).prop_map(|(name, content)| {
+        content
+            .into_iter()
+            .fold(ApertureMacro::new(name), ApertureMacro::add_content)
+    })
+}
+
+/// A complete command stream: A header, aperture definitions, operations
+/// with the defined apertures, and the end of file.
+///
+/// The operations start with linear interpolation mode (G01) and a move to
+/// the origin, so that every interpolation has a mode and a current point.
+/// Aperture macros, arcs and regions are not generated.
+pub fn arb_command_stream() -> impl Strategy<Value = Vec<Command>> {
+    arb_coordinate_format().prop_flat_map(|format| {
+        let operation = prop_oneof![
+            arb_coordinates(format).prop_map(|c| Operation::Interpolate(c, None)),
+            arb_coordinates(format).prop_map(Operation::Move),
+            arb_coordinates(format).prop_map(Operation::Flash),
+        ];
+        (
+            prop_oneof![Just(Unit::Millimeters), Just(Unit::Inches)],
+            vec(arb_aperture(), 1..=8),
+            vec(
+                (any::<prop::sample::Index>(), vec(operation, 1..=16)),
+                1..=8,
+            ),
+        )
+            .prop_map(move |(unit, apertures, blocks)| {
+                let mut commands: Vec<Command> = vec![
+                    ExtendedCode::CoordinateFormat(format).into(),
+                    ExtendedCode::Unit(unit).into(),
+                ];
+                let count = apertures.len();
+                for (i, aperture) in apertures.into_iter().enumerate() {
+                    let definition = ApertureDefinition::new(10 + i as i32, aperture);
+                    commands.push(ExtendedCode::ApertureDefinition(definition).into());
+                }
+                commands.push(GCode::InterpolationMode(InterpolationMode::Linear).into());
+                let origin = Coordinates::new(0, 0, format);
+                commands.push(DCode::Operation(Operation::Move(origin)).into());
+                for (aperture, operations) in blocks {
+                    commands.push(DCode::SelectAperture(10 + aperture.index(count) as i32).into());
+                    commands.extend(operations.into_iter().map(|op| DCode::Operation(op).into()));
+                }
+                commands.push(MCode::EndOfFile.into());
+                commands
+            })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::traits::GerberCodeExt;
+    use crate::types::FunctionCode;
+
+    proptest! {
+        #[test]
+        fn test_command_stream_serializes(commands in arb_command_stream()) {
+            prop_assert!(commands.to_code_string().is_ok());
+        }
+
+        #[test]
+        fn test_command_stream_interpolates_in_linear_mode(commands in arb_command_stream()) {
+            let (mut mode, mut moved) = (None, false);
+            for command in &commands {
+                match command {
+                    Command::FunctionCode(FunctionCode::GCode(GCode::InterpolationMode(m))) => {
+                        mode = Some(*m);
+                    }
+                    Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) => {
+                        if let Operation::Interpolate(..) = op {
+                            prop_assert_eq!(mode, Some(InterpolationMode::Linear));
+                            prop_assert!(moved);
+                        }
+                        moved = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        #[test]
+        fn test_aperture_macro_serializes(am in arb_aperture_macro()) {
+            prop_assert!(ExtendedCode::ApertureMacro(am).to_code_string().is_ok());
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Iterator adapters for command streams.
+
+use std::iter::FilterMap;
+use std::vec;
+
+use crate::coordinates::Coordinates;
+use crate::deprecated::DeprecatedCode;
+use crate::extended_codes::ApertureDefinition;
+use crate::function_codes::{DCode, Operation};
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// Iterator returned by [`CommandStreamExt::operations`].
+pub type Operations<I> = FilterMap<I, fn(Command) -> Option<Operation>>;
+
+/// Iterator returned by [`CommandStreamExt::filter_function_codes`].
+pub type FunctionCodes<I> = FilterMap<I, fn(Command) -> Option<FunctionCode>>;
+
+/// Iterator adapters for everything that iterates over owned commands, e.g.
+/// a `Vec<Command>`.
+pub trait CommandStreamExt: IntoIterator<Item = Command> + Sized {
+    /// The operations (D01, D02 and D03) of the stream.
+    fn operations(self) -> Operations<Self::IntoIter> {
+        self.into_iter().filter_map(|command| match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) => Some(op),
+            _ => None,
+        })
+    }
+
+    /// The aperture definitions of the stream, including the ones in
+    /// extended code blocks.
+    fn aperture_definitions(self) -> ApertureDefinitions<Self::IntoIter> {
+        ApertureDefinitions {
+            commands: self.into_iter(),
+            block: Vec::new().into_iter(),
+        }
+    }
+
+    /// Replace the coordinates of all operations, including coordinates
+    /// without an operation code. Arc offsets are relative to the start
+    /// point and not passed to `f`.
+    fn map_coordinates<F>(self, f: F) -> MapCoordinates<Self::IntoIter, F>
+    where
+        F: FnMut(Coordinates) -> Coordinates,
+    {
+        MapCoordinates {
+            commands: self.into_iter(),
+            f,
+        }
+    }
+
+    /// The function codes of the stream, i.e. all commands except extended
+    /// and deprecated codes.
+    fn filter_function_codes(self) -> FunctionCodes<Self::IntoIter> {
+        self.into_iter().filter_map(|command| match command {
+            Command::FunctionCode(code) => Some(code),
+            _ => None,
+        })
+    }
+}
+
+impl<I: IntoIterator<Item = Command>> CommandStreamExt for I {}
+
+/// Iterator returned by [`CommandStreamExt::aperture_definitions`].
+#[derive(Debug, Clone)]
+pub struct ApertureDefinitions<I> {
+    commands: I,
+    block: vec::IntoIter<ExtendedCode>,
+}
+
+impl<I: Iterator<Item = Command>> Iterator for ApertureDefinitions<I> {
+    type Item = ApertureDefinition;
+
+    fn next(&mut self) -> Option<ApertureDefinition> {
+        loop {
+            for code in self.block.by_ref() {
+                if let ExtendedCode::ApertureDefinition(def) = code {
+                    return Some(def);
+                }
+            }
+            match self.commands.next()? {
+                Command::ExtendedCode(ExtendedCode::ApertureDefinition(def)) => return Some(def),
+                Command::ExtendedCodeBlock(block) => self.block = block.codes.into_iter(),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Iterator returned by [`CommandStreamExt::map_coordinates`].
+#[derive(Debug, Clone)]
+pub struct MapCoordinates<I, F> {
+    commands: I,
+    f: F,
+}
+
+impl<I, F> Iterator for MapCoordinates<I, F>
+where
+    I: Iterator<Item = Command>,
+    F: FnMut(Coordinates) -> Coordinates,
+{
+    type Item = Command;
+
+    fn next(&mut self) -> Option<Command> {
+        let f = &mut self.f;
+        Some(match self.commands.next()? {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) => {
+                let op = match op {
+                    Operation::Interpolate(coords, offset) => {
+                        Operation::Interpolate(f(coords), offset)
+                    }
+                    Operation::Move(coords) => Operation::Move(f(coords)),
+                    Operation::Flash(coords) => Operation::Flash(f(coords)),
+                };
+                DCode::Operation(op).into()
+            }
+            Command::Deprecated(DeprecatedCode::CoordinatesOnly(coords)) => {
+                DeprecatedCode::CoordinatesOnly(f(coords)).into()
+            }
+            Command::Deprecated(DeprecatedCode::CoordinatesWithOffset(coords, offset)) => {
+                DeprecatedCode::CoordinatesWithOffset(f(coords), offset).into()
+            }
+            command => command,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.commands.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::{CoordinateFormat, CoordinateNumber};
+    use crate::extended_codes::{Aperture, Circle, Unit};
+    use crate::function_codes::{GCode, MCode};
+    use crate::types::ExtendedCodeBlock;
+
+    fn commands() -> Vec<Command> {
+        let cf = CoordinateFormat::new(2, 4);
+        vec![
+            ExtendedCodeBlock::new(vec![
+                Unit::Millimeters.into(),
+                ApertureDefinition::new(10, Aperture::Circle(Circle::new(0.1))).into(),
+            ])
+            .into(),
+            ApertureDefinition::new(11, Aperture::Circle(Circle::new(0.2))).into(),
+            GCode::Comment("flash".into()).into(),
+            DCode::SelectAperture(10).into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(1, 2, cf))).into(),
+            MCode::EndOfFile.into(),
+        ]
+    }
+
+    #[test]
+    fn test_operations_and_function_codes() {
+        let cf = CoordinateFormat::new(2, 4);
+        let operations: Vec<Operation> = commands().operations().collect();
+        assert_eq!(
+            operations,
+            vec![Operation::Flash(Coordinates::new(1, 2, cf))]
+        );
+        assert_eq!(commands().filter_function_codes().count(), 4);
+    }
+
+    #[test]
+    fn test_aperture_definitions() {
+        let codes: Vec<i32> = commands()
+            .aperture_definitions()
+            .map(|def| def.code)
+            .collect();
+        assert_eq!(codes, vec![10, 11]);
+    }
+
+    #[test]
+    fn test_map_coordinates() {
+        let offset = CoordinateNumber::from(10);
+        let moved: Vec<Command> = commands()
+            .map_coordinates(|mut coords| {
+                coords.x = coords.x.map(|x| x + offset);
+                coords
+            })
+            .collect();
+        let cf = CoordinateFormat::new(2, 4);
+        assert_eq!(moved.len(), 6);
+        assert_eq!(
+            moved.operations().next(),
+            Some(Operation::Flash(Coordinates::new(11, 2, cf)))
+        );
+    }
+}
@@ -0,0 +1,447 @@
+//! Stroke text generator.
+//!
+//! Text is rendered with a simple built-in vector font into draw operations
+//! using a line aperture. Glyphs are designed on a grid that is 4 units wide
+//! and 6 units high (the cap height), characters are advanced by 6 units.
+//! Lowercase letters are rendered as uppercase letters.
+
+use crate::coordinates::{CoordinateFormat, Coordinates};
+use crate::errors::{GerberError, GerberResult};
+use crate::function_codes::{DCode, GCode, InterpolationMode, Operation};
+use crate::types::Command;
+
+const GLYPH_HEIGHT: f64 = 6.0;
+const GLYPH_ADVANCE: f64 = 6.0;
+
+/// A single stroke of a glyph. A stroke with only one point is rendered as a
+/// flash (e.g. for the period).
+type Stroke = &'static [(i8, i8)];
+
+/// A line of text rendered with strokes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrokeText {
+    pub text: String,
+    /// Cap height of the text.
+    pub height: f64,
+    /// Code of the (circular) aperture used to draw the strokes.
+    pub aperture_code: i32,
+    /// Position of the left end of the baseline.
+    pub position: (f64, f64),
+    /// Counterclockwise rotation around the position, in degrees.
+    pub rotation: f64,
+    /// Mirror the text horizontally (e.g. for bottom side legends).
+    pub mirrored: bool,
+}
+
+impl StrokeText {
+    pub fn new<S: Into<String>>(text: S, height: f64, aperture_code: i32) -> Self {
+        StrokeText {
+            text: text.into(),
+            height,
+            aperture_code,
+            position: (0.0, 0.0),
+            rotation: 0.0,
+            mirrored: false,
+        }
+    }
+
+    pub fn with_position(mut self, x: f64, y: f64) -> Self {
+        self.position = (x, y);
+        self
+    }
+
+    pub fn with_rotation(mut self, angle: f64) -> Self {
+        self.rotation = angle;
+        self
+    }
+
+    pub fn mirrored(mut self, mirrored: bool) -> Self {
+        self.mirrored = mirrored;
+        self
+    }
+
+    /// The advance width of the rendered text.
+    pub fn width(&self) -> f64 {
+        self.text.chars().count() as f64 * GLYPH_ADVANCE * self.scale()
+    }
+
+    fn scale(&self) -> f64 {
+        self.height / GLYPH_HEIGHT
+    }
+
+    /// Generate the aperture selection and the draw operations for the text.
+    pub fn commands(&self, format: CoordinateFormat) -> GerberResult<Vec<Command>> {
+        if self.height <= 0.0 {
+            return Err(GerberError::RangeError(
+                "Text height must be positive".into(),
+            ));
+        }
+        let scale = self.scale();
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let mirror = if self.mirrored { -1.0 } else { 1.0 };
+        let (x0, y0) = self.position;
+        let transform = |cursor: f64, (gx, gy): (i8, i8)| {
+            let lx = mirror * (cursor + f64::from(gx) * scale);
+            let ly = f64::from(gy) * scale;
+            Coordinates::from_f64_rounded(
+                x0 + lx * cos - ly * sin,
+                y0 + lx * sin + ly * cos,
+                format,
+            )
+        };
+
+        let mut commands: Vec<Command> = vec![
+            DCode::SelectAperture(self.aperture_code).into(),
+            GCode::InterpolationMode(InterpolationMode::Linear).into(),
+        ];
+        for (index, c) in self.text.chars().enumerate() {
+            let strokes = glyph(c).ok_or_else(|| {
+                GerberError::ConversionError(format!(
+                    "Character {:?} is not supported by the stroke font",
+                    c
+                ))
+            })?;
+            let cursor = index as f64 * GLYPH_ADVANCE * scale;
+            for stroke in strokes {
+                match stroke.split_first() {
+                    Some((&first, [])) => commands
+                        .push(DCode::Operation(Operation::Flash(transform(cursor, first)?)).into()),
+                    Some((&first, rest)) => {
+                        commands.push(
+                            DCode::Operation(Operation::Move(transform(cursor, first)?)).into(),
+                        );
+                        for &point in rest {
+                            commands.push(
+                                DCode::Operation(Operation::Interpolate(
+                                    transform(cursor, point)?,
+                                    None,
+                                ))
+                                .into(),
+                            );
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+        Ok(commands)
+    }
+}
+
+/// Return the strokes of a glyph, or `None` if the character is not part of
+/// the font.
+fn glyph(c: char) -> Option<&'static [Stroke]> {
+    let strokes: &'static [Stroke] = match c.to_ascii_uppercase() {
+        ' ' => &[],
+        '!' => &[&[(2, 6), (2, 2)], &[(2, 0)]],
+        '"' => &[&[(1, 6), (1, 4)], &[(3, 6), (3, 4)]],
+        '#' => &[
+            &[(1, 0), (1, 6)],
+            &[(3, 0), (3, 6)],
+            &[(0, 2), (4, 2)],
+            &[(0, 4), (4, 4)],
+        ],
+        '$' => &[
+            &[
+                (4, 5),
+                (3, 6),
+                (1, 6),
+                (0, 5),
+                (0, 4),
+                (1, 3),
+                (3, 3),
+                (4, 2),
+                (4, 1),
+                (3, 0),
+                (1, 0),
+                (0, 1),
+            ],
+            &[(2, 7), (2, -1)],
+        ],
+        '%' => &[&[(0, 0), (4, 6)], &[(1, 5)], &[(3, 1)]],
+        '&' => &[&[
+            (4, 0),
+            (0, 4),
+            (0, 5),
+            (1, 6),
+            (2, 5),
+            (2, 4),
+            (0, 2),
+            (0, 1),
+            (1, 0),
+            (2, 0),
+            (4, 2),
+        ]],
+        '\'' => &[&[(2, 6), (2, 4)]],
+        '(' => &[&[(3, 6), (1, 4), (1, 2), (3, 0)]],
+        ')' => &[&[(1, 6), (3, 4), (3, 2), (1, 0)]],
+        '*' => &[&[(2, 1), (2, 5)], &[(0, 2), (4, 4)], &[(0, 4), (4, 2)]],
+        '+' => &[&[(0, 3), (4, 3)], &[(2, 1), (2, 5)]],
+        ',' => &[&[(2, 0), (1, -1)]],
+        '-' => &[&[(1, 3), (3, 3)]],
+        '.' => &[&[(2, 0)]],
+        '/' => &[&[(0, 0), (4, 6)]],
+        '0' => &[
+            &[
+                (1, 0),
+                (0, 1),
+                (0, 5),
+                (1, 6),
+                (3, 6),
+                (4, 5),
+                (4, 1),
+                (3, 0),
+                (1, 0),
+            ],
+            &[(0, 1), (4, 5)],
+        ],
+        '1' => &[&[(1, 5), (2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        '2' => &[&[(0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (0, 0), (4, 0)]],
+        '3' => &[
+            &[
+                (0, 5),
+                (1, 6),
+                (3, 6),
+                (4, 5),
+                (4, 4),
+                (3, 3),
+                (4, 2),
+                (4, 1),
+                (3, 0),
+                (1, 0),
+                (0, 1),
+            ],
+            &[(1, 3), (3, 3)],
+        ],
+        '4' => &[&[(3, 0), (3, 6), (0, 2), (4, 2)]],
+        '5' => &[&[
+            (4, 6),
+            (0, 6),
+            (0, 3),
+            (3, 3),
+            (4, 2),
+            (4, 1),
+            (3, 0),
+            (0, 0),
+        ]],
+        '6' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 2),
+            (3, 3),
+            (0, 3),
+        ]],
+        '7' => &[&[(0, 6), (4, 6), (1, 0)]],
+        '8' => &[&[
+            (1, 3),
+            (0, 4),
+            (0, 5),
+            (1, 6),
+            (3, 6),
+            (4, 5),
+            (4, 4),
+            (3, 3),
+            (1, 3),
+            (0, 2),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 2),
+            (3, 3),
+        ]],
+        '9' => &[&[
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 4),
+            (1, 3),
+            (4, 3),
+        ]],
+        ':' => &[&[(2, 1)], &[(2, 4)]],
+        ';' => &[&[(2, 4)], &[(2, 1), (1, -1)]],
+        '<' => &[&[(4, 5), (0, 3), (4, 1)]],
+        '=' => &[&[(0, 2), (4, 2)], &[(0, 4), (4, 4)]],
+        '>' => &[&[(0, 5), (4, 3), (0, 1)]],
+        '?' => &[
+            &[(0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (2, 3), (2, 2)],
+            &[(2, 0)],
+        ],
+        '@' => &[&[
+            (3, 2),
+            (1, 2),
+            (1, 4),
+            (3, 4),
+            (3, 2),
+            (4, 2),
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (4, 0),
+        ]],
+        'A' => &[&[(0, 0), (0, 4), (2, 6), (4, 4), (4, 0)], &[(0, 3), (4, 3)]],
+        'B' => &[
+            &[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)],
+            &[(3, 3), (4, 2), (4, 1), (3, 0), (0, 0)],
+        ],
+        'C' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+        ]],
+        'D' => &[&[(0, 0), (0, 6), (3, 6), (4, 5), (4, 1), (3, 0), (0, 0)]],
+        'E' => &[&[(4, 6), (0, 6), (0, 0), (4, 0)], &[(0, 3), (3, 3)]],
+        'F' => &[&[(4, 6), (0, 6), (0, 0)], &[(0, 3), (3, 3)]],
+        'G' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 3),
+            (2, 3),
+        ]],
+        'H' => &[&[(0, 0), (0, 6)], &[(4, 0), (4, 6)], &[(0, 3), (4, 3)]],
+        'I' => &[&[(1, 6), (3, 6)], &[(2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        'J' => &[&[(4, 6), (4, 1), (3, 0), (1, 0), (0, 1)]],
+        'K' => &[&[(0, 0), (0, 6)], &[(4, 6), (0, 2)], &[(1, 3), (4, 0)]],
+        'L' => &[&[(0, 6), (0, 0), (4, 0)]],
+        'M' => &[&[(0, 0), (0, 6), (2, 3), (4, 6), (4, 0)]],
+        'N' => &[&[(0, 0), (0, 6), (4, 0), (4, 6)]],
+        'O' => &[&[
+            (1, 0),
+            (0, 1),
+            (0, 5),
+            (1, 6),
+            (3, 6),
+            (4, 5),
+            (4, 1),
+            (3, 0),
+            (1, 0),
+        ]],
+        'P' => &[&[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)]],
+        'Q' => &[
+            &[
+                (1, 0),
+                (0, 1),
+                (0, 5),
+                (1, 6),
+                (3, 6),
+                (4, 5),
+                (4, 1),
+                (3, 0),
+                (1, 0),
+            ],
+            &[(2, 2), (4, 0)],
+        ],
+        'R' => &[
+            &[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)],
+            &[(2, 3), (4, 0)],
+        ],
+        'S' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 4),
+            (1, 3),
+            (3, 3),
+            (4, 2),
+            (4, 1),
+            (3, 0),
+            (1, 0),
+            (0, 1),
+        ]],
+        'T' => &[&[(0, 6), (4, 6)], &[(2, 6), (2, 0)]],
+        'U' => &[&[(0, 6), (0, 1), (1, 0), (3, 0), (4, 1), (4, 6)]],
+        'V' => &[&[(0, 6), (2, 0), (4, 6)]],
+        'W' => &[&[(0, 6), (1, 0), (2, 4), (3, 0), (4, 6)]],
+        'X' => &[&[(0, 0), (4, 6)], &[(0, 6), (4, 0)]],
+        'Y' => &[&[(0, 6), (2, 3), (4, 6)], &[(2, 3), (2, 0)]],
+        'Z' => &[&[(0, 6), (4, 6), (0, 0), (4, 0)]],
+        '[' => &[&[(3, 6), (1, 6), (1, 0), (3, 0)]],
+        '\\' => &[&[(0, 6), (4, 0)]],
+        ']' => &[&[(1, 6), (3, 6), (3, 0), (1, 0)]],
+        '^' => &[&[(0, 4), (2, 6), (4, 4)]],
+        '_' => &[&[(0, -1), (4, -1)]],
+        '`' => &[&[(1, 6), (2, 5)]],
+        '{' => &[&[(3, 6), (2, 5), (2, 4), (1, 3), (2, 2), (2, 1), (3, 0)]],
+        '|' => &[&[(2, -1), (2, 7)]],
+        '}' => &[&[(1, 6), (2, 5), (2, 4), (3, 3), (2, 2), (2, 1), (1, 0)]],
+        '~' => &[&[(0, 3), (1, 4), (3, 2), (4, 3)]],
+        _ => return None,
+    };
+    Some(strokes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::BufWriter;
+
+    use crate::traits::GerberCode;
+
+    #[test]
+    fn test_glyph_coverage() {
+        for c in ' '..='~' {
+            assert!(glyph(c).is_some(), "Missing glyph for {:?}", c);
+        }
+        assert!(glyph('ä').is_none());
+        assert_eq!(glyph('a'), glyph('A'));
+    }
+
+    #[test]
+    fn test_text_commands() {
+        let cf = CoordinateFormat::new(2, 4);
+        let text = StrokeText::new("L.", 6.0, 12).with_position(1.0, 2.0);
+        assert_code!(
+            text.commands(cf).unwrap(),
+            "D12*\nG01*\nX10000Y80000D02*\nX10000Y20000D01*\nX50000Y20000D01*\nX90000Y20000D03*\n"
+        );
+        assert_eq!(text.width(), 12.0);
+    }
+
+    #[test]
+    fn test_text_rotated_and_mirrored() {
+        let cf = CoordinateFormat::new(2, 4);
+        let rotated = StrokeText::new("-", 6.0, 10).with_rotation(90.0);
+        assert_code!(
+            rotated.commands(cf).unwrap(),
+            "D10*\nG01*\nX-30000Y10000D02*\nX-30000Y30000D01*\n"
+        );
+        let mirrored = StrokeText::new("-", 6.0, 10).mirrored(true);
+        assert_code!(
+            mirrored.commands(cf).unwrap(),
+            "D10*\nG01*\nX-10000Y30000D02*\nX-30000Y30000D01*\n"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_character() {
+        let cf = CoordinateFormat::new(2, 4);
+        assert!(StrokeText::new("Ω", 1.0, 10).commands(cf).is_err());
+    }
+}
@@ -0,0 +1,194 @@
+//! Parsing of enums from their serialized spellings, e.g. `"MM"` for
+//! `Unit::Millimeters`, for parsers and configuration files.
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use crate::attributes::{
+    CopperType, DrillFunction, ExtendedPosition, FiducialScope, FilePolarity, Part, Position,
+    Profile, SmdPadType,
+};
+use crate::charset::unescape_field;
+use crate::deprecated::ImagePolarity;
+use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::{Polarity, Unit};
+
+fn invalid(what: &str, token: &str) -> GerberError {
+    GerberError::ConversionError(format!("Invalid {}: {:?}", what, token))
+}
+
+macro_rules! impl_from_str {
+    ($type:ident, $what:expr, { $($token:expr => $variant:expr),+ $(,)? }) => {
+        impl FromStr for $type {
+            type Err = GerberError;
+
+            fn from_str(s: &str) -> GerberResult<Self> {
+                match s {
+                    $($token => Ok($variant),)+
+                    _ => Err(invalid($what, s)),
+                }
+            }
+        }
+
+        impl<'a> TryFrom<&'a str> for $type {
+            type Error = GerberError;
+
+            fn try_from(s: &'a str) -> GerberResult<Self> {
+                s.parse()
+            }
+        }
+    };
+}
+
+impl_from_str!(Unit, "unit", {
+    "MM" => Unit::Millimeters,
+    "IN" => Unit::Inches,
+});
+
+impl_from_str!(Polarity, "polarity", {
+    "C" => Polarity::Clear,
+    "D" => Polarity::Dark,
+});
+
+impl_from_str!(ImagePolarity, "image polarity", {
+    "POS" => ImagePolarity::Positive,
+    "NEG" => ImagePolarity::Negative,
+});
+
+impl_from_str!(FilePolarity, "file polarity", {
+    "Positive" => FilePolarity::Positive,
+    "Negative" => FilePolarity::Negative,
+});
+
+impl_from_str!(Position, "position", {
+    "Top" => Position::Top,
+    "Bot" => Position::Bottom,
+});
+
+impl_from_str!(ExtendedPosition, "position", {
+    "Top" => ExtendedPosition::Top,
+    "Inr" => ExtendedPosition::Inner,
+    "Bot" => ExtendedPosition::Bottom,
+});
+
+impl_from_str!(CopperType, "copper type", {
+    "Plane" => CopperType::Plane,
+    "Signal" => CopperType::Signal,
+    "Mixed" => CopperType::Mixed,
+    "Hatched" => CopperType::Hatched,
+});
+
+impl_from_str!(Profile, "profile", {
+    "P" => Profile::Plated,
+    "NP" => Profile::NonPlated,
+});
+
+impl_from_str!(DrillFunction, "drill function", {
+    "BreakOut" => DrillFunction::BreakOut,
+    "Tooling" => DrillFunction::Tooling,
+    "Other" => DrillFunction::Other,
+});
+
+impl_from_str!(SmdPadType, "SMD pad type", {
+    "CuDef" => SmdPadType::CopperDefined,
+    "SMDef" => SmdPadType::SoldermaskDefined,
+});
+
+impl_from_str!(FiducialScope, "fiducial scope", {
+    "Global" => FiducialScope::Global,
+    "Local" => FiducialScope::Local,
+});
+
+/// The description of `Other` is unescaped, e.g. `"Other,A,B"`.
+impl FromStr for Part {
+    type Err = GerberError;
+
+    fn from_str(s: &str) -> GerberResult<Self> {
+        match s {
+            "Single" => Ok(Part::Single),
+            "Array" => Ok(Part::Array),
+            "FabricationPanel" => Ok(Part::FabricationPanel),
+            "Coupon" => Ok(Part::Coupon),
+            _ => match s.strip_prefix("Other,") {
+                Some(description) => Ok(Part::Other(unescape_field(description)?.into_owned())),
+                None => Err(invalid("part", s)),
+            },
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for Part {
+    type Error = GerberError;
+
+    fn try_from(s: &'a str) -> GerberResult<Self> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fmt::Debug;
+
+    use crate::traits::PartialGerberCode;
+
+    /// Assert that the values are parsed from their serialization.
+    fn assert_round_trip<T>(values: &[T])
+    where
+        T: FromStr<Err = GerberError> + PartialGerberCode<Vec<u8>> + PartialEq + Debug,
+    {
+        for value in values {
+            let mut buf = Vec::new();
+            value.serialize_partial(&mut buf).unwrap();
+            let token = String::from_utf8(buf).unwrap();
+            assert_eq!(&token.parse::<T>().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_round_trip(&[Unit::Millimeters, Unit::Inches]);
+        assert_round_trip(&[Polarity::Clear, Polarity::Dark]);
+        assert_round_trip(&[ImagePolarity::Positive, ImagePolarity::Negative]);
+        assert_round_trip(&[FilePolarity::Positive, FilePolarity::Negative]);
+        assert_round_trip(&[Position::Top, Position::Bottom]);
+        assert_round_trip(&[
+            ExtendedPosition::Top,
+            ExtendedPosition::Inner,
+            ExtendedPosition::Bottom,
+        ]);
+        assert_round_trip(&[
+            CopperType::Plane,
+            CopperType::Signal,
+            CopperType::Mixed,
+            CopperType::Hatched,
+        ]);
+        assert_round_trip(&[Profile::Plated, Profile::NonPlated]);
+        assert_round_trip(&[
+            DrillFunction::BreakOut,
+            DrillFunction::Tooling,
+            DrillFunction::Other,
+        ]);
+        assert_round_trip(&[SmdPadType::CopperDefined, SmdPadType::SoldermaskDefined]);
+        assert_round_trip(&[FiducialScope::Global, FiducialScope::Local]);
+        assert_round_trip(&[
+            Part::Single,
+            Part::Array,
+            Part::FabricationPanel,
+            Part::Coupon,
+            Part::Other("Panel, 2x2".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_tokens() {
+        assert!(matches!(
+            "mm".parse::<Unit>(),
+            Err(GerberError::ConversionError(_))
+        ));
+        assert!(Position::try_from("Bottom").is_err());
+        assert!(Part::try_from("Other").is_err());
+        assert_eq!(Profile::try_from("NP").unwrap(), Profile::NonPlated);
+    }
+}
@@ -1,19 +1,222 @@
 //! Traits used in gerber-types.
 
-use std::io::Write;
+use std::fmt;
+use std::io::{self, Write};
 
-use crate::GerberResult;
+use crate::context::SerializationContext;
+use crate::errors::{GerberError, GerberResult};
 
 /// All types that implement this trait can be converted to a complete Gerber
 /// Code line. Generated code should end with a newline.
+///
+/// Implementing `serialize` is enough. Types that honor the settings of a
+/// [`SerializationContext`] also implement `serialize_with_context`.
 pub trait GerberCode<W: Write> {
     fn serialize(&self, writer: &mut W) -> GerberResult<()>;
+
+    /// Serialize with the given context. By default, the context is ignored.
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        _ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.serialize(writer)
+    }
 }
 
+/// An object safe variant of [`GerberCode`], implemented for all types that
+/// implement it.
+///
+/// Code is serialized into a `&mut dyn Write`, so it is only compiled once
+/// instead of once per writer type, and values of different types can be
+/// stored as `Box<dyn DynGerberCode>`.
+pub trait DynGerberCode {
+    /// Serialize with a default context.
+    fn serialize_dyn(&self, writer: &mut dyn Write) -> GerberResult<()> {
+        self.serialize_dyn_with_context(writer, &SerializationContext::new())
+    }
+
+    /// Serialize with the given context.
+    fn serialize_dyn_with_context(
+        &self,
+        writer: &mut dyn Write,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()>;
+
+    /// Serialize into a `fmt::Write`, e.g. a `String` or a `fmt::Formatter`,
+    /// with the given context.
+    fn serialize_fmt_with_context(
+        &self,
+        writer: &mut dyn fmt::Write,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.serialize_dyn_with_context(&mut FmtWriter(writer), ctx)
+    }
+}
+
+/// Adapter that writes the UTF-8 output of the serialization into a
+/// `fmt::Write`.
+struct FmtWriter<'a>(&'a mut dyn fmt::Write);
+
+impl<'a> Write for FmtWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let s =
+            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.0
+            .write_str(s)
+            .map_err(|_| io::Error::from(io::ErrorKind::Other))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T> DynGerberCode for T
+where
+    T: for<'a> GerberCode<&'a mut dyn Write> + ?Sized,
+{
+    fn serialize_dyn_with_context(
+        &self,
+        mut writer: &mut dyn Write,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.serialize_with_context(&mut writer, ctx)
+    }
+}
+
+impl<'a, W: Write> GerberCode<W> for dyn DynGerberCode + 'a {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_dyn(writer)
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        self.serialize_dyn_with_context(writer, ctx)
+    }
+}
+
+/// Serialize the items of an iterable one by one, e.g. a filtered or chained
+/// iterator over commands, without collecting them into a `Vec` first.
+///
+/// The iterable is cloned for every serialization, so it should be cheap to
+/// clone, like most iterators over borrowed data.
+#[derive(Debug, Clone)]
+pub struct CodeSequence<I>(pub I);
+
 /// All types that implement this trait can be converted to a Gerber Code
 /// representation.
 ///
 /// This is a crate-internal trait.
 pub trait PartialGerberCode<W: Write> {
-    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()>;
+    #[allow(dead_code)]
+    fn serialize_partial(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_partial_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_partial_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()>;
+}
+
+/// Convenience methods for all types that implement [`GerberCode`].
+pub trait GerberCodeExt {
+    /// Serialize into a string.
+    fn to_code_string(&self) -> GerberResult<String> {
+        self.to_code_string_with_context(&SerializationContext::new())
+    }
+
+    /// Serialize into a string with the given context.
+    fn to_code_string_with_context(&self, ctx: &SerializationContext) -> GerberResult<String>;
+}
+
+impl<T: GerberCode<Vec<u8>> + ?Sized> GerberCodeExt for T {
+    fn to_code_string_with_context(&self, ctx: &SerializationContext) -> GerberResult<String> {
+        let mut buf = Vec::new();
+        self.serialize_with_context(&mut buf, ctx)?;
+        String::from_utf8(buf).map_err(|e| GerberError::ConversionError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::function_codes::{GCode, MCode};
+    use crate::types::Command;
+
+    #[test]
+    fn test_to_code_string() {
+        let commands: Vec<Command> = vec![
+            GCode::Comment("hello".into()).into(),
+            MCode::EndOfFile.into(),
+        ];
+        assert_eq!(commands.to_code_string().unwrap(), "G04 hello*\nM02*\n");
+        assert_eq!(MCode::EndOfFile.to_code_string().unwrap(), "M02*\n");
+    }
+
+    #[test]
+    fn test_dyn_gerber_code() {
+        let codes: Vec<Box<dyn DynGerberCode>> = vec![
+            Box::new(GCode::Comment("hello".into())),
+            Box::new(Command::from(MCode::EndOfFile)),
+        ];
+        let mut buf: Vec<u8> = Vec::new();
+        for code in &codes {
+            code.serialize_dyn(&mut buf).unwrap();
+        }
+        assert_eq!(buf, b"G04 hello*\nM02*\n");
+        assert_eq!(codes.to_code_string().unwrap(), "G04 hello*\nM02*\n");
+    }
+
+    #[test]
+    fn test_references_and_containers() {
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        let commands: Vec<Command> = vec![
+            GCode::Comment("hello".into()).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let expected = "G04 hello*\nM02*\n";
+        assert_eq!(commands[..].to_code_string().unwrap(), expected);
+        assert_eq!(GerberCodeExt::to_code_string(&&commands).unwrap(), expected);
+        let refs: Vec<&Command> = commands.iter().collect();
+        assert_eq!(refs.to_code_string().unwrap(), expected);
+        let shared: Vec<Rc<Command>> = commands.iter().cloned().map(Rc::new).collect();
+        assert_eq!(shared.to_code_string().unwrap(), expected);
+        let arc: Arc<[Command]> = commands.clone().into();
+        assert_eq!(arc.to_code_string().unwrap(), expected);
+        let boxed: Box<Command> = Box::new(MCode::EndOfFile.into());
+        assert_eq!(boxed.to_code_string().unwrap(), "M02*\n");
+        let sequence = CodeSequence(commands.iter().rev());
+        assert_eq!(sequence.to_code_string().unwrap(), "M02*\nG04 hello*\n");
+    }
+
+    #[test]
+    fn test_serialize_fmt() {
+        struct Comment(GCode);
+
+        impl fmt::Display for Comment {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0
+                    .serialize_fmt_with_context(f, &SerializationContext::new())
+                    .map_err(|_| fmt::Error)
+            }
+        }
+
+        let mut code = String::new();
+        let comment = GCode::Comment("Grüße".into());
+        comment
+            .serialize_fmt_with_context(&mut code, &SerializationContext::new())
+            .unwrap();
+        assert_eq!(code, "G04 Grüße*\n");
+        assert_eq!(Comment(comment).to_string(), "G04 Grüße*\n");
+    }
 }
@@ -0,0 +1,391 @@
+//! Affine transformation of command streams.
+
+use std::convert::TryFrom;
+
+use crate::coordinates::{CoordinateNumber, CoordinateOffset, Coordinates};
+use crate::errors::{deprecated_error, GerberError, GerberResult};
+use crate::function_codes::{DCode, GCode, Operation, QuadrantMode};
+use crate::types::{Command, FunctionCode};
+
+/// Fixed point factor of the transformation matrix.
+const MATRIX_FACTOR: i128 = 1_000_000_000;
+
+/// A transformation of the coordinates of a command stream.
+///
+/// Coordinates are first scaled, then rotated around the origin and finally
+/// translated. The translation is given in the unit of the file.
+///
+/// Only coordinates and arc offsets are transformed. Aperture sizes and
+/// rotations as well as step and repeat distances are not changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translate_x: f64,
+    pub translate_y: f64,
+    /// Counterclockwise rotation in degrees.
+    pub rotation: f64,
+    pub scale: f64,
+}
+
+impl Default for Transform {
+    /// The identity transformation.
+    fn default() -> Self {
+        Transform {
+            translate_x: 0.0,
+            translate_y: 0.0,
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Transform {
+    pub fn new() -> Self {
+        Transform::default()
+    }
+
+    pub fn with_translation(mut self, x: f64, y: f64) -> Self {
+        self.translate_x = x;
+        self.translate_y = y;
+        self
+    }
+
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    pub fn with_scale(mut self, factor: f64) -> Self {
+        self.scale = factor;
+        self
+    }
+
+    /// Transform a single point.
+    pub fn transform_point(
+        &self,
+        x: CoordinateNumber,
+        y: CoordinateNumber,
+    ) -> GerberResult<(CoordinateNumber, CoordinateNumber)> {
+        let point = (Some(x), Some(y));
+        match Matrix::new(self)?.apply(point, point, true)? {
+            (Some(x), Some(y)) => Ok((x, y)),
+            // Only a zero matrix leaves both values unchanged, which is rejected
+            _ => Err(GerberError::RangeError(
+                "The scale factor is too small".into(),
+            )),
+        }
+    }
+
+    /// Transform all coordinates and arc offsets of a command stream.
+    ///
+    /// The computation is done in fixed point. Rotations by multiples of 90
+    /// degrees are exact, other rotations round to the nearest representable
+    /// value. Omitted coordinates are filled in from the current point where
+    /// the rotation makes them depend on the other axis.
+    ///
+    /// Rotations that are not a multiple of 90 degrees cannot be applied to
+    /// arcs in single quadrant mode, since their offsets are unsigned.
+    pub fn apply(&self, commands: &[Command]) -> GerberResult<Vec<Command>> {
+        let matrix = Matrix::new(self)?;
+        let mut current = (None, None);
+        let mut quadrant = None;
+        let mut result = Vec::with_capacity(commands.len());
+        for command in commands {
+            let mut command = command.clone();
+            match command {
+                Command::FunctionCode(FunctionCode::DCode(DCode::Operation(ref mut op))) => {
+                    match op {
+                        Operation::Interpolate(coords, offset) => {
+                            if let Some(offset) = offset {
+                                matrix.apply_offset(offset, quadrant)?;
+                            }
+                            matrix.apply_coordinates(coords, &mut current)?;
+                        }
+                        Operation::Move(coords) | Operation::Flash(coords) => {
+                            matrix.apply_coordinates(coords, &mut current)?;
+                        }
+                    }
+                }
+                Command::FunctionCode(FunctionCode::GCode(GCode::QuadrantMode(mode))) => {
+                    quadrant = Some(mode);
+                }
+                Command::Deprecated(_) => return Err(deprecated_error()),
+                _ => {}
+            }
+            result.push(command);
+        }
+        Ok(result)
+    }
+}
+
+/// The transformation as fixed point matrix
+/// `[[a, -b, tx], [b, a, ty]]`.
+struct Matrix {
+    a: i128,
+    b: i128,
+    tx: CoordinateNumber,
+    ty: CoordinateNumber,
+}
+
+impl Matrix {
+    fn new(transform: &Transform) -> GerberResult<Self> {
+        if !(transform.scale.is_finite() && transform.scale > 0.0) {
+            return Err(GerberError::RangeError(
+                "The scale factor must be positive".into(),
+            ));
+        }
+        if !transform.rotation.is_finite() {
+            return Err(GerberError::RangeError(
+                "The rotation must be a finite number".into(),
+            ));
+        }
+        // Reduce the angle first, so that multiples of 90 degrees result in
+        // exact zeros after rounding.
+        let (sin, cos) = transform.rotation.rem_euclid(360.0).to_radians().sin_cos();
+        let factor = transform.scale * MATRIX_FACTOR as f64;
+        // Larger coefficients would overflow when multiplied with coordinates
+        if factor >= i64::MAX as f64 {
+            return Err(GerberError::RangeError(
+                "The scale factor is too large".into(),
+            ));
+        }
+        let (a, b) = (
+            (cos * factor).round() as i128,
+            (sin * factor).round() as i128,
+        );
+        if a == 0 && b == 0 {
+            return Err(GerberError::RangeError(
+                "The scale factor is too small".into(),
+            ));
+        }
+        Ok(Matrix {
+            a,
+            b,
+            tx: CoordinateNumber::from_f64_rounded(transform.translate_x)?,
+            ty: CoordinateNumber::from_f64_rounded(transform.translate_y)?,
+        })
+    }
+
+    /// Transform a point. The `given` values are the ones written in the
+    /// command, the `full` values include the ones filled in from the current
+    /// point.
+    fn apply(
+        &self,
+        given: Point,
+        full: Point,
+        translate: bool,
+    ) -> GerberResult<(Option<CoordinateNumber>, Option<CoordinateNumber>)> {
+        let (tx, ty) = if translate {
+            (self.tx, self.ty)
+        } else {
+            (CoordinateNumber::new(0), CoordinateNumber::new(0))
+        };
+        Ok((
+            component(given, full, self.a, -self.b, tx)?,
+            component(given, full, self.b, self.a, ty)?,
+        ))
+    }
+
+    fn apply_coordinates(&self, coords: &mut Coordinates, current: &mut Point) -> GerberResult<()> {
+        let given = (coords.x, coords.y);
+        let full = (coords.x.or(current.0), coords.y.or(current.1));
+        let (x, y) = self.apply(given, full, true)?;
+        *current = full;
+        coords.x = x;
+        coords.y = y;
+        Ok(())
+    }
+
+    fn apply_offset(
+        &self,
+        offset: &mut CoordinateOffset,
+        quadrant: Option<QuadrantMode>,
+    ) -> GerberResult<()> {
+        let single = quadrant == Some(QuadrantMode::Single);
+        if single && self.a != 0 && self.b != 0 {
+            return Err(GerberError::UnsupportedError(
+                "Arcs in single quadrant mode can only be rotated by multiples of 90 degrees"
+                    .into(),
+            ));
+        }
+        // Omitted offsets are zero.
+        let zero = CoordinateNumber::new(0);
+        let given = (offset.x, offset.y);
+        let full = (
+            Some(offset.x.unwrap_or(zero)),
+            Some(offset.y.unwrap_or(zero)),
+        );
+        let (i, j) = self.apply(given, full, false)?;
+        // Offsets are unsigned in single quadrant mode.
+        let unsigned = |value: Option<CoordinateNumber>| match value {
+            Some(v) if single => Some(CoordinateNumber::new(v.nano().abs())),
+            value => value,
+        };
+        offset.x = unsigned(i);
+        offset.y = unsigned(j);
+        Ok(())
+    }
+}
+
+type Point = (Option<CoordinateNumber>, Option<CoordinateNumber>);
+
+/// Compute `cx * x + cy * y + t`. The result is `None` if none of the values
+/// it depends on are given, i.e. if it did not change.
+fn component(
+    given: Point,
+    full: Point,
+    cx: i128,
+    cy: i128,
+    t: CoordinateNumber,
+) -> GerberResult<Option<CoordinateNumber>> {
+    let depends_x = cx != 0;
+    let depends_y = cy != 0;
+    let changed = (depends_x && given.0.is_some()) || (depends_y && given.1.is_some());
+    if !changed {
+        return Ok(None);
+    }
+    let value = |value: Option<CoordinateNumber>, depends: bool| match value {
+        Some(v) => Ok(i128::from(v.nano())),
+        None if !depends => Ok(0),
+        None => Err(GerberError::MissingDataError(
+            "Coordinate omitted before the current point is defined".into(),
+        )),
+    };
+    let out_of_range = || GerberError::RangeError("Transformed coordinate is out of range".into());
+    let sum = cx
+        .checked_mul(value(full.0, depends_x)?)
+        .zip(cy.checked_mul(value(full.1, depends_y)?))
+        .and_then(|(x, y)| x.checked_add(y))
+        .ok_or_else(out_of_range)?;
+    // Round half away from zero
+    let mut quotient = sum / MATRIX_FACTOR;
+    if 2 * (sum % MATRIX_FACTOR).abs() >= MATRIX_FACTOR {
+        quotient += sum.signum();
+    }
+    quotient
+        .checked_add(i128::from(t.nano()))
+        .and_then(|nano| i64::try_from(nano).ok())
+        .map(|nano| Some(CoordinateNumber::new(nano)))
+        .ok_or_else(out_of_range)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::CoordinateFormat;
+    use crate::function_codes::MCode;
+
+    #[test]
+    fn test_transform_point() {
+        let transform = Transform::new()
+            .with_scale(2.0)
+            .with_rotation(90.0)
+            .with_translation(10.0, 0.0);
+        let (x, y) = transform
+            .transform_point(CoordinateNumber::from(1), CoordinateNumber::from(2))
+            .unwrap();
+        assert_eq!(x, CoordinateNumber::from(6));
+        assert_eq!(y, CoordinateNumber::from(2));
+    }
+
+    #[test]
+    fn test_apply() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            DCode::Operation(Operation::Move(Coordinates::new(1, 0, cf))).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::at_x(3, cf),
+                Some(CoordinateOffset::at_x(1, cf)),
+            ))
+            .into(),
+            MCode::EndOfFile.into(),
+        ];
+
+        // Translation keeps omitted coordinates
+        let translated = Transform::new()
+            .with_translation(1.0, 2.0)
+            .apply(&commands)
+            .unwrap();
+        assert_eq!(
+            translated[1],
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::at_x(4, cf),
+                Some(CoordinateOffset::at_x(1, cf)),
+            ))
+            .into()
+        );
+
+        // Rotation fills in the current point and rotates the offset
+        let rotated = Transform::new()
+            .with_rotation(90.0)
+            .apply(&commands)
+            .unwrap();
+        assert_eq!(
+            rotated[1],
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::at_y(3, cf),
+                Some(CoordinateOffset::at_y(1, cf)),
+            ))
+            .into()
+        );
+        assert_eq!(rotated[2], commands[2]);
+    }
+
+    #[test]
+    fn test_apply_errors() {
+        let cf = CoordinateFormat::new(2, 4);
+        let omitted: Vec<Command> =
+            vec![DCode::Operation(Operation::Flash(Coordinates::at_x(1, cf))).into()];
+        assert!(Transform::new()
+            .with_translation(1.0, 1.0)
+            .apply(&omitted)
+            .is_ok());
+        assert!(matches!(
+            Transform::new().with_rotation(45.0).apply(&omitted),
+            Err(GerberError::MissingDataError(_))
+        ));
+
+        let single: Vec<Command> = vec![
+            GCode::QuadrantMode(QuadrantMode::Single).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, 1, cf),
+                Some(CoordinateOffset::new(1, 0, cf)),
+            ))
+            .into(),
+        ];
+        assert!(Transform::new().with_rotation(180.0).apply(&single).is_ok());
+        assert!(matches!(
+            Transform::new().with_rotation(30.0).apply(&single),
+            Err(GerberError::UnsupportedError(_))
+        ));
+        assert!(matches!(
+            Transform::new().with_scale(0.0).apply(&single),
+            Err(GerberError::RangeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_extreme_scales() {
+        let (x, y) = (CoordinateNumber::from(1), CoordinateNumber::from(2));
+        for &scale in &[1e-10, 1e25] {
+            assert!(matches!(
+                Transform::new().with_scale(scale).transform_point(x, y),
+                Err(GerberError::RangeError(_))
+            ));
+        }
+        assert!(matches!(
+            Transform::new()
+                .with_scale(1e9)
+                .transform_point(CoordinateNumber::from(1_000_000), y),
+            Err(GerberError::RangeError(_))
+        ));
+        assert_eq!(
+            Transform::new()
+                .with_scale(1e-9)
+                .transform_point(CoordinateNumber::from(1000), y)
+                .unwrap()
+                .0,
+            CoordinateNumber::from_f64_rounded(1e-6).unwrap()
+        );
+    }
+}
@@ -4,10 +4,12 @@
 //! to render themselves. This means for example that each `Coordinates`
 //! instance contains a reference to the coordinate format to be used.
 
+use std::borrow::Cow;
 use std::convert::From;
 
 use crate::attributes;
 use crate::coordinates;
+use crate::deprecated;
 use crate::extended_codes;
 use crate::function_codes;
 use crate::macros;
@@ -27,13 +29,24 @@ macro_rules! impl_from {
 // Root type
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum Command {
     FunctionCode(FunctionCode),
     ExtendedCode(ExtendedCode),
+    /// Several extended codes within one pair of `%` delimiters
+    ExtendedCodeBlock(ExtendedCodeBlock),
+    /// A code that is no longer part of the spec
+    Deprecated(deprecated::DeprecatedCode),
 }
 
 impl_from!(FunctionCode, Command, Command::FunctionCode);
 impl_from!(ExtendedCode, Command, Command::ExtendedCode);
+impl_from!(ExtendedCodeBlock, Command, Command::ExtendedCodeBlock);
+impl_from!(deprecated::DeprecatedCode, Command, Command::Deprecated);
 
 macro_rules! impl_command_fromfrom {
     ($from:ty, $inner:path) => {
@@ -48,6 +61,11 @@ macro_rules! impl_command_fromfrom {
 // Main categories
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "ordered-float", derive(Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum FunctionCode {
     DCode(function_codes::DCode),
     GCode(function_codes::GCode),
@@ -63,6 +81,11 @@ impl_command_fromfrom!(function_codes::GCode, FunctionCode::from);
 impl_command_fromfrom!(function_codes::MCode, FunctionCode::from);
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[non_exhaustive]
 pub enum ExtendedCode {
     /// FS
     CoordinateFormat(coordinates::CoordinateFormat),
@@ -81,7 +104,57 @@ pub enum ExtendedCode {
     /// TA
     ApertureAttribute(attributes::ApertureAttribute),
     /// TD
-    DeleteAttribute(String),
+    DeleteAttribute(
+        #[cfg_attr(feature = "arbitrary", arbitrary(with = crate::fuzzing::arbitrary_cow))]
+        Cow<'static, str>,
+    ),
+}
+
+impl ExtendedCode {
+    /// Whether this is an attribute command (TF, TA or TD).
+    pub fn is_attribute(&self) -> bool {
+        matches!(
+            *self,
+            ExtendedCode::FileAttribute(_)
+                | ExtendedCode::ApertureAttribute(_)
+                | ExtendedCode::DeleteAttribute(_)
+        )
+    }
+
+    /// Whether this code belongs into the header of a file, i.e. is a
+    /// format, unit, attribute, aperture or macro definition.
+    pub fn is_header_code(&self) -> bool {
+        self.is_attribute()
+            || matches!(
+                *self,
+                ExtendedCode::CoordinateFormat(_)
+                    | ExtendedCode::Unit(_)
+                    | ExtendedCode::ApertureDefinition(_)
+                    | ExtendedCode::ApertureMacro(_)
+            )
+    }
+}
+
+/// Several extended codes within one pair of `%` delimiters, e.g.
+/// `%FSLAX24Y24*MOMM*%`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "ordered-float", derive(Eq, Hash))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct ExtendedCodeBlock {
+    pub codes: Vec<ExtendedCode>,
+}
+
+impl ExtendedCodeBlock {
+    pub fn new(codes: Vec<ExtendedCode>) -> Self {
+        ExtendedCodeBlock { codes }
+    }
+
+    pub fn add_code<C: Into<ExtendedCode>>(mut self, code: C) -> Self {
+        self.codes.push(code.into());
+        self
+    }
 }
 
 impl_from!(
@@ -130,6 +203,53 @@ impl_command_fromfrom!(extended_codes::StepAndRepeat, ExtendedCode::from);
 impl_command_fromfrom!(attributes::FileAttribute, ExtendedCode::from);
 impl_command_fromfrom!(attributes::ApertureAttribute, ExtendedCode::from);
 
+// Borrowed commands
+
+/// A command that can borrow its string, e.g. from an input buffer, instead
+/// of owning it.
+///
+/// Only comments (G04) and attribute deletions (TD) borrow their string. All
+/// other commands are stored in the `Command` variant, either owned or
+/// borrowed as a whole. Strings within those commands, e.g. of aperture
+/// macros, macro apertures and attributes, are owned by the command unless
+/// they are `'static`.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum CommandRef<'a> {
+    /// A comment (G04)
+    Comment(&'a str),
+    /// Delete an attribute, or all attributes if the name is empty (TD)
+    DeleteAttribute(&'a str),
+    Command(Cow<'a, Command>),
+}
+
+impl<'a> CommandRef<'a> {
+    /// Convert into an owned command, copying the borrowed strings.
+    pub fn into_owned(self) -> Command {
+        match self {
+            CommandRef::Comment(comment) => {
+                function_codes::GCode::Comment(comment.to_string().into()).into()
+            }
+            CommandRef::DeleteAttribute(name) => {
+                ExtendedCode::DeleteAttribute(name.to_string().into()).into()
+            }
+            CommandRef::Command(command) => command.into_owned(),
+        }
+    }
+}
+
+impl<'a> From<Command> for CommandRef<'a> {
+    fn from(val: Command) -> Self {
+        CommandRef::Command(Cow::Owned(val))
+    }
+}
+
+impl<'a> From<&'a Command> for CommandRef<'a> {
+    fn from(val: &'a Command) -> Self {
+        CommandRef::Command(Cow::Borrowed(val))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -138,12 +258,12 @@ mod test {
 
     use crate::extended_codes::Polarity;
     use crate::function_codes::GCode;
-    use crate::traits::GerberCode;
+    use crate::traits::{GerberCode, GerberCodeExt};
 
     #[test]
     fn test_debug() {
         //! The debug representation should work properly.
-        let c = Command::FunctionCode(FunctionCode::GCode(GCode::Comment("test".to_string())));
+        let c = Command::FunctionCode(FunctionCode::GCode(GCode::Comment("test".into())));
         let debug = format!("{:?}", c);
         assert_eq!(debug, "FunctionCode(GCode(Comment(\"test\")))");
     }
@@ -151,10 +271,39 @@ mod test {
     #[test]
     fn test_function_code_serialize() {
         //! A `FunctionCode` should implement `GerberCode`
-        let c = FunctionCode::GCode(GCode::Comment("comment".to_string()));
+        let c = FunctionCode::GCode(GCode::Comment("comment".into()));
         assert_code!(c, "G04 comment*\n");
     }
 
+    #[test]
+    fn test_command_ref() {
+        let input = String::from("G04 borrowed*");
+        let comment = CommandRef::Comment(&input[4..12]);
+        assert_code!(comment, "G04 borrowed*\n");
+        let delete = CommandRef::DeleteAttribute(&input[4..12]);
+        assert_code!(delete, "%TDborrowed*%\n");
+
+        let eof: Command = function_codes::MCode::EndOfFile.into();
+        let commands = vec![comment.clone(), delete, CommandRef::from(&eof)];
+        assert_eq!(
+            commands.to_code_string().unwrap(),
+            "G04 borrowed*\n%TDborrowed*%\nM02*\n"
+        );
+        assert_eq!(
+            comment.into_owned(),
+            Command::from(function_codes::GCode::Comment("borrowed".into()))
+        );
+    }
+
+    #[test]
+    fn test_static_strings_are_borrowed() {
+        let comment = GCode::Comment("static".into());
+        assert!(matches!(comment, GCode::Comment(Cow::Borrowed(_))));
+        let owned = GCode::Comment(format!("{}", 42).into());
+        assert!(matches!(owned, GCode::Comment(Cow::Owned(_))));
+        assert_code!(owned, "G04 42*\n");
+    }
+
     #[test]
     fn test_function_code_from_gcode() {
         let comment = GCode::Comment("hello".into());
@@ -0,0 +1,600 @@
+//! Validation of values independent of serialization, e.g. to show problems
+//! in a user interface before exporting.
+
+use std::fmt;
+use std::io;
+
+use crate::attributes::{is_guid, ApertureAttribute, FileAttribute, FileFunction};
+use crate::charset::is_valid_name;
+use crate::coordinates::{
+    CoordinateFormat, CoordinateNumber, CoordinateOffset, Coordinates, ZeroSuppression,
+};
+use crate::decimal::{from_decimal, Decimal};
+use crate::errors::{GerberError, GerberResult};
+use crate::extended_codes::{
+    Aperture, ApertureDefinition, Circle, Obround, Polygon, Rectangular, StepAndRepeat,
+};
+use crate::function_codes::{DCode, Operation};
+use crate::macros::{ApertureMacro, MacroContent, MacroDecimal};
+use crate::types::{Command, ExtendedCode, ExtendedCodeBlock, FunctionCode};
+
+/// The severity of a [`ValidationIssue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// The value is valid, but likely not what was intended or not supported
+    /// by all readers.
+    Warning,
+    /// The value is invalid and cannot be serialized or violates the spec.
+    Error,
+}
+
+/// A problem found by [`Validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    pub message: String,
+    /// The location of the problem within the validated value, e.g.
+    /// `[3].aperture.diameter`. Empty for the value itself.
+    pub path: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        };
+        if self.path.is_empty() {
+            write!(f, "{}: {}", severity, self.message)
+        } else {
+            write!(f, "{} at {}: {}", severity, self.path, self.message)
+        }
+    }
+}
+
+/// Validation of values, e.g. aperture sizes, coordinates or attributes.
+pub trait Validate {
+    /// Return all issues found, an empty list means the value is valid.
+    fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        self.validate_at("", &mut issues);
+        issues
+    }
+
+    /// Add the issues found to `issues`, with paths relative to `path`.
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>);
+}
+
+/// Return the value if it has no errors, or a `RangeError` for the first one.
+pub(crate) fn validated<T: Validate>(value: T) -> GerberResult<T> {
+    match value
+        .validate()
+        .into_iter()
+        .find(|issue| issue.severity == Severity::Error)
+    {
+        Some(issue) => Err(GerberError::RangeError(issue.to_string())),
+        None => Ok(value),
+    }
+}
+
+fn field(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn index(path: &str, index: usize) -> String {
+    format!("{}[{}]", path, index)
+}
+
+fn push(issues: &mut Vec<ValidationIssue>, severity: Severity, path: String, message: String) {
+    issues.push(ValidationIssue {
+        severity,
+        message,
+        path,
+    });
+}
+
+/// Check that a size is finite and positive, or zero if allowed.
+fn check_size(issues: &mut Vec<ValidationIssue>, path: String, value: Decimal, allow_zero: bool) {
+    let value = from_decimal(value);
+    if !value.is_finite() {
+        push(issues, Severity::Error, path, "Value is not finite".into());
+    } else if value < 0.0 || (!allow_zero && value == 0.0) {
+        let message = format!("Size must be positive, not {}", value);
+        push(issues, Severity::Error, path, message);
+    }
+}
+
+/// Check that a hole fits into an aperture of the given minimal size.
+fn check_hole(issues: &mut Vec<ValidationIssue>, path: &str, hole: Option<Decimal>, size: f64) {
+    if let Some(hole) = hole {
+        let path = field(path, "hole_diameter");
+        check_size(issues, path.clone(), hole, false);
+        if from_decimal(hole) >= size {
+            let message = "The hole must be smaller than the aperture".into();
+            push(issues, Severity::Error, path, message);
+        }
+    }
+}
+
+impl<T: Validate> Validate for [T] {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        for (i, item) in self.iter().enumerate() {
+            item.validate_at(&index(path, i), issues);
+        }
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        self.as_slice().validate_at(path, issues)
+    }
+}
+
+impl Validate for CoordinateFormat {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        if !(1..=6).contains(&self.integer) {
+            let message = format!("Integer places must be 1 to 6, not {}", self.integer);
+            push(issues, Severity::Error, field(path, "integer"), message);
+        }
+        if !(4..=6).contains(&self.decimal) {
+            let message = format!("Decimal places must be 4 to 6, not {}", self.decimal);
+            push(issues, Severity::Error, field(path, "decimal"), message);
+        }
+    }
+}
+
+fn check_number(
+    issues: &mut Vec<ValidationIssue>,
+    path: String,
+    number: Option<CoordinateNumber>,
+    format: &CoordinateFormat,
+) {
+    if let Some(number) = number {
+        let mut sink = io::sink();
+        if number
+            .write_gerber(&mut sink, format, ZeroSuppression::Leading)
+            .is_err()
+        {
+            let message = format!("{} does not fit into the coordinate format", number);
+            push(issues, Severity::Error, path, message);
+        }
+    }
+}
+
+impl Validate for Coordinates {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        self.format.validate_at(&field(path, "format"), issues);
+        check_number(issues, field(path, "x"), self.x, &self.format);
+        check_number(issues, field(path, "y"), self.y, &self.format);
+    }
+}
+
+impl Validate for CoordinateOffset {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        self.format.validate_at(&field(path, "format"), issues);
+        check_number(issues, field(path, "x"), self.x, &self.format);
+        check_number(issues, field(path, "y"), self.y, &self.format);
+    }
+}
+
+impl Validate for Circle {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        check_size(issues, field(path, "diameter"), self.diameter, true);
+        check_hole(
+            issues,
+            path,
+            self.hole_diameter,
+            from_decimal(self.diameter),
+        );
+    }
+}
+
+impl Validate for Rectangular {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        check_size(issues, field(path, "x"), self.x, false);
+        check_size(issues, field(path, "y"), self.y, false);
+        let size = from_decimal(self.x).min(from_decimal(self.y));
+        check_hole(issues, path, self.hole_diameter, size);
+    }
+}
+
+impl Validate for Obround {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        check_size(issues, field(path, "x"), self.x, false);
+        check_size(issues, field(path, "y"), self.y, false);
+        let size = from_decimal(self.x).min(from_decimal(self.y));
+        check_hole(issues, path, self.hole_diameter, size);
+    }
+}
+
+impl Validate for Polygon {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        check_size(issues, field(path, "diameter"), self.diameter, false);
+        if !(3..=12).contains(&self.vertices) {
+            let message = format!(
+                "A polygon must have 3 to 12 vertices, not {}",
+                self.vertices
+            );
+            push(issues, Severity::Error, field(path, "vertices"), message);
+        }
+        check_hole(
+            issues,
+            path,
+            self.hole_diameter,
+            from_decimal(self.diameter),
+        );
+    }
+}
+
+impl Validate for Aperture {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        match *self {
+            Aperture::Circle(ref circle) => circle.validate_at(path, issues),
+            Aperture::Rectangle(ref rect) => rect.validate_at(path, issues),
+            Aperture::Obround(ref obround) => obround.validate_at(path, issues),
+            Aperture::Polygon(ref polygon) => polygon.validate_at(path, issues),
+            Aperture::Other(ref other) => {
+                let name = other.split(',').next().unwrap_or_default();
+                if !is_valid_name(name) {
+                    let message = format!("Invalid macro name {:?}", name);
+                    push(issues, Severity::Error, path.to_string(), message);
+                }
+            }
+        }
+    }
+}
+
+impl Validate for ApertureDefinition {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        if self.code < 10 {
+            let message = format!("Aperture code must be at least 10, not {}", self.code);
+            push(issues, Severity::Error, field(path, "code"), message);
+        }
+        self.aperture.validate_at(&field(path, "aperture"), issues);
+    }
+}
+
+fn check_macro_decimal(issues: &mut Vec<ValidationIssue>, path: String, decimal: &MacroDecimal) {
+    if let MacroDecimal::Value(value) = *decimal {
+        if !from_decimal(value).is_finite() {
+            push(issues, Severity::Error, path, "Value is not finite".into());
+        }
+    }
+}
+
+impl Validate for MacroContent {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        let decimals: Vec<(&str, &MacroDecimal)> = match *self {
+            MacroContent::Circle(ref c) => {
+                let mut decimals = vec![
+                    ("diameter", &c.diameter),
+                    ("center.0", &c.center.0),
+                    ("center.1", &c.center.1),
+                ];
+                decimals.extend(c.angle.as_ref().map(|angle| ("angle", angle)));
+                decimals
+            }
+            MacroContent::VectorLine(ref vl) => vec![
+                ("width", &vl.width),
+                ("start.0", &vl.start.0),
+                ("start.1", &vl.start.1),
+                ("end.0", &vl.end.0),
+                ("end.1", &vl.end.1),
+                ("angle", &vl.angle),
+            ],
+            MacroContent::CenterLine(ref cl) => vec![
+                ("dimensions.0", &cl.dimensions.0),
+                ("dimensions.1", &cl.dimensions.1),
+                ("center.0", &cl.center.0),
+                ("center.1", &cl.center.1),
+                ("angle", &cl.angle),
+            ],
+            MacroContent::Outline(ref outline) => {
+                let points = &outline.points;
+                if points.len() < 4 {
+                    let message = "An outline needs at least 3 points plus the closing point";
+                    push(
+                        issues,
+                        Severity::Error,
+                        field(path, "points"),
+                        message.into(),
+                    );
+                } else if points.first() != points.last() {
+                    let message = "The last point of an outline must equal the first";
+                    push(
+                        issues,
+                        Severity::Error,
+                        field(path, "points"),
+                        message.into(),
+                    );
+                }
+                for (i, (x, y)) in points.iter().enumerate() {
+                    let point = index(&field(path, "points"), i);
+                    check_macro_decimal(issues, field(&point, "0"), x);
+                    check_macro_decimal(issues, field(&point, "1"), y);
+                }
+                vec![("angle", &outline.angle)]
+            }
+            MacroContent::Polygon(ref polygon) => {
+                if !(3..=12).contains(&polygon.vertices) {
+                    let message = format!(
+                        "A polygon must have 3 to 12 vertices, not {}",
+                        polygon.vertices
+                    );
+                    push(issues, Severity::Error, field(path, "vertices"), message);
+                }
+                vec![
+                    ("center.0", &polygon.center.0),
+                    ("center.1", &polygon.center.1),
+                    ("diameter", &polygon.diameter),
+                    ("angle", &polygon.angle),
+                ]
+            }
+            MacroContent::Moire(ref moire) => vec![
+                ("center.0", &moire.center.0),
+                ("center.1", &moire.center.1),
+                ("diameter", &moire.diameter),
+                ("ring_thickness", &moire.ring_thickness),
+                ("gap", &moire.gap),
+                ("cross_hair_thickness", &moire.cross_hair_thickness),
+                ("cross_hair_length", &moire.cross_hair_length),
+                ("angle", &moire.angle),
+            ],
+            MacroContent::Thermal(ref thermal) => {
+                if let (MacroDecimal::Value(outer), MacroDecimal::Value(inner)) =
+                    (&thermal.outer_diameter, &thermal.inner_diameter)
+                {
+                    if inner >= outer {
+                        let message = "The inner diameter must be smaller than the outer";
+                        push(
+                            issues,
+                            Severity::Error,
+                            field(path, "inner_diameter"),
+                            message.into(),
+                        );
+                    }
+                }
+                vec![
+                    ("center.0", &thermal.center.0),
+                    ("center.1", &thermal.center.1),
+                    ("outer_diameter", &thermal.outer_diameter),
+                    ("inner_diameter", &thermal.inner_diameter),
+                    ("gap", &thermal.gap),
+                    ("angle", &thermal.angle),
+                ]
+            }
+            MacroContent::VariableDefinition(_) | MacroContent::Comment(_) => Vec::new(),
+        };
+        for (name, decimal) in decimals {
+            check_macro_decimal(issues, field(path, name), decimal);
+        }
+    }
+}
+
+impl Validate for ApertureMacro {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        if !is_valid_name(&self.name) {
+            let message = format!("Invalid macro name {:?}", self.name);
+            push(issues, Severity::Error, field(path, "name"), message);
+        }
+        if self.content.is_empty() {
+            let message = "There must be at least 1 content element in an aperture macro";
+            push(
+                issues,
+                Severity::Error,
+                field(path, "content"),
+                message.into(),
+            );
+        }
+        self.content.validate_at(&field(path, "content"), issues);
+    }
+}
+
+impl Validate for FileAttribute {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        match *self {
+            FileAttribute::FileFunction(FileFunction::Copper { layer, .. }) if layer < 1 => {
+                let message = format!("Copper layers start at 1, not {}", layer);
+                push(issues, Severity::Error, path.to_string(), message);
+            }
+            FileAttribute::ProjectIdText { ref guid, .. } if !is_guid(guid) => {
+                let message = format!("{:?} is not a GUID", guid);
+                push(issues, Severity::Warning, field(path, "guid"), message);
+            }
+            FileAttribute::Md5(ref hash)
+                if hash.len() != 32 || !hash.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                let message = format!("{:?} is not an MD5 hash", hash);
+                push(issues, Severity::Error, path.to_string(), message);
+            }
+            FileAttribute::UserDefined { ref name, .. }
+                if !is_valid_name(name) || name.starts_with('.') =>
+            {
+                let message = format!("{:?} is not a valid user attribute name", name);
+                push(issues, Severity::Error, field(path, "name"), message);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Validate for ApertureAttribute {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        if let ApertureAttribute::DrillTolerance { plus, minus } = *self {
+            check_size(issues, field(path, "plus"), plus, true);
+            check_size(issues, field(path, "minus"), minus, true);
+        }
+    }
+}
+
+impl Validate for StepAndRepeat {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        if let StepAndRepeat::Open {
+            repeat_x,
+            repeat_y,
+            distance_x,
+            distance_y,
+        } = *self
+        {
+            for (name, repeat) in [("repeat_x", repeat_x), ("repeat_y", repeat_y)] {
+                if repeat < 1 {
+                    let message = "There must be at least 1 repetition".into();
+                    push(issues, Severity::Error, field(path, name), message);
+                }
+            }
+            check_size(issues, field(path, "distance_x"), distance_x, true);
+            check_size(issues, field(path, "distance_y"), distance_y, true);
+        }
+    }
+}
+
+impl Validate for Operation {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        match *self {
+            Operation::Interpolate(ref coords, ref offset) => {
+                coords.validate_at(path, issues);
+                if let Some(ref offset) = *offset {
+                    offset.validate_at(&field(path, "offset"), issues);
+                }
+            }
+            Operation::Move(ref coords) | Operation::Flash(ref coords) => {
+                coords.validate_at(path, issues)
+            }
+        }
+    }
+}
+
+impl Validate for FunctionCode {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        match *self {
+            FunctionCode::DCode(DCode::Operation(ref op)) => op.validate_at(path, issues),
+            FunctionCode::DCode(DCode::SelectAperture(code)) if code < 10 => {
+                let message = format!("Aperture code must be at least 10, not {}", code);
+                push(issues, Severity::Error, path.to_string(), message);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Validate for ExtendedCode {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        match *self {
+            ExtendedCode::CoordinateFormat(ref cf) => cf.validate_at(path, issues),
+            ExtendedCode::ApertureDefinition(ref def) => def.validate_at(path, issues),
+            ExtendedCode::ApertureMacro(ref am) => am.validate_at(path, issues),
+            ExtendedCode::StepAndRepeat(ref sr) => sr.validate_at(path, issues),
+            ExtendedCode::FileAttribute(ref attr) => attr.validate_at(path, issues),
+            ExtendedCode::ApertureAttribute(ref attr) => attr.validate_at(path, issues),
+            ExtendedCode::DeleteAttribute(ref name) => {
+                if !name.is_empty() && !is_valid_name(name) {
+                    let message = format!("Invalid attribute name {:?}", name);
+                    push(issues, Severity::Error, path.to_string(), message);
+                }
+            }
+            ExtendedCode::Unit(_) | ExtendedCode::LoadPolarity(_) => {}
+        }
+    }
+}
+
+impl Validate for ExtendedCodeBlock {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        self.codes.validate_at(&field(path, "codes"), issues);
+    }
+}
+
+impl Validate for Command {
+    fn validate_at(&self, path: &str, issues: &mut Vec<ValidationIssue>) {
+        match *self {
+            Command::FunctionCode(ref code) => code.validate_at(path, issues),
+            Command::ExtendedCode(ref code) => code.validate_at(path, issues),
+            Command::ExtendedCodeBlock(ref block) => block.validate_at(path, issues),
+            Command::Deprecated(_) => {
+                let message = "Deprecated codes should not be used in new files".into();
+                push(issues, Severity::Warning, path.to_string(), message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::function_codes::MCode;
+    use crate::macros::{
+        CenterLinePrimitive, CirclePrimitive, OutlinePrimitive, VectorLinePrimitive,
+    };
+
+    #[test]
+    fn test_validate_aperture() {
+        assert_eq!(Circle::with_hole(1.0, 0.5).validate(), vec![]);
+        let issues = Rectangular::with_hole(1.0, 0.5, 0.8).validate();
+        assert_eq!(
+            issues,
+            vec![ValidationIssue {
+                severity: Severity::Error,
+                message: "The hole must be smaller than the aperture".into(),
+                path: "hole_diameter".into(),
+            }]
+        );
+        let issues = Polygon::new(-1.0, 2).validate();
+        let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, ["diameter", "vertices"]);
+    }
+
+    #[test]
+    fn test_validate_commands() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCode::CoordinateFormat(cf).into(),
+            ApertureDefinition::new(10, Aperture::Circle(Circle::new(f64::NAN))).into(),
+            ApertureMacro::new("DOT")
+                .add_content(CirclePrimitive::new(MacroDecimal::from(1.0)))
+                .add_content(OutlinePrimitive::new())
+                .into(),
+            DCode::Operation(Operation::Flash(Coordinates::new(100, 0, cf))).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let issues = commands.validate();
+        let paths: Vec<&str> = issues.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            ["[1].aperture.diameter", "[2].content[1].points", "[3].x"]
+        );
+        assert_eq!(
+            issues[2].to_string(),
+            "Error at [3].x: 100 does not fit into the coordinate format"
+        );
+    }
+
+    #[test]
+    fn test_validate_macro_points() {
+        let nan = || MacroDecimal::from(f64::NAN);
+        let zero = || MacroDecimal::from(0.0);
+        let line = VectorLinePrimitive::new((nan(), zero()), (zero(), nan()));
+        let paths = |content: MacroContent| -> Vec<String> {
+            content.validate().into_iter().map(|i| i.path).collect()
+        };
+        assert_eq!(paths(MacroContent::VectorLine(line)), ["start.0", "end.1"]);
+        let center_line =
+            CenterLinePrimitive::new((MacroDecimal::from(1.0), MacroDecimal::from(1.0)))
+                .centered_at((zero(), nan()));
+        assert_eq!(paths(MacroContent::CenterLine(center_line)), ["center.1"]);
+        let outline = OutlinePrimitive::from_points(vec![
+            (zero(), zero()),
+            (nan(), zero()),
+            (zero(), nan()),
+            (zero(), zero()),
+        ]);
+        assert_eq!(
+            paths(MacroContent::Outline(outline)),
+            ["points[1].0", "points[2].1"]
+        );
+    }
+}
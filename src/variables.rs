@@ -0,0 +1,208 @@
+//! Renumbering of aperture macro variables.
+
+use std::collections::BTreeMap;
+
+use crate::macros::{ApertureMacro, MacroContent, MacroDecimal, VariableDefinition};
+
+impl ApertureMacro {
+    /// Renumber the variables into a dense sequence `$1..$n`, in definitions,
+    /// expressions and primitives. Returns the new number of each old one.
+    ///
+    /// Variables that are used before they are defined are arguments of the
+    /// aperture definition. They come first and keep their order, the other
+    /// variables follow in the order of their definitions. If arguments were
+    /// renumbered, the parameters of aperture definitions using this macro
+    /// must be adjusted with the returned mapping.
+    pub fn renumber_variables(&mut self) -> BTreeMap<u32, u32> {
+        let mut arguments = Vec::new();
+        let mut defined = Vec::new();
+        for content in self.content.iter_mut() {
+            let mut used = Vec::new();
+            if let MacroContent::VariableDefinition(definition) = content {
+                used.extend(expression_variables(definition.expression()));
+            }
+            used.extend(decimals_mut(content).into_iter().filter_map(|d| match *d {
+                MacroDecimal::Variable(v) => Some(v),
+                MacroDecimal::Value(_) => None,
+            }));
+            for v in used {
+                if !defined.contains(&v) && !arguments.contains(&v) {
+                    arguments.push(v);
+                }
+            }
+            if let MacroContent::VariableDefinition(definition) = content {
+                let v = definition.number();
+                if !defined.contains(&v) {
+                    defined.push(v);
+                }
+            }
+        }
+        arguments.sort_unstable();
+        let mapping: BTreeMap<u32, u32> = arguments
+            .iter()
+            .chain(defined.iter().filter(|v| !arguments.contains(v)))
+            .zip(1..)
+            .map(|(&old, new)| (old, new))
+            .collect();
+
+        for content in self.content.iter_mut() {
+            if let MacroContent::VariableDefinition(definition) = content {
+                *definition = VariableDefinition::new(
+                    mapping[&definition.number()],
+                    &rewrite_expression(definition.expression(), &mapping),
+                );
+            }
+            for decimal in decimals_mut(content) {
+                if let MacroDecimal::Variable(v) = decimal {
+                    *v = mapping[v];
+                }
+            }
+        }
+        mapping
+    }
+}
+
+/// All decimals of a primitive.
+fn decimals_mut(content: &mut MacroContent) -> Vec<&mut MacroDecimal> {
+    match content {
+        MacroContent::Circle(c) => {
+            let mut decimals = vec![&mut c.diameter, &mut c.center.0, &mut c.center.1];
+            decimals.extend(c.angle.as_mut());
+            decimals
+        }
+        MacroContent::VectorLine(vl) => vec![
+            &mut vl.width,
+            &mut vl.start.0,
+            &mut vl.start.1,
+            &mut vl.end.0,
+            &mut vl.end.1,
+            &mut vl.angle,
+        ],
+        MacroContent::CenterLine(cl) => vec![
+            &mut cl.dimensions.0,
+            &mut cl.dimensions.1,
+            &mut cl.center.0,
+            &mut cl.center.1,
+            &mut cl.angle,
+        ],
+        MacroContent::Outline(o) => {
+            let mut decimals: Vec<_> = o.points.iter_mut().flat_map(|(x, y)| vec![x, y]).collect();
+            decimals.push(&mut o.angle);
+            decimals
+        }
+        MacroContent::Polygon(p) => vec![
+            &mut p.center.0,
+            &mut p.center.1,
+            &mut p.diameter,
+            &mut p.angle,
+        ],
+        MacroContent::Moire(m) => vec![
+            &mut m.center.0,
+            &mut m.center.1,
+            &mut m.diameter,
+            &mut m.ring_thickness,
+            &mut m.gap,
+            &mut m.cross_hair_thickness,
+            &mut m.cross_hair_length,
+            &mut m.angle,
+        ],
+        MacroContent::Thermal(t) => vec![
+            &mut t.center.0,
+            &mut t.center.1,
+            &mut t.outer_diameter,
+            &mut t.inner_diameter,
+            &mut t.gap,
+            &mut t.angle,
+        ],
+        MacroContent::VariableDefinition(_) | MacroContent::Comment(_) => Vec::new(),
+    }
+}
+
+/// Split an expression into literal parts and variable numbers.
+fn split_expression(expression: &str) -> Vec<Result<&str, u32>> {
+    let mut parts = Vec::new();
+    let mut rest = expression;
+    while let Some(start) = rest.find('$') {
+        let digits = rest[start + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(rest.len(), |end| start + 1 + end);
+        match rest[start + 1..digits].parse() {
+            Ok(v) => {
+                parts.push(Ok(&rest[..start]));
+                parts.push(Err(v));
+            }
+            Err(_) => parts.push(Ok(&rest[..digits])),
+        }
+        rest = &rest[digits..];
+    }
+    parts.push(Ok(rest));
+    parts
+}
+
+fn expression_variables(expression: &str) -> Vec<u32> {
+    split_expression(expression)
+        .into_iter()
+        .filter_map(Result::err)
+        .collect()
+}
+
+fn rewrite_expression(expression: &str, mapping: &BTreeMap<u32, u32>) -> String {
+    split_expression(expression)
+        .into_iter()
+        .map(|part| match part {
+            Ok(literal) => literal.to_string(),
+            Err(v) => format!("${}", mapping[&v]),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::macros::{CirclePrimitive, OutlinePrimitive};
+    use crate::traits::PartialGerberCode;
+
+    #[test]
+    fn test_split_expression() {
+        assert_eq!(
+            split_expression("$12x2-$3"),
+            vec![Ok(""), Err(12), Ok("x2-"), Err(3), Ok("")]
+        );
+        assert_eq!(split_expression("1+$"), vec![Ok("1+$"), Ok("")]);
+    }
+
+    #[test]
+    fn test_renumber_variables() {
+        let mut am = ApertureMacro::new("M")
+            .add_content(VariableDefinition::new(10, "$3x2"))
+            .add_content(
+                CirclePrimitive::new(MacroDecimal::Variable(10))
+                    .centered_at((MacroDecimal::Variable(7), 0.0.into())),
+            )
+            .add_content(VariableDefinition::new(5, "$10+$7"))
+            .add_content(OutlinePrimitive::from_points(vec![
+                (0.0.into(), 0.0.into()),
+                (MacroDecimal::Variable(5), 0.0.into()),
+                (0.0.into(), 0.0.into()),
+            ]));
+        let mapping = am.renumber_variables();
+        assert_eq!(
+            mapping.into_iter().collect::<Vec<_>>(),
+            vec![(3, 1), (5, 4), (7, 2), (10, 3)]
+        );
+
+        let mut buf = Vec::new();
+        am.serialize_partial(&mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "AMM*\n$3=$1x2*\n1,1,$3,$2,0*\n$4=$3+$2*\n4,1,2,\n0,0,\n$4,0,\n0,0,\n0*"
+        );
+
+        // Renumbering again changes nothing
+        let before = am.clone();
+        let mapping = am.renumber_variables();
+        assert!(mapping.iter().all(|(old, new)| old == new));
+        assert_eq!(am, before);
+    }
+}
@@ -0,0 +1,131 @@
+//! A stable, versioned serde representation of command streams, requires the
+//! `serde` feature.
+//!
+//! Command streams are wrapped in an envelope that names the format version:
+//!
+//! ```json
+//! {"format": "gerber-types/1", "commands": [...]}
+//! ```
+//!
+//! Within the commands:
+//!
+//! - Enums are externally tagged with the variant name, e.g.
+//!   `{"FunctionCode": {"MCode": "EndOfFile"}}`. Unit variants are written
+//!   as plain strings.
+//! - Struct fields are written by name.
+//! - Coordinate numbers are decimal strings like `"-1.25"`, independent of the
+//!   `high-resolution` feature.
+//! - Dates are RFC 3339 strings and UUIDs are hyphenated strings.
+//!
+//! Within a format version, new variants and fields may be added, so newer
+//! versions of this crate can read data written by older versions. Renaming
+//! or removing variants or fields, or changing their meaning, requires a new
+//! format version. The internally tagged envelope requires a self-describing
+//! format like JSON or CBOR.
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::Command;
+
+/// The name of the current format version.
+pub const VERSIONED_FORMAT: &str = "gerber-types/1";
+
+/// A command stream with a format version, see the [module
+/// documentation](index.html) for the representation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(tag = "format")]
+pub enum VersionedCommands {
+    #[serde(rename = "gerber-types/1")]
+    V1 { commands: Vec<Command> },
+}
+
+impl VersionedCommands {
+    /// Wrap commands in the current format version.
+    pub fn new(commands: Vec<Command>) -> Self {
+        VersionedCommands::V1 { commands }
+    }
+
+    /// The commands, converted to the current format version.
+    pub fn into_commands(self) -> Vec<Command> {
+        match self {
+            VersionedCommands::V1 { commands } => commands,
+        }
+    }
+}
+
+/// The JSON Schema of the versioned representation, requires the `schemars`
+/// feature.
+#[cfg(feature = "schemars")]
+pub fn versioned_commands_schema() -> schemars::schema::RootSchema {
+    schemars::schema_for!(VersionedCommands)
+}
+
+impl From<Vec<Command>> for VersionedCommands {
+    fn from(commands: Vec<Command>) -> Self {
+        VersionedCommands::new(commands)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::{CoordinateFormat, CoordinateNumber, Coordinates};
+    use crate::extended_codes::Unit;
+    use crate::function_codes::{DCode, GCode, MCode, Operation, RegionMode};
+    use crate::types::ExtendedCode;
+
+    fn commands() -> Vec<Command> {
+        let cf = CoordinateFormat::new(2, 4);
+        let coords = Coordinates::new(
+            CoordinateNumber::from_f64_rounded(-1.25).unwrap(),
+            CoordinateNumber::from(3),
+            cf,
+        );
+        vec![
+            GCode::Comment("versioned".into()).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+            GCode::RegionMode(RegionMode::Begin).into(),
+            DCode::Operation(Operation::Flash(coords)).into(),
+            MCode::EndOfFile.into(),
+        ]
+    }
+
+    /// The representation must not change within a format version.
+    #[test]
+    fn test_versioned_representation() {
+        let json = serde_json::to_string(&VersionedCommands::new(commands())).unwrap();
+        assert_eq!(
+            json,
+            "{\"format\":\"gerber-types/1\",\"commands\":[\
+             {\"FunctionCode\":{\"GCode\":{\"Comment\":\"versioned\"}}},\
+             {\"ExtendedCode\":{\"Unit\":\"Millimeters\"}},\
+             {\"FunctionCode\":{\"GCode\":{\"RegionMode\":true}}},\
+             {\"FunctionCode\":{\"DCode\":{\"Operation\":{\"Flash\":\
+             {\"x\":\"-1.25\",\"y\":\"3\",\"format\":{\"integer\":2,\"decimal\":4}}}}}},\
+             {\"FunctionCode\":{\"MCode\":\"EndOfFile\"}}]}"
+        );
+        let deserialized: VersionedCommands = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.into_commands(), commands());
+    }
+
+    #[test]
+    #[cfg(feature = "schemars")]
+    fn test_versioned_commands_schema() {
+        let schema = serde_json::to_value(versioned_commands_schema()).unwrap();
+        let format = &schema["oneOf"][0]["properties"]["format"];
+        assert_eq!(format["enum"][0], "gerber-types/1");
+        let definitions = schema["definitions"].as_object().unwrap();
+        assert!(definitions.contains_key("Command"));
+        assert!(definitions.contains_key("CoordinateNumber"));
+    }
+
+    #[test]
+    fn test_versioned_unknown_format() {
+        let json = "{\"format\":\"gerber-types/99\",\"commands\":[]}";
+        assert!(serde_json::from_str::<VersionedCommands>(json).is_err());
+        let coordinate = "\"1.2.3\"";
+        assert!(serde_json::from_str::<CoordinateNumber>(coordinate).is_err());
+    }
+}
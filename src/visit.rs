@@ -0,0 +1,184 @@
+//! Traversal of command trees.
+
+use crate::attributes::{ApertureAttribute, FileAttribute};
+use crate::coordinates::{CoordinateOffset, Coordinates};
+use crate::deprecated::DeprecatedCode;
+use crate::extended_codes::{Aperture, ApertureDefinition, StepAndRepeat};
+use crate::function_codes::{DCode, GCode, MCode, Operation};
+use crate::macros::{ApertureMacro, MacroContent};
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// Callbacks for the nodes of a command tree, called by [`walk`].
+///
+/// All callbacks do nothing by default, so visitors only implement the ones
+/// they are interested in. Nodes are visited before their children, e.g. a
+/// command before its function code.
+#[allow(unused_variables)]
+pub trait CommandVisitor {
+    fn visit_command(&mut self, command: &Command) {}
+    fn visit_function_code(&mut self, code: &FunctionCode) {}
+    fn visit_operation(&mut self, operation: &Operation) {}
+    fn visit_coordinates(&mut self, coordinates: &Coordinates) {}
+    fn visit_offset(&mut self, offset: &CoordinateOffset) {}
+    fn visit_select_aperture(&mut self, code: i32) {}
+    fn visit_gcode(&mut self, code: &GCode) {}
+    fn visit_mcode(&mut self, code: &MCode) {}
+    fn visit_extended_code(&mut self, code: &ExtendedCode) {}
+    fn visit_aperture_definition(&mut self, definition: &ApertureDefinition) {}
+    fn visit_aperture(&mut self, aperture: &Aperture) {}
+    fn visit_aperture_macro(&mut self, aperture_macro: &ApertureMacro) {}
+    fn visit_macro_content(&mut self, content: &MacroContent) {}
+    fn visit_step_and_repeat(&mut self, step_and_repeat: &StepAndRepeat) {}
+    fn visit_file_attribute(&mut self, attribute: &FileAttribute) {}
+    fn visit_aperture_attribute(&mut self, attribute: &ApertureAttribute) {}
+    fn visit_deprecated(&mut self, code: &DeprecatedCode) {}
+}
+
+/// Visit all nodes of the commands in order.
+pub fn walk<V: CommandVisitor + ?Sized>(visitor: &mut V, commands: &[Command]) {
+    for command in commands {
+        walk_command(visitor, command);
+    }
+}
+
+/// Visit all nodes of a single command.
+pub fn walk_command<V: CommandVisitor + ?Sized>(visitor: &mut V, command: &Command) {
+    visitor.visit_command(command);
+    match *command {
+        Command::FunctionCode(ref code) => walk_function_code(visitor, code),
+        Command::ExtendedCode(ref code) => walk_extended_code(visitor, code),
+        Command::ExtendedCodeBlock(ref block) => {
+            for code in &block.codes {
+                walk_extended_code(visitor, code);
+            }
+        }
+        Command::Deprecated(ref code) => {
+            visitor.visit_deprecated(code);
+            match *code {
+                DeprecatedCode::CoordinatesOnly(ref coords) => visitor.visit_coordinates(coords),
+                DeprecatedCode::CoordinatesWithOffset(ref coords, ref offset) => {
+                    visitor.visit_coordinates(coords);
+                    visitor.visit_offset(offset);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn walk_function_code<V: CommandVisitor + ?Sized>(visitor: &mut V, code: &FunctionCode) {
+    visitor.visit_function_code(code);
+    match *code {
+        FunctionCode::DCode(DCode::Operation(ref operation)) => {
+            visitor.visit_operation(operation);
+            match *operation {
+                Operation::Interpolate(ref coords, ref offset) => {
+                    visitor.visit_coordinates(coords);
+                    if let Some(ref offset) = *offset {
+                        visitor.visit_offset(offset);
+                    }
+                }
+                Operation::Move(ref coords) | Operation::Flash(ref coords) => {
+                    visitor.visit_coordinates(coords);
+                }
+            }
+        }
+        FunctionCode::DCode(DCode::SelectAperture(code)) => visitor.visit_select_aperture(code),
+        FunctionCode::GCode(ref code) => visitor.visit_gcode(code),
+        FunctionCode::MCode(ref code) => visitor.visit_mcode(code),
+    }
+}
+
+fn walk_extended_code<V: CommandVisitor + ?Sized>(visitor: &mut V, code: &ExtendedCode) {
+    visitor.visit_extended_code(code);
+    match *code {
+        ExtendedCode::ApertureDefinition(ref definition) => {
+            visitor.visit_aperture_definition(definition);
+            visitor.visit_aperture(&definition.aperture);
+        }
+        ExtendedCode::ApertureMacro(ref am) => {
+            visitor.visit_aperture_macro(am);
+            for content in &am.content {
+                visitor.visit_macro_content(content);
+            }
+        }
+        ExtendedCode::StepAndRepeat(ref sr) => visitor.visit_step_and_repeat(sr),
+        ExtendedCode::FileAttribute(ref attr) => visitor.visit_file_attribute(attr),
+        ExtendedCode::ApertureAttribute(ref attr) => visitor.visit_aperture_attribute(attr),
+        ExtendedCode::CoordinateFormat(_)
+        | ExtendedCode::Unit(_)
+        | ExtendedCode::LoadPolarity(_)
+        | ExtendedCode::DeleteAttribute(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::CoordinateFormat;
+    use crate::extended_codes::Circle;
+    use crate::macros::CirclePrimitive;
+    use crate::types::ExtendedCodeBlock;
+
+    #[derive(Default)]
+    struct Stats {
+        commands: usize,
+        coordinates: usize,
+        offsets: usize,
+        apertures: Vec<i32>,
+        macro_contents: usize,
+    }
+
+    impl CommandVisitor for Stats {
+        fn visit_command(&mut self, _command: &Command) {
+            self.commands += 1;
+        }
+
+        fn visit_coordinates(&mut self, _coordinates: &Coordinates) {
+            self.coordinates += 1;
+        }
+
+        fn visit_offset(&mut self, _offset: &CoordinateOffset) {
+            self.offsets += 1;
+        }
+
+        fn visit_aperture_definition(&mut self, definition: &ApertureDefinition) {
+            self.apertures.push(definition.code);
+        }
+
+        fn visit_macro_content(&mut self, _content: &MacroContent) {
+            self.macro_contents += 1;
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let cf = CoordinateFormat::new(2, 4);
+        let commands: Vec<Command> = vec![
+            ExtendedCodeBlock::new(vec![
+                ApertureDefinition::new(10, Aperture::Circle(Circle::new(0.1))).into(),
+                ApertureDefinition::new(11, Aperture::Circle(Circle::new(0.2))).into(),
+            ])
+            .into(),
+            ApertureMacro::new("DOT")
+                .add_content(CirclePrimitive::new(1.0.into()))
+                .into(),
+            DCode::Operation(Operation::Move(Coordinates::new(0, 0, cf))).into(),
+            DCode::Operation(Operation::Interpolate(
+                Coordinates::new(1, 1, cf),
+                Some(CoordinateOffset::new(0, 1, cf)),
+            ))
+            .into(),
+            DeprecatedCode::CoordinatesOnly(Coordinates::new(2, 2, cf)).into(),
+            MCode::EndOfFile.into(),
+        ];
+        let mut stats = Stats::default();
+        walk(&mut stats, &commands);
+        assert_eq!(stats.commands, 6);
+        assert_eq!(stats.coordinates, 3);
+        assert_eq!(stats.offsets, 1);
+        assert_eq!(stats.apertures, vec![10, 11]);
+        assert_eq!(stats.macro_contents, 1);
+    }
+}
@@ -0,0 +1,248 @@
+//! Detection and normalization of the winding direction of outlines and
+//! region contours.
+
+use crate::coordinates::{CoordinateNumber, Coordinates};
+use crate::decimal::from_decimal;
+use crate::errors::{deprecated_error, GerberError, GerberResult};
+use crate::function_codes::{DCode, GCode, InterpolationMode, Operation, RegionMode};
+use crate::macros::{MacroContent, MacroDecimal, OutlinePrimitive};
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+/// The direction in which the points of a closed contour are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Winding {
+    Clockwise,
+    Counterclockwise,
+}
+
+/// The winding direction of a polygon, from its signed area. Returns `None`
+/// if the area is zero.
+pub fn winding(points: &[(f64, f64)]) -> Option<Winding> {
+    let area: f64 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum();
+    if area > 0.0 {
+        Some(Winding::Counterclockwise)
+    } else if area < 0.0 {
+        Some(Winding::Clockwise)
+    } else {
+        None
+    }
+}
+
+impl OutlinePrimitive {
+    /// The winding direction of the points, or `None` if the area is zero or
+    /// a point uses a macro variable.
+    pub fn winding(&self) -> Option<Winding> {
+        let value = |d: &MacroDecimal| match *d {
+            MacroDecimal::Value(v) => Some(from_decimal(v)),
+            MacroDecimal::Variable(_) => None,
+        };
+        let points = self
+            .points
+            .iter()
+            .map(|(x, y)| Some((value(x)?, value(y)?)))
+            .collect::<Option<Vec<_>>>()?;
+        winding(&points)
+    }
+
+    /// Reverse the points if they are not in the given direction. Returns
+    /// whether the points were reversed.
+    pub fn normalize_winding(&mut self, winding: Winding) -> bool {
+        match self.winding() {
+            Some(w) if w != winding => {
+                self.points.reverse();
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Normalize the winding direction of all region contours and all outline
+/// primitives of aperture macros.
+///
+/// A contour is a move (D02) followed by linear interpolations (D01) back to
+/// its start. Contours that are not closed or have no area are left
+/// unchanged, as are outlines that use macro variables. Contours with arcs
+/// result in an `UnsupportedError`, deprecated codes as well.
+pub fn normalize_winding(commands: &[Command], winding: Winding) -> GerberResult<Vec<Command>> {
+    let mut result = Vec::with_capacity(commands.len());
+    let mut in_region = false;
+    let mut linear = true;
+    let mut point: (Option<CoordinateNumber>, Option<CoordinateNumber>) = (None, None);
+    let mut contour: Vec<(Option<Coordinates>, &Command)> = Vec::new();
+    for command in commands {
+        match command {
+            Command::FunctionCode(FunctionCode::DCode(DCode::Operation(op))) => {
+                let coords = match op {
+                    Operation::Interpolate(coords, _)
+                    | Operation::Move(coords)
+                    | Operation::Flash(coords) => coords,
+                };
+                point = (coords.x.or(point.0), coords.y.or(point.1));
+                let explicit = Coordinates {
+                    x: point.0,
+                    y: point.1,
+                    format: coords.format,
+                };
+                match op {
+                    Operation::Interpolate(..) if in_region && !contour.is_empty() => {
+                        if !linear {
+                            return Err(GerberError::UnsupportedError(
+                                "Normalizing the winding of contours with arcs".into(),
+                            ));
+                        }
+                        contour.push((Some(explicit), command));
+                        continue;
+                    }
+                    Operation::Move(_) if in_region => {
+                        flush_contour(&mut contour, winding, &mut result);
+                        contour.push((Some(explicit), command));
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::InterpolationMode(mode))) => {
+                linear = *mode == InterpolationMode::Linear;
+                if !contour.is_empty() {
+                    contour.push((None, command));
+                    continue;
+                }
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::Comment(_)))
+                if !contour.is_empty() =>
+            {
+                contour.push((None, command));
+                continue;
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::RegionMode(mode))) => {
+                in_region = *mode == RegionMode::Begin;
+            }
+            Command::ExtendedCode(ExtendedCode::ApertureMacro(am)) => {
+                let mut am = am.clone();
+                for content in am.content.iter_mut() {
+                    if let MacroContent::Outline(outline) = content {
+                        outline.normalize_winding(winding);
+                    }
+                }
+                flush_contour(&mut contour, winding, &mut result);
+                result.push(ExtendedCode::ApertureMacro(am).into());
+                continue;
+            }
+            Command::Deprecated(_) => return Err(deprecated_error()),
+            _ => {}
+        }
+        flush_contour(&mut contour, winding, &mut result);
+        result.push(command.clone());
+    }
+    flush_contour(&mut contour, winding, &mut result);
+    Ok(result)
+}
+
+/// Write a collected contour, reversed if it is closed and in the other
+/// direction. Mode changes and comments within a reversed contour are
+/// written before it.
+fn flush_contour(
+    contour: &mut Vec<(Option<Coordinates>, &Command)>,
+    winding: Winding,
+    result: &mut Vec<Command>,
+) {
+    let coords: Vec<&Coordinates> = contour.iter().filter_map(|(c, _)| c.as_ref()).collect();
+    let points: Vec<(f64, f64)> = coords
+        .iter()
+        .map(|c| (c.x.map_or(0.0, f64::from), c.y.map_or(0.0, f64::from)))
+        .collect();
+    let closed = coords.len() > 1 && coords.first() == coords.last();
+    if !closed || !matches!(self::winding(&points), Some(w) if w != winding) {
+        result.extend(contour.drain(..).map(|(_, command)| command.clone()));
+        return;
+    }
+    let (points, others): (Vec<_>, Vec<_>) = contour.drain(..).partition(|(c, _)| c.is_some());
+    result.extend(others.into_iter().map(|(_, command)| command.clone()));
+    let mut coords = points.into_iter().rev().filter_map(|(c, _)| c);
+    if let Some(start) = coords.next() {
+        result.push(DCode::Operation(Operation::Move(start)).into());
+    }
+    for c in coords {
+        result.push(DCode::Operation(Operation::Interpolate(c, None)).into());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::CoordinateFormat;
+    use crate::drawing::region;
+    use crate::macros::ApertureMacro;
+    use crate::traits::GerberCodeExt;
+
+    #[test]
+    fn test_winding() {
+        let square = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        assert_eq!(winding(&square), Some(Winding::Counterclockwise));
+        let mut reversed = square;
+        reversed.reverse();
+        assert_eq!(winding(&reversed), Some(Winding::Clockwise));
+        assert_eq!(winding(&[(0.0, 0.0), (1.0, 1.0)]), None);
+
+        let mut outline = OutlinePrimitive::from_points(
+            square
+                .iter()
+                .chain(&square[..1])
+                .map(|&(x, y)| (x.into(), y.into()))
+                .collect(),
+        );
+        assert_eq!(outline.winding(), Some(Winding::Counterclockwise));
+        assert!(!outline.normalize_winding(Winding::Counterclockwise));
+        assert!(outline.normalize_winding(Winding::Clockwise));
+        assert_eq!(outline.winding(), Some(Winding::Clockwise));
+        assert_eq!(outline.points[0], (0.0.into(), 0.0.into()));
+
+        let outline = outline.add_point((MacroDecimal::Variable(1), 0.0.into()));
+        assert_eq!(outline.winding(), None);
+    }
+
+    #[test]
+    fn test_normalize_winding() {
+        let cf = CoordinateFormat::new(2, 4);
+        let mut commands = region(&[(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)], cf).unwrap();
+        commands.push(
+            ApertureMacro::new("O")
+                .add_content(OutlinePrimitive::from_points(vec![
+                    (0.0.into(), 0.0.into()),
+                    (0.0.into(), 1.0.into()),
+                    (1.0.into(), 0.0.into()),
+                    (0.0.into(), 0.0.into()),
+                ]))
+                .into(),
+        );
+        let normalized = normalize_winding(&commands, Winding::Counterclockwise).unwrap();
+        assert_eq!(
+            normalized.to_code_string().unwrap(),
+            "G36*\nX0Y0D02*\nX10000Y0D01*\nX10000Y10000D01*\nX0Y10000D01*\nX0Y0D01*\nG37*\n\
+             %AMO*\n4,1,3,\n0,0,\n1,0,\n0,1,\n0,0,\n0*%\n"
+        );
+        assert_eq!(
+            normalize_winding(&normalized, Winding::Clockwise).unwrap(),
+            commands
+        );
+
+        // Comments within the contour are moved before it
+        let mut commented = commands.clone();
+        commented.insert(3, GCode::Comment("side".into()).into());
+        let normalized = normalize_winding(&commented, Winding::Counterclockwise).unwrap();
+        assert_eq!(normalized[1], commented[3]);
+
+        // Arcs are not supported
+        commands.insert(
+            3,
+            GCode::InterpolationMode(InterpolationMode::ClockwiseCircular).into(),
+        );
+        assert!(normalize_winding(&commands, Winding::Clockwise).is_err());
+    }
+}
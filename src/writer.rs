@@ -0,0 +1,315 @@
+//! Streaming output of Gerber code.
+
+use std::io::Write;
+
+use crate::attributes::ApertureAttribute;
+use crate::context::SerializationContext;
+use crate::deprecated::DeprecatedCode;
+use crate::errors::{GerberError, GerberResult};
+use crate::function_codes::{GCode, MCode};
+use crate::traits::GerberCode;
+use crate::types::{Command, ExtendedCode, FunctionCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Header,
+    Body,
+    Finished,
+}
+
+/// What a command means for the structure of the file.
+enum Kind {
+    Format,
+    Unit,
+    /// Commands that may appear anywhere, e.g. comments
+    Neutral,
+    Body,
+    EndOfFile,
+}
+
+impl Kind {
+    /// The kinds of the codes of a command, one for each code of a block.
+    fn of(command: &Command) -> Vec<Self> {
+        let kind = match command {
+            Command::ExtendedCode(code) => Kind::of_extended(code),
+            Command::ExtendedCodeBlock(block) => {
+                return block.codes.iter().map(Kind::of_extended).collect()
+            }
+            Command::FunctionCode(FunctionCode::GCode(GCode::Comment(_))) => Kind::Neutral,
+            Command::FunctionCode(FunctionCode::MCode(MCode::EndOfFile)) => Kind::EndOfFile,
+            Command::Deprecated(DeprecatedCode::UnitInches)
+            | Command::Deprecated(DeprecatedCode::UnitMillimeters) => Kind::Unit,
+            _ => Kind::Body,
+        };
+        vec![kind]
+    }
+
+    fn of_extended(code: &ExtendedCode) -> Self {
+        match code {
+            ExtendedCode::CoordinateFormat(_) => Kind::Format,
+            ExtendedCode::Unit(_) => Kind::Unit,
+            ExtendedCode::FileAttribute(_)
+            | ExtendedCode::ApertureAttribute(_)
+            | ExtendedCode::DeleteAttribute(_) => Kind::Neutral,
+            _ => Kind::Body,
+        }
+    }
+}
+
+/// A writer that serializes commands one at a time, so that large files can
+/// be generated without collecting all commands in memory first.
+///
+/// The writer checks the structure of the file: The coordinate format (FS)
+/// and the unit (MO) must be set in the header, before the first command of
+/// the body, and no command may follow the end of file (M02). Call
+/// [`finish`](#method.finish) when done, which writes the M02 command if it
+/// has not been written yet.
+pub struct GerberWriter<W: Write> {
+    writer: W,
+    ctx: SerializationContext,
+    state: State,
+    has_format: bool,
+    has_unit: bool,
+}
+
+impl<W: Write> GerberWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_context(writer, SerializationContext::new())
+    }
+
+    pub fn with_context(writer: W, ctx: SerializationContext) -> Self {
+        GerberWriter {
+            writer,
+            ctx,
+            state: State::Header,
+            has_format: false,
+            has_unit: false,
+        }
+    }
+
+    /// Serialize a command.
+    pub fn command(&mut self, command: &Command) -> GerberResult<()> {
+        if self.state == State::Finished {
+            return Err(GerberError::OrderError(
+                "No command may follow the end of file".into(),
+            ));
+        }
+        for kind in Kind::of(command) {
+            match kind {
+                Kind::Format | Kind::Unit if self.state == State::Body => {
+                    return Err(GerberError::OrderError(
+                        "The coordinate format and unit must be set in the header".into(),
+                    ));
+                }
+                Kind::Format => self.has_format = true,
+                Kind::Unit => self.has_unit = true,
+                Kind::Neutral => {}
+                Kind::Body => self.start_body()?,
+                Kind::EndOfFile => self.state = State::Finished,
+            }
+        }
+        command.serialize_with_context(&mut self.writer, &self.ctx)
+    }
+
+    /// Serialize several commands.
+    pub fn commands<'a, I>(&mut self, commands: I) -> GerberResult<()>
+    where
+        I: IntoIterator<Item = &'a Command>,
+    {
+        for command in commands {
+            self.command(command)?;
+        }
+        Ok(())
+    }
+
+    /// Write the aperture attributes (TA), run `body` and delete the
+    /// attributes again (TD), so that they only apply to the aperture
+    /// definitions written by `body`.
+    pub fn with_aperture_attributes<F, T>(
+        &mut self,
+        attributes: &[ApertureAttribute],
+        body: F,
+    ) -> GerberResult<T>
+    where
+        F: FnOnce(&mut Self) -> GerberResult<T>,
+    {
+        for attribute in attributes {
+            self.command(&ExtendedCode::ApertureAttribute(attribute.clone()).into())?;
+        }
+        let result = body(self)?;
+        for attribute in attributes {
+            self.command(&delete_attribute(attribute))?;
+        }
+        Ok(result)
+    }
+
+    /// Write the end of file (M02) if it has not been written yet, flush the
+    /// writer and return it.
+    pub fn finish(mut self) -> GerberResult<W> {
+        if self.state != State::Finished {
+            self.command(&MCode::EndOfFile.into())?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn start_body(&mut self) -> GerberResult<()> {
+        if self.state == State::Header {
+            if !self.has_format {
+                return Err(GerberError::MissingDataError(
+                    "The coordinate format must be set before the body".into(),
+                ));
+            }
+            if !self.has_unit {
+                return Err(GerberError::MissingDataError(
+                    "The unit must be set before the body".into(),
+                ));
+            }
+            self.state = State::Body;
+        }
+        Ok(())
+    }
+}
+
+/// Push the aperture attributes (TA), run `body` and push the commands that
+/// delete the attributes again (TD), like
+/// [`GerberWriter::with_aperture_attributes`].
+pub fn with_aperture_attributes<F, T>(
+    commands: &mut Vec<Command>,
+    attributes: &[ApertureAttribute],
+    body: F,
+) -> T
+where
+    F: FnOnce(&mut Vec<Command>) -> T,
+{
+    commands.extend(
+        attributes
+            .iter()
+            .map(|attribute| ExtendedCode::ApertureAttribute(attribute.clone()).into()),
+    );
+    let result = body(commands);
+    commands.extend(attributes.iter().map(delete_attribute));
+    result
+}
+
+fn delete_attribute(attribute: &ApertureAttribute) -> Command {
+    ExtendedCode::DeleteAttribute(attribute.name().into()).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coordinates::{CoordinateFormat, Coordinates};
+    use crate::extended_codes::Unit;
+    use crate::function_codes::{DCode, Operation};
+
+    fn header() -> Vec<Command> {
+        vec![
+            GCode::Comment("Streamed".into()).into(),
+            ExtendedCode::CoordinateFormat(CoordinateFormat::new(2, 4)).into(),
+            ExtendedCode::Unit(Unit::Millimeters).into(),
+        ]
+    }
+
+    fn flash() -> Command {
+        let cf = CoordinateFormat::new(2, 4);
+        DCode::Operation(Operation::Flash(Coordinates::new(1, 0, cf))).into()
+    }
+
+    #[test]
+    fn test_gerber_writer() {
+        let mut writer = GerberWriter::new(Vec::new());
+        writer.commands(&header()).unwrap();
+        writer.command(&flash()).unwrap();
+        writer.command(&flash()).unwrap();
+        let code = writer.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(code).unwrap(),
+            "G04 Streamed*\n%FSLAX24Y24*%\n%MOMM*%\nX10000Y0D03*\nX10000Y0D03*\nM02*\n"
+        );
+    }
+
+    #[test]
+    fn test_gerber_writer_order() {
+        // Missing header
+        let mut writer = GerberWriter::new(Vec::new());
+        assert!(writer.command(&flash()).is_err());
+
+        // Unit in the body
+        let mut writer = GerberWriter::new(Vec::new());
+        writer.commands(&header()).unwrap();
+        writer.command(&flash()).unwrap();
+        let unit = ExtendedCode::Unit(Unit::Inches).into();
+        assert!(writer.command(&unit).is_err());
+
+        // Command after the end of file
+        let mut writer = GerberWriter::new(Vec::new());
+        writer.command(&MCode::EndOfFile.into()).unwrap();
+        assert!(writer.command(&header()[0]).is_err());
+        assert_eq!(writer.finish().unwrap(), b"M02*\n");
+    }
+
+    #[test]
+    fn test_gerber_writer_block() {
+        use crate::types::ExtendedCodeBlock;
+
+        // A block that sets both the coordinate format and the unit
+        let block = ExtendedCodeBlock {
+            codes: vec![
+                ExtendedCode::CoordinateFormat(CoordinateFormat::new(2, 4)),
+                ExtendedCode::Unit(Unit::Millimeters),
+            ],
+        };
+        let mut writer = GerberWriter::new(Vec::new());
+        writer.command(&block.into()).unwrap();
+        writer.command(&flash()).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.finish().unwrap()).unwrap(),
+            "%FSLAX24Y24*MOMM*%\nX10000Y0D03*\nM02*\n"
+        );
+
+        // The unit must not follow the body, even after a neutral code
+        let mut writer = GerberWriter::new(Vec::new());
+        writer.commands(&header()).unwrap();
+        writer.command(&flash()).unwrap();
+        let late = ExtendedCodeBlock {
+            codes: vec![
+                ExtendedCode::DeleteAttribute(".AperFunction".into()),
+                ExtendedCode::Unit(Unit::Inches),
+            ],
+        };
+        assert!(writer.command(&late.into()).is_err());
+    }
+
+    #[test]
+    fn test_with_aperture_attributes() {
+        use crate::attributes::ApertureFunction;
+        use crate::extended_codes::{Aperture, ApertureDefinition, Circle};
+
+        let attributes = [ApertureAttribute::ApertureFunction(
+            ApertureFunction::ViaPad,
+        )];
+        let via = || -> Command {
+            ApertureDefinition::new(10, Aperture::Circle(Circle::new(0.5))).into()
+        };
+
+        let mut writer = GerberWriter::new(Vec::new());
+        writer.commands(&header()).unwrap();
+        let code = writer
+            .with_aperture_attributes(&attributes, |writer| {
+                writer.command(&via())?;
+                Ok(10)
+            })
+            .unwrap();
+        assert_eq!(code, 10);
+        let written = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(written
+            .ends_with("%TA.AperFunction,ViaPad*%\n%ADD10C,0.5*%\n%TD.AperFunction*%\nM02*\n"));
+
+        let mut commands = Vec::new();
+        with_aperture_attributes(&mut commands, &attributes, |commands| commands.push(via()));
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[2], delete_attribute(&attributes[0]));
+    }
+}
@@ -0,0 +1,63 @@
+//! Implementations of `GerberCode` outside of the crate.
+
+use std::io::Write;
+
+use gerber_types::{
+    CodegenSettings, Command, GerberCode, GerberCodeExt, GerberResult, LineEnding, MCode,
+    SerializationContext,
+};
+
+/// Only implements `serialize`, like implementors written before the
+/// serialization context existed.
+struct Stop;
+
+impl<W: Write> GerberCode<W> for Stop {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        writeln!(writer, "M00*")?;
+        Ok(())
+    }
+}
+
+/// Honors the line ending settings of the context.
+struct Marker;
+
+impl<W: Write> GerberCode<W> for Marker {
+    fn serialize(&self, writer: &mut W) -> GerberResult<()> {
+        self.serialize_with_context(writer, &SerializationContext::new())
+    }
+
+    fn serialize_with_context(
+        &self,
+        writer: &mut W,
+        ctx: &SerializationContext,
+    ) -> GerberResult<()> {
+        write!(writer, "G04 marker*")?;
+        ctx.end_line(writer)
+    }
+}
+
+#[test]
+fn test_serialize_only() {
+    let ctx = SerializationContext::new().with_decimal_places(2);
+    assert_eq!(Stop.to_code_string().unwrap(), "M00*\n");
+    assert_eq!(Stop.to_code_string_with_context(&ctx).unwrap(), "M00*\n");
+}
+
+#[test]
+fn test_serialize_with_context() {
+    let crlf = SerializationContext::new()
+        .with_codegen(CodegenSettings::new().with_line_ending(LineEnding::CrLf));
+    assert_eq!(Marker.to_code_string().unwrap(), "G04 marker*\n");
+    assert_eq!(
+        Marker.to_code_string_with_context(&crlf).unwrap(),
+        "G04 marker*\r\n"
+    );
+
+    let compact = SerializationContext::new().with_codegen(CodegenSettings::compact());
+    let mut buf = Vec::new();
+    Marker.serialize_with_context(&mut buf, &compact).unwrap();
+    Command::from(MCode::EndOfFile)
+        .serialize_with_context(&mut buf, &compact)
+        .unwrap();
+    assert_eq!(buf, b"G04 marker*M02*\n");
+}